@@ -0,0 +1,976 @@
+//! Command-line parsing and the mount entry point
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use encoding_rs::Encoding;
+use fuser::MountOption;
+use log::{info, warn};
+use rusqlite::{Connection, OpenFlags};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::fs::{
+    CollisionPolicy, FsError, FsOptions, MetadataFormat, PLATFORM, PlatformSupport, SortOrder,
+    SqliteFS, TodoExec,
+};
+use crate::inode::{
+    FIRST_DYNAMIC_INODE, FIRST_PINNABLE_INODE, parse_pin_inode_entry, pinned_inodes,
+};
+use crate::store::{
+    DB_BUSY_TIMEOUT, MountLock, SELFTEST_DB_URI, discover_resource_dir, fix_folder_problems,
+    folder_problems, selftest_database, spawn_maintenance,
+};
+
+/// Rejects mount labels that would corrupt the comma-separated `-o` option
+/// string handed to the kernel (commas, `=`, whitespace, control characters).
+pub(crate) fn validate_mount_label(value: &str) -> std::result::Result<String, String> {
+    if value.is_empty() {
+        return Err("must not be empty".to_string());
+    }
+    if let Some(c) = value
+        .chars()
+        .find(|c| *c == ',' || *c == '=' || c.is_whitespace() || c.is_control())
+    {
+        return Err(format!("invalid character {c:?}"));
+    }
+    Ok(value.to_string())
+}
+
+/// Parses an `--encoding` label such as `latin1` or `windows-1252`
+///
+/// Encodings that cannot be written back as themselves (UTF-16 and the
+/// replacement encoding) are rejected.
+pub(crate) fn parse_encoding(label: &str) -> std::result::Result<&'static Encoding, String> {
+    let encoding = Encoding::for_label(label.as_bytes())
+        .ok_or_else(|| format!("unknown encoding {label:?}"))?;
+    if encoding.output_encoding() != encoding {
+        return Err(format!(
+            "{} cannot be used to store note bodies",
+            encoding.name()
+        ));
+    }
+    Ok(encoding)
+}
+
+/// Check that the resource directory can be listed, with an error naming it
+pub(crate) fn check_resource_dir(dir: &std::path::Path) -> std::result::Result<(), String> {
+    std::fs::read_dir(dir)
+        .map(|_| ())
+        .map_err(|e| format!("resource directory {} is not readable: {e}", dir.display()))
+}
+
+/// Parses a `--uid-map` entry of the form `folderId=uid` or `folderId=uid:gid`
+pub(crate) fn parse_uid_map_entry(
+    value: &str,
+) -> std::result::Result<(String, u32, Option<u32>), String> {
+    let (folder_id, owner) = value
+        .split_once('=')
+        .ok_or_else(|| "expected <folderId>=<uid>[:<gid>]".to_string())?;
+    if folder_id.is_empty() {
+        return Err("missing folder id".to_string());
+    }
+    let (uid, gid) = match owner.split_once(':') {
+        Some((uid, gid)) => (uid, Some(gid)),
+        None => (owner, None),
+    };
+    let uid = uid.parse().map_err(|_| format!("invalid uid {uid:?}"))?;
+    let gid = gid
+        .map(|gid| gid.parse().map_err(|_| format!("invalid gid {gid:?}")))
+        .transpose()?;
+    Ok((folder_id.to_string(), uid, gid))
+}
+
+/// Parses a `--markup-ext` entry of the form `markupLanguage=ext`
+pub(crate) fn parse_markup_ext_entry(value: &str) -> std::result::Result<(i64, String), String> {
+    let (markup, ext) = value
+        .split_once('=')
+        .ok_or_else(|| "expected <markupLanguage>=<ext>".to_string())?;
+    let markup = markup
+        .parse()
+        .map_err(|_| format!("invalid markup language {markup:?}"))?;
+    let ext = ext.strip_prefix('.').unwrap_or(ext);
+    if ext.is_empty() || ext.len() > 16 || !ext.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!(
+            "invalid extension {ext:?}: expected 1-16 ASCII letters or digits"
+        ));
+    }
+    Ok((markup, ext.to_string()))
+}
+
+/// Parses a `--todo-suffix` marker such as `todo`, with or without its dot
+pub(crate) fn parse_todo_marker(value: &str) -> std::result::Result<String, String> {
+    let marker = value.strip_prefix('.').unwrap_or(value);
+    if marker.is_empty() || marker.contains(['.', '/']) || marker.chars().any(char::is_control) {
+        return Err(format!(
+            "invalid marker {value:?}: expected a word without dots or slashes"
+        ));
+    }
+    Ok(marker.to_string())
+}
+
+/// Default filesystem name: the database file's basename, with any character
+/// `validate_mount_label` would reject replaced by `_`.
+pub(crate) fn default_mount_name(database_path: &str) -> String {
+    let base = std::path::Path::new(database_path)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name: String = base
+        .chars()
+        .map(|c| {
+            if c == ',' || c == '=' || c.is_whitespace() || c.is_control() {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    if name.is_empty() {
+        "sqlite_fuse".to_string()
+    } else {
+        name
+    }
+}
+
+pub(crate) fn cli() -> Command {
+    Command::new("sqlite_fuse")
+        .author("Ryan Greenup")
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true)
+        .arg(
+            Arg::new("DATABASE")
+                .required(true)
+                .index(1)
+                .help("Path to the SQLite database file"),
+        )
+        .arg(
+            Arg::new("MOUNT_POINT")
+                .required(true)
+                .index(2)
+                .help("Act as a client, and mount FUSE at given path"),
+        )
+        .arg(
+            Arg::new("auto_unmount")
+                .long("auto_unmount")
+                .action(ArgAction::SetTrue)
+                .help("Automatically unmount on process exit (Linux only; ignored with a warning elsewhere)"),
+        )
+        .arg(
+            Arg::new("allow-root")
+                .long("allow-root")
+                .action(ArgAction::SetTrue)
+                .help("Allow root user to access filesystem"),
+        )
+        .arg(
+            Arg::new("no-md-suffix")
+                .long("no-md-suffix")
+                .action(ArgAction::SetTrue)
+                .help("Show notes under their raw titles without appending .md"),
+        )
+        .arg(
+            Arg::new("ignore-extension-case")
+                .long("ignore-extension-case")
+                .action(ArgAction::SetTrue)
+                .help("Match the .md suffix case-insensitively on lookup"),
+        )
+        .arg(
+            Arg::new("notebooks-by-id")
+                .long("notebooks-by-id")
+                .action(ArgAction::SetTrue)
+                .help("Also expose every notebook at /.notebooks/<folder id>, a path stable across renames"),
+        )
+        .arg(
+            Arg::new("include-deleted")
+                .long("include-deleted")
+                .action(ArgAction::SetTrue)
+                .help("List trashed notes and notebooks, read-only, under /.trash"),
+        )
+        .arg(
+            Arg::new("write-debounce-ms")
+                .long("write-debounce-ms")
+                .value_name("MS")
+                .value_parser(clap::value_parser!(u64))
+                .help("Commit writes to an open note at most once per MS milliseconds (pending writes are committed on close)"),
+        )
+        .arg(
+            Arg::new("maintenance-interval")
+                .long("maintenance-interval")
+                .value_name("SECONDS")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .help("Checkpoint and truncate the WAL every SECONDS in a background thread, skipping rounds while the database is busy"),
+        )
+        .arg(
+            Arg::new("trash-retention-days")
+                .long("trash-retention-days")
+                .value_name("DAYS")
+                .value_parser(clap::value_parser!(u64))
+                .requires("maintenance-interval")
+                .help("During maintenance, permanently delete notes and folders trashed more than DAYS ago"),
+        )
+        .arg(
+            Arg::new("debug-invariants")
+                .long("debug-invariants")
+                .action(ArgAction::SetTrue)
+                .help("Warn when a note's reported size and readable bytes disagree after a write"),
+        )
+        .arg(
+            Arg::new("sort")
+                .long("sort")
+                .value_name("ORDER")
+                .value_parser(["title", "created_desc", "created_asc"])
+                .default_value("title")
+                .help("Order of directory listings; folders and notes are each sorted by the same key"),
+        )
+        .arg(
+            Arg::new("title-collision-policy")
+                .long("title-collision-policy")
+                .value_name("POLICY")
+                .value_parser(["newest-wins", "suffix-id", "suffix-count", "error"])
+                .default_value("suffix-id")
+                .help("How notes sharing a name with another note or a folder are listed and looked up"),
+        )
+        .arg(
+            Arg::new("max-open-files")
+                .long("max-open-files")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .help("Fail open and create with EMFILE while N notes are already open"),
+        )
+        .arg(
+            Arg::new("max-inodes")
+                .long("max-inodes")
+                .value_name("N")
+                .value_parser(clap::value_parser!(u64).range(FIRST_DYNAMIC_INODE..))
+                .help("Keep at most N inodes, evicting ones the kernel has forgotten and failing lookups with ENFILE when none can go"),
+        )
+        .arg(
+            Arg::new("persist-inodes")
+                .long("persist-inodes")
+                .action(ArgAction::SetTrue)
+                .help("Record each note's and folder's inode in a fuse_inodes table so it stays the same across mounts"),
+        )
+        .arg(
+            Arg::new("folder-index")
+                .long("folder-index")
+                .value_name("TITLE")
+                .help("Also list the note titled TITLE in each folder as .index.md"),
+        )
+        .arg(
+            Arg::new("folder")
+                .long("folder")
+                .value_name("PATH_OR_ID")
+                .help("Mount only this notebook, given by path (/Projects) or folder id, as the root"),
+        )
+        .arg(
+            Arg::new("recent-count")
+                .long("recent-count")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .help("Add a read-only /.recent of symlinks to the N most recently updated notes, newest first"),
+        )
+        .arg(
+            Arg::new("control")
+                .long("control")
+                .action(ArgAction::SetTrue)
+                .help("Add /.control, whose files query or steer the mount (e.g. preview-rename)"),
+        )
+        .arg(
+            Arg::new("tags")
+                .long("tags")
+                .action(ArgAction::SetTrue)
+                .help("Add /.tags, with a directory per Joplin tag listing the notes carrying it"),
+        )
+        .arg(
+            Arg::new("resolve-duplicate-parents")
+                .long("resolve-duplicate-parents")
+                .action(ArgAction::SetTrue)
+                .help("Before mounting, give folders that share a title with a sibling a unique one, Title (a1b2c3), so every folder has a path"),
+        )
+        .arg(
+            Arg::new("rebuild-fts")
+                .long("rebuild-fts")
+                .action(ArgAction::SetTrue)
+                .help("Rebuild Joplin's notes_fts search index before mounting, so notes written without Joplin show up in its search"),
+        )
+        .arg(
+            Arg::new("hide-icon")
+                .long("hide-icon")
+                .value_name("EMOJI")
+                .help("Leave out notebooks whose icon is EMOJI, together with everything inside them"),
+        )
+        .arg(
+            Arg::new("uid-map")
+                .long("uid-map")
+                .value_name("FOLDER_ID=UID[:GID]")
+                .value_parser(parse_uid_map_entry)
+                .action(ArgAction::Append)
+                .help("Report a notebook and everything under it as owned by UID (and GID); repeatable"),
+        )
+        .arg(
+            Arg::new("uid")
+                .long("uid")
+                .value_name("UID")
+                .value_parser(clap::value_parser!(u32))
+                .help("Owner reported for entries no --uid-map entry covers"),
+        )
+        .arg(
+            Arg::new("gid")
+                .long("gid")
+                .value_name("GID")
+                .value_parser(clap::value_parser!(u32))
+                .help("Group reported for entries no --uid-map entry gives one"),
+        )
+        .arg(
+            Arg::new("read-only")
+                .long("read-only")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "resolve-duplicate-parents",
+                    "rebuild-fts",
+                    "maintenance-interval",
+                ])
+                .help("Mount read-only: open the database read-only and refuse every change with EROFS"),
+        )
+        .arg(
+            Arg::new("body-read-only")
+                .long("body-read-only")
+                .action(ArgAction::SetTrue)
+                .help("Refuse edits to note bodies (EROFS) while still allowing create, rename and delete"),
+        )
+        .arg(
+            Arg::new("hard-delete")
+                .long("hard-delete")
+                .action(ArgAction::SetTrue)
+                .help("Delete rows removed through the mount instead of moving them to Joplin's trash"),
+        )
+        .arg(
+            Arg::new("name-max")
+                .long("name-max")
+                .value_name("BYTES")
+                .value_parser(clap::value_parser!(u32).range(1..))
+                .default_value("255")
+                .help("Longest file name accepted (ENAMETOOLONG beyond it), reported by statfs"),
+        )
+        .arg(
+            Arg::new("block-size")
+                .long("block-size")
+                .value_name("BYTES")
+                .value_parser(clap::value_parser!(u32).range(1..))
+                .default_value("512")
+                .help("Block size reported by statfs and st_blksize"),
+        )
+        .arg(
+            Arg::new("encoding")
+                .long("encoding")
+                .value_name("LABEL")
+                .value_parser(parse_encoding)
+                .help("Encoding note bodies are stored in, e.g. latin1; read decodes to UTF-8 and write encodes back"),
+        )
+        .arg(
+            Arg::new("strict-parent")
+                .long("strict-parent")
+                .action(ArgAction::SetTrue)
+                .help("Diagnostic mode: fail with EIO when a path matches several sibling folders instead of using the newest"),
+        )
+        .arg(
+            Arg::new("create-parents")
+                .long("create-parents")
+                .action(ArgAction::SetTrue)
+                .help("Create missing parent folders when creating a note, like mkdir -p"),
+        )
+        .arg(
+            Arg::new("pin-inode")
+                .long("pin-inode")
+                .value_name("PATH=INODE")
+                .value_parser(parse_pin_inode_entry)
+                .action(ArgAction::Append)
+                .help(format!(
+                    "Always report PATH under INODE ({FIRST_PINNABLE_INODE}..{FIRST_DYNAMIC_INODE}); repeatable"
+                )),
+        )
+        .arg(
+            Arg::new("selftest")
+                .long("selftest")
+                .action(ArgAction::SetTrue)
+                .help("Before mounting, check the FUSE setup by mounting a scratch in-memory database and creating, writing, reading and deleting a note in it"),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .action(ArgAction::SetTrue)
+                .help("Mount writable even while another writable mount holds the database's .fuse-lock file"),
+        )
+        .arg(
+            Arg::new("number-by-order")
+                .long("number-by-order")
+                .action(ArgAction::SetTrue)
+                .help("Prefix note names with their zero-padded rank in Joplin's custom order, e.g. 010-Title.md"),
+        )
+        .arg(
+            Arg::new("gpx")
+                .long("gpx")
+                .action(ArgAction::SetTrue)
+                .help("Add a read-only .track.gpx of the geotagged notes to each notebook that has any"),
+        )
+        .arg(
+            Arg::new("metadata-format")
+                .long("metadata-format")
+                .value_name("FORMAT")
+                .value_parser(["json", "toml", "yaml"])
+                .default_value("json")
+                .help("Format of the note.md.<FORMAT> metadata sidecar"),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .action(ArgAction::SetTrue)
+                .help("Record per-operation latencies and print a summary to stderr on unmount"),
+        )
+        .arg(
+            Arg::new("resources-dir")
+                .long("resources-dir")
+                .value_name("DIR")
+                .help("Directory holding Joplin's resource files [default: the settings table's resourceDir, else resources/ beside the database]"),
+        )
+        .arg(
+            Arg::new("resources")
+                .long("resources")
+                .action(ArgAction::SetTrue)
+                .help("List every resource (attachment) file, read-only, under /.resources"),
+        )
+        .arg(
+            Arg::new("inline-resources")
+                .long("inline-resources")
+                .action(ArgAction::SetTrue)
+                .help("Embed linked images as data: URIs when reading notes (bodies become read-only)"),
+        )
+        .arg(
+            Arg::new("rewrite-note-links")
+                .long("rewrite-note-links")
+                .action(ArgAction::SetTrue)
+                .help("Rewrite :/id links to other notes as relative paths when reading notes (bodies become read-only)"),
+        )
+        .arg(
+            Arg::new("inline-attachment-files")
+                .long("inline-attachment-files")
+                .action(ArgAction::SetTrue)
+                .help("List each note's resources beside it as read-only <note>.<resource>.<ext> files"),
+        )
+        .arg(
+            Arg::new("adaptive-ttl")
+                .long("adaptive-ttl")
+                .action(ArgAction::SetTrue)
+                .help("Cache entries for 30s while no other process writes the database, and not at all for 10s after one does"),
+        )
+        .arg(
+            Arg::new("decorate-notebooks")
+                .long("decorate-notebooks")
+                .action(ArgAction::SetTrue)
+                .help("List top-level notebooks as \"[tag] Title\", the tag being the tag or color key of the folder's user_data"),
+        )
+        .arg(
+            Arg::new("todo-suffix")
+                .long("todo-suffix")
+                .value_name("MARKER")
+                .value_parser(parse_todo_marker)
+                .help("List to-dos as title.MARKER.md; creating or renaming to that form makes a note a to-do, and renaming away from it makes it a plain note"),
+        )
+        .arg(
+            Arg::new("todo-as-exec")
+                .long("todo-as-exec")
+                .value_name("WHICH")
+                .value_parser(["all", "open"])
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("all")
+                .help("Show to-dos with the executable bits set: all of them, or only those not completed (open); chmod does not change is_todo"),
+        )
+        .arg(
+            Arg::new("backlinks")
+                .long("backlinks")
+                .action(ArgAction::SetTrue)
+                .help("Show the notes linking to note.md as symlinks in the read-only directory note.md.backlinks"),
+        )
+        .arg(
+            Arg::new("normalize-unicode")
+                .long("normalize-unicode")
+                .value_name("BOOL")
+                .value_parser(clap::value_parser!(bool))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_value(if cfg!(target_os = "macos") { "true" } else { "false" })
+                .default_missing_value("true")
+                .help("Match names that differ from a title only in Unicode normalization, such as a decomposed é (default: on for macOS)"),
+        )
+        .arg(
+            Arg::new("markup-ext")
+                .long("markup-ext")
+                .value_name("N=EXT")
+                .value_parser(parse_markup_ext_entry)
+                .action(ArgAction::Append)
+                .help("Show notes whose markup_language is N with extension EXT, and give new EXT files that markup language; repeatable"),
+        )
+        .arg(
+            Arg::new("mount-name")
+                .long("mount-name")
+                .value_name("NAME")
+                .value_parser(validate_mount_label)
+                .help("Filesystem name shown in mount/findmnt (default: database file name)"),
+        )
+        .arg(
+            Arg::new("subtype")
+                .long("subtype")
+                .value_name("TYPE")
+                .value_parser(validate_mount_label)
+                .help("Filesystem subtype, shown as fuse.<TYPE> (ignored with a warning on macOS)"),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Check the database for folder parent_id cycles and missing parents instead of mounting")
+                .arg(
+                    Arg::new("DATABASE")
+                        .required(true)
+                        .help("Path to the SQLite database file"),
+                )
+                .arg(
+                    Arg::new("fix")
+                        .long("fix")
+                        .action(ArgAction::SetTrue)
+                        .help("Move each orphaned folder, and one folder of each cycle, to the top level"),
+                ),
+        )
+}
+
+/// Report the folder hierarchy faults of the database `doctor` was given
+/// and, with `--fix`, repair them; returns the exit status
+///
+/// Without `--fix` the database is opened read-only and any problem makes the
+/// status 1, so scripts can run the check before mounting.
+pub(crate) fn run_doctor(matches: &ArgMatches) -> i32 {
+    let database_path = matches.get_one::<String>("DATABASE").unwrap();
+    let fix = matches.get_flag("fix");
+    let flags = if fix {
+        OpenFlags::SQLITE_OPEN_READ_WRITE
+    } else {
+        OpenFlags::SQLITE_OPEN_READ_ONLY
+    };
+    let conn = match Connection::open_with_flags(database_path, flags)
+        .and_then(|conn| conn.busy_timeout(DB_BUSY_TIMEOUT).map(|_| conn))
+    {
+        Ok(conn) => conn,
+        Err(e) => {
+            eprintln!("doctor: cannot open {database_path}: {e}");
+            return 1;
+        }
+    };
+    let problems = match folder_problems(&conn) {
+        Ok(problems) => problems,
+        Err(e) => {
+            eprintln!("doctor: cannot read the folders of {database_path}: {e}");
+            return 1;
+        }
+    };
+
+    for problem in &problems {
+        println!("doctor: {problem}");
+    }
+    if problems.is_empty() {
+        println!("doctor: no problems found");
+        return 0;
+    }
+    if !fix {
+        println!("doctor: run with --fix to move the offending folders to the top level");
+        return 1;
+    }
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    match fix_folder_problems(&conn, &problems, now) {
+        Ok(()) => {
+            for problem in &problems {
+                println!(
+                    "doctor: moved folder {} to the top level",
+                    problem.offender()
+                );
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("doctor: fix failed, nothing was changed: {e}");
+            1
+        }
+    }
+}
+
+/// Mount the `--selftest` database on a temporary directory and drive
+/// `readdir`, `create`, `write`, `read` and `unlink` through the kernel,
+/// printing a pass/fail line for each step
+///
+/// The mount uses `mount_options`, so a missing `user_allow_other` for
+/// `--allow-root` shows up here too. Returns whether every step passed.
+pub(crate) fn run_selftest(mount_options: &[MountOption]) -> bool {
+    use std::io::Write;
+
+    fn report(step: &str, result: std::result::Result<(), String>) -> bool {
+        match &result {
+            Ok(()) => println!("selftest: {step:<8} ok"),
+            Err(e) => println!("selftest: {step:<8} FAILED: {e}"),
+        }
+        result.is_ok()
+    }
+
+    let _db = match selftest_database() {
+        Ok(conn) => conn,
+        Err(e) => return report("database", Err(e.to_string())),
+    };
+    let fs = match SqliteFS::new(SELFTEST_DB_URI, FsOptions::default()) {
+        Ok(fs) => fs,
+        Err(e) => return report("database", Err(e.to_string())),
+    };
+    let dir = std::env::temp_dir().join(format!("sqlite-fuse-selftest-{}", std::process::id()));
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        return report(
+            "mount",
+            Err(format!("cannot create {}: {e}", dir.display())),
+        );
+    }
+
+    let session = match fuser::spawn_mount2(fs, &dir, mount_options) {
+        Ok(session) => session,
+        Err(e) => {
+            let _ = std::fs::remove_dir(&dir);
+            return report(
+                "mount",
+                Err(format!(
+                    "{e} (is FUSE installed and /dev/fuse accessible? --allow-root needs user_allow_other in /etc/fuse.conf)"
+                )),
+            );
+        }
+    };
+    let mut passed = report("mount", Ok(()));
+
+    let io = |e: std::io::Error| e.to_string();
+    let probe = dir.join("selftest-probe.md");
+    passed &= report(
+        "readdir",
+        (|| {
+            let names = std::fs::read_dir(&dir)
+                .map_err(io)?
+                .map(|entry| entry.map(|entry| entry.file_name()))
+                .collect::<std::io::Result<Vec<_>>>()
+                .map_err(io)?;
+            if !names.iter().any(|name| name == "welcome.md") {
+                return Err(format!("welcome.md missing from {names:?}"));
+            }
+            Ok(())
+        })(),
+    );
+    let file = std::fs::File::create_new(&probe);
+    passed &= report(
+        "create",
+        file.as_ref().map(|_| ()).map_err(|e| e.to_string()),
+    );
+    passed &= report(
+        "write",
+        file.map_err(io)
+            .and_then(|mut file| file.write_all(b"selftest").map_err(io)),
+    );
+    passed &= report(
+        "read",
+        (|| {
+            let body = std::fs::read_to_string(&probe).map_err(io)?;
+            if body != "selftest" {
+                return Err(format!("read back {body:?}"));
+            }
+            Ok(())
+        })(),
+    );
+    passed &= report(
+        "unlink",
+        (|| {
+            std::fs::remove_file(&probe).map_err(io)?;
+            if probe.exists() {
+                return Err("the note is still listed".to_string());
+            }
+            Ok(())
+        })(),
+    );
+
+    session.join();
+    passed &= report("unmount", std::fs::remove_dir(&dir).map_err(io));
+    passed
+}
+
+/// Assemble the mount options requested on the command line, leaving out
+/// any the platform cannot honour (see `PlatformSupport`)
+pub(crate) fn mount_options(matches: &ArgMatches) -> Vec<MountOption> {
+    platform_mount_options(matches, &PLATFORM)
+}
+
+/// `mount_options` for `platform` rather than the one this build targets
+pub(crate) fn platform_mount_options(
+    matches: &ArgMatches,
+    platform: &PlatformSupport,
+) -> Vec<MountOption> {
+    let fs_name = match matches.get_one::<String>("mount-name") {
+        Some(name) => name.clone(),
+        None => default_mount_name(matches.get_one::<String>("DATABASE").unwrap()),
+    };
+    let mut options = vec![MountOption::FSName(fs_name)];
+    if let Some(subtype) = matches.get_one::<String>("subtype") {
+        if platform.subtype {
+            options.push(MountOption::Subtype(subtype.clone()));
+        } else {
+            warn!(
+                "--subtype is not supported on {}; ignoring it",
+                platform.name
+            );
+        }
+    }
+    if matches.get_flag("auto_unmount") {
+        if platform.auto_unmount {
+            options.push(MountOption::AutoUnmount);
+        } else {
+            warn!(
+                "--auto_unmount is not supported on {}; ignoring it",
+                platform.name
+            );
+        }
+    }
+    if matches.get_flag("allow-root") {
+        options.push(MountOption::AllowRoot);
+    }
+    if matches.get_flag("read-only") {
+        options.push(MountOption::RO);
+    }
+    options
+}
+
+/// Parse the command line and mount the database until it is unmounted
+///
+/// Bad arguments or an unusable database end the process with a message and
+/// a non-zero exit status.
+pub fn run() {
+    let matches = cli().get_matches();
+    env_logger::init();
+
+    if let Some(("doctor", doctor)) = matches.subcommand() {
+        std::process::exit(run_doctor(doctor));
+    }
+
+    let database_path = matches.get_one::<String>("DATABASE").unwrap();
+    let mountpoint = matches.get_one::<String>("MOUNT_POINT").unwrap();
+
+    if matches.get_flag("selftest") && !run_selftest(&mount_options(&matches)) {
+        eprintln!("Self-test failed; not mounting {database_path}");
+        std::process::exit(1);
+    }
+
+    let resource_dir = match matches.get_one::<String>("resources-dir") {
+        Some(dir) => {
+            let dir = std::path::PathBuf::from(dir);
+            if let Err(e) = check_resource_dir(&dir) {
+                eprintln!("Invalid --resources-dir: {e}");
+                std::process::exit(2);
+            }
+            dir
+        }
+        None => {
+            let dir = discover_resource_dir(database_path);
+            // Databases without attachments still mount; only reading
+            // resources fails
+            if let Err(e) = check_resource_dir(&dir) {
+                warn!("{e}; attachments and inlined images are unavailable (see --resources-dir)");
+            }
+            dir
+        }
+    };
+
+    let options = FsOptions {
+        no_md_suffix: matches.get_flag("no-md-suffix"),
+        notebooks_by_id: matches.get_flag("notebooks-by-id"),
+        include_deleted: matches.get_flag("include-deleted"),
+        ignore_extension_case: matches.get_flag("ignore-extension-case"),
+        write_debounce: matches
+            .get_one::<u64>("write-debounce-ms")
+            .filter(|ms| **ms > 0)
+            .map(|ms| Duration::from_millis(*ms)),
+        debug_invariants: matches.get_flag("debug-invariants"),
+        sort: match matches.get_one::<String>("sort").map(String::as_str) {
+            Some("created_desc") => SortOrder::CreatedDesc,
+            Some("created_asc") => SortOrder::CreatedAsc,
+            _ => SortOrder::Title,
+        },
+        hide_icon: matches.get_one::<String>("hide-icon").cloned(),
+        folder_index: matches.get_one::<String>("folder-index").cloned(),
+        folder: matches.get_one::<String>("folder").cloned(),
+        resources: matches.get_flag("resources"),
+        max_open_files: matches
+            .get_one::<usize>("max-open-files")
+            .copied()
+            .filter(|max| *max > 0),
+        max_inodes: matches
+            .get_one::<u64>("max-inodes")
+            .map(|max| *max as usize),
+        persist_inodes: matches.get_flag("persist-inodes"),
+        recent_count: matches
+            .get_one::<usize>("recent-count")
+            .copied()
+            .unwrap_or(0),
+        control: matches.get_flag("control"),
+        tags: matches.get_flag("tags"),
+        collision_policy: match matches
+            .get_one::<String>("title-collision-policy")
+            .map(String::as_str)
+        {
+            Some("suffix-id") => CollisionPolicy::SuffixId,
+            Some("suffix-count") => CollisionPolicy::SuffixCount,
+            Some("error") => CollisionPolicy::Error,
+            _ => CollisionPolicy::NewestWins,
+        },
+        uid_map: matches
+            .get_many::<(String, u32, Option<u32>)>("uid-map")
+            .unwrap_or_default()
+            .map(|(folder_id, uid, gid)| (folder_id.clone(), (*uid, *gid)))
+            .collect(),
+        uid: matches.get_one::<u32>("uid").copied(),
+        gid: matches.get_one::<u32>("gid").copied(),
+        body_read_only: matches.get_flag("body-read-only"),
+        read_only: matches.get_flag("read-only"),
+        hard_delete: matches.get_flag("hard-delete"),
+        name_max: matches.get_one::<u32>("name-max").copied(),
+        block_size: matches.get_one::<u32>("block-size").copied(),
+        encoding: matches
+            .get_one::<&'static Encoding>("encoding")
+            .copied()
+            .filter(|&encoding| encoding != encoding_rs::UTF_8),
+        strict_parent: matches.get_flag("strict-parent"),
+        create_parents: matches.get_flag("create-parents"),
+        pinned_inodes: match pinned_inodes(
+            matches
+                .get_many::<(String, u64)>("pin-inode")
+                .unwrap_or_default()
+                .cloned(),
+        ) {
+            Ok(pins) => pins,
+            Err(e) => {
+                eprintln!("Invalid --pin-inode: {e}");
+                std::process::exit(2);
+            }
+        },
+        gpx: matches.get_flag("gpx"),
+        number_by_order: matches.get_flag("number-by-order"),
+        metadata_format: match matches
+            .get_one::<String>("metadata-format")
+            .map(String::as_str)
+        {
+            Some("toml") => MetadataFormat::Toml,
+            Some("yaml") => MetadataFormat::Yaml,
+            _ => MetadataFormat::Json,
+        },
+        profile: matches.get_flag("profile"),
+        inline_resources: matches.get_flag("inline-resources"),
+        rewrite_note_links: matches.get_flag("rewrite-note-links"),
+        backlinks: matches.get_flag("backlinks"),
+        inline_attachment_files: matches.get_flag("inline-attachment-files"),
+        adaptive_ttl: matches.get_flag("adaptive-ttl"),
+        decorate_notebooks: matches.get_flag("decorate-notebooks"),
+        normalize_unicode: matches
+            .get_one::<bool>("normalize-unicode")
+            .copied()
+            .unwrap_or(false),
+        markup_exts: matches
+            .get_many::<(i64, String)>("markup-ext")
+            .unwrap_or_default()
+            .cloned()
+            .collect(),
+        todo_suffix: matches.get_one::<String>("todo-suffix").cloned(),
+        todo_as_exec: match matches
+            .get_one::<String>("todo-as-exec")
+            .map(String::as_str)
+        {
+            Some("open") => Some(TodoExec::Open),
+            Some(_) => Some(TodoExec::All),
+            None => None,
+        },
+        resource_dir,
+    };
+
+    // Taken before the database is opened, since opening it may already
+    // create indexes; held until `mount2` returns on unmount. Read-only
+    // mounts need none.
+    let mount_lock = if options.read_only || !database_writable(database_path) {
+        None
+    } else {
+        match MountLock::acquire(database_path) {
+            Ok(lock) => Some(lock),
+            Err(e) if matches.get_flag("force") => {
+                warn!("{e}; mounting anyway (--force)");
+                None
+            }
+            Err(e) => {
+                eprintln!("{e}; not mounting (use --force to override)");
+                std::process::exit(1);
+            }
+        }
+    };
+
+    let fs = match SqliteFS::new(database_path, options) {
+        Ok(fs) => fs,
+        Err(e @ FsError::FolderNotFound(_)) => {
+            drop(mount_lock);
+            eprintln!("Invalid --folder: {e}");
+            std::process::exit(2);
+        }
+        Err(e) => {
+            drop(mount_lock);
+            eprintln!("Failed to open database: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    if matches.get_flag("resolve-duplicate-parents") {
+        match fs.rename_duplicate_folders() {
+            Ok(renames) if renames.is_empty() => {
+                println!("resolve-duplicate-parents: no duplicate folders")
+            }
+            Ok(renames) => {
+                for rename in &renames {
+                    println!("resolve-duplicate-parents: {rename}");
+                }
+            }
+            Err(e) => warn!("resolve-duplicate-parents failed, nothing was changed: {e}"),
+        }
+    }
+
+    if matches.get_flag("rebuild-fts")
+        && let Err(e) = fs.rebuild_fts()
+    {
+        warn!("cannot rebuild the search index: {e}");
+    }
+
+    if let Some(seconds) = matches.get_one::<u64>("maintenance-interval") {
+        spawn_maintenance(
+            database_path.clone(),
+            Duration::from_secs(*seconds),
+            matches
+                .get_one::<u64>("trash-retention-days")
+                .map(|days| Duration::from_secs(days * 24 * 60 * 60)),
+        );
+    }
+
+    let mount_options = mount_options(&matches);
+    info!("mounting on {} with {mount_options:?}", PLATFORM.name);
+    fuser::mount2(fs, mountpoint, &mount_options).unwrap();
+    drop(mount_lock);
+}
+
+/// Whether the database at `database_path` can be opened for writing, as
+/// `SqliteFS::new` would, without opening it through SQLite
+///
+/// A file that does not exist yet counts as writable: SQLite creates it.
+fn database_writable(database_path: &str) -> bool {
+    match std::fs::OpenOptions::new().write(true).open(database_path) {
+        Ok(_) => true,
+        Err(e) => e.kind() == std::io::ErrorKind::NotFound,
+    }
+}
@@ -0,0 +1,6708 @@
+//! The FUSE `Filesystem` over a Joplin database and the attributes it reports
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use encoding_rs::Encoding;
+use fuser::{
+    FileAttr, FileType, Filesystem, KernelConfig, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry,
+    ReplyStatfs, Request,
+};
+use libc::{ENOENT, c_int};
+use log::{info, warn};
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::{Connection, OptionalExtension, Result};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use unicode_normalization::UnicodeNormalization;
+use uuid::Uuid;
+
+use crate::inode::InodeTable;
+use crate::store::{
+    DB_BUSY_TIMEOUT, FTS_TABLE, FolderRename, INODE_TABLE, NoteLock, NoteStore, StoredBody,
+    rename_duplicate_folders,
+};
+
+const TTL: Duration = Duration::from_secs(1); // 1 second
+/// TTL under `--adaptive-ttl` once the database has been quiet
+const ADAPTIVE_TTL_IDLE: Duration = Duration::from_secs(30);
+/// TTL under `--adaptive-ttl` while another process is writing
+const ADAPTIVE_TTL_BUSY: Duration = Duration::ZERO;
+/// How long without an external commit before the database counts as quiet
+const ADAPTIVE_TTL_QUIET: Duration = Duration::from_secs(10);
+
+/// Fewest digits in a `--number-by-order` prefix
+const ORDER_PREFIX_MIN_WIDTH: usize = 3;
+
+/// Extended attribute exposing the note's manual sort `order` column
+const XATTR_ORDER: &str = "user.joplin.order";
+/// Extended attribute exposing the free-form JSON `user_data` column
+const XATTR_USER_DATA: &str = "user.joplin.user_data";
+/// Extended attribute exposing the row's id
+const XATTR_ID: &str = "user.joplin.id";
+
+/// How a `user.joplin.*` attribute's value is checked before it is stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum XattrFormat {
+    /// Not writable
+    ReadOnly,
+    /// An integer or finite real
+    Number,
+    /// A non-negative integer, such as a millisecond timestamp
+    Timestamp,
+    /// `0` or `1`
+    Flag,
+    /// Any UTF-8 text
+    Text,
+    /// JSON, or empty to clear it
+    Json,
+}
+
+impl XattrFormat {
+    /// The value removing the attribute restores: the column's Joplin default
+    fn default_value(self) -> &'static [u8] {
+        match self {
+            XattrFormat::Number | XattrFormat::Timestamp | XattrFormat::Flag => b"0",
+            XattrFormat::ReadOnly | XattrFormat::Text | XattrFormat::Json => b"",
+        }
+    }
+}
+
+/// A `user.joplin.*` extended attribute and the column of the entry's row
+/// it exposes
+#[derive(Debug)]
+struct XattrColumn {
+    name: &'static str,
+    column: &'static str,
+    format: XattrFormat,
+}
+
+/// Extended attributes of notes, in `listxattr` order
+const NOTE_XATTRS: [XattrColumn; 8] = [
+    XattrColumn {
+        name: XATTR_ORDER,
+        column: "`order`",
+        format: XattrFormat::Number,
+    },
+    XattrColumn {
+        name: XATTR_USER_DATA,
+        column: "user_data",
+        format: XattrFormat::Json,
+    },
+    XattrColumn {
+        name: XATTR_ID,
+        column: "id",
+        format: XattrFormat::ReadOnly,
+    },
+    XattrColumn {
+        name: "user.joplin.author",
+        column: "author",
+        format: XattrFormat::Text,
+    },
+    XattrColumn {
+        name: "user.joplin.source_url",
+        column: "source_url",
+        format: XattrFormat::Text,
+    },
+    XattrColumn {
+        name: "user.joplin.is_todo",
+        column: "is_todo",
+        format: XattrFormat::Flag,
+    },
+    XattrColumn {
+        name: "user.joplin.todo_due",
+        column: "todo_due",
+        format: XattrFormat::Timestamp,
+    },
+    // Read-only: it picks the note's extension, so changing it would rename
+    // the file; rename the file to switch between Markdown and HTML
+    XattrColumn {
+        name: "user.joplin.markup_language",
+        column: "markup_language",
+        format: XattrFormat::ReadOnly,
+    },
+];
+
+/// Extended attributes of folders, in `listxattr` order
+const FOLDER_XATTRS: [XattrColumn; 2] = [
+    XattrColumn {
+        name: XATTR_USER_DATA,
+        column: "user_data",
+        format: XattrFormat::Json,
+    },
+    XattrColumn {
+        name: XATTR_ID,
+        column: "id",
+        format: XattrFormat::ReadOnly,
+    },
+];
+
+/// Virtual directory listing every notebook under its folder id (`--notebooks-by-id`)
+const NOTEBOOKS_DIR: &str = "/.notebooks";
+/// Virtual directory holding deleted notes and folders (`--include-deleted`)
+const TRASH_DIR: &str = "/.trash";
+/// Virtual directory of symlinks to the most recently updated notes (`--recent-count`)
+const RECENT_DIR: &str = "/.recent";
+/// Virtual directory of files that query or steer the mount (`--control`)
+const CONTROL_DIR: &str = "/.control";
+/// Virtual directory with a directory of tagged notes per tag (`--tags`)
+const TAGS_DIR: &str = "/.tags";
+/// Virtual directory of every resource file (`--resources`)
+const RESOURCES_DIR: &str = "/.resources";
+
+/// A synthetic top-level directory with no folder row behind it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VirtualDir {
+    Notebooks,
+    Trash,
+    Recent,
+    Control,
+    Tags,
+    Resources,
+}
+
+impl VirtualDir {
+    const ALL: [VirtualDir; 6] = [
+        VirtualDir::Notebooks,
+        VirtualDir::Trash,
+        VirtualDir::Recent,
+        VirtualDir::Control,
+        VirtualDir::Tags,
+        VirtualDir::Resources,
+    ];
+
+    fn path(self) -> &'static str {
+        match self {
+            VirtualDir::Notebooks => NOTEBOOKS_DIR,
+            VirtualDir::Trash => TRASH_DIR,
+            VirtualDir::Recent => RECENT_DIR,
+            VirtualDir::Control => CONTROL_DIR,
+            VirtualDir::Tags => TAGS_DIR,
+            VirtualDir::Resources => RESOURCES_DIR,
+        }
+    }
+
+    /// Reserved inode, below `FIRST_DYNAMIC_INODE`
+    fn inode(self) -> u64 {
+        match self {
+            VirtualDir::Notebooks => 2,
+            VirtualDir::Trash => 3,
+            VirtualDir::Recent => 4,
+            VirtualDir::Control => 5,
+            VirtualDir::Tags => 6,
+            VirtualDir::Resources => 7,
+        }
+    }
+}
+
+/// A file in `/.control`: written to ask for something, read for the answer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ControlFile {
+    /// Takes a source and a destination path, one per line, and reports
+    /// every path the rename would move without performing it
+    PreviewRename,
+    /// Rebuilds Joplin's full-text index on any write and reports how that
+    /// went
+    RebuildFts,
+}
+
+impl ControlFile {
+    const ALL: [ControlFile; 2] = [ControlFile::PreviewRename, ControlFile::RebuildFts];
+
+    fn name(self) -> &'static str {
+        match self {
+            ControlFile::PreviewRename => "preview-rename",
+            ControlFile::RebuildFts => "rebuild-fts",
+        }
+    }
+}
+
+/// Suffix of the read-only sidecar previewing a note's pending writes as a diff
+const DIFF_SIDECAR_SUFFIX: &str = ".diff";
+/// Suffix of the read-only sidecar presenting a note as plain text
+const PLAIN_TEXT_SIDECAR_SUFFIX: &str = ".txt";
+/// Suffix of the read-only sidecar holding a note gzip-compressed
+const GZIP_SIDECAR_SUFFIX: &str = ".gz";
+
+/// Suffix of the read-only directory listing a note's resources
+const ATTACHMENTS_SUFFIX: &str = ".attachments";
+/// Suffix of the read-only directory of symlinks to the notes linking to a
+/// note (`--backlinks`)
+const BACKLINKS_SUFFIX: &str = ".backlinks";
+
+/// Name under which each folder lists its index note (`--folder-index`)
+const FOLDER_INDEX_NAME: &str = ".index.md";
+
+/// Path suffix of a notebook's GPX track of geotagged notes (`--gpx`)
+const GPX_TRACK_SUFFIX: &str = "/.track.gpx";
+
+/// Body prefix of a note standing for a symbolic link; the rest of the body
+/// is the link target
+const SYMLINK_PREFIX: &str = "x-joplin-symlink:";
+
+/// Generated read-only files, addressed as `<note name><suffix>` or, for the
+/// GPX track, `<folder path><suffix>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sidecar {
+    /// Unified diff of the writes still buffered for the note
+    Diff,
+    /// The body with Markdown syntax stripped
+    PlainText,
+    /// Waypoints of the geotagged notes directly inside a folder
+    GpxTrack,
+    /// Every column of the note row except the body, in `--metadata-format`
+    Metadata,
+    /// The body as `read` returns it, gzip-compressed
+    Gzip,
+}
+
+impl Sidecar {
+    const ALL: [Sidecar; 5] = [
+        Sidecar::Diff,
+        Sidecar::PlainText,
+        Sidecar::GpxTrack,
+        Sidecar::Metadata,
+        Sidecar::Gzip,
+    ];
+
+    fn suffix(self, metadata_format: MetadataFormat) -> &'static str {
+        match self {
+            Sidecar::Diff => DIFF_SIDECAR_SUFFIX,
+            Sidecar::PlainText => PLAIN_TEXT_SIDECAR_SUFFIX,
+            Sidecar::GpxTrack => GPX_TRACK_SUFFIX,
+            Sidecar::Metadata => metadata_format.suffix(),
+            Sidecar::Gzip => GZIP_SIDECAR_SUFFIX,
+        }
+    }
+}
+
+/// Serialization of note metadata (`--metadata-format`), shared by every
+/// path that emits a note record
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum MetadataFormat {
+    #[default]
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl MetadataFormat {
+    /// Suffix of the metadata sidecar, e.g. `note.md.toml`
+    fn suffix(self) -> &'static str {
+        match self {
+            MetadataFormat::Json => ".json",
+            MetadataFormat::Toml => ".toml",
+            MetadataFormat::Yaml => ".yaml",
+        }
+    }
+
+    /// Serialize a flat record of scalars, ending with a newline
+    ///
+    /// TOML has no null, so null fields are left out of it.
+    fn serialize(
+        self,
+        record: &serde_json::Map<String, serde_json::Value>,
+    ) -> std::result::Result<String, c_int> {
+        match self {
+            MetadataFormat::Json => serde_json::to_string_pretty(record)
+                .map(|json| json + "\n")
+                .map_err(|_| libc::EIO),
+            MetadataFormat::Toml => {
+                let present: serde_json::Map<String, serde_json::Value> = record
+                    .iter()
+                    .filter(|(_, value)| !value.is_null())
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect();
+                toml::to_string(&present).map_err(|_| libc::EIO)
+            }
+            // JSON scalars are valid YAML flow scalars, so quoting and
+            // escaping follow JSON's rules
+            MetadataFormat::Yaml => {
+                let mut yaml = String::new();
+                for (key, value) in record {
+                    yaml.push_str(&format!("{key}: {value}\n"));
+                }
+                Ok(yaml)
+            }
+        }
+    }
+}
+
+/// Largest resource file `--inline-resources` will embed as a `data:` URI;
+/// bigger attachments keep their `:/id` link
+const INLINE_RESOURCE_MAX_BYTES: u64 = 1024 * 1024;
+
+/// Reply sink for operations that either succeed or fail with an errno
+///
+/// fuser's reply types cannot be constructed outside of fuser, so the shared
+/// reply helpers are written against these thin traits and tests substitute
+/// capturing implementations.
+trait EmptyReply {
+    fn ok(self);
+    fn error(self, err: c_int);
+}
+
+impl EmptyReply for fuser::ReplyEmpty {
+    fn ok(self) {
+        fuser::ReplyEmpty::ok(self)
+    }
+
+    fn error(self, err: c_int) {
+        fuser::ReplyEmpty::error(self, err)
+    }
+}
+
+/// Reply sink for extended attribute queries
+trait XattrReply {
+    fn size(self, size: u32);
+    fn data(self, data: &[u8]);
+    fn error(self, err: c_int);
+}
+
+impl XattrReply for fuser::ReplyXattr {
+    fn size(self, size: u32) {
+        fuser::ReplyXattr::size(self, size)
+    }
+
+    fn data(self, data: &[u8]) {
+        fuser::ReplyXattr::data(self, data)
+    }
+
+    fn error(self, err: c_int) {
+        fuser::ReplyXattr::error(self, err)
+    }
+}
+
+/// The kind of database row a filesystem path resolves to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EntryKind {
+    Folder,
+    Note,
+}
+
+/// Mount-time options controlling how the database is presented
+///
+/// Built by the command line; library users start from `Default` and
+/// chain the setters below.
+#[derive(Debug, Clone, Default)]
+pub struct FsOptions {
+    /// Present notes under their raw Joplin titles, without the synthetic `.md` suffix
+    pub(crate) no_md_suffix: bool,
+    /// Expose every notebook at `/.notebooks/<folder id>`, a path that survives renames
+    pub(crate) notebooks_by_id: bool,
+    /// Expose deleted notes and folders under `/.trash`
+    pub(crate) include_deleted: bool,
+    /// Accept `.MD`, `.Md`, ... wherever a `.md` suffix is expected
+    pub(crate) ignore_extension_case: bool,
+    /// Coalesce writes to an open note into at most one commit per interval
+    /// (`--write-debounce-ms`); `None` writes every change straight through
+    pub(crate) write_debounce: Option<Duration>,
+    /// After every write/setattr, check that getattr's size matches what a
+    /// full read returns and log a warning when they disagree
+    pub(crate) debug_invariants: bool,
+    /// Order of folders and notes in directory listings (`--sort`)
+    pub(crate) sort: SortOrder,
+    /// Presentation of notes whose names collide (`--title-collision-policy`)
+    pub(crate) collision_policy: CollisionPolicy,
+    /// Emoji whose notebooks are left out of the mount (`--hide-icon`)
+    pub(crate) hide_icon: Option<String>,
+    /// Number of notes `/.recent` links to; 0 leaves the directory out
+    pub(crate) recent_count: usize,
+    /// Add `/.control`, whose files query or steer the mount
+    pub(crate) control: bool,
+    /// Add `/.tags`, a directory per tag listing the notes carrying it
+    pub(crate) tags: bool,
+    /// Most note handles open at once (`--max-open-files`); None is unlimited
+    pub(crate) max_open_files: Option<usize>,
+    /// Most path/inode mappings kept at once (`--max-inodes`); past it,
+    /// inodes the kernel has forgotten are evicted and lookups otherwise
+    /// fail with ENFILE
+    pub(crate) max_inodes: Option<usize>,
+    /// Keep each note's and folder's inode across mounts, recorded by id in
+    /// `INODE_TABLE` (`--persist-inodes`)
+    pub(crate) persist_inodes: bool,
+    /// Title of the note each folder also lists as `.index.md`
+    /// (`--folder-index`)
+    pub(crate) folder_index: Option<String>,
+    /// Expose every resource file, read-only, under `/.resources`
+    pub(crate) resources: bool,
+    /// Notebook mounted as "/", by path from the top level (`/Projects`) or
+    /// by id (`--folder`); `None` mounts every notebook
+    pub(crate) folder: Option<String>,
+    /// Owner (uid and optional gid) of each listed notebook and its contents
+    /// (`--uid-map <folderId=uid[:gid]>`)
+    pub(crate) uid_map: HashMap<String, (u32, Option<u32>)>,
+    /// Owner of every entry `--uid-map` does not cover (`--uid`)
+    pub(crate) uid: Option<u32>,
+    /// Group of every entry `--uid-map` does not give one (`--gid`)
+    pub(crate) gid: Option<u32>,
+    /// Refuse writes and size changes to note bodies while still allowing
+    /// notes and folders to be created, renamed and removed
+    pub(crate) body_read_only: bool,
+    /// Refuse every change with EROFS and leave the database untouched,
+    /// opening it read-only (`--read-only`)
+    pub(crate) read_only: bool,
+    /// Drop deleted notes and folders from the database instead of moving
+    /// them to Joplin's trash (`--hard-delete`)
+    pub(crate) hard_delete: bool,
+    /// Encoding note bodies are stored in (`--encoding`); None stores and
+    /// reads UTF-8 as is
+    pub(crate) encoding: Option<&'static Encoding>,
+    /// Longest name, in bytes, that lookup/create/mkdir/rename accept
+    /// before failing with ENAMETOOLONG (`--name-max`)
+    pub(crate) name_max: Option<u32>,
+    /// Preferred I/O size reported by `statfs` and in every attribute (`--block-size`)
+    pub(crate) block_size: Option<u32>,
+    /// Fail with EIO instead of picking the newest folder when a path
+    /// component matches several sibling folders (`--strict-parent`)
+    pub(crate) strict_parent: bool,
+    /// Create missing parent folders when creating a note, like `mkdir -p`
+    pub(crate) create_parents: bool,
+    /// Fixed inodes for well-known paths (`--pin-inode <path=number>`),
+    /// assigned at mount time; validated by `pinned_inodes`
+    pub(crate) pinned_inodes: HashMap<String, u64>,
+    /// List a read-only `.track.gpx` of geotagged notes in each notebook
+    pub(crate) gpx: bool,
+    /// Serialization of the `note.md.<format>` metadata sidecar
+    pub(crate) metadata_format: MetadataFormat,
+    /// Prefix note names with their rank in Joplin's custom order
+    /// (`--number-by-order`)
+    pub(crate) number_by_order: bool,
+    /// Time every FUSE callback and print a latency summary on unmount
+    pub(crate) profile: bool,
+    /// Rewrite `:/id` image links to `data:` URIs on read; note bodies become read-only
+    pub(crate) inline_resources: bool,
+    /// Rewrite `:/id` links to other notes as relative paths within the
+    /// mount on read; note bodies become read-only
+    pub(crate) rewrite_note_links: bool,
+    /// Answer lookups of `<note>.backlinks` with a directory of symlinks to
+    /// the notes whose bodies reference the note
+    pub(crate) backlinks: bool,
+    /// List each note's resources next to it as read-only
+    /// `<note>.<resource>.<ext>` files
+    pub(crate) inline_attachment_files: bool,
+    /// Pick the entry and attribute TTL by whether another process has
+    /// written the database lately (see `ChangeWatch`)
+    pub(crate) adaptive_ttl: bool,
+    /// List top-level notebooks as `[tag] Title`, the tag coming from the
+    /// folder's `user_data`
+    pub(crate) decorate_notebooks: bool,
+    /// Match names to titles after normalizing both to NFC, so a
+    /// decomposed `é` finds a note stored composed
+    pub(crate) normalize_unicode: bool,
+    /// File extension per `markup_language` value (`--markup-ext`), over
+    /// `DEFAULT_MARKUP_EXTS`
+    pub(crate) markup_exts: HashMap<i64, String>,
+    /// Marker (`--todo-suffix`) shown between a to-do's title and its
+    /// extension, `task.todo.md`; naming a note so makes it a to-do
+    pub(crate) todo_suffix: Option<String>,
+    /// Report to-dos as executable (`--todo-as-exec`); the bits follow
+    /// `is_todo` alone and cannot be changed with `chmod`
+    pub(crate) todo_as_exec: Option<TodoExec>,
+    /// Directory holding Joplin's resource files, named `<id>.<file_extension>`
+    pub(crate) resource_dir: std::path::PathBuf,
+}
+
+impl FsOptions {
+    /// Present notes under their raw titles, without the `.md` suffix
+    pub fn no_md_suffix(mut self, enabled: bool) -> Self {
+        self.no_md_suffix = enabled;
+        self
+    }
+
+    /// Expose every notebook at `/.notebooks/<folder id>`
+    pub fn notebooks_by_id(mut self, enabled: bool) -> Self {
+        self.notebooks_by_id = enabled;
+        self
+    }
+
+    /// Expose deleted notes and folders under `/.trash`
+    pub fn include_deleted(mut self, enabled: bool) -> Self {
+        self.include_deleted = enabled;
+        self
+    }
+
+    /// Refuse every change with EROFS, opening the database read-only
+    pub fn read_only(mut self, enabled: bool) -> Self {
+        self.read_only = enabled;
+        self
+    }
+
+    /// Drop deleted rows instead of moving them to Joplin's trash
+    pub fn hard_delete(mut self, enabled: bool) -> Self {
+        self.hard_delete = enabled;
+        self
+    }
+
+    /// Mount the notebook at `folder`, a path from the top level or an id, as "/"
+    pub fn folder(mut self, folder: impl Into<String>) -> Self {
+        self.folder = Some(folder.into());
+        self
+    }
+
+    /// Owner of every entry, in place of the mounting user's real uid
+    pub fn uid(mut self, uid: u32) -> Self {
+        self.uid = Some(uid);
+        self
+    }
+
+    /// Group of every entry, in place of the mounting user's real gid
+    pub fn gid(mut self, gid: u32) -> Self {
+        self.gid = Some(gid);
+        self
+    }
+
+    /// Preferred I/O size reported by `statfs` and in every attribute
+    pub fn block_size(mut self, size: u32) -> Self {
+        self.block_size = Some(size);
+        self
+    }
+
+    /// Directory holding Joplin's resource files
+    pub fn resource_dir(mut self, dir: impl Into<std::path::PathBuf>) -> Self {
+        self.resource_dir = dir.into();
+        self
+    }
+}
+
+/// File extension of each Joplin `markup_language` unless `--markup-ext`
+/// says otherwise; values in neither are shown as Markdown
+const DEFAULT_MARKUP_EXTS: [(i64, &str); 2] = [(1, "md"), (2, "html")];
+
+/// Columns a duplicated note does not take from the original: its own
+/// identity, place and timestamps, and sharing, which is per note
+const DUPLICATE_RESET_COLUMNS: [&str; 9] = [
+    "id",
+    "title",
+    "parent_id",
+    "created_time",
+    "updated_time",
+    "user_created_time",
+    "user_updated_time",
+    "is_shared",
+    "share_id",
+];
+
+/// Prepared statements kept per connection for reuse by `prepare_cached`
+const STATEMENT_CACHE_CAPACITY: usize = 64;
+
+/// Longest file name accepted unless `--name-max` says otherwise
+const DEFAULT_NAME_MAX: u32 = 255;
+/// Block size reported by `statfs` and `st_blksize` unless `--block-size` says otherwise
+const DEFAULT_BLOCK_SIZE: u32 = 512;
+
+/// What every attribute reply shares, whichever entry it describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttrConfig {
+    /// How long the kernel may cache an entry or its attributes
+    pub ttl: Duration,
+    /// `st_blksize`, and the block size `statfs` reports
+    pub block_size: u32,
+    /// Owner of entries no `--uid-map` entry covers: the mounting user's
+    /// real uid unless `--uid` says otherwise
+    pub uid: u32,
+    /// Group of entries no `--uid-map` entry covers, or whose entry has no
+    /// gid: the mounting user's real gid unless `--gid` says otherwise
+    pub gid: u32,
+}
+
+impl Default for AttrConfig {
+    fn default() -> Self {
+        AttrConfig {
+            ttl: TTL,
+            block_size: DEFAULT_BLOCK_SIZE,
+            // SAFETY: getuid and getgid cannot fail and touch no memory
+            uid: unsafe { libc::getuid() },
+            gid: unsafe { libc::getgid() },
+        }
+    }
+}
+
+impl AttrConfig {
+    /// The defaults with the overrides given in `options`
+    fn from_options(options: &FsOptions) -> Self {
+        let defaults = AttrConfig::default();
+        AttrConfig {
+            block_size: options.block_size.unwrap_or(DEFAULT_BLOCK_SIZE),
+            uid: options.uid.unwrap_or(defaults.uid),
+            gid: options.gid.unwrap_or(defaults.gid),
+            ..defaults
+        }
+    }
+}
+
+/// Why a filesystem could not be built over a database
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum FsError {
+    /// The database could not be opened, or the indexes created
+    Database(rusqlite::Error),
+    /// `--folder` names no live notebook, by path or by id
+    FolderNotFound(String),
+}
+
+impl std::fmt::Display for FsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FsError::Database(e) => e.fmt(f),
+            FsError::FolderNotFound(folder) => {
+                write!(f, "no notebook at the path or with the id {folder}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FsError::Database(e) => Some(e),
+            FsError::FolderNotFound(_) => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for FsError {
+    fn from(e: rusqlite::Error) -> Self {
+        FsError::Database(e)
+    }
+}
+
+/// Number of rows fetched per keyset page when listing a directory
+const DIR_PAGE_SIZE: usize = 256;
+
+/// Order in which `readdir` lists the folders, then the notes, of a directory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum SortOrder {
+    /// Alphabetical by title
+    #[default]
+    Title,
+    /// Newest `created_time` first, ties broken by id
+    CreatedDesc,
+    /// Oldest `created_time` first, ties broken by id
+    CreatedAsc,
+}
+
+/// How notes whose display names collide are listed and looked up
+/// (`--title-collision-policy`)
+///
+/// Names collide when several live notes or folders in a folder share a
+/// title, or when a note's name equals a sibling folder's, which always keeps
+/// the plain name. Duplicate folders are listed like `SuffixId` notes under
+/// either suffix policy, and an id-suffixed folder name resolves under any.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum CollisionPolicy {
+    /// List each name once for the most recently updated note, hiding older
+    /// duplicates and notes shadowed by a folder
+    NewestWins,
+    /// List every note, suffixing all but the newest with the start of
+    /// their id: `Title (a1b2c3).md`
+    #[default]
+    SuffixId,
+    /// List every note, numbering all but the newest from 2, newest first:
+    /// `Title (2).md`
+    SuffixCount,
+    /// List each name once, but fail lookups of a shared name with EIO
+    Error,
+}
+
+impl CollisionPolicy {
+    /// Whether every note row gets its own listing entry
+    fn lists_every_note(self) -> bool {
+        matches!(
+            self,
+            CollisionPolicy::SuffixId | CollisionPolicy::SuffixCount
+        )
+    }
+}
+
+/// Which to-dos `--todo-as-exec` shows with the executable bits set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TodoExec {
+    /// Every to-do
+    All,
+    /// To-dos not yet completed; completed ones look like plain notes
+    Open,
+}
+
+/// A resource embedded in a note, as listed in `<note>.attachments/`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Attachment {
+    /// File name within the attachments directory
+    name: String,
+    resource_id: String,
+    /// The resource's file in the resource directory
+    file: std::path::PathBuf,
+}
+
+/// A resource file kept open while any handle reads it
+///
+/// Opens of the same resource share the file, which `read` seeks within so
+/// only the requested window is ever held in memory.
+#[derive(Debug)]
+struct OpenResource {
+    file: std::fs::File,
+    /// Handles in `resource_handles` referring to this resource
+    handles: usize,
+}
+
+/// The row a rename moves, found by `rename_source`
+#[derive(Debug)]
+struct RenameSource {
+    kind: EntryKind,
+    id: String,
+    parent_folder_id: String,
+    new_parent_folder_id: String,
+    /// The note already at the destination, which a note moved there replaces
+    replaced: Option<String>,
+}
+
+/// One symlink to a note, in `/.recent` or a `.backlinks` directory
+#[derive(Debug, Clone)]
+struct NoteSymlink {
+    /// Name in the directory listing it
+    name: String,
+    /// Link target, relative to that directory
+    target: String,
+    note_id: String,
+}
+
+/// A trashed note or folder listed in `/.trash` (`--include-deleted`)
+#[derive(Debug, Clone)]
+struct TrashEntry {
+    /// The title followed by the deletion time, `Plan (2024-05-01T09:30:00Z).md`
+    name: String,
+    kind: EntryKind,
+    id: String,
+}
+
+/// Which group of entries a directory listing is currently streaming
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DirPhase {
+    Dots,
+    /// Folder ids listed in `/.notebooks`
+    Notebooks,
+    /// Resources listed in a `<note>.attachments` directory
+    Attachments,
+    /// Symlinks to the notes linking to the note of a `<note>.backlinks`
+    /// directory
+    Backlinks,
+    /// Resources listed beside their notes under `--inline-attachment-files`
+    SiblingAttachments,
+    /// Symlinks to recently updated notes listed in `/.recent`
+    Recent,
+    /// Trashed notes and folders listed in `/.trash`
+    Trash,
+    /// Every resource file, listed in `/.resources`
+    Resources,
+    /// Tag directories listed in `/.tags`
+    Tags,
+    /// Notes carrying the tag of a `/.tags/<tag>` directory
+    TagNotes,
+    Folders,
+    Notes,
+    Done,
+}
+
+/// State for an open directory, created by `opendir` and dropped by `releasedir`
+///
+/// Entries are fetched a page at a time in `--sort` order, resuming after
+/// `last_key` (`WHERE title > ?`, or `(created_time, id)` past the cursor),
+/// so only one page is held in memory no matter how large the folder is.
+#[derive(Debug)]
+struct DirHandle {
+    ino: u64,
+    path: String,
+    parent_id: String,
+    phase: DirPhase,
+    /// Sort key and id of the last row fetched in the current phase; the
+    /// keyset cursor for the next page
+    last_key: Option<(Value, String)>,
+    page: std::collections::VecDeque<(u64, FileType, String)>,
+    /// Offset of the last entry accepted by the kernel
+    offset: i64,
+}
+
+/// Free blocks and inodes advertised by `statfs`; SQLite grows on demand
+const STATFS_FREE: u64 = 1 << 30;
+
+/// The fields of a `statfs` reply, in `ReplyStatfs::statfs` order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct StatFs {
+    blocks: u64,
+    bfree: u64,
+    bavail: u64,
+    files: u64,
+    ffree: u64,
+    bsize: u32,
+    namelen: u32,
+    frsize: u32,
+}
+
+/// State for an open note, created by `open`/`create` and dropped by `release`
+#[derive(Debug)]
+struct FileHandle {
+    note_id: String,
+    /// Opened with `O_APPEND`: every write goes to the end of the body
+    append: bool,
+    /// Body with writes not yet committed to the database, when debouncing
+    pending: Option<Vec<u8>>,
+    /// When this handle last committed a body to the database
+    last_commit: Option<Instant>,
+}
+
+/// Watches the database for commits by other processes, such as Joplin
+/// syncing, to pick the TTL under `--adaptive-ttl`
+///
+/// SQLite's `PRAGMA data_version` changes whenever another connection
+/// commits, and never for this connection's own writes. It is polled on
+/// each reply that carries a TTL. While an external commit has been seen
+/// within `ADAPTIVE_TTL_QUIET`, the kernel is told not to cache at all
+/// (`ADAPTIVE_TTL_BUSY`) so the next access sees the new data; once the
+/// database has been quiet that long, entries are cached for
+/// `ADAPTIVE_TTL_IDLE`. The quiet period counts from mount, so a mount
+/// that starts during a sync begins uncached.
+#[derive(Debug, Clone, Copy)]
+struct ChangeWatch {
+    /// `data_version` at the last poll
+    data_version: Option<i64>,
+    /// When an external commit was last seen, or the mount time
+    last_change: Instant,
+}
+
+impl ChangeWatch {
+    fn new() -> Self {
+        ChangeWatch {
+            data_version: None,
+            last_change: Instant::now(),
+        }
+    }
+
+    /// Poll `db` and return the TTL its recent activity calls for
+    fn ttl(&mut self, db: &Connection) -> Duration {
+        let version = db
+            .query_row("PRAGMA data_version", [], |row| row.get::<_, i64>(0))
+            .ok();
+        if version.is_none() || self.data_version.is_some_and(|seen| Some(seen) != version) {
+            self.last_change = Instant::now();
+        }
+        self.data_version = version;
+        if self.last_change.elapsed() < ADAPTIVE_TTL_QUIET {
+            ADAPTIVE_TTL_BUSY
+        } else {
+            ADAPTIVE_TTL_IDLE
+        }
+    }
+}
+
+/// Latency samples per FUSE operation, collected under `--profile`
+///
+/// Disabled profilers hold no storage and their spans record nothing. The
+/// samples sit behind a mutex so spans stay valid should callbacks ever run
+/// on more than one thread.
+#[derive(Debug, Clone, Default)]
+struct Profiler {
+    samples: Option<LatencySamples>,
+}
+
+type LatencySamples = Arc<Mutex<HashMap<&'static str, Vec<Duration>>>>;
+
+impl Profiler {
+    fn new(enabled: bool) -> Self {
+        Profiler {
+            samples: enabled.then(Default::default),
+        }
+    }
+
+    /// Start timing `op`; the sample is recorded when the span is dropped
+    fn span(&self, op: &'static str) -> ProfileSpan {
+        ProfileSpan {
+            profiler: self.clone(),
+            op,
+            start: Instant::now(),
+        }
+    }
+
+    fn record(&self, op: &'static str, elapsed: Duration) {
+        if let Some(samples) = &self.samples {
+            samples
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .entry(op)
+                .or_default()
+                .push(elapsed);
+        }
+    }
+
+    /// One line per operation with its call count, p50/p95/p99 and total
+    /// time, sorted by name; None when profiling is off
+    fn summary(&self) -> Option<String> {
+        let samples = self
+            .samples
+            .as_ref()?
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let mut ops: Vec<_> = samples.iter().collect();
+        ops.sort_by_key(|(op, _)| **op);
+
+        let mut out = format!(
+            "{:<12} {:>8} {:>12} {:>12} {:>12} {:>12}\n",
+            "op", "count", "p50", "p95", "p99", "total"
+        );
+        for (op, durations) in ops {
+            let mut sorted = durations.clone();
+            sorted.sort();
+            // Nearest-rank percentile
+            let pct = |p: usize| sorted[(sorted.len() * p).div_ceil(100).max(1) - 1];
+            out.push_str(&format!(
+                "{:<12} {:>8} {:>12} {:>12} {:>12} {:>12}\n",
+                op,
+                sorted.len(),
+                format!("{:?}", pct(50)),
+                format!("{:?}", pct(95)),
+                format!("{:?}", pct(99)),
+                format!("{:?}", sorted.iter().sum::<Duration>()),
+            ));
+        }
+        Some(out)
+    }
+}
+
+/// Times one callback for the `Profiler` it came from
+struct ProfileSpan {
+    profiler: Profiler,
+    op: &'static str,
+    start: Instant,
+}
+
+impl Drop for ProfileSpan {
+    fn drop(&mut self) {
+        self.profiler.record(self.op, self.start.elapsed());
+    }
+}
+
+/// Mount features whose support differs by platform
+///
+/// | Feature           | Linux (libfuse3)  | macOS (macFUSE) | FreeBSD (fusefs) |
+/// |-------------------|-------------------|-----------------|------------------|
+/// | `auto_unmount`    | yes (fusermount3) | no              | no               |
+/// | `subtype`         | yes               | no              | yes              |
+/// | `allow_root`      | yes               | yes             | yes              |
+/// | READDIRPLUS       | kernel 3.9+       | no              | no               |
+/// | `copy_file_range` | kernel 4.20+      | no              | no               |
+///
+/// Options a platform lacks are dropped with a warning instead of failing the
+/// mount. READDIRPLUS is never requested, so the kernel falls back to
+/// `readdir` + `lookup`. `copy_file_range` needs fuser's `abi-7-28`
+/// feature, which this crate's feature of the same name turns on; without
+/// it the kernel copies with reads and writes.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PlatformSupport {
+    pub(crate) name: &'static str,
+    pub(crate) auto_unmount: bool,
+    pub(crate) subtype: bool,
+    pub(crate) readdirplus: bool,
+    pub(crate) copy_file_range: bool,
+}
+
+pub(crate) const PLATFORM: PlatformSupport = if cfg!(target_os = "linux") {
+    PlatformSupport {
+        name: "Linux",
+        auto_unmount: true,
+        subtype: true,
+        readdirplus: true,
+        copy_file_range: true,
+    }
+} else if cfg!(target_os = "macos") {
+    PlatformSupport {
+        name: "macOS",
+        auto_unmount: false,
+        subtype: false,
+        readdirplus: false,
+        copy_file_range: false,
+    }
+} else {
+    PlatformSupport {
+        name: std::env::consts::OS,
+        auto_unmount: false,
+        subtype: true,
+        readdirplus: false,
+        copy_file_range: false,
+    }
+};
+
+impl PlatformSupport {
+    /// One line per kernel feature, saying whether it is in use and why not
+    fn kernel_summary(&self) -> String {
+        let state = |supported: bool, built: bool| {
+            if !supported {
+                format!("unavailable on {}", self.name)
+            } else if built {
+                "in use".to_string()
+            } else {
+                "supported by the kernel but not requested by this build".to_string()
+            }
+        };
+        format!(
+            "READDIRPLUS: {}\ncopy_file_range: {}",
+            state(self.readdirplus, false),
+            state(self.copy_file_range, cfg!(feature = "abi-7-28"))
+        )
+    }
+}
+
+/// A note's compressed `.gz` sidecar, valid while the note's `updated_time`
+/// and body hash still match
+///
+/// `updated_time` has one-second resolution, so the hash catches edits made
+/// within the same second.
+#[derive(Debug, Clone)]
+struct GzipCacheEntry {
+    updated_time: i64,
+    body_hash: u64,
+    compressed: Arc<[u8]>,
+}
+
+/// Ids of the notes whose bodies hold a `:/<id>` reference, keyed by the id
+/// referenced, as `.backlinks` directories list them
+///
+/// Built by one scan over every body and kept until the newest
+/// `updated_time` or the number of rows in `notes` changes.
+#[derive(Debug)]
+struct BacklinkIndex {
+    stamp: (i64, i64),
+    sources: HashMap<String, Vec<String>>,
+}
+
+/// A Joplin database presented as a FUSE filesystem
+///
+/// Mount it with `fuser::mount2` or `fuser::spawn_mount2`.
+pub struct SqliteFS {
+    db: Connection,
+    inodes: InodeTable,
+    options: FsOptions,
+    attr: AttrConfig,
+    /// Joplin parent_id that "/" maps to: empty for Joplin's top level, or a
+    /// folder id when only that subtree is mounted
+    root_folder_id: String,
+    dir_handles: HashMap<u64, DirHandle>,
+    file_handles: HashMap<u64, FileHandle>,
+    /// Resource id behind each open attachment handle
+    resource_handles: HashMap<u64, String>,
+    /// Open resource files, keyed by resource id
+    open_resources: HashMap<String, OpenResource>,
+    next_fh: u64,
+    profiler: Profiler,
+    /// Compressed bodies served by `.gz` sidecars, keyed by note id
+    gzip_cache: Mutex<HashMap<String, GzipCacheEntry>>,
+    /// Which notes link to which, for `--backlinks`
+    backlink_index: Mutex<Option<BacklinkIndex>>,
+    /// Inode of each note and folder id, loaded from and recorded in
+    /// `INODE_TABLE` under `--persist-inodes`
+    persisted_inodes: HashMap<String, u64>,
+    /// Permission bits given by `create` or `chmod`, keyed by note id
+    ///
+    /// Joplin has no column for them, so they last as long as the mount.
+    note_modes: HashMap<String, u16>,
+    change_watch: ChangeWatch,
+    /// What has been written to a `/.control` file since the last write
+    /// at offset 0
+    control_input: String,
+    /// Answer of `/.control/preview-rename` to the last request
+    rename_preview: String,
+    /// Outcome of the last rebuild through `/.control/rebuild-fts`
+    fts_rebuild: String,
+}
+
+impl SqliteFS {
+    /// Open the database at `db_path` and build a filesystem over it
+    pub fn new(db_path: &str, options: FsOptions) -> std::result::Result<Self, FsError> {
+        let db = if options.read_only {
+            Connection::open_with_flags(
+                db_path,
+                rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY
+                    | rusqlite::OpenFlags::SQLITE_OPEN_URI
+                    | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX,
+            )?
+        } else {
+            Connection::open(db_path)?
+        };
+        db.busy_timeout(DB_BUSY_TIMEOUT)?;
+        Self::from_connection(db, options)
+    }
+
+    /// Build a filesystem over a connection the caller already opened
+    ///
+    /// The filesystem takes ownership of `db` and is its only user from then
+    /// on; close it by dropping the filesystem (or unmounting). Settings such
+    /// as the busy timeout are left as the caller configured them, where
+    /// `new` applies `DB_BUSY_TIMEOUT`. Unless the connection is read-only,
+    /// the indexes the lookups rely on are created if missing, which is the
+    /// only way this can fail.
+    pub fn from_connection(
+        db: Connection,
+        options: FsOptions,
+    ) -> std::result::Result<Self, FsError> {
+        // SQLite silently opens a file it cannot write, or one on read-only
+        // media, read-only; say so now rather than at the first failed write
+        if options.read_only {
+            // Asked for: nothing to warn about, and nothing may be created
+        } else if db.is_readonly(rusqlite::MAIN_DB)? {
+            let name = db
+                .path()
+                .filter(|path| !path.is_empty())
+                .unwrap_or("the database");
+            warn!("{name} is read-only; changes to the mount will fail with EROFS");
+        } else {
+            db.ensure_indexes()?;
+            if options.persist_inodes {
+                db.ensure_inode_table()?;
+            }
+        }
+        let persisted_inodes = if options.persist_inodes {
+            db.persisted_inodes()?
+        } else {
+            HashMap::new()
+        };
+
+        // Room for every statement a busy mount keeps running: lookup,
+        // getattr, read and readdir each use several
+        db.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+        let profile = options.profile;
+        let mut fs = SqliteFS {
+            db,
+            inodes: {
+                let mut inodes = InodeTable::new(&options.pinned_inodes);
+                inodes.set_limit(options.max_inodes);
+                if let Some(&last) = persisted_inodes.values().max() {
+                    inodes.reserve_through(last);
+                }
+                inodes
+            },
+            attr: AttrConfig::from_options(&options),
+            options,
+            root_folder_id: String::new(),
+            dir_handles: HashMap::new(),
+            file_handles: HashMap::new(),
+            resource_handles: HashMap::new(),
+            open_resources: HashMap::new(),
+            next_fh: 1,
+            profiler: Profiler::new(profile),
+            gzip_cache: Mutex::new(HashMap::new()),
+            backlink_index: Mutex::new(None),
+            persisted_inodes,
+            note_modes: HashMap::new(),
+            change_watch: ChangeWatch::new(),
+            control_input: String::new(),
+            rename_preview: String::new(),
+            fts_rebuild: String::new(),
+        };
+        if let Some(folder) = fs.options.folder.clone() {
+            fs.root_folder_id = fs.resolve_root_folder(&folder)?;
+        }
+        Ok(fs)
+    }
+
+    /// The id of the live notebook `folder` names, by id or else by path
+    /// from Joplin's top level, for `--folder`
+    fn resolve_root_folder(&self, folder: &str) -> std::result::Result<String, FsError> {
+        let by_id: Option<String> = self
+            .db
+            .query_row(
+                "SELECT id FROM folders WHERE id = ?1 AND deleted_time = 0",
+                [folder],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(id) = by_id {
+            return Ok(id);
+        }
+        let path = format!("/{}", folder.trim_matches('/'));
+        self.get_parent_folder_id(&path)
+            .map_err(|_| FsError::FolderNotFound(folder.to_string()))
+    }
+
+    /// How long the kernel may cache the entry or attributes in a reply
+    ///
+    /// `attr.ttl`, unless `--adaptive-ttl` picks one from `ChangeWatch`.
+    fn ttl(&mut self) -> Duration {
+        if self.options.adaptive_ttl {
+            self.change_watch.ttl(&self.db)
+        } else {
+            self.attr.ttl
+        }
+    }
+
+    /// The settings every attribute reply is built from
+    pub fn attr_config(&self) -> AttrConfig {
+        self.attr
+    }
+
+    /// The inode of `path`, allocating one if it has none
+    ///
+    /// Fails with ENFILE when `--max-inodes` is reached and every inode is
+    /// still referenced by the kernel.
+    fn get_or_create_inode(&mut self, path: &str) -> std::result::Result<u64, c_int> {
+        if let Some(inode) = self.inodes.inode(path) {
+            return Ok(inode);
+        }
+
+        match self.virtual_dir(path) {
+            Some(dir) => {
+                self.inodes.insert(path, dir.inode());
+                Ok(dir.inode())
+            }
+            None if self.options.persist_inodes => self.persistent_inode(path),
+            None => self.inodes.allocate(path).ok_or(libc::ENFILE),
+        }
+    }
+
+    /// The inode `--persist-inodes` recorded for the note or folder at
+    /// `path`, allocating and recording one the first time its id is seen
+    ///
+    /// Entries without a row of their own (sidecars, attachments and the
+    /// like) and a second path to an already numbered row, such as its
+    /// `/.notebooks` alias, get an inode for this mount only. On a read-only
+    /// database new assignments last until unmount.
+    fn persistent_inode(&mut self, path: &str) -> std::result::Result<u64, c_int> {
+        let Some((_, id)) = self.resolve_path(path) else {
+            return self.inodes.allocate(path).ok_or(libc::ENFILE);
+        };
+        match self.persisted_inodes.get(&id) {
+            Some(&inode) if self.inodes.path(inode).is_none() => self
+                .inodes
+                .assign(path, inode)
+                .then_some(inode)
+                .ok_or(libc::ENFILE),
+            Some(_) => self.inodes.allocate(path).ok_or(libc::ENFILE),
+            None => {
+                let inode = self.inodes.allocate(path).ok_or(libc::ENFILE)?;
+                if !self.read_only()
+                    && let Err(err) = self.db.persist_inode(&id, inode)
+                {
+                    warn!("could not record inode {inode} of {id} in {INODE_TABLE}: {err}");
+                }
+                self.persisted_inodes.insert(id, inode);
+                Ok(inode)
+            }
+        }
+    }
+
+    fn get_path_from_inode(&self, inode: u64) -> Option<&String> {
+        self.inodes.path(inode)
+    }
+
+    fn get_parent_folder_id(&self, parent_path: &str) -> Result<String> {
+        if parent_path == "/" {
+            return Ok(self.root_folder_id.clone());
+        }
+        // A tag directory gathers notes from any folder and is not one itself
+        if self.options.tags
+            && (parent_path == TAGS_DIR || parent_path.starts_with(&format!("{TAGS_DIR}/")))
+        {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+
+        let mut current_parent_id = self.root_folder_id.clone();
+        let mut rest = parent_path;
+
+        // Under /.notebooks the first hop is a folder id rather than a title
+        if let Some(sub) = self.notebooks_subpath(parent_path) {
+            let (id, tail) = sub.split_once('/').unwrap_or((sub, ""));
+            current_parent_id = self.db.query_row(
+                "SELECT id FROM folders WHERE id = ?1 AND deleted_time = 0",
+                [id],
+                |row| row.get(0),
+            )?;
+            rest = tail;
+        }
+
+        let path_parts: Vec<&str> = rest.split('/').filter(|part| !part.is_empty()).collect();
+        if let Some(id) = self.resolve_titles(&current_parent_id, &path_parts)? {
+            return Ok(id);
+        }
+
+        // Otherwise walk the hierarchy one segment at a time, which also
+        // understands decorated names, id suffixes and hidden folders
+
+        // A corrupt database can nest folders in a `parent_id` cycle, which
+        // would make the path endless; stop at the first folder seen twice
+        let mut visited = std::collections::HashSet::from([current_parent_id.clone()]);
+        for part in path_parts {
+            // Find the folder with this title under current_parent_id
+            current_parent_id = self
+                .folder_child(&current_parent_id, part)?
+                .ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+            if !visited.insert(current_parent_id.clone()) {
+                return Err(rusqlite::Error::SqliteFailure(
+                    rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CORRUPT),
+                    Some(format!(
+                        "folder {current_parent_id} is its own ancestor (run `doctor`)"
+                    )),
+                ));
+            }
+        }
+
+        Ok(current_parent_id)
+    }
+
+    /// The folder reached from `start_id` by following `titles` literally,
+    /// resolved in one recursive query
+    ///
+    /// Each level takes the most recently updated live folder of that title.
+    /// `None` when some segment has no such folder, when the path revisits a
+    /// folder, or when `--hide-icon`, `--strict-parent` or
+    /// `--title-collision-policy error` need the segment-by-segment walk.
+    fn resolve_titles(&self, start_id: &str, titles: &[&str]) -> Result<Option<String>> {
+        if self.options.hide_icon.is_some()
+            || self.options.strict_parent
+            || self.options.collision_policy == CollisionPolicy::Error
+        {
+            return Ok(None);
+        }
+        let titles_json = serde_json::to_string(titles).expect("titles serialize to JSON");
+        let mut stmt = self.db.prepare_cached(
+            "WITH RECURSIVE
+                 segment(depth, title) AS (SELECT key, value FROM json_each(?2)),
+                 walk(depth, id) AS (
+                     SELECT 0, ?1
+                     UNION ALL
+                     SELECT walk.depth + 1, (
+                         SELECT f.id FROM folders f
+                         WHERE f.parent_id = walk.id AND f.title = segment.title AND f.deleted_time = 0
+                         ORDER BY f.user_updated_time DESC, f.id LIMIT 1
+                     )
+                     FROM walk JOIN segment ON segment.depth = walk.depth
+                     WHERE walk.id IS NOT NULL
+                 )
+             SELECT id FROM walk WHERE id IS NOT NULL ORDER BY depth",
+        )?;
+        let ids = stmt
+            .query_map((start_id, titles_json.as_str()), |row| {
+                row.get::<_, String>(0)
+            })?
+            .collect::<Result<Vec<_>>>()?;
+        let unique: std::collections::HashSet<&String> = ids.iter().collect();
+        if ids.len() != titles.len() + 1 || unique.len() != ids.len() {
+            return Ok(None);
+        }
+        Ok(ids.last().cloned())
+    }
+
+    /// The folder titled `title` directly under `parent_id`
+    ///
+    /// Duplicate titles resolve to the most recently updated folder, except
+    /// under `--strict-parent` or `--title-collision-policy error`, where
+    /// they fail with `QueryReturnedMoreThanOneRow` so the ambiguity is
+    /// reported.
+    ///
+    /// Folders hidden by `--hide-icon` are skipped as if they did not exist.
+    /// Under `--decorate-notebooks`, `[tag] Title` names the top-level
+    /// folder `Title` when `tag` is its decoration.
+    fn folder_child(&self, parent_id: &str, title: &str) -> Result<Option<String>> {
+        let found = self.folder_child_titled(parent_id, title)?;
+        if found.is_some() {
+            return Ok(found);
+        }
+        if self.decorates(parent_id)
+            && let Some((tag, title)) = split_decoration(title)
+            && let Some(id) = self.folder_child_titled(parent_id, title)?
+            && self.notebook_tag(&id)?.as_deref() == Some(tag)
+        {
+            return Ok(Some(id));
+        }
+        // An older duplicate, listed as `Title (a1b2c3)`
+        let Some((title, fragment)) = Self::split_id_fragment(title) else {
+            return Ok(None);
+        };
+        let fragment = fragment.to_ascii_lowercase();
+        Ok(self
+            .folder_candidates(parent_id, title)?
+            .into_iter()
+            .find(|id| id.starts_with(&fragment)))
+    }
+
+    /// The folder whose title is exactly `title` directly under `parent_id`,
+    /// as `folder_child` resolves it
+    fn folder_child_titled(&self, parent_id: &str, title: &str) -> Result<Option<String>> {
+        let ids = self.folder_candidates(parent_id, title)?;
+        let strict =
+            self.options.strict_parent || self.options.collision_policy == CollisionPolicy::Error;
+        if strict && ids.len() > 1 {
+            return Err(rusqlite::Error::QueryReturnedMoreThanOneRow);
+        }
+        Ok(ids.into_iter().next())
+    }
+
+    /// Ids of the visible folders titled `title` under `parent_id`, most
+    /// recently updated first
+    fn folder_candidates(&self, parent_id: &str, title: &str) -> Result<Vec<String>> {
+        let mut stmt = self.db.prepare_cached(
+            "SELECT id FROM folders WHERE parent_id = ?1 AND title = ?2 AND deleted_time = 0 ORDER BY user_updated_time DESC, id",
+        )?;
+        let mut ids = Vec::new();
+        for id in stmt.query_map([parent_id, title], |row| row.get::<_, String>(0))? {
+            let id = id?;
+            if !self.is_hidden_folder(&id)? {
+                ids.push(id);
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Whether `--decorate-notebooks` applies to the folders under `parent_id`:
+    /// those listed at the mount root
+    fn decorates(&self, parent_id: &str) -> bool {
+        self.options.decorate_notebooks && parent_id == self.root_folder_id
+    }
+
+    /// The short tag `--decorate-notebooks` shows before a notebook's title
+    ///
+    /// Read from the top-level `tag` key of the folder's `user_data` JSON,
+    /// or `color` when there is no tag, such as `{"color":"red"}`. Values
+    /// that are not strings, are blank, or hold `]`, `/` or a control
+    /// character give no decoration.
+    fn notebook_tag(&self, id: &str) -> Result<Option<String>> {
+        let user_data: Option<String> = self
+            .db
+            .query_row("SELECT user_data FROM folders WHERE id = ?1", [id], |row| {
+                row.get(0)
+            })
+            .optional()?
+            .flatten();
+        let Some(Ok(serde_json::Value::Object(data))) =
+            user_data.map(|text| serde_json::from_str::<serde_json::Value>(&text))
+        else {
+            return Ok(None);
+        };
+        let tag = ["tag", "color"]
+            .into_iter()
+            .find_map(|key| data.get(key).and_then(serde_json::Value::as_str))
+            .map(str::trim)
+            .filter(|tag| {
+                !tag.is_empty() && !tag.contains([']', '/']) && !tag.chars().any(char::is_control)
+            });
+        Ok(tag.map(str::to_string))
+    }
+
+    /// The name the folder `title` is listed under in `parent_id`: `[tag]
+    /// Title` for a decorated top-level notebook, the title otherwise
+    fn decorated_folder_name(&self, parent_id: &str, title: String) -> Result<String> {
+        if !self.decorates(parent_id) {
+            return Ok(title);
+        }
+        let Some(id) = self.folder_child_titled(parent_id, &title)? else {
+            return Ok(title);
+        };
+        Ok(match self.notebook_tag(&id)? {
+            Some(tag) => format!("[{tag}] {title}"),
+            None => title,
+        })
+    }
+
+    /// The name the folder row `id` titled `title` is listed under when every
+    /// row gets an entry: the decorated title for the most recently updated
+    /// folder of that title, `Title (a1b2c3)` for older ones, None for a
+    /// folder `--hide-icon` hides
+    fn listed_folder_name(
+        &self,
+        parent_id: &str,
+        title: String,
+        id: &str,
+    ) -> Result<Option<String>> {
+        let candidates = self.folder_candidates(parent_id, &title)?;
+        if !candidates.iter().any(|candidate| candidate == id) {
+            return Ok(None);
+        }
+        if candidates[0] == id {
+            return self.decorated_folder_name(parent_id, title).map(Some);
+        }
+        let fragment: String = id.chars().take(6).collect();
+        Ok(Some(format!("{title} ({fragment})")))
+    }
+
+    /// Whether `--hide-icon` hides the folder `id`
+    ///
+    /// Joplin stores a folder's `icon` as JSON such as
+    /// `{"emoji":"🗄️","name":"file cabinet","type":1}`; its `emoji` field,
+    /// or a bare non-JSON icon string, is compared with the flag's value
+    /// after trimming whitespace and dropping U+FE0F variation selectors, so
+    /// `🗄` and `🗄️` match each other. Image icons never match.
+    fn is_hidden_folder(&self, id: &str) -> Result<bool> {
+        let Some(hidden) = &self.options.hide_icon else {
+            return Ok(false);
+        };
+        let icon: Option<String> = self
+            .db
+            .query_row("SELECT icon FROM folders WHERE id = ?1", [id], |row| {
+                row.get(0)
+            })
+            .optional()?
+            .flatten();
+        let Some(icon) = icon.filter(|icon| !icon.trim().is_empty()) else {
+            return Ok(false);
+        };
+        let emoji = match serde_json::from_str::<serde_json::Value>(&icon) {
+            Ok(value) => value
+                .get("emoji")
+                .and_then(serde_json::Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            Err(_) => icon,
+        };
+        Ok(normalize_emoji(&emoji) == normalize_emoji(hidden) && !hidden.trim().is_empty())
+    }
+
+    /// Errno for a path that failed to resolve: EIO when `--strict-parent` or
+    /// `--title-collision-policy error` found it ambiguous or it runs through
+    /// a folder cycle, ENOENT otherwise
+    fn resolve_errno(err: rusqlite::Error) -> c_int {
+        match err {
+            rusqlite::Error::QueryReturnedMoreThanOneRow => libc::EIO,
+            err if err.sqlite_error_code() == Some(rusqlite::ErrorCode::DatabaseCorrupt) => {
+                libc::EIO
+            }
+            _ => ENOENT,
+        }
+    }
+
+    /// The enabled virtual directory `path` names, if any
+    fn virtual_dir(&self, path: &str) -> Option<VirtualDir> {
+        VirtualDir::ALL.into_iter().find(|dir| {
+            dir.path() == path
+                && match dir {
+                    VirtualDir::Notebooks => self.options.notebooks_by_id,
+                    VirtualDir::Trash => self.options.include_deleted,
+                    VirtualDir::Recent => self.options.recent_count > 0,
+                    VirtualDir::Control => self.options.control,
+                    VirtualDir::Tags => self.options.tags,
+                    VirtualDir::Resources => self.options.resources,
+                }
+        })
+    }
+
+    /// Whether `path` is the virtual `/.notebooks` directory itself
+    fn is_notebooks_dir(&self, path: &str) -> bool {
+        self.virtual_dir(path) == Some(VirtualDir::Notebooks)
+    }
+
+    /// The id of the tag whose `/.tags/<tag>` directory `path` names, under
+    /// `--tags`
+    fn tag_dir(&self, path: &str) -> Option<String> {
+        if !self.options.tags {
+            return None;
+        }
+        let title = path.strip_prefix(TAGS_DIR)?.strip_prefix('/')?;
+        if !is_tag_name(title) {
+            return None;
+        }
+        self.db
+            .query_row(
+                "SELECT id FROM tags WHERE title = ?1 ORDER BY id LIMIT 1",
+                [title],
+                |row| row.get(0),
+            )
+            .ok()
+    }
+
+    /// Titles of the tags `/.tags` lists, one directory each
+    ///
+    /// Titles that cannot be a file name (empty, `.`, `..` or holding a
+    /// `/`) are left out.
+    fn tag_titles(&self) -> std::result::Result<Vec<String>, c_int> {
+        let mut stmt = self
+            .db
+            .prepare("SELECT title FROM tags GROUP BY title ORDER BY title")
+            .map_err(|_| libc::EIO)?;
+        let titles = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .and_then(|rows| rows.collect::<Result<Vec<_>>>())
+            .map_err(|_| libc::EIO)?;
+        Ok(titles
+            .into_iter()
+            .filter(|title| is_tag_name(title))
+            .collect())
+    }
+
+    /// The live notes carrying tag `tag_id`, as listed in its `/.tags/<tag>`
+    /// directory `dir`
+    ///
+    /// They are files rather than links, but named as `note_symlinks` names
+    /// links: like the note in its folder, with the id suffix when two
+    /// tagged notes share a name.
+    fn tag_notes(&self, tag_id: &str, dir: &str) -> std::result::Result<Vec<NoteSymlink>, c_int> {
+        let rows = {
+            let mut stmt = self
+                .db
+                .prepare(
+                    "SELECT n.id, n.title, n.parent_id FROM note_tags nt JOIN notes n ON n.id = nt.note_id \
+                     WHERE nt.tag_id = ?1 AND n.deleted_time = 0 GROUP BY n.id ORDER BY n.title, n.id",
+                )
+                .map_err(|_| libc::EIO)?;
+            stmt.query_map([tag_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+                .and_then(|rows| rows.collect::<Result<Vec<_>>>())
+                .map_err(|_| libc::EIO)?
+        };
+        self.note_symlinks(dir, rows)
+    }
+
+    /// The part of `path` below `/.notebooks/`, starting with a folder id
+    fn notebooks_subpath<'a>(&self, path: &'a str) -> Option<&'a str> {
+        if !self.options.notebooks_by_id {
+            return None;
+        }
+        path.strip_prefix(NOTEBOOKS_DIR)?
+            .strip_prefix('/')
+            .filter(|sub| !sub.is_empty())
+    }
+
+    fn name_max(&self) -> u32 {
+        self.options.name_max.unwrap_or(DEFAULT_NAME_MAX)
+    }
+
+    fn block_size(&self) -> u32 {
+        self.attr.block_size
+    }
+
+    /// Refuse names longer than `--name-max` bytes with ENAMETOOLONG
+    fn check_name_len(&self, name: &str) -> std::result::Result<(), c_int> {
+        if name.len() > self.name_max() as usize {
+            return Err(libc::ENAMETOOLONG);
+        }
+        Ok(())
+    }
+
+    /// Refuse to create, rename or remove `name` in `parent_path` when that
+    /// would touch a virtual directory itself rather than a real notebook's
+    /// contents
+    fn check_not_virtual(&self, parent_path: &str, name: &str) -> std::result::Result<(), c_int> {
+        if self.virtual_dir(parent_path).is_some()
+            || self.tag_dir(parent_path).is_some()
+            || (parent_path == "/" && self.virtual_dir(&join_path("/", name)).is_some())
+        {
+            return Err(libc::EPERM);
+        }
+        Ok(())
+    }
+
+    /// Map a filesystem name to the note title stored in the database
+    ///
+    /// See `split_note_extension`; only the title is kept.
+    fn strip_note_suffix<'a>(&self, filename: &'a str) -> &'a str {
+        self.split_note_extension(filename).0
+    }
+
+    /// Split a filesystem name into the note title and the display extension
+    ///
+    /// Exactly one trailing extension is removed: `.md`, or that of a
+    /// `markup_language` (`.html`, or one added by `--markup-ext`). Anything
+    /// before it, including another `.md`, is part of the title. So
+    /// `report.md` is the note titled `report`, `report.md.md` the one
+    /// titled `report.md`, and a bare `report` (no extension) also names the
+    /// note `report`.
+    ///
+    /// With `--no-md-suffix` names have no extension. With
+    /// `--ignore-extension-case` any casing of the extension is stripped,
+    /// while the rest of the name keeps its case; the extension is returned
+    /// as configured.
+    ///
+    /// Under `--todo-suffix todo`, a `.todo` just before the extension is
+    /// taken with it: `task.todo.md` is the to-do `task` with extension
+    /// `todo.md`.
+    fn split_note_extension<'a>(&self, filename: &'a str) -> (&'a str, Option<Cow<'_, str>>) {
+        if self.options.no_md_suffix {
+            return (filename, None);
+        }
+        let extensions = DEFAULT_MARKUP_EXTS
+            .iter()
+            .map(|(_, ext)| *ext)
+            .chain(self.options.markup_exts.values().map(String::as_str));
+        let mut found: Option<&str> = None;
+        for ext in extensions {
+            let Some(stem_len) = filename.len().checked_sub(ext.len() + 1) else {
+                continue;
+            };
+            let Some(suffix) = filename.get(stem_len..).and_then(|s| s.strip_prefix('.')) else {
+                continue;
+            };
+            let matches = if self.options.ignore_extension_case {
+                suffix.eq_ignore_ascii_case(ext)
+            } else {
+                suffix == ext
+            };
+            if matches && found.is_none_or(|found| ext.len() > found.len()) {
+                found = Some(ext);
+            }
+        }
+        let Some(ext) = found else {
+            return (filename, None);
+        };
+        let stem = &filename[..filename.len() - ext.len() - 1];
+        match &self.options.todo_suffix {
+            Some(marker)
+                if let Some(title) = stem
+                    .strip_suffix(marker.as_str())
+                    .and_then(|rest| rest.strip_suffix('.')) =>
+            {
+                (title, Some(Cow::Owned(format!("{marker}.{ext}"))))
+            }
+            _ => (stem, Some(Cow::Borrowed(ext))),
+        }
+    }
+
+    /// The markup extension within `ext` when it carries the `--todo-suffix`
+    /// marker: `md` for `todo.md`
+    fn unmarked_ext<'e>(&self, ext: &'e str) -> Option<&'e str> {
+        ext.strip_prefix(self.options.todo_suffix.as_deref()?)?
+            .strip_prefix('.')
+    }
+
+    /// Map a note title to the name displayed in the filesystem for a
+    /// Markdown note
+    fn add_md_suffix(&self, title: &str) -> String {
+        self.with_extension(title, "md")
+    }
+
+    /// Map a note title to the name displayed in the filesystem
+    ///
+    /// The inverse of `split_note_extension`: `.ext` is always appended, even
+    /// to a title already ending in an extension, so every title
+    /// round-trips. With `--no-md-suffix`, or for a symlink's empty
+    /// extension, this is the identity function.
+    fn with_extension(&self, title: &str, ext: &str) -> String {
+        if self.options.no_md_suffix || ext.is_empty() {
+            title.to_string()
+        } else {
+            format!("{title}.{ext}")
+        }
+    }
+
+    /// File extension of notes whose `markup_language` is `markup`
+    fn markup_ext(&self, markup: i64) -> &str {
+        if let Some(ext) = self.options.markup_exts.get(&markup) {
+            return ext;
+        }
+        DEFAULT_MARKUP_EXTS
+            .iter()
+            .find(|(value, _)| *value == markup)
+            .map_or("md", |(_, ext)| ext)
+    }
+
+    /// The `markup_language` a note named with extension `ext` is given:
+    /// the smallest value shown with it
+    fn markup_for_ext(&self, ext: &str) -> Option<i64> {
+        let ext = self.unmarked_ext(ext).unwrap_or(ext);
+        DEFAULT_MARKUP_EXTS
+            .iter()
+            .map(|(value, _)| *value)
+            .chain(self.options.markup_exts.keys().copied())
+            .filter(|&value| self.markup_ext(value) == ext)
+            .min()
+    }
+
+    /// Display extension of note `id`, following its `markup_language` and,
+    /// under `--todo-suffix`, led by the marker for a to-do
+    ///
+    /// Symlinks have none: they are listed under their bare title.
+    fn note_ext(&self, id: &str) -> Result<Cow<'_, str>> {
+        if self.is_symlink_note(id)? {
+            return Ok(Cow::Borrowed(""));
+        }
+        let query = if self.options.todo_suffix.is_some() {
+            "SELECT markup_language, is_todo FROM notes WHERE id = ?1"
+        } else {
+            "SELECT markup_language, 0 FROM notes WHERE id = ?1"
+        };
+        let (markup, is_todo) = self
+            .db
+            .prepare_cached(query)?
+            .query_row([id], |row| {
+                Ok((row.get::<_, Option<i64>>(0)?, row.get::<_, i64>(1)?))
+            })
+            .optional()?
+            .unwrap_or((None, 0));
+        let ext = self.markup_ext(markup.unwrap_or(1));
+        Ok(match &self.options.todo_suffix {
+            Some(marker) if is_todo != 0 => Cow::Owned(format!("{marker}.{ext}")),
+            _ => Cow::Borrowed(ext),
+        })
+    }
+
+    /// Errno for a failed database write
+    ///
+    /// ENOSPC when SQLite reports `SQLITE_FULL`, so tools can say "no space
+    /// left on device"; EIO for I/O and every other error.
+    fn write_errno(err: rusqlite::Error) -> c_int {
+        if let rusqlite::Error::ToSqlConversionFailure(_) = err {
+            // A body with characters `--encoding` cannot represent
+            return libc::EILSEQ;
+        }
+        match err.sqlite_error_code() {
+            Some(rusqlite::ErrorCode::DiskFull) => libc::ENOSPC,
+            Some(rusqlite::ErrorCode::ReadOnly) => libc::EROFS,
+            _ => libc::EIO,
+        }
+    }
+
+    /// Bind `body` for storage, encoded per `--encoding`
+    ///
+    /// Without it the bytes are stored as they are, valid UTF-8 or not. An
+    /// encoding can only represent text, so invalid UTF-8 is unmappable.
+    fn body_param<'a>(&self, body: &'a [u8]) -> StoredBody<'a> {
+        match self.options.encoding {
+            None => StoredBody {
+                bytes: Cow::Borrowed(body),
+                unmappable_in: None,
+            },
+            Some(encoding) => {
+                let Ok(body) = std::str::from_utf8(body) else {
+                    return StoredBody {
+                        bytes: Cow::Borrowed(body),
+                        unmappable_in: Some(encoding),
+                    };
+                };
+                let (bytes, _, had_errors) = encoding.encode(body);
+                StoredBody {
+                    bytes,
+                    unmappable_in: had_errors.then_some(encoding),
+                }
+            }
+        }
+    }
+
+    /// Generate a UUID v4 string for database record IDs
+    fn generate_uuid() -> String {
+        Uuid::new_v4().to_string()
+    }
+
+    /// Create a new folder in the database
+    ///
+    /// This helper method handles the database insertion for new folders,
+    /// including UUID generation, timestamp management, and parent relationship setup.
+    ///
+    /// Arguments:
+    /// - parent_path: Filesystem path of the parent directory (e.g., "/Projects")
+    /// - folder_name: Name of the new folder to create
+    ///
+    /// Returns:
+    /// - Ok(String): UUID of the newly created folder
+    /// - Err: Database error if insertion fails
+    fn create_folder(&mut self, parent_path: &str, folder_name: &str) -> Result<String> {
+        // Get the parent folder ID
+        let parent_folder_id = self.get_parent_folder_id(parent_path)?;
+
+        // Generate new UUID for the folder
+        let folder_id = Self::generate_uuid();
+
+        // Get current timestamp
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        // Insert new folder into database
+        self.db.execute(
+            "INSERT INTO folders (id, title, created_time, updated_time, user_created_time, user_updated_time, parent_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            [&folder_id, folder_name, &now.to_string(), &now.to_string(), &now.to_string(), &now.to_string(), &parent_folder_id],
+        )?;
+        self.db.touch_folder(&parent_folder_id, now)?;
+
+        Ok(folder_id)
+    }
+
+    /// Create a new note (file) in the database
+    ///
+    /// This helper method handles the database insertion for new notes,
+    /// including UUID generation, content storage, and parent relationship setup.
+    ///
+    /// Arguments:
+    /// - parent_path: Filesystem path of the parent directory (e.g., "/Projects")
+    /// - file_name: Name of the new file (with .md suffix, will be stripped for DB)
+    /// - content: Initial content to store in the note's body field
+    ///
+    /// With `--create-parents`, missing folders along `parent_path` are
+    /// created first, like `mkdir -p`, in the same transaction as the note.
+    ///
+    /// Returns:
+    /// - Ok(String): UUID of the newly created note
+    /// - Err: Database error if insertion fails
+    fn create_note(&mut self, parent_path: &str, file_name: &str, content: &str) -> Result<String> {
+        if self.options.create_parents {
+            return self.in_savepoint(|fs| {
+                fs.create_folder_path(parent_path)?;
+                fs.insert_note(parent_path, file_name, content)
+            });
+        }
+        self.insert_note(parent_path, file_name, content)
+    }
+
+    /// Insert the note row for `create_note` under an existing parent
+    fn insert_note(&mut self, parent_path: &str, file_name: &str, content: &str) -> Result<String> {
+        // Get the parent folder ID
+        let parent_folder_id = self.get_parent_folder_id(parent_path)?;
+
+        // Strip the extension from filename for database storage; it picks
+        // the markup language
+        let (note_title, ext) = self.split_note_extension(file_name);
+        let markup = ext
+            .as_deref()
+            .and_then(|ext| self.markup_for_ext(ext))
+            .unwrap_or(1);
+        let is_todo = ext
+            .as_deref()
+            .is_some_and(|ext| self.unmarked_ext(ext).is_some());
+
+        let note_id =
+            self.insert_note_row(&parent_folder_id, note_title, content.as_bytes(), markup)?;
+        if is_todo {
+            self.db
+                .execute("UPDATE notes SET is_todo = 1 WHERE id = ?1", [&note_id])?;
+        }
+        Ok(note_id)
+    }
+
+    /// Insert a note titled `title` into the folder `parent_folder_id` and
+    /// return its new id
+    fn insert_note_row(
+        &mut self,
+        parent_folder_id: &str,
+        title: &str,
+        body: &[u8],
+        markup: i64,
+    ) -> Result<String> {
+        // Generate new UUID for the note
+        let note_id = Self::generate_uuid();
+
+        // Get current timestamp
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        // Insert new note into database
+        self.db.execute(
+            "INSERT INTO notes (id, title, body, created_time, updated_time, user_created_time, user_updated_time, parent_id, markup_language) VALUES (?1, ?2, ?3, ?4, ?4, ?4, ?4, ?5, ?6)",
+            rusqlite::params![note_id, title, self.body_param(body), now, parent_folder_id, markup],
+        )?;
+        self.db.touch_folder(parent_folder_id, now)?;
+
+        Ok(note_id)
+    }
+
+    /// Resolve the folder at `path`, creating it and any missing ancestors
+    fn create_folder_path(&mut self, path: &str) -> Result<String> {
+        match self.get_parent_folder_id(path) {
+            Err(rusqlite::Error::QueryReturnedNoRows) if path != "/" => {
+                let (parent, name) = split_path(path);
+                self.create_folder_path(parent)?;
+                self.create_folder(parent, name)
+            }
+            resolved => resolved,
+        }
+    }
+
+    /// Run `f` inside a savepoint, rolling back everything it wrote if it fails
+    fn in_savepoint<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        self.db.execute_batch("SAVEPOINT fs_op")?;
+        let result = f(self);
+        let end = if result.is_ok() {
+            "RELEASE fs_op"
+        } else {
+            "ROLLBACK TO fs_op; RELEASE fs_op"
+        };
+        self.db.execute_batch(end)?;
+        result
+    }
+
+    /// Run `f` in a transaction that takes the database's write lock up front
+    ///
+    /// Reads made by `f` therefore cannot be invalidated by another process
+    /// before `f` writes; a competing writer waits up to `DB_BUSY_TIMEOUT`.
+    /// Rolled back if `f` fails.
+    fn in_write_transaction<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> std::result::Result<T, c_int>,
+    ) -> std::result::Result<T, c_int> {
+        self.db
+            .execute_batch("BEGIN IMMEDIATE")
+            .map_err(Self::write_errno)?;
+        let result = f(self).and_then(|value| {
+            self.db.execute_batch("COMMIT").map_err(Self::write_errno)?;
+            Ok(value)
+        });
+        // SQLite may already have rolled back, e.g. after SQLITE_FULL
+        if result.is_err() && !self.db.is_autocommit() {
+            let _ = self.db.execute_batch("ROLLBACK");
+        }
+        result
+    }
+
+    /// Resolve a filesystem path to the kind and id of the row it names
+    ///
+    /// Folders take precedence over notes of the same name, matching `lookup`.
+    /// Returns None for the root directory and the virtual directories, which
+    /// have no backing row.
+    fn resolve_path(&self, path: &str) -> Option<(EntryKind, String)> {
+        self.resolve_path_checked(path).ok().flatten()
+    }
+
+    /// `resolve_path`, keeping the error when resolution fails
+    ///
+    /// Ambiguity that `--strict-parent` or `--title-collision-policy error`
+    /// refuses to settle is `QueryReturnedMoreThanOneRow`.
+    fn resolve_path_checked(&self, path: &str) -> Result<Option<(EntryKind, String)>> {
+        let (parent, name) = split_path(path);
+        if name.is_empty() {
+            return Ok(None);
+        }
+        if let Some(tag_id) = self.tag_dir(parent) {
+            // A failed listing finds nothing, as any unresolvable path
+            return Ok(self
+                .tag_notes(&tag_id, parent)
+                .unwrap_or_default()
+                .into_iter()
+                .find(|entry| entry.name == name)
+                .map(|entry| (EntryKind::Note, entry.note_id)));
+        }
+        if self.is_notebooks_dir(parent) {
+            let id: Option<String> = self
+                .db
+                .query_row(
+                    "SELECT id FROM folders WHERE id = ?1 AND deleted_time = 0",
+                    [name],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            return match id {
+                Some(id) if !self.is_hidden_folder(&id)? => Ok(Some((EntryKind::Folder, id))),
+                _ => Ok(None),
+            };
+        }
+        let parent_folder_id = match self.get_parent_folder_id(parent) {
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+            result => result?,
+        };
+        if let Some(id) = self.folder_index_note(&parent_folder_id, name)? {
+            return Ok(Some((EntryKind::Note, id)));
+        }
+
+        if let Some(id) = self.folder_child(&parent_folder_id, name)? {
+            if self.options.collision_policy == CollisionPolicy::Error
+                && self.note_listed_as(&parent_folder_id, name)?
+            {
+                return Err(rusqlite::Error::QueryReturnedMoreThanOneRow);
+            }
+            return Ok(Some((EntryKind::Folder, id)));
+        }
+        Ok(self
+            .resolve_note(&parent_folder_id, name)?
+            .map(|id| (EntryKind::Note, id)))
+    }
+
+    /// The spelling of `name` that resolves in `parent_path` when it differs
+    /// from a stored title only in Unicode normalization
+    ///
+    /// macOS passes names decomposed (NFD) while Joplin usually stores them
+    /// composed (NFC), so under `--normalize-unicode` a name that does not
+    /// resolve as given is compared with the titles in the folder, both in
+    /// NFC, and rebuilt around the stored title. The longest matching title
+    /// wins, so suffixed and decorated names are rebuilt too. Otherwise, and
+    /// for ASCII names, `name` is returned unchanged.
+    fn canonical_name<'a>(&self, parent_path: &str, name: &'a str) -> Cow<'a, str> {
+        if !self.options.normalize_unicode
+            || name.is_ascii()
+            || self.resolve_path(&join_path(parent_path, name)).is_some()
+        {
+            return Cow::Borrowed(name);
+        }
+        let Ok(parent_id) = self.get_parent_folder_id(parent_path) else {
+            return Cow::Borrowed(name);
+        };
+        let Ok(mut titles) = self.folder_titles(&parent_id) else {
+            return Cow::Borrowed(name);
+        };
+        titles.retain(|title| !title.is_ascii());
+        titles.sort_by_key(|title| std::cmp::Reverse(title.len()));
+
+        let wanted: String = name.nfc().collect();
+        for title in titles {
+            let composed: String = title.nfc().collect();
+            let Some(start) = wanted.find(&composed) else {
+                continue;
+            };
+            let candidate = format!(
+                "{}{title}{}",
+                &wanted[..start],
+                &wanted[start + composed.len()..]
+            );
+            if self
+                .resolve_path(&join_path(parent_path, &candidate))
+                .is_some()
+            {
+                return Cow::Owned(candidate);
+            }
+        }
+        Cow::Borrowed(name)
+    }
+
+    /// Titles of the live folders and notes directly under `parent_id`
+    fn folder_titles(&self, parent_id: &str) -> Result<Vec<String>> {
+        let mut stmt = self.db.prepare(
+            "SELECT title FROM folders WHERE parent_id = ?1 AND deleted_time = 0
+             UNION SELECT title FROM notes WHERE parent_id = ?1 AND deleted_time = 0",
+        )?;
+        stmt.query_map([parent_id], |row| row.get(0))?.collect()
+    }
+
+    /// The note `.index.md` stands for when `name` is that alias under
+    /// `--folder-index`: the newest note in `parent_id` with the configured
+    /// title
+    fn folder_index_note(&self, parent_id: &str, name: &str) -> Result<Option<String>> {
+        match &self.options.folder_index {
+            Some(title) if name == FOLDER_INDEX_NAME => Ok(self
+                .db
+                .note_candidates(parent_id, title)?
+                .into_iter()
+                .next()),
+            _ => Ok(None),
+        }
+    }
+
+    /// Whether a note in `parent_id` would be listed under exactly `name`
+    /// were it not for a folder of that name
+    fn note_listed_as(&self, parent_id: &str, name: &str) -> Result<bool> {
+        let title = self.strip_note_suffix(name);
+        match self.db.note_candidates(parent_id, title)?.first() {
+            Some(newest) => Ok(self.with_extension(title, &self.note_ext(newest)?) == name),
+            None => Ok(false),
+        }
+    }
+
+    /// The note a display name in `parent_id` refers to, once no folder
+    /// claimed the name
+    ///
+    /// The plain name is the newest note with that title. `Title (a1b2c3)`
+    /// addresses a duplicate by the start of its id under every policy, and
+    /// `Title (2)` by position under `suffix-count`. Under `error`, a title
+    /// shared by several notes fails with `QueryReturnedMoreThanOneRow`.
+    ///
+    /// Under `--number-by-order`, `010-Title` names the note ranked 10th
+    /// whose name is otherwise `Title`; the plain name resolves as well.
+    ///
+    /// An extension must be the note's own (`.html` does not find a Markdown
+    /// note); a bare name finds the note whatever its extension. So under
+    /// `--todo-suffix`, `task.todo.md` finds only a to-do and `task.md` only
+    /// a plain note, unless the plain note's title is `task.todo`.
+    fn resolve_note(&self, parent_id: &str, name: &str) -> Result<Option<String>> {
+        if self.options.number_by_order
+            && let Some((rank, rest)) = Self::split_order_prefix(name)
+            && let Some((id, title)) = self.note_at_order_rank(parent_id, rank)?
+            && self
+                .listed_note_name(parent_id, &title, &id)
+                .ok()
+                .flatten()
+                .as_deref()
+                == Some(rest)
+        {
+            return Ok(Some(id));
+        }
+        let (title, ext) = self.split_note_extension(name);
+        if let Some(id) = self.resolve_note_with_ext(parent_id, title, ext.as_deref())? {
+            return Ok(Some(id));
+        }
+        // A note whose title itself ends in the to-do marker
+        if let Some(ext) = ext.as_deref().and_then(|ext| self.unmarked_ext(ext))
+            && let Some(id) = self.resolve_note_with_ext(
+                parent_id,
+                &name[..name.len() - ext.len() - 1],
+                Some(ext),
+            )?
+        {
+            return Ok(Some(id));
+        }
+        // A symlink, whose name is its whole title
+        match ext {
+            Some(_) => self.resolve_note_with_ext(parent_id, name, Some("")),
+            None => Ok(None),
+        }
+    }
+
+    /// `resolve_note_title`, keeping the note only when its extension is
+    /// `ext` or no extension was given
+    fn resolve_note_with_ext(
+        &self,
+        parent_id: &str,
+        title: &str,
+        ext: Option<&str>,
+    ) -> Result<Option<String>> {
+        let Some(id) = self.resolve_note_title(parent_id, title, ext)? else {
+            return Ok(None);
+        };
+        match ext {
+            Some(ext) if self.note_ext(&id)? != ext => Ok(None),
+            _ => Ok(Some(id)),
+        }
+    }
+
+    /// The note `resolve_note` finds for a name without its extension `ext`,
+    /// before checking the extension
+    fn resolve_note_title(
+        &self,
+        parent_id: &str,
+        title: &str,
+        ext: Option<&str>,
+    ) -> Result<Option<String>> {
+        let candidates = self.db.note_candidates(parent_id, title)?;
+        if self.options.collision_policy == CollisionPolicy::Error && candidates.len() > 1 {
+            return Err(rusqlite::Error::QueryReturnedMoreThanOneRow);
+        }
+        if let Some(id) = candidates.into_iter().next() {
+            return Ok(Some(id));
+        }
+
+        if let Some((title, fragment)) = Self::split_id_fragment(title) {
+            let fragment = fragment.to_lowercase();
+            return Ok(self
+                .db
+                .note_candidates(parent_id, title)?
+                .into_iter()
+                .find(|id| id.starts_with(&fragment)));
+        }
+        if self.options.collision_policy == CollisionPolicy::SuffixCount
+            && let Some((title, number)) = Self::split_count_suffix(title)
+        {
+            // A folder holding the plain name pushes every note down one
+            let plain = self.with_extension(title, ext.unwrap_or(self.markup_ext(1)));
+            let shadowed = self.folder_child(parent_id, &plain)?.is_some();
+            return Ok(self
+                .db
+                .note_candidates(parent_id, title)?
+                .into_iter()
+                .nth(number - 1 - usize::from(shadowed)));
+        }
+        Ok(None)
+    }
+
+    /// The name `readdir` lists the note `id` titled `title` under, or None
+    /// when `--title-collision-policy` hides it
+    ///
+    /// Under the policies listing one entry per title, the page query has
+    /// already picked the newest row, so only folder shadowing is checked.
+    fn listed_note_name(
+        &self,
+        parent_id: &str,
+        title: &str,
+        id: &str,
+    ) -> std::result::Result<Option<String>, c_int> {
+        let ext = self.listed_note_ext(parent_id, title, id)?;
+        let name = self.with_extension(title, &ext);
+        let shadowed = self.is_shadowed_note_name(parent_id, &name)?;
+        let policy = self.options.collision_policy;
+        if !policy.lists_every_note() {
+            return Ok((!shadowed).then_some(name));
+        }
+
+        let candidates = self
+            .db
+            .note_candidates(parent_id, title)
+            .map_err(|_| libc::EIO)?;
+        let rank = candidates.iter().position(|c| c == id).unwrap_or(0);
+        if rank == 0 && !shadowed {
+            return Ok(Some(name));
+        }
+        let suffix = match policy {
+            CollisionPolicy::SuffixCount => (rank + 1 + usize::from(shadowed)).to_string(),
+            _ => id.chars().take(6).collect(),
+        };
+        Ok(Some(
+            self.with_extension(&format!("{title} ({suffix})"), &ext),
+        ))
+    }
+
+    /// Display extension `listed_note_name` uses for note `id` titled
+    /// `title`; listing one row per title leaves the id out, and the name
+    /// then belongs to the newest note with the title
+    fn listed_note_ext(
+        &self,
+        parent_id: &str,
+        title: &str,
+        id: &str,
+    ) -> std::result::Result<String, c_int> {
+        if self.options.no_md_suffix {
+            return Ok(String::new());
+        }
+        let newest;
+        let id = if id.is_empty() {
+            newest = self
+                .db
+                .note_candidates(parent_id, title)
+                .map_err(|_| libc::EIO)?
+                .into_iter()
+                .next()
+                .unwrap_or_default();
+            &newest
+        } else {
+            id
+        };
+        self.note_ext(id)
+            .map(Cow::into_owned)
+            .map_err(|_| libc::EIO)
+    }
+
+    /// The name note `id` can be opened under: the one `readdir` lists or,
+    /// when `--title-collision-policy` hides it, `Title (a1b2c3)`
+    fn reachable_note_name(
+        &self,
+        parent_id: &str,
+        title: &str,
+        id: &str,
+    ) -> std::result::Result<String, c_int> {
+        self.listed_note_name(parent_id, title, id)?
+            .map_or_else(|| self.id_suffixed_name(title, id), Ok)
+    }
+
+    /// `Title (a1b2c3).ext`, the name note `id` resolves under whatever the
+    /// collision policy
+    fn id_suffixed_name(&self, title: &str, id: &str) -> std::result::Result<String, c_int> {
+        let fragment: String = id.chars().take(6).collect();
+        let ext = self.note_ext(id).map_err(|_| libc::EIO)?;
+        Ok(self.with_extension(&format!("{title} ({fragment})"), &ext))
+    }
+
+    /// Path of the live note `id` within the mount, or None when it is
+    /// missing, trashed or outside the mounted tree
+    fn note_path(&self, id: &str) -> std::result::Result<Option<String>, c_int> {
+        let Some((title, parent_id)) = self
+            .db
+            .query_row(
+                "SELECT title, parent_id FROM notes WHERE id = ?1 AND deleted_time = 0",
+                [id],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+            )
+            .optional()
+            .map_err(|_| libc::EIO)?
+        else {
+            return Ok(None);
+        };
+        let Some(folder) = self.folder_path(&parent_id).map_err(|_| libc::EIO)? else {
+            return Ok(None);
+        };
+        let name = self.reachable_note_name(&parent_id, &title, id)?;
+        Ok(Some(join_path(&folder, &name)))
+    }
+
+    /// Rank of note `id` among the live notes of `parent_id`, counting from 1
+    ///
+    /// Ranks follow Joplin's custom sort: `order` descending, so notes
+    /// dragged to the top come first, then title and id for notes never
+    /// reordered (whose `order` is 0).
+    fn order_rank(&self, parent_id: &str, id: &str) -> Result<usize> {
+        let rank: i64 = self.db.query_row(
+            "SELECT COUNT(*) + 1 FROM notes n, notes me WHERE me.id = ?2 AND n.parent_id = ?1 AND n.deleted_time = 0 \
+             AND (n.`order` > me.`order` OR (n.`order` = me.`order` AND (n.title < me.title OR (n.title = me.title AND n.id < me.id))))",
+            [parent_id, id],
+            |row| row.get(0),
+        )?;
+        Ok(rank as usize)
+    }
+
+    /// The id and title of the note at `rank` in `parent_id`, as
+    /// `order_rank` counts
+    fn note_at_order_rank(&self, parent_id: &str, rank: usize) -> Result<Option<(String, String)>> {
+        if rank == 0 {
+            return Ok(None);
+        }
+        self.db
+            .query_row(
+                "SELECT id, title FROM notes WHERE parent_id = ?1 AND deleted_time = 0 ORDER BY `order` DESC, title, id LIMIT 1 OFFSET ?2",
+                rusqlite::params![parent_id, rank as i64 - 1],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+    }
+
+    /// Digits in a `--number-by-order` prefix
+    ///
+    /// Wide enough for the notebook holding the most notes, so every
+    /// notebook uses the same width, and never under three digits so the
+    /// width does not shift as small notebooks grow.
+    fn order_prefix_width(&self) -> Result<usize> {
+        let largest: i64 = self.db.query_row(
+            "SELECT COALESCE(MAX(n), 0) FROM (SELECT COUNT(*) AS n FROM notes WHERE deleted_time = 0 GROUP BY parent_id)",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(largest.to_string().len().max(ORDER_PREFIX_MIN_WIDTH))
+    }
+
+    /// Split a `010-Title.md` name into its rank and the rest of the name
+    fn split_order_prefix(name: &str) -> Option<(usize, &str)> {
+        let (digits, rest) = name.split_once('-')?;
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) || rest.is_empty() {
+            return None;
+        }
+        Some((digits.parse().ok()?, rest))
+    }
+
+    /// Split a `Title (2)` name into the title and its number (2 or more)
+    fn split_count_suffix(name: &str) -> Option<(&str, usize)> {
+        let (title, digits) = name.strip_suffix(')')?.rsplit_once(" (")?;
+        let number: usize = digits.parse().ok()?;
+        // Only the canonical spelling, so `Title (02)` stays a plain title
+        (number >= 2 && number.to_string() == digits).then_some((title, number))
+    }
+
+    /// Split a `Title (a1b2c3)` name into the title and the id fragment
+    ///
+    /// The fragment must be at least six hex digits; anything else is not a
+    /// disambiguated name and yields None.
+    fn split_id_fragment(name: &str) -> Option<(&str, &str)> {
+        let (title, fragment) = name.strip_suffix(')')?.rsplit_once(" (")?;
+        if fragment.len() >= 6 && fragment.chars().all(|c| c.is_ascii_hexdigit()) {
+            Some((title, fragment))
+        } else {
+            None
+        }
+    }
+
+    /// The extended attributes available on a row of the given kind
+    fn xattrs(kind: EntryKind) -> &'static [XattrColumn] {
+        match kind {
+            EntryKind::Note => &NOTE_XATTRS,
+            EntryKind::Folder => &FOLDER_XATTRS,
+        }
+    }
+
+    /// The extended attribute `name` on a row of the given kind, if it has one
+    fn xattr(kind: EntryKind, name: &str) -> Option<&'static XattrColumn> {
+        Self::xattrs(kind).iter().find(|xattr| xattr.name == name)
+    }
+
+    /// Reject names outside the `user.` namespace
+    ///
+    /// `security.*`, `system.*` and `trusted.*` attributes have kernel-defined
+    /// semantics we cannot honour, so they are reported as unsupported rather
+    /// than merely absent.
+    fn check_xattr_namespace(name: &str) -> std::result::Result<(), c_int> {
+        if name.starts_with("user.") {
+            Ok(())
+        } else {
+            Err(libc::ENOTSUP)
+        }
+    }
+
+    /// Read a `user.joplin.*` extended attribute
+    ///
+    /// Returns the attribute value, or the errno to reply with:
+    /// - ENOENT: the inode is unknown
+    /// - ENOTSUP: the name is outside the `user.` namespace
+    /// - ENODATA: the attribute does not exist on this entry
+    fn get_xattr(&self, ino: u64, name: &str) -> std::result::Result<Vec<u8>, c_int> {
+        let path = self.get_path_from_inode(ino).ok_or(ENOENT)?;
+        Self::check_xattr_namespace(name)?;
+        let (kind, id) = self.resolve_path(path).ok_or(libc::ENODATA)?;
+        let xattr = Self::xattr(kind, name).ok_or(libc::ENODATA)?;
+
+        let table = match kind {
+            EntryKind::Note => "notes",
+            EntryKind::Folder => "folders",
+        };
+        let value: Value = self
+            .db
+            .query_row(
+                &format!("SELECT {} FROM {table} WHERE id = ?1", xattr.column),
+                [&id],
+                |row| row.get(0),
+            )
+            .map_err(|_| libc::EIO)?;
+
+        let text = match value {
+            Value::Null => String::new(),
+            Value::Integer(i) => i.to_string(),
+            Value::Real(f) => f.to_string(),
+            Value::Text(t) => t,
+            Value::Blob(b) => return Ok(b),
+        };
+        Ok(text.into_bytes())
+    }
+
+    /// Write a `user.joplin.*` extended attribute
+    ///
+    /// `user.joplin.order` must parse as a number, `user.joplin.user_data`
+    /// must be valid JSON (or empty to clear it), `user.joplin.is_todo` must
+    /// be `0` or `1` and `user.joplin.todo_due` a millisecond timestamp;
+    /// `author` and `source_url` take any text. Successful writes stamp the
+    /// row with `NoteStore::touch_row`.
+    ///
+    /// Returns the errno to reply with on failure:
+    /// - ENOENT: the inode is unknown
+    /// - ENOTSUP: the attribute does not exist on this entry
+    /// - EPERM: the attribute is read-only (`id`, `markup_language`)
+    /// - EROFS: the database is read-only
+    /// - EINVAL: the value is malformed
+    /// - EIO: the database update failed
+    fn set_xattr(&mut self, ino: u64, name: &str, value: &[u8]) -> std::result::Result<(), c_int> {
+        let path = self.get_path_from_inode(ino).ok_or(ENOENT)?;
+        Self::check_xattr_namespace(name)?;
+        let (kind, id) = self.resolve_path(path).ok_or(libc::ENOTSUP)?;
+        let xattr = Self::xattr(kind, name).ok_or(libc::ENOTSUP)?;
+        if xattr.format == XattrFormat::ReadOnly {
+            return Err(libc::EPERM);
+        }
+        self.check_writable()?;
+
+        let text = std::str::from_utf8(value).map_err(|_| libc::EINVAL)?;
+        let trimmed = text.trim();
+        let new_value = match xattr.format {
+            XattrFormat::Number => {
+                if let Ok(i) = trimmed.parse::<i64>() {
+                    Value::Integer(i)
+                } else {
+                    match trimmed.parse::<f64>() {
+                        Ok(f) if f.is_finite() => Value::Real(f),
+                        _ => return Err(libc::EINVAL),
+                    }
+                }
+            }
+            XattrFormat::Timestamp => match trimmed.parse::<i64>() {
+                Ok(ms) if ms >= 0 => Value::Integer(ms),
+                _ => return Err(libc::EINVAL),
+            },
+            XattrFormat::Flag => match trimmed {
+                "0" => Value::Integer(0),
+                "1" => Value::Integer(1),
+                _ => return Err(libc::EINVAL),
+            },
+            XattrFormat::Text => Value::Text(text.to_string()),
+            XattrFormat::Json => {
+                if !trimmed.is_empty()
+                    && serde_json::from_str::<serde_json::Value>(trimmed).is_err()
+                {
+                    return Err(libc::EINVAL);
+                }
+                Value::Text(trimmed.to_string())
+            }
+            XattrFormat::ReadOnly => unreachable!("read-only attributes are refused above"),
+        };
+        let column = xattr.column;
+
+        let table = match kind {
+            EntryKind::Note => "notes",
+            EntryKind::Folder => "folders",
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        self.in_write_transaction(|fs| {
+            fs.db
+                .execute(
+                    &format!("UPDATE {table} SET {column} = ?1 WHERE id = ?2"),
+                    rusqlite::params![new_value, id],
+                )
+                .and_then(|_| fs.db.touch_row(table, &id, now))
+                .map_err(|_| libc::EIO)?;
+            Ok(())
+        })
+    }
+
+    /// Remove a `user.joplin.*` extended attribute by restoring the column's
+    /// Joplin default (`0` for numbers, empty for text)
+    ///
+    /// Returns the errno to reply with on failure:
+    /// - ENOENT: the inode is unknown
+    /// - ENOTSUP: the name is outside the `user.` namespace
+    /// - ENODATA: the attribute does not exist on this entry
+    /// - EPERM: the attribute is read-only
+    fn remove_xattr(&mut self, ino: u64, name: &str) -> std::result::Result<(), c_int> {
+        let path = self.get_path_from_inode(ino).ok_or(ENOENT)?;
+        Self::check_xattr_namespace(name)?;
+        let (kind, _) = self.resolve_path(path).ok_or(libc::ENODATA)?;
+        let xattr = Self::xattr(kind, name).ok_or(libc::ENODATA)?;
+        self.set_xattr(ino, name, xattr.format.default_value())
+    }
+
+    /// List the extended attributes of an entry as a NUL-separated buffer
+    fn list_xattr(&self, ino: u64) -> std::result::Result<Vec<u8>, c_int> {
+        let path = self.get_path_from_inode(ino).ok_or(ENOENT)?;
+        let mut names = Vec::new();
+        if let Some((kind, _)) = self.resolve_path(path) {
+            for xattr in Self::xattrs(kind) {
+                names.extend_from_slice(xattr.name.as_bytes());
+                names.push(0);
+            }
+        }
+        Ok(names)
+    }
+
+    /// Resolve `name` inside the directory `parent` and return its attributes
+    ///
+    /// Allocates an inode for the child on first lookup.
+    /// Returns ENOENT if the parent is unknown or the child does not exist.
+    fn lookup_child(&mut self, parent: u64, name: &str) -> std::result::Result<FileAttr, c_int> {
+        self.check_name_len(name)?;
+        let parent_path = self.get_path_from_inode(parent).ok_or(ENOENT)?.clone();
+
+        // Construct full path
+        let name = self.canonical_name(&parent_path, name);
+        let full_path = join_path(&parent_path, &name);
+
+        // Surface ambiguity along the path and in the name itself, and
+        // folder cycles, rather than letting resolution pick a row
+        if let Err(err) = self.resolve_path_checked(&full_path)
+            && Self::resolve_errno(err) == libc::EIO
+        {
+            return Err(libc::EIO);
+        }
+
+        if self.resolve_path(&full_path).is_none()
+            && self.virtual_dir(&full_path).is_none()
+            && self.sidecar(&full_path).is_none()
+            && self.attachments_note(&full_path).is_none()
+            && self.attachment(&full_path).is_none()
+            && self.recent_entry(&full_path)?.is_none()
+            && self.trash_entry(&full_path)?.is_none()
+            && self.resource_entry(&full_path)?.is_none()
+            && self.backlinks_note(&full_path).is_none()
+            && self.backlink_entry(&full_path)?.is_none()
+            && self.control_file(&full_path).is_none()
+            && self.tag_dir(&full_path).is_none()
+        {
+            return Err(ENOENT);
+        }
+
+        let inode = self.get_or_create_inode(&full_path)?;
+        let attr = self.get_attr(inode)?;
+        self.inodes.remember(inode);
+        Ok(attr)
+    }
+
+    /// List a whole directory as `(inode, kind, name)` entries, starting with `.` and `..`
+    ///
+    /// Drains a directory handle in one go, so the result is exactly what a
+    /// streamed `readdir` would return: folders then notes, each in `--sort`
+    /// order, with each display name listed once.
+    #[cfg(test)]
+    fn list_directory(
+        &mut self,
+        ino: u64,
+    ) -> std::result::Result<Vec<(u64, FileType, String)>, c_int> {
+        let fh = self.open_directory(ino)?;
+        let mut entries = Vec::new();
+        let result = self.read_directory(fh, 0, |ino, _, kind, name| {
+            entries.push((ino, kind, name.to_string()));
+            false
+        });
+        self.release_directory(fh);
+        result.map(|_| entries)
+    }
+
+    /// Register a directory handle for `ino` and return its file handle
+    fn open_directory(&mut self, ino: u64) -> std::result::Result<u64, c_int> {
+        let path = self.get_path_from_inode(ino).ok_or(ENOENT)?.clone();
+        let parent_id = if self.virtual_dir(&path).is_some() || self.trash_entry(&path)?.is_some() {
+            String::new()
+        } else if let Some(id) = self
+            .attachments_note(&path)
+            .or_else(|| self.backlinks_note(&path))
+            .or_else(|| self.tag_dir(&path))
+        {
+            id
+        } else {
+            self.get_parent_folder_id(&path)
+                .map_err(Self::resolve_errno)?
+        };
+
+        let fh = self.next_fh;
+        self.next_fh += 1;
+        self.dir_handles.insert(
+            fh,
+            DirHandle {
+                ino,
+                path,
+                parent_id,
+                phase: DirPhase::Dots,
+                last_key: None,
+                page: std::collections::VecDeque::new(),
+                offset: 0,
+            },
+        );
+        Ok(fh)
+    }
+
+    fn release_directory(&mut self, fh: u64) {
+        self.dir_handles.remove(&fh);
+    }
+
+    /// Stream entries of an open directory starting after `offset`
+    ///
+    /// `add` mirrors `ReplyDirectory::add`: it receives the entry and its
+    /// offset and returns true once the buffer is full, in which case that
+    /// entry stays queued for the next call. An entry's offset is its
+    /// 1-based position in the listing, which keyset paging keeps stable, so
+    /// the offset the kernel resumes from always names the next entry to send.
+    fn read_directory(
+        &mut self,
+        fh: u64,
+        offset: i64,
+        add: impl FnMut(u64, i64, FileType, &str) -> bool,
+    ) -> std::result::Result<(), c_int> {
+        let mut handle = self.dir_handles.remove(&fh).ok_or(libc::EBADF)?;
+        let result = self.fill_directory(&mut handle, offset, add);
+        self.dir_handles.insert(fh, handle);
+        result
+    }
+
+    fn fill_directory(
+        &mut self,
+        handle: &mut DirHandle,
+        offset: i64,
+        mut add: impl FnMut(u64, i64, FileType, &str) -> bool,
+    ) -> std::result::Result<(), c_int> {
+        // A seek away from where we stopped restarts the stream and skips forward
+        if offset != handle.offset {
+            handle.phase = DirPhase::Dots;
+            handle.last_key = None;
+            handle.page.clear();
+            handle.offset = 0;
+        }
+        let mut skip = offset - handle.offset;
+
+        loop {
+            if handle.page.is_empty() {
+                if handle.phase == DirPhase::Done {
+                    return Ok(());
+                }
+                self.next_directory_page(handle)?;
+                continue;
+            }
+            let (ino, kind, name) = handle.page.front().unwrap();
+            if skip > 0 {
+                skip -= 1;
+            } else if add(*ino, handle.offset + 1, *kind, name) {
+                return Ok(());
+            }
+            handle.page.pop_front();
+            handle.offset += 1;
+        }
+    }
+
+    /// Load the next page of entries into `handle`, advancing its phase when a
+    /// group is exhausted
+    fn next_directory_page(&mut self, handle: &mut DirHandle) -> std::result::Result<(), c_int> {
+        let query = match handle.phase {
+            DirPhase::Dots => {
+                handle
+                    .page
+                    .push_back((handle.ino, FileType::Directory, ".".to_string()));
+                handle
+                    .page
+                    .push_back((1, FileType::Directory, "..".to_string()));
+                handle.phase = match self.virtual_dir(&handle.path) {
+                    Some(VirtualDir::Notebooks) => DirPhase::Notebooks,
+                    Some(VirtualDir::Trash) => DirPhase::Trash,
+                    Some(VirtualDir::Recent) => DirPhase::Recent,
+                    Some(VirtualDir::Control) => {
+                        for file in ControlFile::ALL {
+                            let inode =
+                                self.get_or_create_inode(&join_path(CONTROL_DIR, file.name()))?;
+                            handle.page.push_back((
+                                inode,
+                                FileType::RegularFile,
+                                file.name().to_string(),
+                            ));
+                        }
+                        DirPhase::Done
+                    }
+                    // A trashed folder is shown without its contents
+                    None if self.trash_entry(&handle.path)?.is_some() => DirPhase::Done,
+                    None if self.attachments_note(&handle.path).is_some() => DirPhase::Attachments,
+                    None if self.backlinks_note(&handle.path).is_some() => DirPhase::Backlinks,
+                    Some(VirtualDir::Tags) => DirPhase::Tags,
+                    Some(VirtualDir::Resources) => DirPhase::Resources,
+                    None if self.tag_dir(&handle.path).is_some() => DirPhase::TagNotes,
+                    None => DirPhase::Folders,
+                };
+                if self.options.gpx
+                    && handle.phase == DirPhase::Folders
+                    && self.geotagged_updated_time(&handle.parent_id)?.is_some()
+                {
+                    let track_path = format!("{}{GPX_TRACK_SUFFIX}", handle.path.trim_end_matches('/'));
+                    let inode = self.get_or_create_inode(&track_path)?;
+                    handle.page.push_back((
+                        inode,
+                        FileType::RegularFile,
+                        GPX_TRACK_SUFFIX[1..].to_string(),
+                    ));
+                }
+                if handle.phase == DirPhase::Folders
+                    && self
+                        .folder_index_note(&handle.parent_id, FOLDER_INDEX_NAME)
+                        .map_err(|_| libc::EIO)?
+                        .is_some()
+                {
+                    let index_path = join_path(&handle.path, FOLDER_INDEX_NAME);
+                    let inode = self.get_or_create_inode(&index_path)?;
+                    handle.page.push_back((
+                        inode,
+                        FileType::RegularFile,
+                        FOLDER_INDEX_NAME.to_string(),
+                    ));
+                }
+                if handle.path == "/" {
+                    for dir in VirtualDir::ALL {
+                        if self.virtual_dir(dir.path()).is_some() {
+                            let inode = self.get_or_create_inode(dir.path())?;
+                            handle.page.push_back((
+                                inode,
+                                FileType::Directory,
+                                dir.path()[1..].to_string(),
+                            ));
+                        }
+                    }
+                }
+                return Ok(());
+            }
+            // Every notebook, keyed by id; ?1 (the parent) is not used here
+            DirPhase::Notebooks => "SELECT id, id, id FROM folders WHERE deleted_time = 0 AND (?2 IS NULL OR id > ?2) ORDER BY id LIMIT ?4".to_string(),
+            DirPhase::Folders => Self::page_query(
+                "folders",
+                self.options.sort,
+                self.options.collision_policy.lists_every_note(),
+            ),
+            DirPhase::Notes => Self::page_query(
+                "notes",
+                self.options.sort,
+                self.options.collision_policy.lists_every_note(),
+            ),
+            DirPhase::Attachments => {
+                for attachment in self.note_attachments(&handle.parent_id)? {
+                    let inode = self.get_or_create_inode(&join_path(&handle.path, &attachment.name))?;
+                    handle
+                        .page
+                        .push_back((inode, FileType::RegularFile, attachment.name));
+                }
+                handle.phase = DirPhase::Done;
+                return Ok(());
+            }
+            DirPhase::Backlinks => {
+                for entry in self.backlinks(&handle.parent_id, &handle.path)? {
+                    let inode = self.get_or_create_inode(&join_path(&handle.path, &entry.name))?;
+                    handle
+                        .page
+                        .push_back((inode, FileType::Symlink, entry.name));
+                }
+                handle.phase = DirPhase::Done;
+                return Ok(());
+            }
+            DirPhase::SiblingAttachments => {
+                for (_, attachment) in self.sibling_attachments(&handle.parent_id)? {
+                    let inode = self.get_or_create_inode(&join_path(&handle.path, &attachment.name))?;
+                    handle
+                        .page
+                        .push_back((inode, FileType::RegularFile, attachment.name));
+                }
+                handle.phase = DirPhase::Done;
+                return Ok(());
+            }
+            DirPhase::Tags => {
+                for title in self.tag_titles()? {
+                    let inode = self.get_or_create_inode(&join_path(TAGS_DIR, &title))?;
+                    handle.page.push_back((inode, FileType::Directory, title));
+                }
+                handle.phase = DirPhase::Done;
+                return Ok(());
+            }
+            DirPhase::TagNotes => {
+                for entry in self.tag_notes(&handle.parent_id, &handle.path)? {
+                    let inode = self.get_or_create_inode(&join_path(&handle.path, &entry.name))?;
+                    handle
+                        .page
+                        .push_back((inode, FileType::RegularFile, entry.name));
+                }
+                handle.phase = DirPhase::Done;
+                return Ok(());
+            }
+            DirPhase::Recent => {
+                for entry in self.recent_notes()? {
+                    let inode = self.get_or_create_inode(&join_path(RECENT_DIR, &entry.name))?;
+                    handle
+                        .page
+                        .push_back((inode, FileType::Symlink, entry.name));
+                }
+                handle.phase = DirPhase::Done;
+                return Ok(());
+            }
+            DirPhase::Resources => {
+                for resource in self.resource_entries()? {
+                    let inode = self.get_or_create_inode(&join_path(RESOURCES_DIR, &resource.name))?;
+                    handle
+                        .page
+                        .push_back((inode, FileType::RegularFile, resource.name));
+                }
+                handle.phase = DirPhase::Done;
+                return Ok(());
+            }
+            DirPhase::Trash => {
+                for entry in self.trashed_entries()? {
+                    let inode = self.get_or_create_inode(&join_path(TRASH_DIR, &entry.name))?;
+                    let kind = match entry.kind {
+                        EntryKind::Folder => FileType::Directory,
+                        EntryKind::Note => FileType::RegularFile,
+                    };
+                    handle.page.push_back((inode, kind, entry.name));
+                }
+                handle.phase = DirPhase::Done;
+                return Ok(());
+            }
+            DirPhase::Done => return Ok(()),
+        };
+
+        let rows = {
+            let (last_key, last_id) = handle
+                .last_key
+                .clone()
+                .unwrap_or((Value::Null, String::new()));
+            let mut stmt = self.db.prepare_cached(&query).map_err(|_| libc::EIO)?;
+            stmt.query_map(
+                rusqlite::params![handle.parent_id, last_key, last_id, DIR_PAGE_SIZE as i64],
+                // A title that fails to read is kept as an error so one
+                // malformed row is skipped below rather than failing the page
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0),
+                        row.get::<_, Value>(1)?,
+                        row.get::<_, String>(2)?,
+                    ))
+                },
+            )
+            .and_then(|rows| rows.collect::<Result<Vec<_>>>())
+            .map_err(|_| libc::EIO)?
+        };
+
+        let phase = handle.phase;
+        if rows.len() < DIR_PAGE_SIZE {
+            handle.phase = match phase {
+                DirPhase::Folders => DirPhase::Notes,
+                DirPhase::Notes if self.options.inline_attachment_files => {
+                    DirPhase::SiblingAttachments
+                }
+                _ => DirPhase::Done,
+            };
+            handle.last_key = None;
+        } else {
+            handle.last_key = rows.last().map(|(_, key, id)| (key.clone(), id.clone()));
+        }
+
+        let prefix_width = if self.options.number_by_order && phase == DirPhase::Notes {
+            Some(self.order_prefix_width().map_err(|_| libc::EIO)?)
+        } else {
+            None
+        };
+        for (title, key, id) in rows {
+            let title = match title {
+                Ok(title) => title,
+                Err(err) => {
+                    warn!(
+                        "skipping malformed row in {} (key {key:?}, id {id:?}): {err}",
+                        handle.path
+                    );
+                    continue;
+                }
+            };
+            let (kind, name) = match phase {
+                DirPhase::Notebooks => {
+                    if self.is_hidden_folder(&title).map_err(|_| libc::EIO)? {
+                        continue;
+                    }
+                    (FileType::Directory, title)
+                }
+                DirPhase::Folders if !id.is_empty() => {
+                    // One entry per row: older duplicates carry their id
+                    let Some(name) = self
+                        .listed_folder_name(&handle.parent_id, title, &id)
+                        .map_err(|_| libc::EIO)?
+                    else {
+                        continue;
+                    };
+                    (FileType::Directory, name)
+                }
+                DirPhase::Folders => {
+                    // Listed when lookup would find a visible folder by the title
+                    if self.options.hide_icon.is_some()
+                        && let Ok(None) = self.folder_child(&handle.parent_id, &title)
+                    {
+                        continue;
+                    }
+                    let name = self
+                        .decorated_folder_name(&handle.parent_id, title)
+                        .map_err(|_| libc::EIO)?;
+                    (FileType::Directory, name)
+                }
+                _ => {
+                    let Some(mut name) = self.listed_note_name(&handle.parent_id, &title, &id)?
+                    else {
+                        continue;
+                    };
+                    // Listing one row per title leaves the id out; the name
+                    // belongs to the newest note with the title
+                    let id = if id.is_empty() {
+                        self.db
+                            .note_candidates(&handle.parent_id, &title)
+                            .map_err(|_| libc::EIO)?
+                            .into_iter()
+                            .next()
+                            .unwrap_or_default()
+                    } else {
+                        id
+                    };
+                    if let Some(width) = prefix_width {
+                        let rank = self
+                            .order_rank(&handle.parent_id, &id)
+                            .map_err(|_| libc::EIO)?;
+                        name = format!("{rank:0width$}-{name}");
+                    }
+                    if self.is_symlink_note(&id).map_err(|_| libc::EIO)? {
+                        (FileType::Symlink, name)
+                    } else {
+                        (FileType::RegularFile, name)
+                    }
+                }
+            };
+            let full_path = join_path(&handle.path, &name);
+            let inode = self.get_or_create_inode(&full_path)?;
+            handle.page.push_back((inode, kind, name));
+        }
+        Ok(())
+    }
+
+    /// Keyset query for one page of `table` under `--sort`
+    ///
+    /// Rows are `(title, sort key, id)`. ?1 is the parent id, ?2/?3 the key
+    /// and id of the last row already listed (NULL for the first page) and ?4
+    /// the page size. Unless `every_row` is set, each title is listed once:
+    /// by title, DISTINCT collapses duplicates; by creation time each title
+    /// is represented by the row lookup resolves it to (the most recently
+    /// updated). The id breaks ties so pages never skip or repeat rows
+    /// sharing a key.
+    fn page_query(table: &str, sort: SortOrder, every_row: bool) -> String {
+        let (cmp, dir) = match sort {
+            SortOrder::Title if every_row => {
+                return format!(
+                    "SELECT title, title, id FROM {table} WHERE parent_id = ?1 AND deleted_time = 0 AND (?2 IS NULL OR title > ?2 OR (title = ?2 AND id > ?3)) ORDER BY title, id LIMIT ?4"
+                );
+            }
+            SortOrder::Title => {
+                return format!(
+                    "SELECT DISTINCT title, title, '' FROM {table} WHERE parent_id = ?1 AND deleted_time = 0 AND (?2 IS NULL OR title > ?2) ORDER BY title LIMIT ?4"
+                );
+            }
+            SortOrder::CreatedDesc => ("<", "DESC"),
+            SortOrder::CreatedAsc => (">", "ASC"),
+        };
+        let newest_only = if every_row {
+            String::new()
+        } else {
+            format!(
+                "AND id = (SELECT id FROM {table} WHERE parent_id = t.parent_id AND title = t.title AND deleted_time = 0 ORDER BY user_updated_time DESC, id LIMIT 1)"
+            )
+        };
+        format!(
+            "SELECT title, created_time, id FROM {table} t WHERE parent_id = ?1 AND deleted_time = 0 \
+             {newest_only} \
+             AND (?2 IS NULL OR created_time {cmp} ?2 OR (created_time = ?2 AND id {cmp} ?3)) \
+             ORDER BY created_time {dir}, id {dir} LIMIT ?4"
+        )
+    }
+
+    /// Whether a note displayed as `name` is hidden behind a folder of the
+    /// same name, which lookup resolves first
+    fn is_shadowed_note_name(
+        &self,
+        parent_id: &str,
+        name: &str,
+    ) -> std::result::Result<bool, c_int> {
+        self.db
+            .query_row(
+                "SELECT 1 FROM folders WHERE parent_id = ?1 AND title = ?2 AND deleted_time = 0 LIMIT 1",
+                [parent_id, name],
+                |_| Ok(()),
+            )
+            .optional()
+            .map(|row| row.is_some())
+            .map_err(|_| libc::EIO)
+    }
+
+    /// Create a folder named `folder_name` under `parent_path` and return its attributes
+    ///
+    /// Returns EPERM if the parent is a virtual directory, ENAMETOOLONG past
+    /// `--name-max`, EROFS if the database is read-only, ENOSPC if its disk is
+    /// full and EIO if the insertion otherwise fails.
+    fn make_directory(
+        &mut self,
+        parent_path: &str,
+        folder_name: &str,
+    ) -> std::result::Result<FileAttr, c_int> {
+        self.check_not_virtual(parent_path, folder_name)?;
+        self.check_name_len(folder_name)?;
+        self.check_writable()?;
+        self.check_inode_room()?;
+
+        // Create the folder in the database
+        let folder_id = self
+            .create_folder(parent_path, folder_name)
+            .map_err(Self::write_errno)?;
+
+        // Create the full path for the new folder
+        let full_path = join_path(parent_path, folder_name);
+
+        // Create inode for the new folder and report what getattr will
+        let inode = self.get_or_create_inode(&full_path)?;
+        let attr = self.folder_attr(inode, &folder_id)?;
+        self.inodes.remember(inode);
+        Ok(attr)
+    }
+
+    /// Exclusively create an empty note, as `open(O_CREAT | O_EXCL)` does
+    #[cfg(test)]
+    fn create_file(
+        &mut self,
+        parent_path: &str,
+        file_name: &str,
+    ) -> std::result::Result<FileAttr, c_int> {
+        self.create_or_open_file(parent_path, file_name, libc::O_EXCL, 0o644)
+    }
+
+    /// Create an empty note named `file_name` under `parent_path` and return its attributes
+    ///
+    /// The name follows `strip_note_suffix`. The existence check and insert run
+    /// in one write transaction, so of two processes racing on a name only
+    /// one inserts; the other sees the new note and, without `O_EXCL` in
+    /// `flags`, reuses it. A new note takes the permission bits of `mode`;
+    /// a reused one keeps its own. Returns:
+    /// - EEXIST: the name is taken under `O_EXCL`, or by a folder
+    /// - EPERM: the parent is a virtual directory
+    /// - ENAMETOOLONG: past `--name-max`
+    /// - EROFS: the database is read-only
+    /// - ENOSPC: the database's disk is full
+    /// - EIO: the insertion otherwise fails
+    fn create_or_open_file(
+        &mut self,
+        parent_path: &str,
+        file_name: &str,
+        flags: i32,
+        mode: u32,
+    ) -> std::result::Result<FileAttr, c_int> {
+        self.check_not_virtual(parent_path, file_name)?;
+        self.check_name_len(file_name)?;
+        self.check_writable()?;
+        self.check_inode_room()?;
+
+        // Create the full path for the new file
+        let full_path = join_path(parent_path, file_name);
+
+        let (note_id, created) = self.in_write_transaction(|fs| {
+            // `report` and `report.md` both name the note titled `report`
+            match fs.resolve_path(&full_path) {
+                Some((EntryKind::Note, id)) if flags & libc::O_EXCL == 0 => Ok((id, false)),
+                Some(_) => Err(libc::EEXIST),
+                // Create the note in the database with empty content
+                // initially; a new `.index.md` gets the `--folder-index` title
+                None => fs
+                    .create_note(
+                        parent_path,
+                        &match &fs.options.folder_index {
+                            Some(title) if file_name == FOLDER_INDEX_NAME => {
+                                fs.add_md_suffix(title)
+                            }
+                            _ => file_name.to_string(),
+                        },
+                        "",
+                    )
+                    .map(|id| (id, true))
+                    .map_err(Self::write_errno),
+            }
+        })?;
+        if created {
+            self.note_modes
+                .insert(note_id.clone(), (mode & 0o7777) as u16);
+        }
+
+        // Create inode for the new file and report what getattr will
+        let inode = self.get_or_create_inode(&full_path)?;
+        let attr = self.note_attr(inode, &note_id)?;
+        self.inodes.remember(inode);
+        Ok(attr)
+    }
+
+    /// Attributes of a directory, whether backed by a folder row or virtual
+    ///
+    /// Every directory attribute is built here so that lookup, getattr and
+    /// mkdir replies cannot drift apart. Times are the raw column values and
+    /// ownership follows `--uid-map` for `folder_id` ("" when virtual).
+    fn build_folder_attr(
+        &self,
+        ino: u64,
+        folder_id: &str,
+        created_time: i64,
+        updated_time: i64,
+    ) -> FileAttr {
+        let (uid, gid) = self.owner_of(folder_id);
+        FileAttr {
+            ino,
+            size: 0,
+            blocks: 0,
+            atime: UNIX_EPOCH + Duration::from_secs(created_time as u64),
+            mtime: UNIX_EPOCH + Duration::from_secs(updated_time as u64),
+            ctime: UNIX_EPOCH + Duration::from_secs(updated_time as u64),
+            crtime: UNIX_EPOCH + Duration::from_secs(created_time as u64),
+            kind: FileType::Directory,
+            perm: 0o755,
+            nlink: 2,
+            uid,
+            gid,
+            rdev: 0,
+            flags: 0,
+            blksize: self.block_size(),
+        }
+    }
+
+    /// Attributes of a note whose displayed body is `body_len` bytes long
+    ///
+    /// The note counterpart of `build_folder_attr`; `blocks` is always
+    /// counted in 512-byte units, as `st_blocks` requires. Ownership is that
+    /// of the notebook `parent_id`.
+    fn build_note_attr(
+        &self,
+        ino: u64,
+        parent_id: &str,
+        body_len: u64,
+        created_time: i64,
+        updated_time: i64,
+    ) -> FileAttr {
+        let (uid, gid) = self.owner_of(parent_id);
+        FileAttr {
+            ino,
+            size: body_len,
+            blocks: body_len.div_ceil(512),
+            atime: UNIX_EPOCH + Duration::from_secs(created_time as u64),
+            mtime: UNIX_EPOCH + Duration::from_secs(updated_time as u64),
+            ctime: UNIX_EPOCH + Duration::from_secs(updated_time as u64),
+            crtime: UNIX_EPOCH + Duration::from_secs(created_time as u64),
+            kind: FileType::RegularFile,
+            perm: 0o644,
+            nlink: 1,
+            uid,
+            gid,
+            rdev: 0,
+            flags: 0,
+            blksize: self.block_size(),
+        }
+    }
+
+    /// Owner reported for a folder and everything inside it
+    ///
+    /// The nearest notebook, walking up from `folder_id`, with a `--uid-map`
+    /// entry decides; without one the `AttrConfig` owner applies. A mapping
+    /// without a gid keeps its gid.
+    fn owner_of(&self, folder_id: &str) -> (u32, u32) {
+        let default = (self.attr.uid, self.attr.gid);
+        if self.options.uid_map.is_empty() {
+            return default;
+        }
+
+        let mut current = folder_id.to_string();
+        let mut seen = std::collections::HashSet::new();
+        while !current.is_empty() && seen.insert(current.clone()) {
+            if let Some(&(uid, gid)) = self.options.uid_map.get(&current) {
+                return (uid, gid.unwrap_or(default.1));
+            }
+            current = match self
+                .db
+                .query_row(
+                    "SELECT parent_id FROM folders WHERE id = ?1",
+                    [&current],
+                    |row| row.get(0),
+                )
+                .optional()
+            {
+                Ok(Some(parent)) => parent,
+                _ => break,
+            };
+        }
+        default
+    }
+
+    /// Attributes of the folder row `id`, reported under inode `ino`
+    fn folder_attr(&self, ino: u64, id: &str) -> std::result::Result<FileAttr, c_int> {
+        let (created_time, updated_time) = self
+            .db
+            .prepare_cached("SELECT created_time, updated_time FROM folders WHERE id = ?1")
+            .and_then(|mut stmt| {
+                stmt.query_row([id], |row| {
+                    Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
+                })
+            })
+            .optional()
+            .map_err(|_| libc::EIO)?
+            .ok_or(ENOENT)?;
+        Ok(FileAttr {
+            nlink: self.folder_nlink(id)?,
+            ..self.build_folder_attr(ino, id, created_time, updated_time)
+        })
+    }
+
+    /// Link count of the folder `id` (`""` for Joplin's top level): 2 plus
+    /// one per live subfolder, whose `..` links back to it
+    ///
+    /// `find` relies on this to stop looking for subdirectories once it has
+    /// seen that many, so it must never undercount.
+    fn folder_nlink(&self, id: &str) -> std::result::Result<u32, c_int> {
+        let subfolders: u32 = self
+            .db
+            .prepare_cached(
+                "SELECT COUNT(*) FROM folders WHERE parent_id = ?1 AND deleted_time = 0",
+            )
+            .and_then(|mut stmt| stmt.query_row([id], |row| row.get(0)))
+            .map_err(|_| libc::EIO)?;
+        Ok(2 + subfolders)
+    }
+
+    /// Attributes of the note row `id`, reported under inode `ino`
+    fn note_attr(&self, ino: u64, id: &str) -> std::result::Result<FileAttr, c_int> {
+        let (parent_id, content_size, created_time, updated_time) = self
+            .db
+            .prepare_cached(
+                "SELECT parent_id, length(CAST(body AS BLOB)), created_time, updated_time FROM notes WHERE id = ?1",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_row([id], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, Option<i64>>(1)?.unwrap_or(0),
+                        row.get::<_, i64>(2)?,
+                        row.get::<_, i64>(3)?,
+                    ))
+                })
+            })
+            .optional()
+            .map_err(|_| libc::EIO)?
+            .ok_or(ENOENT)?;
+        let dir = self
+            .get_path_from_inode(ino)
+            .map_or("/", |path| split_path(path).0);
+        let content_size = self.displayed_note_size(dir, id, content_size)?;
+        let mut attr =
+            self.build_note_attr(ino, &parent_id, content_size, created_time, updated_time);
+        if let Some(&perm) = self.note_modes.get(id) {
+            attr.perm = perm;
+        }
+        if let Some(which) = self.options.todo_as_exec {
+            let (is_todo, completed): (i64, i64) = self
+                .db
+                .query_row(
+                    "SELECT is_todo, todo_completed FROM notes WHERE id = ?1",
+                    [id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .map_err(|_| libc::EIO)?;
+            // Execute wherever read is granted, so 0o644 becomes 0o755
+            attr.perm &= !0o111;
+            if is_todo != 0 && (which == TodoExec::All || completed == 0) {
+                attr.perm |= (attr.perm & 0o444) >> 2;
+            }
+        }
+        if let Some(target) = self.symlink_target(id).map_err(|_| libc::EIO)? {
+            let len = target.len() as u64;
+            return Ok(FileAttr {
+                kind: FileType::Symlink,
+                size: len,
+                blocks: len.div_ceil(512),
+                perm: 0o777,
+                ..attr
+            });
+        }
+        Ok(attr)
+    }
+
+    /// Build the attributes of the folder or note behind an inode
+    ///
+    /// Shared by `getattr` and the other callbacks that need to report
+    /// attributes. Empty notes are legitimate and report size 0 with 0 blocks.
+    ///
+    /// Returns ENOENT if the inode is unknown or no longer backed by a row.
+    fn get_attr(&self, ino: u64) -> std::result::Result<FileAttr, c_int> {
+        if ino == 1 {
+            // Joplin's top level has no row and so no timestamps; a subtree
+            // mount takes them from the folder it is rooted at
+            let attr = if self.root_folder_id.is_empty() {
+                FileAttr {
+                    nlink: self.folder_nlink("")?,
+                    ..self.build_folder_attr(1, "", 0, 0)
+                }
+            } else {
+                self.folder_attr(1, &self.root_folder_id)?
+            };
+            // The enabled virtual directories are subdirectories of the root too
+            let virtual_dirs = VirtualDir::ALL
+                .into_iter()
+                .filter(|dir| self.virtual_dir(dir.path()).is_some())
+                .count() as u32;
+            return Ok(FileAttr {
+                nlink: attr.nlink + virtual_dirs,
+                ..attr
+            });
+        }
+
+        let path = self.get_path_from_inode(ino).ok_or(ENOENT)?;
+        if let Some(dir) = self.virtual_dir(path) {
+            return self.virtual_dir_attr(ino, dir);
+        }
+        if let Some(file) = self.control_file(path) {
+            let len = self.control_output(file).len() as u64;
+            return Ok(FileAttr {
+                kind: FileType::RegularFile,
+                size: len,
+                blocks: len.div_ceil(512),
+                perm: 0o644,
+                nlink: 1,
+                ..self.build_folder_attr(ino, "", 0, 0)
+            });
+        }
+        match self.resolve_path(path) {
+            Some((EntryKind::Folder, id)) => self.folder_attr(ino, &id),
+            Some((EntryKind::Note, id)) => self.note_attr(ino, &id),
+            None if let Some(note_id) = self
+                .attachments_note(path)
+                .or_else(|| self.backlinks_note(path)) =>
+            {
+                Ok(FileAttr {
+                    kind: FileType::Directory,
+                    size: 0,
+                    blocks: 0,
+                    perm: 0o555,
+                    nlink: 2,
+                    ..self.note_attr(ino, &note_id)?
+                })
+            }
+            None if let Some(tag_id) = self.tag_dir(path) => {
+                let mtime: i64 = self
+                    .db
+                    .query_row(
+                        "SELECT updated_time FROM tags WHERE id = ?1",
+                        [&tag_id],
+                        |row| row.get(0),
+                    )
+                    .map_err(|_| libc::EIO)?;
+                Ok(FileAttr {
+                    perm: 0o555,
+                    ..self.build_folder_attr(ino, "", 0, mtime)
+                })
+            }
+            None if let Some(entry) = self.trash_entry(path)? => match entry.kind {
+                EntryKind::Folder => Ok(FileAttr {
+                    perm: 0o555,
+                    nlink: 2,
+                    ..self.folder_attr(ino, &entry.id)?
+                }),
+                EntryKind::Note => {
+                    let len = self
+                        .stored_body_bytes(&entry.id)
+                        .map_err(|_| libc::EIO)?
+                        .len() as u64;
+                    Ok(FileAttr {
+                        kind: FileType::RegularFile,
+                        size: len,
+                        blocks: len.div_ceil(512),
+                        perm: 0o444,
+                        ..self.note_attr(ino, &entry.id)?
+                    })
+                }
+            },
+            None if let Some(entry) = self.recent_entry(path)?.or(self.backlink_entry(path)?) => {
+                let len = entry.target.len() as u64;
+                Ok(FileAttr {
+                    kind: FileType::Symlink,
+                    size: len,
+                    blocks: len.div_ceil(512),
+                    perm: 0o777,
+                    ..self.note_attr(ino, &entry.note_id)?
+                })
+            }
+            None if let Some(resource) = self.resource_entry(path)? => {
+                let (size, created_time, updated_time): (i64, i64, i64) = self
+                    .db
+                    .query_row(
+                        "SELECT size, created_time, updated_time FROM resources WHERE id = ?1",
+                        [&resource.resource_id],
+                        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                    )
+                    .map_err(|_| libc::EIO)?;
+                // Joplin leaves `size` at -1 until it has measured the file
+                let len = match u64::try_from(size) {
+                    Ok(len) => len,
+                    Err(_) => std::fs::metadata(&resource.file).map_err(|_| ENOENT)?.len(),
+                };
+                Ok(FileAttr {
+                    perm: 0o444,
+                    ..self.build_note_attr(ino, "", len, created_time, updated_time)
+                })
+            }
+            None if let Some((note_id, attachment)) = self.attachment(path) => {
+                let len = std::fs::metadata(&attachment.file)
+                    .map_err(|_| ENOENT)?
+                    .len();
+                Ok(FileAttr {
+                    kind: FileType::RegularFile,
+                    size: len,
+                    blocks: len.div_ceil(512),
+                    perm: 0o444,
+                    ..self.note_attr(ino, &note_id)?
+                })
+            }
+            None => {
+                let (sidecar, id) = self.sidecar(path).ok_or(ENOENT)?;
+                let len = self.sidecar_content(path, sidecar, &id)?.len() as u64;
+                let base = match sidecar {
+                    Sidecar::GpxTrack => {
+                        let updated = self.geotagged_updated_time(&id)?.unwrap_or(0);
+                        self.build_note_attr(ino, &id, 0, 0, updated)
+                    }
+                    _ => self.note_attr(ino, &id)?,
+                };
+                Ok(FileAttr {
+                    kind: FileType::RegularFile,
+                    size: len,
+                    blocks: len.div_ceil(512),
+                    perm: 0o444,
+                    ..base
+                })
+            }
+        }
+    }
+
+    /// Attributes of a virtual directory: read-only, modified when the newest
+    /// row it presents last changed (`/.trash`: the latest deletion)
+    fn virtual_dir_attr(&self, ino: u64, dir: VirtualDir) -> std::result::Result<FileAttr, c_int> {
+        let query = match dir {
+            VirtualDir::Resources if self.has_table("resources")? => {
+                "SELECT MAX(updated_time) FROM resources"
+            }
+            VirtualDir::Resources => "SELECT NULL",
+            VirtualDir::Notebooks => "SELECT MAX(updated_time) FROM folders WHERE deleted_time = 0",
+            VirtualDir::Trash => {
+                "SELECT MAX(deleted_time) FROM (SELECT deleted_time FROM notes UNION ALL SELECT deleted_time FROM folders)"
+            }
+            VirtualDir::Recent => "SELECT MAX(updated_time) FROM notes WHERE deleted_time = 0",
+            VirtualDir::Control => "SELECT NULL",
+            VirtualDir::Tags => "SELECT MAX(updated_time) FROM tags",
+        };
+        let mtime: Option<i64> = self
+            .db
+            .query_row(query, [], |row| row.get(0))
+            .map_err(|_| libc::EIO)?;
+        Ok(FileAttr {
+            perm: 0o555,
+            ..self.build_folder_attr(ino, "", 0, mtime.unwrap_or(0))
+        })
+    }
+
+    /// Filesystem statistics for `statfs`
+    ///
+    /// Used blocks cover every live note body plus one block of overhead per
+    /// folder; free space is reported as a large constant since the database
+    /// simply grows. The block and name limits are the configured
+    /// `--block-size` and `--name-max`.
+    fn stat_fs(&self) -> std::result::Result<StatFs, c_int> {
+        let (body_bytes, notes, folders): (i64, i64, i64) = self
+            .db
+            .query_row(
+                "SELECT (SELECT COALESCE(SUM(length(CAST(body AS BLOB))), 0) FROM notes WHERE deleted_time = 0),
+                        (SELECT COUNT(*) FROM notes WHERE deleted_time = 0),
+                        (SELECT COUNT(*) FROM folders WHERE deleted_time = 0)",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .map_err(|_| libc::EIO)?;
+        let bsize = self.block_size();
+        Ok(StatFs {
+            blocks: (body_bytes as u64).div_ceil(bsize as u64) + folders as u64,
+            bfree: STATFS_FREE,
+            bavail: STATFS_FREE,
+            files: (notes + folders) as u64,
+            ffree: STATFS_FREE,
+            bsize,
+            namelen: self.name_max(),
+            frsize: bsize,
+        })
+    }
+
+    /// Size of a note as presented to readers
+    ///
+    /// Equal to the stored body length unless writes are still pending in an
+    /// open handle, `--encoding` decodes the body or `--inline-resources`
+    /// rewrites it, in which case that length is reported so `stat` agrees
+    /// with what `read` returns.
+    fn displayed_note_size(
+        &self,
+        dir: &str,
+        note_id: &str,
+        stored_size: i64,
+    ) -> std::result::Result<u64, c_int> {
+        if let Some(pending) = self.pending_body(note_id) {
+            return Ok(pending.len() as u64);
+        }
+        if !self.rewrites_bodies() && self.options.encoding.is_none() {
+            return Ok(stored_size as u64);
+        }
+        let body: String = self.stored_body(note_id).map_err(|_| libc::EIO)?;
+        Ok(self.rewritten_body(body, dir).len() as u64)
+    }
+
+    /// Whether reads show something other than the stored body
+    fn rewrites_bodies(&self) -> bool {
+        self.options.inline_resources || self.options.rewrite_note_links
+    }
+
+    /// A stored body with the `--inline-resources` and `--rewrite-note-links`
+    /// rewrites applied, for a note reached through the directory `dir`
+    fn rewritten_body(&self, body: String, dir: &str) -> String {
+        let body = if self.options.inline_resources {
+            self.inline_resource_links(&body)
+        } else {
+            body
+        };
+        if self.options.rewrite_note_links {
+            self.rewrite_note_links(&body, dir)
+        } else {
+            body
+        }
+    }
+
+    /// Replace `](:/<id>)` links to other notes with their paths relative to
+    /// `dir`
+    ///
+    /// A `#heading` after the id is kept. Links to resources, to missing or
+    /// trashed notes and to notes outside the mounted tree are left untouched.
+    fn rewrite_note_links(&self, body: &str, dir: &str) -> String {
+        let mut out = String::with_capacity(body.len());
+        let mut rest = body;
+        while let Some(pos) = rest.find("](:/") {
+            out.push_str(&rest[..pos + 2]);
+            let after = &rest[pos + 4..];
+            let id = after
+                .get(..32)
+                .filter(|id| id.bytes().all(|b| b.is_ascii_hexdigit()))
+                .filter(|_| after[32..].starts_with([')', '#']));
+            match id.and_then(|id| self.note_path(id).ok().flatten()) {
+                Some(target) => {
+                    out.push_str(&relative_link(dir, &target));
+                    rest = &after[32..];
+                }
+                None => {
+                    out.push_str(":/");
+                    rest = after;
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+
+    /// Replace `](:/<id>)` links to image resources with `data:` URIs
+    ///
+    /// Links to non-image, missing or oversized resources are left untouched.
+    fn inline_resource_links(&self, body: &str) -> String {
+        let mut out = String::with_capacity(body.len());
+        let mut rest = body;
+        while let Some(pos) = rest.find("](:/") {
+            out.push_str(&rest[..pos + 2]);
+            let after = &rest[pos + 4..];
+            let id = after
+                .get(..32)
+                .filter(|id| id.bytes().all(|b| b.is_ascii_hexdigit()))
+                .filter(|_| after[32..].starts_with(')'));
+            match id.and_then(|id| self.resource_data_uri(id)) {
+                Some(uri) => {
+                    out.push_str(&uri);
+                    rest = &after[32..];
+                }
+                None => {
+                    out.push_str(":/");
+                    rest = after;
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+
+    /// Where Joplin keeps the file of resource `id`
+    fn resource_file(&self, id: &str, extension: &str) -> std::path::PathBuf {
+        let file_name = if extension.is_empty() {
+            id.to_string()
+        } else {
+            format!("{id}.{extension}")
+        };
+        self.options.resource_dir.join(file_name)
+    }
+
+    /// Encode an image resource's file as a base64 `data:` URI
+    fn resource_data_uri(&self, id: &str) -> Option<String> {
+        let (mime, extension): (String, String) = self
+            .db
+            .query_row(
+                "SELECT mime, file_extension FROM resources WHERE id = ?1",
+                [id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .ok()?;
+        if !mime.starts_with("image/") {
+            return None;
+        }
+
+        let path = self.resource_file(id, &extension);
+        if std::fs::metadata(&path).ok()?.len() > INLINE_RESOURCE_MAX_BYTES {
+            return None;
+        }
+        let data = std::fs::read(&path).ok()?;
+        Some(format!("data:{mime};base64,{}", BASE64.encode(data)))
+    }
+
+    /// A note's stored body, with a NULL `body` (possible in databases not
+    /// written by Joplin, despite the schema default) read as empty
+    ///
+    /// Under `--encoding` the stored bytes are decoded to UTF-8, with bytes
+    /// the encoding cannot decode replaced by U+FFFD. Without it a body
+    /// holding invalid UTF-8 (see `stored_body_bytes`) is read the same way.
+    fn stored_body(&self, note_id: &str) -> Result<String> {
+        let Some(encoding) = self.options.encoding else {
+            return self
+                .stored_body_bytes(note_id)
+                .map(|bytes| match String::from_utf8(bytes) {
+                    Ok(body) => body,
+                    Err(err) => String::from_utf8_lossy(err.as_bytes()).into_owned(),
+                });
+        };
+        self.db
+            .query_row("SELECT body FROM notes WHERE id = ?1", [note_id], |row| {
+                Ok(match row.get_ref(0)? {
+                    ValueRef::Text(bytes) | ValueRef::Blob(bytes) => {
+                        encoding.decode_without_bom_handling(bytes).0.into_owned()
+                    }
+                    _ => String::new(),
+                })
+            })
+    }
+
+    /// A note's body as the bytes readers and writers see
+    ///
+    /// Without `--encoding` these are the stored bytes exactly. The column is
+    /// TEXT, but a write can leave it holding invalid UTF-8 for a while, for
+    /// example a multibyte character split across two `write` calls, and
+    /// those bytes are kept rather than replaced by U+FFFD. Under
+    /// `--encoding` they are the UTF-8 of `stored_body`.
+    fn stored_body_bytes(&self, note_id: &str) -> Result<Vec<u8>> {
+        if self.options.encoding.is_some() {
+            return self.stored_body(note_id).map(String::into_bytes);
+        }
+        self.db
+            .query_row("SELECT body FROM notes WHERE id = ?1", [note_id], |row| {
+                Ok(match row.get_ref(0)? {
+                    ValueRef::Text(bytes) | ValueRef::Blob(bytes) => bytes.to_vec(),
+                    _ => Vec::new(),
+                })
+            })
+    }
+
+    /// Look up the note a filesystem path refers to and return its id and body
+    ///
+    /// Resolution is that of `resolve_path`, so duplicate suffixes address
+    /// the same row everywhere. Returns ENOENT if the path does not name a
+    /// live note.
+    fn note_body_for_path(&self, path: &str) -> std::result::Result<(String, Vec<u8>), c_int> {
+        let note_id = match self.resolve_path(path) {
+            Some((EntryKind::Note, id)) => id,
+            _ => return Err(ENOENT),
+        };
+        let body = self.stored_body_bytes(&note_id).map_err(|_| libc::EIO)?;
+        Ok((note_id, body))
+    }
+
+    /// Read a note's content starting at `offset`
+    ///
+    /// Reading at or past the end of the body (including any read of an
+    /// empty note) yields an empty buffer rather than an error.
+    fn read_note(&self, ino: u64, offset: i64) -> std::result::Result<Vec<u8>, c_int> {
+        self.read_note_range(ino, offset, u64::MAX)
+    }
+
+    /// Read at most `size` bytes of a note's content starting at `offset`,
+    /// as `read` must: `content[offset..min(offset + size, len)]`
+    fn read_note_range(
+        &self,
+        ino: u64,
+        offset: i64,
+        size: u64,
+    ) -> std::result::Result<Vec<u8>, c_int> {
+        let path = self.get_path_from_inode(ino).ok_or(ENOENT)?;
+        if let Some(file) = self.control_file(path) {
+            return Ok(byte_window(self.control_output(file).as_bytes(), offset, size).to_vec());
+        }
+        if let Some(entry) = self.trash_entry(path)? {
+            let body = self.stored_body_bytes(&entry.id).map_err(|_| libc::EIO)?;
+            return Ok(byte_window(&body, offset, size).to_vec());
+        }
+        if let Some(resource) = self.resource_entry(path)? {
+            let mut file = std::fs::File::open(&resource.file).map_err(|_| ENOENT)?;
+            return read_window(&mut file, offset, size);
+        }
+        if self.resolve_path(path).is_none()
+            && let Some((_, attachment)) = self.attachment(path)
+        {
+            let mut file = std::fs::File::open(&attachment.file).map_err(|_| ENOENT)?;
+            return read_window(&mut file, offset, size);
+        }
+        let body = if self.resolve_path(path).is_none()
+            && let Some((sidecar, note_id)) = self.sidecar(path)
+        {
+            self.sidecar_content(path, sidecar, &note_id)?
+        } else {
+            self.note_content(path)?
+        };
+
+        Ok(byte_window(&body, offset, size).to_vec())
+    }
+
+    /// A note's body as readers see it: pending writes, then any inlining
+    fn note_content(&self, path: &str) -> std::result::Result<Vec<u8>, c_int> {
+        let (note_id, body) = self.note_body_for_path(path)?;
+        let body = if let Some(pending) = self.pending_body(&note_id) {
+            pending.to_vec()
+        } else if self.rewrites_bodies() {
+            let body = String::from_utf8_lossy(&body).into_owned();
+            self.rewritten_body(body, split_path(path).0).into_bytes()
+        } else {
+            body
+        };
+        Ok(body)
+    }
+
+    /// Whether note bodies refuse writes and size changes
+    ///
+    /// Under `--body-read-only` and the read rewrites notes can still be
+    /// created, renamed and removed; a read-only mount refuses those too.
+    fn bodies_read_only(&self) -> bool {
+        self.options.body_read_only || self.rewrites_bodies() || self.read_only()
+    }
+
+    /// Whether the mount refuses every change: under `--read-only`, or when
+    /// the database cannot be written
+    pub(crate) fn read_only(&self) -> bool {
+        self.options.read_only || self.database_read_only()
+    }
+
+    /// Whether the connection cannot write to the database, because the file
+    /// is not writable by this user or sits on read-only media
+    fn database_read_only(&self) -> bool {
+        self.db.is_readonly(rusqlite::MAIN_DB).unwrap_or(false)
+    }
+
+    /// Rebuild Joplin's full-text index from the notes, as `--rebuild-fts`
+    /// and `/.control/rebuild-fts` ask, logging how long it took
+    ///
+    /// Returns false, having done nothing, when the database has no index.
+    pub(crate) fn rebuild_fts(&self) -> Result<bool> {
+        info!("rebuilding the {FTS_TABLE} search index");
+        let started = Instant::now();
+        let rebuilt = self.db.rebuild_fts()?;
+        if rebuilt {
+            info!("rebuilt {FTS_TABLE} in {:?}", started.elapsed());
+        } else {
+            info!("no {FTS_TABLE} table; nothing to rebuild");
+        }
+        Ok(rebuilt)
+    }
+
+    /// Retitle folders sharing a title with a sibling, as
+    /// `--resolve-duplicate-parents` asks before mounting
+    pub(crate) fn rename_duplicate_folders(&self) -> Result<Vec<FolderRename>> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        rename_duplicate_folders(&self.db, now)
+    }
+
+    /// ENFILE when `--max-inodes` leaves no room for the inode of a new
+    /// entry, checked before the row is written
+    fn check_inode_room(&mut self) -> std::result::Result<(), c_int> {
+        if self.inodes.make_room() {
+            Ok(())
+        } else {
+            Err(libc::ENFILE)
+        }
+    }
+
+    /// Whether the user `uid` in group `gid` may access `ino` as `mask`
+    /// (`R_OK`, `W_OK` and `X_OK` bits, or `F_OK`) asks, as `access(2)` checks
+    ///
+    /// The owner, group or other bits of the reported mode apply, as for any
+    /// file; only the request's primary group is known, so supplementary
+    /// groups are not consulted. Root may read and write anything and
+    /// execute whatever has an execute bit or is a directory. Returns:
+    /// - ENOENT: the inode is unknown
+    /// - EROFS: write access on a read-only mount
+    /// - EACCES: the mode does not grant the access
+    fn check_access(
+        &self,
+        ino: u64,
+        uid: u32,
+        gid: u32,
+        mask: i32,
+    ) -> std::result::Result<(), c_int> {
+        let attr = self.get_attr(ino)?;
+        if mask & libc::W_OK != 0 && self.read_only() {
+            return Err(libc::EROFS);
+        }
+        let wanted = (mask & (libc::R_OK | libc::W_OK | libc::X_OK)) as u16;
+        let granted = if uid == 0 {
+            let executable = attr.kind == FileType::Directory || attr.perm & 0o111 != 0;
+            0o6 | if executable { 0o1 } else { 0 }
+        } else if uid == attr.uid {
+            (attr.perm >> 6) & 0o7
+        } else if gid == attr.gid {
+            (attr.perm >> 3) & 0o7
+        } else {
+            attr.perm & 0o7
+        };
+        if wanted & !granted != 0 {
+            return Err(libc::EACCES);
+        }
+        Ok(())
+    }
+
+    /// EROFS when the mount is read-only, for operations that change the
+    /// database
+    fn check_writable(&self) -> std::result::Result<(), c_int> {
+        if self.read_only() {
+            return Err(libc::EROFS);
+        }
+        Ok(())
+    }
+
+    /// Write `data` into a note's body at `offset`
+    ///
+    /// Returns the number of bytes written, or the errno to reply with:
+    /// - ENOENT: the inode does not name a live note
+    /// - EROFS: bodies are read-only (`--body-read-only`, `--inline-resources`)
+    /// - ENOSPC: the database's disk is full
+    /// - EIO: the database update failed
+    fn write_note(
+        &mut self,
+        ino: u64,
+        offset: i64,
+        data: &[u8],
+    ) -> std::result::Result<u32, c_int> {
+        let path = self.get_path_from_inode(ino).ok_or(ENOENT)?;
+        if let Some(file) = self.control_file(path) {
+            return self.write_control(file, offset, data);
+        }
+        let note_id = match self.resolve_path(path) {
+            Some((EntryKind::Note, id)) => id,
+            _ => return Err(ENOENT),
+        };
+        self.write_body(&note_id, Some(offset), data)
+    }
+
+    /// Write `data` into the body of `note_id` at `offset`, or at its end
+    /// when `offset` is None
+    ///
+    /// The body is read, patched and stored under the note's lock and in one
+    /// write transaction, so concurrent writers to the same note, in this
+    /// process or another, serialize instead of losing each other's updates.
+    fn write_body(
+        &mut self,
+        note_id: &str,
+        offset: Option<i64>,
+        data: &[u8],
+    ) -> std::result::Result<u32, c_int> {
+        if self.bodies_read_only() {
+            return Err(libc::EROFS);
+        }
+        let _lock = NoteLock::acquire(note_id);
+        self.in_write_transaction(|fs| {
+            let current_content = fs.stored_body_bytes(note_id).map_err(|_| ENOENT)?;
+            let offset = offset.unwrap_or(current_content.len() as i64);
+
+            // Overwrite `[offset, offset + data.len())`, keeping any tail
+            // beyond it; only truncate (`setattr` size or `O_TRUNC`) shortens
+            let new_content = {
+                let mut content_bytes = current_content;
+                let start_pos = offset as usize;
+
+                if start_pos > content_bytes.len() {
+                    // If offset is beyond current content, pad with zeros
+                    content_bytes.resize(start_pos, 0);
+                }
+
+                // Replace or extend content
+                if start_pos + data.len() <= content_bytes.len() {
+                    // Replace existing content
+                    content_bytes[start_pos..start_pos + data.len()].copy_from_slice(data);
+                } else {
+                    // Extend content
+                    content_bytes.truncate(start_pos);
+                    content_bytes.extend_from_slice(data);
+                }
+
+                content_bytes
+            };
+
+            // Update the note in the database
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs() as i64;
+
+            fs.db
+                .execute(
+                    "UPDATE notes SET body = ?1, updated_time = ?2, user_updated_time = ?2 WHERE id = ?3",
+                    rusqlite::params![fs.body_param(&new_content), now, note_id],
+                )
+                .map_err(Self::write_errno)?;
+            fs.db.touch_note_parent(note_id, now)
+                .map_err(Self::write_errno)?;
+
+            Ok(data.len() as u32)
+        })
+    }
+
+    /// Open the note behind an inode and return a new file handle for it
+    ///
+    /// A writable open with `O_TRUNC` empties the body. Returns ENOENT if the
+    /// inode is unknown or not backed by a note, EACCES when a sidecar is
+    /// opened for writing, EROFS when a note is opened for writing on a
+    /// read-only database and EMFILE at `--max-open-files`.
+    fn open_note(&mut self, ino: u64, flags: i32) -> std::result::Result<u64, c_int> {
+        let path = self.get_path_from_inode(ino).ok_or(ENOENT)?;
+        // Control files keep their state on the mount, not the handle
+        if self.control_file(path).is_some() {
+            return Ok(0);
+        }
+        // Resources are read-only and, like attachments, keep their file open
+        if let Some(resource) = self.resource_entry(path)? {
+            if flags & libc::O_ACCMODE != libc::O_RDONLY {
+                return Err(libc::EACCES);
+            }
+            return self.open_resource(resource);
+        }
+        // Trashed notes are read straight from their row
+        if self.trash_entry(path)?.is_some() {
+            if flags & libc::O_ACCMODE != libc::O_RDONLY {
+                return Err(libc::EROFS);
+            }
+            return Ok(0);
+        }
+        let note_id = match self.note_body_for_path(path) {
+            Ok((note_id, _)) => note_id,
+            Err(e) => {
+                // Sidecars and attachments are read-only; only attachments
+                // carry handle state, their open resource file
+                let attachment = self.attachment(path);
+                if self.sidecar(path).is_none() && attachment.is_none() {
+                    return Err(e);
+                }
+                if flags & libc::O_ACCMODE != libc::O_RDONLY {
+                    return Err(libc::EACCES);
+                }
+                return match attachment {
+                    Some((_, attachment)) => self.open_resource(attachment),
+                    None => Ok(0),
+                };
+            }
+        };
+        self.check_open_files()?;
+        if flags & libc::O_ACCMODE != libc::O_RDONLY {
+            self.check_writable()?;
+            if flags & libc::O_TRUNC != 0 && self.body_len(&note_id)? > 0 {
+                self.set_attr(ino, None, None, None, Some(0))?;
+            }
+        }
+
+        let fh = self.next_fh;
+        self.next_fh += 1;
+        self.file_handles.insert(
+            fh,
+            FileHandle {
+                note_id,
+                append: flags & libc::O_APPEND != 0,
+                pending: None,
+                last_commit: None,
+            },
+        );
+        Ok(fh)
+    }
+
+    /// A new handle on an attachment's resource file, opening the file
+    /// unless another handle already has
+    fn open_resource(&mut self, attachment: Attachment) -> std::result::Result<u64, c_int> {
+        if let Some(open) = self.open_resources.get_mut(&attachment.resource_id) {
+            open.handles += 1;
+        } else {
+            let file = std::fs::File::open(&attachment.file).map_err(|_| ENOENT)?;
+            self.open_resources.insert(
+                attachment.resource_id.clone(),
+                OpenResource { file, handles: 1 },
+            );
+        }
+        let fh = self.next_fh;
+        self.next_fh += 1;
+        self.resource_handles.insert(fh, attachment.resource_id);
+        Ok(fh)
+    }
+
+    /// Forget an attachment handle, closing its resource file once no
+    /// other handle reads it
+    fn release_resource(&mut self, fh: u64) {
+        let Some(resource_id) = self.resource_handles.remove(&fh) else {
+            return;
+        };
+        if let Some(open) = self.open_resources.get_mut(&resource_id) {
+            open.handles -= 1;
+            if open.handles == 0 {
+                self.open_resources.remove(&resource_id);
+            }
+        }
+    }
+
+    /// Read up to `size` bytes at `offset` of the file behind `ino`
+    ///
+    /// Attachments opened as `fh` are read straight from their open
+    /// resource file, so streaming a large one holds a single window in
+    /// memory; everything else comes from `read_note_range`.
+    fn read_range(
+        &mut self,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+        size: u32,
+    ) -> std::result::Result<Vec<u8>, c_int> {
+        if let Some(resource_id) = self.resource_handles.get(&fh) {
+            let open = self
+                .open_resources
+                .get_mut(resource_id)
+                .ok_or(libc::EBADF)?;
+            return read_window(&mut open.file, offset, u64::from(size));
+        }
+        self.read_note_range(ino, offset, u64::from(size))
+    }
+
+    /// EMFILE when `--max-open-files` note handles are already open
+    ///
+    /// Only note handles count: they are what can hold a buffered body.
+    /// Sidecars are opened without a handle, and attachment handles hold
+    /// only an open file.
+    fn check_open_files(&self) -> std::result::Result<(), c_int> {
+        match self.options.max_open_files {
+            Some(max) if self.file_handles.len() >= max => Err(libc::EMFILE),
+            _ => Ok(()),
+        }
+    }
+
+    /// Uncommitted body of a note, if any open handle is holding one
+    fn pending_body(&self, note_id: &str) -> Option<&[u8]> {
+        self.file_handles
+            .values()
+            .filter(|handle| handle.note_id == note_id)
+            .find_map(|handle| handle.pending.as_deref())
+    }
+
+    /// Describe how `getattr` and a full `read` of a note disagree, if they do
+    ///
+    /// Editors trust the reported size, so a mismatch truncates or pads what
+    /// they load. Returns None when the two agree.
+    fn size_invariant_violation(&self, ino: u64) -> Option<String> {
+        let attr = match self.get_attr(ino) {
+            Ok(attr) => attr,
+            Err(e) => return Some(format!("getattr failed with errno {e}")),
+        };
+        match self.read_note(ino, 0) {
+            Ok(data) if data.len() as u64 == attr.size => None,
+            Ok(data) => Some(format!(
+                "getattr reports {} bytes but read returns {}",
+                attr.size,
+                data.len()
+            )),
+            Err(e) => Some(format!(
+                "getattr reports {} bytes but read failed with errno {e}",
+                attr.size
+            )),
+        }
+    }
+
+    /// Log a warning for an inode whose size and content disagree when
+    /// `--debug-invariants` is on
+    fn debug_check_invariants(&self, ino: u64) {
+        if !self.options.debug_invariants {
+            return;
+        }
+        if let Some(violation) = self.size_invariant_violation(ino) {
+            let path = self.get_path_from_inode(ino).map_or("?", |p| p.as_str());
+            warn!("size invariant violated for {path} (inode {ino}): {violation}");
+        }
+    }
+
+    /// Current length in bytes of a note's body, including pending writes
+    fn body_len(&self, note_id: &str) -> std::result::Result<u64, c_int> {
+        if let Some(pending) = self.pending_body(note_id) {
+            return Ok(pending.len() as u64);
+        }
+        if self.options.encoding.is_some() {
+            return self
+                .stored_body(note_id)
+                .map(|body| body.len() as u64)
+                .map_err(|_| ENOENT);
+        }
+        self.db
+            .query_row(
+                "SELECT length(CAST(body AS BLOB)) FROM notes WHERE id = ?1",
+                [note_id],
+                |row| row.get::<_, Option<i64>>(0),
+            )
+            .map(|len| len.unwrap_or(0) as u64)
+            .map_err(|_| ENOENT)
+    }
+
+    /// The kind of sidecar `path` names (e.g. `<note>.md.diff`) and the id of
+    /// the note, or for a GPX track the folder, it belongs to
+    ///
+    /// Note sidecars are not listed by `readdir`; they resolve only when
+    /// looked up by name. GPX tracks exist only under `--gpx` in folders
+    /// holding geotagged notes. Neither shadows a real entry of the same name.
+    fn sidecar(&self, path: &str) -> Option<(Sidecar, String)> {
+        Sidecar::ALL.into_iter().find_map(|sidecar| {
+            let base = path.strip_suffix(sidecar.suffix(self.options.metadata_format))?;
+            if sidecar == Sidecar::GpxTrack {
+                if !self.options.gpx || self.virtual_dir(base).is_some() {
+                    return None;
+                }
+                let folder_id = self
+                    .get_parent_folder_id(if base.is_empty() { "/" } else { base })
+                    .ok()?;
+                self.geotagged_updated_time(&folder_id).ok()??;
+                return Some((sidecar, folder_id));
+            }
+            match self.resolve_path(base)? {
+                (EntryKind::Note, id) => Some((sidecar, id)),
+                (EntryKind::Folder, _) => None,
+            }
+        })
+    }
+
+    /// Content of the sidecar at `sidecar_path` for the note or folder `id`
+    fn sidecar_content(
+        &self,
+        sidecar_path: &str,
+        sidecar: Sidecar,
+        id: &str,
+    ) -> std::result::Result<Vec<u8>, c_int> {
+        let text = match sidecar {
+            Sidecar::Diff => self.pending_diff(sidecar_path, id)?,
+            Sidecar::PlainText => {
+                let body: String = self.stored_body(id).map_err(|_| libc::EIO)?;
+                markdown_to_plain_text(&body)
+            }
+            Sidecar::GpxTrack => self.gpx_track(id)?,
+            Sidecar::Metadata => self
+                .options
+                .metadata_format
+                .serialize(&self.note_record(id)?)?,
+            Sidecar::Gzip => {
+                let note_path = sidecar_path
+                    .strip_suffix(GZIP_SIDECAR_SUFFIX)
+                    .ok_or(ENOENT)?;
+                return self.gzipped_note(note_path, id).map(|gz| gz.to_vec());
+            }
+        };
+        Ok(text.into_bytes())
+    }
+
+    /// The note at `note_path` as `read` returns it, gzip-compressed
+    ///
+    /// Compression happens on first use and is cached per note version;
+    /// bodies with writes still pending are compressed afresh every time.
+    fn gzipped_note(
+        &self,
+        note_path: &str,
+        note_id: &str,
+    ) -> std::result::Result<Arc<[u8]>, c_int> {
+        use std::hash::{Hash, Hasher};
+        use std::io::Write;
+
+        let body = self.note_content(note_path)?;
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        body.hash(&mut hasher);
+        let body_hash = hasher.finish();
+        let updated_time: i64 = self
+            .db
+            .query_row(
+                "SELECT updated_time FROM notes WHERE id = ?1",
+                [note_id],
+                |row| row.get(0),
+            )
+            .map_err(|_| ENOENT)?;
+        let cacheable = self.pending_body(note_id).is_none();
+
+        if cacheable
+            && let Some(entry) = self.gzip_cache.lock().unwrap().get(note_id)
+            && entry.updated_time == updated_time
+            && entry.body_hash == body_hash
+        {
+            return Ok(Arc::clone(&entry.compressed));
+        }
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&body).map_err(|_| libc::EIO)?;
+        let compressed: Arc<[u8]> = encoder.finish().map_err(|_| libc::EIO)?.into();
+        if cacheable {
+            self.gzip_cache.lock().unwrap().insert(
+                note_id.to_string(),
+                GzipCacheEntry {
+                    updated_time,
+                    body_hash,
+                    compressed: Arc::clone(&compressed),
+                },
+            );
+        }
+        Ok(compressed)
+    }
+
+    /// Every column of a note row except `body`, keyed by column name
+    ///
+    /// Blobs, which Joplin never stores here, come out as base64 strings.
+    fn note_record(
+        &self,
+        note_id: &str,
+    ) -> std::result::Result<serde_json::Map<String, serde_json::Value>, c_int> {
+        let mut stmt = self
+            .db
+            .prepare("SELECT * FROM notes WHERE id = ?1")
+            .map_err(|_| libc::EIO)?;
+        let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+        stmt.query_row([note_id], |row| {
+            let mut record = serde_json::Map::new();
+            for (i, column) in columns.iter().enumerate() {
+                if column == "body" {
+                    continue;
+                }
+                let value = match row.get_ref(i)? {
+                    ValueRef::Null => serde_json::Value::Null,
+                    ValueRef::Integer(n) => n.into(),
+                    ValueRef::Real(x) => serde_json::Number::from_f64(x)
+                        .map_or(serde_json::Value::Null, serde_json::Value::Number),
+                    ValueRef::Text(text) => String::from_utf8_lossy(text).into(),
+                    ValueRef::Blob(blob) => BASE64.encode(blob).into(),
+                };
+                record.insert(column.clone(), value);
+            }
+            Ok(record)
+        })
+        .optional()
+        .map_err(|_| libc::EIO)?
+        .ok_or(ENOENT)
+    }
+
+    /// The `--recent-count` most recently updated live notes, newest first,
+    /// as the symlinks `/.recent` lists
+    ///
+    /// Named and pointed at the notes as `note_symlinks` does.
+    fn recent_notes(&self) -> std::result::Result<Vec<NoteSymlink>, c_int> {
+        let rows = {
+            let mut stmt = self
+                .db
+                .prepare(
+                    "SELECT id, title, parent_id FROM notes WHERE deleted_time = 0 ORDER BY user_updated_time DESC, id LIMIT ?1",
+                )
+                .map_err(|_| libc::EIO)?;
+            stmt.query_map([self.options.recent_count as i64], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<_>>>())
+            .map_err(|_| libc::EIO)?
+        };
+
+        self.note_symlinks(RECENT_DIR, rows)
+    }
+
+    /// The notes and folders in Joplin's trash, folders first, each most
+    /// recently deleted first, as `/.trash` lists them
+    ///
+    /// A name two entries would share gets the `Title (a1b2c3)` id suffix
+    /// before the deletion time.
+    fn trashed_entries(&self) -> std::result::Result<Vec<TrashEntry>, c_int> {
+        let rows = {
+            let mut stmt = self
+                .db
+                .prepare(
+                    "SELECT 0, id, title, deleted_time FROM folders WHERE deleted_time != 0
+                     UNION ALL
+                     SELECT 1, id, title, deleted_time FROM notes WHERE deleted_time != 0
+                     ORDER BY 1, 4 DESC, 2",
+                )
+                .map_err(|_| libc::EIO)?;
+            stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, i64>(3)?,
+                ))
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<_>>>())
+            .map_err(|_| libc::EIO)?
+        };
+
+        let mut entries: Vec<TrashEntry> = Vec::new();
+        for (is_note, id, title, deleted_time) in rows {
+            let (kind, ext) = if is_note == 0 {
+                (EntryKind::Folder, Cow::Borrowed(""))
+            } else {
+                (EntryKind::Note, self.note_ext(&id).map_err(|_| libc::EIO)?)
+            };
+            let title = title.replace('/', "_");
+            let deleted = iso8601_utc(deleted_time);
+            let mut name = self.with_extension(&format!("{title} ({deleted})"), &ext);
+            if entries.iter().any(|entry| entry.name == name) {
+                let fragment: String = id.chars().take(6).collect();
+                name = self.with_extension(&format!("{title} ({fragment}) ({deleted})"), &ext);
+            }
+            entries.push(TrashEntry { name, kind, id });
+        }
+        Ok(entries)
+    }
+
+    /// The `/.trash` entry `path` names, if it is one
+    fn trash_entry(&self, path: &str) -> std::result::Result<Option<TrashEntry>, c_int> {
+        let (dir, name) = split_path(path);
+        if self.virtual_dir(dir) != Some(VirtualDir::Trash) {
+            return Ok(None);
+        }
+        Ok(self
+            .trashed_entries()?
+            .into_iter()
+            .find(|entry| entry.name == name))
+    }
+
+    /// Symlinks in the directory `dir` to the notes `rows` lists as
+    /// `(id, title, parent_id)`, in that order
+    ///
+    /// Each link is named like the note in its folder; a name already used
+    /// by an earlier entry gets the `Title (a1b2c3)` id suffix, which lookup
+    /// accepts too. Notes outside the mounted tree are left out.
+    fn note_symlinks(
+        &self,
+        dir: &str,
+        rows: Vec<(String, String, String)>,
+    ) -> std::result::Result<Vec<NoteSymlink>, c_int> {
+        let up = "../".repeat(dir.split('/').filter(|c| !c.is_empty()).count());
+        let mut entries: Vec<NoteSymlink> = Vec::new();
+        for (note_id, title, parent_id) in rows {
+            let Some(folder) = self.folder_path(&parent_id).map_err(|_| libc::EIO)? else {
+                continue;
+            };
+            let note_name = self.reachable_note_name(&parent_id, &title, &note_id)?;
+            let name = if entries.iter().any(|entry| entry.name == note_name) {
+                self.id_suffixed_name(&title, &note_id)?
+            } else {
+                note_name.clone()
+            };
+            let target = join_path(&folder, &note_name);
+            entries.push(NoteSymlink {
+                name,
+                target: format!("{up}{}", &target[1..]),
+                note_id,
+            });
+        }
+        Ok(entries)
+    }
+
+    /// The `/.recent` symlink `path` names, if it is one
+    fn recent_entry(&self, path: &str) -> std::result::Result<Option<NoteSymlink>, c_int> {
+        let (dir, name) = split_path(path);
+        if self.virtual_dir(dir) != Some(VirtualDir::Recent) {
+            return Ok(None);
+        }
+        Ok(self
+            .recent_notes()?
+            .into_iter()
+            .find(|entry| entry.name == name))
+    }
+
+    /// The note whose `<note>.backlinks` directory `path` names, under
+    /// `--backlinks`
+    fn backlinks_note(&self, path: &str) -> Option<String> {
+        if !self.options.backlinks {
+            return None;
+        }
+        match self.resolve_path(path.strip_suffix(BACKLINKS_SUFFIX)?)? {
+            (EntryKind::Note, id) => Some(id),
+            (EntryKind::Folder, _) => None,
+        }
+    }
+
+    /// The symlink inside a `<note>.backlinks` directory `path` names, if it
+    /// is one
+    fn backlink_entry(&self, path: &str) -> std::result::Result<Option<NoteSymlink>, c_int> {
+        let (dir, name) = split_path(path);
+        let Some(note_id) = self.backlinks_note(dir) else {
+            return Ok(None);
+        };
+        Ok(self
+            .backlinks(&note_id, dir)?
+            .into_iter()
+            .find(|entry| entry.name == name))
+    }
+
+    /// Symlinks in the backlinks directory `dir` to every live note whose
+    /// body references `note_id` as `:/<id>`, ordered by title
+    fn backlinks(&self, note_id: &str, dir: &str) -> std::result::Result<Vec<NoteSymlink>, c_int> {
+        let mut rows = Vec::new();
+        for source in self.backlink_sources(note_id)? {
+            let row = self
+                .db
+                .query_row(
+                    "SELECT title, parent_id FROM notes WHERE id = ?1 AND deleted_time = 0",
+                    [&source],
+                    |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+                )
+                .optional()
+                .map_err(|_| libc::EIO)?;
+            if let Some((title, parent_id)) = row {
+                rows.push((source, title, parent_id));
+            }
+        }
+        rows.sort_by(|a, b| (&a.1, &a.0).cmp(&(&b.1, &b.0)));
+        self.note_symlinks(dir, rows)
+    }
+
+    /// Ids of the notes referencing `note_id`, from the `BacklinkIndex`,
+    /// which is rebuilt first when `notes` has changed since it was built
+    fn backlink_sources(&self, note_id: &str) -> std::result::Result<Vec<String>, c_int> {
+        let stamp: (i64, i64) = self
+            .db
+            .query_row(
+                "SELECT COALESCE(MAX(updated_time), 0), COUNT(*) FROM notes",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|_| libc::EIO)?;
+        let mut index = self.backlink_index.lock().unwrap();
+        if index.as_ref().is_none_or(|index| index.stamp != stamp) {
+            let mut sources: HashMap<String, Vec<String>> = HashMap::new();
+            let mut stmt = self
+                .db
+                .prepare("SELECT id, body FROM notes WHERE deleted_time = 0 AND body LIKE '%:/%'")
+                .map_err(|_| libc::EIO)?;
+            let rows = stmt
+                .query_map([], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+                })
+                .map_err(|_| libc::EIO)?;
+            for row in rows {
+                let (source, body) = row.map_err(|_| libc::EIO)?;
+                for target in referenced_ids(&body) {
+                    let linking = sources.entry(target.to_string()).or_default();
+                    if target != source && !linking.contains(&source) {
+                        linking.push(source.clone());
+                    }
+                }
+            }
+            *index = Some(BacklinkIndex { stamp, sources });
+        }
+        Ok(index
+            .as_ref()
+            .and_then(|index| index.sources.get(note_id))
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    /// The `/.control` file `path` names, when `--control` is on
+    fn control_file(&self, path: &str) -> Option<ControlFile> {
+        let (dir, name) = split_path(path);
+        self.virtual_dir(dir)
+            .filter(|dir| *dir == VirtualDir::Control)?;
+        ControlFile::ALL
+            .into_iter()
+            .find(|file| file.name() == name)
+    }
+
+    /// What reading a control file returns: the answer to the last request
+    fn control_output(&self, file: ControlFile) -> &str {
+        match file {
+            ControlFile::PreviewRename => &self.rename_preview,
+            ControlFile::RebuildFts => &self.fts_rebuild,
+        }
+    }
+
+    /// Take a write to a control file; a write at offset 0 starts a new
+    /// request and later ones extend it, the answer being recomputed each
+    /// time
+    ///
+    /// `rebuild-fts` ignores what is written: each non-empty write rebuilds
+    /// the index once, and truncating the file alone does not.
+    fn write_control(
+        &mut self,
+        file: ControlFile,
+        offset: i64,
+        data: &[u8],
+    ) -> std::result::Result<u32, c_int> {
+        if offset == 0 {
+            self.control_input.clear();
+        }
+        self.control_input.push_str(&String::from_utf8_lossy(data));
+        match file {
+            ControlFile::PreviewRename => {
+                let request = self.control_input.clone();
+                self.rename_preview = self.preview_rename(&request);
+            }
+            ControlFile::RebuildFts if !data.is_empty() => {
+                self.fts_rebuild = match self.check_writable().map(|_| self.rebuild_fts()) {
+                    Ok(Ok(true)) => format!("rebuilt {FTS_TABLE}\n"),
+                    Ok(Ok(false)) => format!("no {FTS_TABLE} table\n"),
+                    Ok(Err(err)) => format!("error: {err}\n"),
+                    Err(errno) => format!("error: {}\n", std::io::Error::from_raw_os_error(errno)),
+                };
+            }
+            ControlFile::RebuildFts => {}
+        }
+        Ok(data.len() as u32)
+    }
+
+    /// Report of what renaming the first path in `request` to the second
+    /// would move, for `/.control/preview-rename`; nothing is changed
+    ///
+    /// Each affected path is a `<old>\t<new>` line, the entry itself and
+    /// everything below a folder, as `rename` remaps them. A refused rename
+    /// is a single `error: <reason>` line instead.
+    fn preview_rename(&mut self, request: &str) -> String {
+        let paths: Vec<&str> = request
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        let [old_path, new_path] = paths[..] else {
+            return if paths.is_empty() {
+                String::new()
+            } else {
+                "error: expected the source and destination paths on two lines\n".to_string()
+            };
+        };
+        let normalize = |path: &str| match path.trim_end_matches('/') {
+            "" => "/".to_string(),
+            path if path.starts_with('/') => path.to_string(),
+            path => format!("/{path}"),
+        };
+        let (old_path, new_path) = (normalize(old_path), normalize(new_path));
+        let ((parent_path, old_name), (new_parent_path, new_name)) =
+            (split_path(&old_path), split_path(&new_path));
+        let source = match self.rename_source(parent_path, old_name, new_parent_path, new_name) {
+            Ok(source) => source,
+            Err(errno) => {
+                return format!(
+                    "error: {old_path} -> {new_path}: {}\n",
+                    std::io::Error::from_raw_os_error(errno)
+                );
+            }
+        };
+
+        // Give everything below a folder an inode, as listing it would, so
+        // the remap covers the whole subtree
+        let allocated = match source.kind {
+            EntryKind::Folder => self.allocate_subtree(&old_path),
+            EntryKind::Note => self.get_or_create_inode(&old_path).map(|_| ()),
+        };
+        if let Err(errno) = allocated {
+            return format!(
+                "error: {old_path}: {}\n",
+                std::io::Error::from_raw_os_error(errno)
+            );
+        }
+        self.inodes
+            .renamed_paths(&old_path, &new_path)
+            .into_iter()
+            .map(|(from, to)| format!("{from}\t{to}\n"))
+            .collect()
+    }
+
+    /// Give the directory `path` and everything below it an inode, as
+    /// listing each directory would
+    fn allocate_subtree(&mut self, path: &str) -> std::result::Result<(), c_int> {
+        let mut pending = vec![path.to_string()];
+        while let Some(dir) = pending.pop() {
+            let ino = self.get_or_create_inode(&dir)?;
+            let Ok(fh) = self.open_directory(ino) else {
+                continue;
+            };
+            let mut entries = Vec::new();
+            let listed = self.read_directory(fh, 0, |_, _, kind, name| {
+                if name != "." && name != ".." {
+                    entries.push((kind, name.to_string()));
+                }
+                false
+            });
+            self.release_directory(fh);
+            if listed == Err(libc::ENFILE) {
+                return Err(libc::ENFILE);
+            }
+            for (kind, name) in entries {
+                let path = join_path(&dir, &name);
+                self.get_or_create_inode(&path)?;
+                if kind == FileType::Directory {
+                    pending.push(path);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Target of the symlink at `ino`, for `readlink`
+    ///
+    /// Returns ENOENT when the inode is unknown and EINVAL when it is not a
+    /// symlink.
+    fn read_link(&self, ino: u64) -> std::result::Result<Vec<u8>, c_int> {
+        let path = self.get_path_from_inode(ino).ok_or(ENOENT)?;
+        if let Some(entry) = self.recent_entry(path)?.or(self.backlink_entry(path)?) {
+            return Ok(entry.target.into_bytes());
+        }
+        match self.resolve_path(path) {
+            Some((EntryKind::Note, id)) => self
+                .symlink_target(&id)
+                .map_err(|_| libc::EIO)?
+                .ok_or(libc::EINVAL),
+            _ => Err(libc::EINVAL),
+        }
+    }
+
+    /// Whether note `id` stands for a symbolic link: its body starts with
+    /// `SYMLINK_PREFIX`
+    fn is_symlink_note(&self, id: &str) -> Result<bool> {
+        Ok(self
+            .db
+            .prepare_cached(
+                "SELECT CAST(substr(CAST(body AS BLOB), 1, ?2) AS TEXT) = ?3 FROM notes WHERE id = ?1",
+            )?
+            .query_row(
+                rusqlite::params![id, SYMLINK_PREFIX.len() as i64, SYMLINK_PREFIX],
+                |row| row.get::<_, Option<bool>>(0),
+            )
+            .optional()?
+            .flatten()
+            .unwrap_or(false))
+    }
+
+    /// The target of the symlink note `id`, or None for an ordinary note
+    fn symlink_target(&self, id: &str) -> Result<Option<Vec<u8>>> {
+        if !self.is_symlink_note(id)? {
+            return Ok(None);
+        }
+        let body = self.stored_body_bytes(id)?;
+        Ok(body
+            .strip_prefix(SYMLINK_PREFIX.as_bytes())
+            .map(<[u8]>::to_vec))
+    }
+
+    /// Create a symlink `link_name` in `parent_path` pointing at `target`
+    ///
+    /// The link is a note titled `link_name` whose body is the target after
+    /// `SYMLINK_PREFIX`; it is listed under its bare title. Returns:
+    /// - EEXIST: the name is taken
+    /// - EPERM: inside a virtual directory
+    /// - ENAMETOOLONG: past `--name-max`
+    /// - EROFS: the database is read-only
+    fn create_symlink(
+        &mut self,
+        parent_path: &str,
+        link_name: &str,
+        target: &[u8],
+    ) -> std::result::Result<FileAttr, c_int> {
+        self.check_not_virtual(parent_path, link_name)?;
+        self.check_name_len(link_name)?;
+        self.check_writable()?;
+        self.check_inode_room()?;
+
+        let full_path = join_path(parent_path, link_name);
+        let note_id = self.in_write_transaction(|fs| {
+            if fs.resolve_path(&full_path).is_some() {
+                return Err(libc::EEXIST);
+            }
+            let parent_id = fs
+                .get_parent_folder_id(parent_path)
+                .map_err(Self::resolve_errno)?;
+            let body = [SYMLINK_PREFIX.as_bytes(), target].concat();
+            fs.insert_note_row(&parent_id, link_name, &body, 1)
+                .map_err(Self::write_errno)
+        })?;
+
+        let inode = self.get_or_create_inode(&full_path)?;
+        let attr = self.note_attr(inode, &note_id)?;
+        self.inodes.remember(inode);
+        Ok(attr)
+    }
+
+    /// Path of folder `id` within the mount, or None when the folder is
+    /// deleted, outside the mounted tree or its ancestry is broken
+    fn folder_path(&self, id: &str) -> Result<Option<String>> {
+        let mut titles = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+        let mut current = id.to_string();
+        while current != self.root_folder_id {
+            if current.is_empty() || !seen.insert(current.clone()) {
+                return Ok(None);
+            }
+            let Some((title, parent_id)) = self
+                .db
+                .query_row(
+                    "SELECT title, parent_id FROM folders WHERE id = ?1 AND deleted_time = 0",
+                    [&current],
+                    |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+                )
+                .optional()?
+            else {
+                return Ok(None);
+            };
+            titles.push(title);
+            current = parent_id;
+        }
+        Ok(Some(
+            titles
+                .iter()
+                .rev()
+                .fold("/".to_string(), |path, title| join_path(&path, title)),
+        ))
+    }
+
+    /// The note whose `<note>.attachments` directory `path` names
+    fn attachments_note(&self, path: &str) -> Option<String> {
+        match self.resolve_path(path.strip_suffix(ATTACHMENTS_SUFFIX)?)? {
+            (EntryKind::Note, id) => Some(id),
+            (EntryKind::Folder, _) => None,
+        }
+    }
+
+    /// The note and attachment a path inside `<note>.attachments/` names,
+    /// or beside the note under `--inline-attachment-files`
+    fn attachment(&self, path: &str) -> Option<(String, Attachment)> {
+        let (dir, name) = split_path(path);
+        let Some(note_id) = self.attachments_note(dir) else {
+            if !self.options.inline_attachment_files {
+                return None;
+            }
+            let parent_id = self.get_parent_folder_id(dir).ok()?;
+            return self
+                .sibling_attachments(&parent_id)
+                .ok()?
+                .into_iter()
+                .find(|(_, attachment)| attachment.name == name);
+        };
+        let attachment = self
+            .note_attachments(&note_id)
+            .ok()?
+            .into_iter()
+            .find(|attachment| attachment.name == name)?;
+        Some((note_id, attachment))
+    }
+
+    /// Resources of every note in a folder, named `<note title>.<name in
+    /// the note's attachments directory>` for listing beside the notes
+    ///
+    /// Only `note_resources` is consulted. A name two notes would share (as
+    /// duplicate titles do) gets the note's id, `Title (a1b2c3).photo.png`,
+    /// from the second note on. Notes and folders win over a sibling with
+    /// their name.
+    fn sibling_attachments(
+        &self,
+        parent_id: &str,
+    ) -> std::result::Result<Vec<(String, Attachment)>, c_int> {
+        if !self.has_table("resources")? || !self.has_table("note_resources")? {
+            return Ok(Vec::new());
+        }
+        let notes: Vec<(String, String)> = {
+            let mut stmt = self
+                .db
+                .prepare(
+                    "SELECT id, title FROM notes WHERE parent_id = ?1 AND deleted_time = 0 \
+                     AND id IN (SELECT note_id FROM note_resources WHERE is_associated = 1) \
+                     ORDER BY title, id",
+                )
+                .map_err(|_| libc::EIO)?;
+            stmt.query_map([parent_id], |row| Ok((row.get(0)?, row.get(1)?)))
+                .and_then(|rows| rows.collect::<Result<_>>())
+                .map_err(|_| libc::EIO)?
+        };
+
+        let mut siblings: Vec<(String, Attachment)> = Vec::new();
+        for (note_id, title) in notes {
+            let title = title.replace('/', "_");
+            for mut attachment in self.note_attachments(&note_id)? {
+                let mut name = format!("{title}.{}", attachment.name);
+                if siblings.iter().any(|(_, taken)| taken.name == name) {
+                    let fragment: String = note_id.chars().take(6).collect();
+                    name = format!("{title} ({fragment}).{}", attachment.name);
+                }
+                attachment.name = name;
+                siblings.push((note_id.clone(), attachment));
+            }
+        }
+        Ok(siblings)
+    }
+
+    /// Whether the database has a table called `name`
+    fn has_table(&self, name: &str) -> std::result::Result<bool, c_int> {
+        self.db
+            .query_row(
+                "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+                [name],
+                |_| Ok(()),
+            )
+            .optional()
+            .map(|found| found.is_some())
+            .map_err(|_| libc::EIO)
+    }
+
+    /// Resources embedded in a note, named by title and extension
+    ///
+    /// Joplin records these in `note_resources`; databases predating that
+    /// table fall back to the `:/<id>` links in the body. A resource whose
+    /// name is already taken is listed under its id instead.
+    fn note_attachments(&self, note_id: &str) -> std::result::Result<Vec<Attachment>, c_int> {
+        if !self.has_table("resources")? {
+            return Ok(Vec::new());
+        }
+
+        let resources: Vec<(String, String, String)> = if self.has_table("note_resources")? {
+            let mut stmt = self
+                .db
+                .prepare(
+                    "SELECT r.id, r.title, r.file_extension FROM note_resources nr \
+                         JOIN resources r ON r.id = nr.resource_id \
+                         WHERE nr.note_id = ?1 AND nr.is_associated = 1 ORDER BY r.title, r.id",
+                )
+                .map_err(|_| libc::EIO)?;
+            stmt.query_map([note_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+                .and_then(|rows| rows.collect::<Result<Vec<_>>>())
+                .map_err(|_| libc::EIO)?
+        } else {
+            let body = self.stored_body(note_id).map_err(|_| libc::EIO)?;
+            let mut resources = Vec::new();
+            for id in linked_resource_ids(&body) {
+                let row = self
+                    .db
+                    .query_row(
+                        "SELECT title, file_extension FROM resources WHERE id = ?1",
+                        [id],
+                        |row| Ok((id.to_string(), row.get(0)?, row.get(1)?)),
+                    )
+                    .optional()
+                    .map_err(|_| libc::EIO)?;
+                resources.extend(row);
+            }
+            resources
+        };
+
+        Ok(self.named_attachments(resources))
+    }
+
+    /// Every resource in the database, named as in an attachments
+    /// directory, as `/.resources` lists them
+    fn resource_entries(&self) -> std::result::Result<Vec<Attachment>, c_int> {
+        if !self.has_table("resources")? {
+            return Ok(Vec::new());
+        }
+        let resources = {
+            let mut stmt = self
+                .db
+                .prepare("SELECT id, title, file_extension FROM resources ORDER BY title, id")
+                .map_err(|_| libc::EIO)?;
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+                .and_then(|rows| rows.collect::<Result<Vec<_>>>())
+                .map_err(|_| libc::EIO)?
+        };
+        Ok(self.named_attachments(resources))
+    }
+
+    /// The `/.resources` file `path` names, if it is one
+    fn resource_entry(&self, path: &str) -> std::result::Result<Option<Attachment>, c_int> {
+        let (dir, name) = split_path(path);
+        if self.virtual_dir(dir) != Some(VirtualDir::Resources) {
+            return Ok(None);
+        }
+        Ok(self
+            .resource_entries()?
+            .into_iter()
+            .find(|resource| resource.name == name))
+    }
+
+    /// Name the `(id, title, file_extension)` resources `resources` lists
+    /// by title and extension, in that order
+    ///
+    /// A resource whose name is already taken is listed under its id instead.
+    fn named_attachments(&self, resources: Vec<(String, String, String)>) -> Vec<Attachment> {
+        let mut attachments: Vec<Attachment> = Vec::with_capacity(resources.len());
+        for (id, title, extension) in resources {
+            let stem = if title.is_empty() { &id } else { &title };
+            let mut name = stem.replace('/', "_");
+            let dotted = format!(".{extension}");
+            if !extension.is_empty() && !name.to_lowercase().ends_with(&dotted.to_lowercase()) {
+                name.push_str(&dotted);
+            }
+            if attachments.iter().any(|taken| taken.name == name) {
+                name = format!("{id}{}", if extension.is_empty() { "" } else { &dotted });
+            }
+            attachments.push(Attachment {
+                name,
+                file: self.resource_file(&id, &extension),
+                resource_id: id,
+            });
+        }
+        attachments
+    }
+
+    /// Latest `updated_time` among the geotagged notes directly in
+    /// `folder_id`, or None when it has none
+    ///
+    /// A note is geotagged unless both its latitude and longitude are zero.
+    fn geotagged_updated_time(&self, folder_id: &str) -> std::result::Result<Option<i64>, c_int> {
+        self.db
+            .query_row(
+                "SELECT MAX(updated_time) FROM notes WHERE parent_id = ?1 AND deleted_time = 0 AND (latitude != 0 OR longitude != 0)",
+                [folder_id],
+                |row| row.get(0),
+            )
+            .map_err(|_| libc::EIO)
+    }
+
+    /// GPX 1.1 document with one waypoint per geotagged note in `folder_id`,
+    /// oldest first
+    fn gpx_track(&self, folder_id: &str) -> std::result::Result<String, c_int> {
+        let mut stmt = self
+            .db
+            .prepare(
+                "SELECT title, latitude, longitude, altitude, user_created_time FROM notes \
+                 WHERE parent_id = ?1 AND deleted_time = 0 AND (latitude != 0 OR longitude != 0) \
+                 ORDER BY user_created_time, id",
+            )
+            .map_err(|_| libc::EIO)?;
+        let waypoints = stmt
+            .query_map([folder_id], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, f64>(1)?,
+                    row.get::<_, f64>(2)?,
+                    row.get::<_, f64>(3)?,
+                    row.get::<_, i64>(4)?,
+                ))
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<_>>>())
+            .map_err(|_| libc::EIO)?;
+
+        let mut gpx = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <gpx version=\"1.1\" creator=\"sqlite_fuse\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n",
+        );
+        for (title, lat, lon, ele, created) in waypoints {
+            gpx.push_str(&format!("  <wpt lat=\"{lat}\" lon=\"{lon}\">\n"));
+            if ele != 0.0 {
+                gpx.push_str(&format!("    <ele>{ele}</ele>\n"));
+            }
+            gpx.push_str(&format!("    <time>{}</time>\n", iso8601_utc(created)));
+            gpx.push_str(&format!("    <name>{}</name>\n", xml_escape(&title)));
+            gpx.push_str("  </wpt>\n");
+        }
+        gpx.push_str("</gpx>\n");
+        Ok(gpx)
+    }
+
+    /// Unified diff from a note's stored body to the body its open handles
+    /// are still buffering; empty when nothing is pending, including when
+    /// the note is not open for writing at all
+    fn pending_diff(
+        &self,
+        sidecar_path: &str,
+        note_id: &str,
+    ) -> std::result::Result<String, c_int> {
+        let Some(pending) = self.pending_body(note_id) else {
+            return Ok(String::new());
+        };
+        let stored: String = self.stored_body(note_id).map_err(|_| libc::EIO)?;
+        let pending = String::from_utf8_lossy(pending);
+
+        let name = sidecar_path
+            .strip_suffix(DIFF_SIDECAR_SUFFIX)
+            .map(|note_path| split_path(note_path).1)
+            .unwrap_or_default();
+        Ok(
+            similar::TextDiff::from_lines(stored.as_str(), pending.as_ref())
+                .unified_diff()
+                .header(&format!("a/{name}"), &format!("b/{name}"))
+                .to_string(),
+        )
+    }
+
+    /// Write through an open handle
+    ///
+    /// Handles opened with `O_APPEND` ignore `offset` and always write at the
+    /// current end of the body, pending writes included.
+    ///
+    /// Without `--write-debounce-ms` this is `write_note`. With it, writes
+    /// land in the handle's buffer and are committed at most once per
+    /// interval; whatever is still pending is committed by `flush`/`release`.
+    /// Until then a crash loses the buffered writes, which is the price of
+    /// keeping Joplin's history free of per-keystroke revisions.
+    fn write_handle(
+        &mut self,
+        fh: u64,
+        ino: u64,
+        offset: i64,
+        data: &[u8],
+    ) -> std::result::Result<u32, c_int> {
+        let control = self
+            .get_path_from_inode(ino)
+            .is_some_and(|path| self.control_file(path).is_some());
+        let Some(interval) = self.options.write_debounce.filter(|_| !control) else {
+            // Appends find the end inside the write's transaction
+            return match self.file_handles.get(&fh) {
+                Some(handle) if handle.append => {
+                    let note_id = handle.note_id.clone();
+                    self.write_body(&note_id, None, data)
+                }
+                _ => self.write_note(ino, offset, data),
+            };
+        };
+        let offset = match self.file_handles.get(&fh) {
+            Some(handle) if handle.append => self.body_len(&handle.note_id)? as i64,
+            _ => offset,
+        };
+        if self.bodies_read_only() {
+            return Err(libc::EROFS);
+        }
+        if !self.file_handles.contains_key(&fh) {
+            return self.write_note(ino, offset, data);
+        }
+
+        if self.file_handles[&fh].pending.is_none() {
+            let note_id = self.file_handles[&fh].note_id.clone();
+            let body = self.stored_body_bytes(&note_id).map_err(|_| ENOENT)?;
+            self.file_handles.get_mut(&fh).unwrap().pending = Some(body);
+        }
+
+        let handle = self.file_handles.get_mut(&fh).unwrap();
+        let buffer = handle.pending.as_mut().unwrap();
+        let start = offset as usize;
+        if buffer.len() < start + data.len() {
+            buffer.resize(start + data.len(), 0);
+        }
+        buffer[start..start + data.len()].copy_from_slice(data);
+
+        let due = handle
+            .last_commit
+            .is_none_or(|last| last.elapsed() >= interval);
+        if due {
+            self.commit_handle(fh)?;
+        }
+        Ok(data.len() as u32)
+    }
+
+    /// Copy `len` bytes of the note behind `ino_in` into the one behind
+    /// `ino_out`, as `copy_file_range` asks
+    ///
+    /// `cp note.md copy.md` creates `copy.md` empty, then asks for the whole
+    /// of `note.md` at offset 0. Within one directory that is taken as
+    /// duplicating the note: see `duplicate_note`. Any other range, within
+    /// one note or across two, copies the bytes through `write_body`, so the
+    /// destination's times advance as a write's would. Ranges involving
+    /// anything but notes get EOPNOTSUPP, on which the kernel falls back to
+    /// reading and writing.
+    fn copy_note_range(
+        &mut self,
+        ino_in: u64,
+        offset_in: i64,
+        ino_out: u64,
+        offset_out: i64,
+        len: u64,
+    ) -> std::result::Result<u32, c_int> {
+        let note_id = |fs: &Self, ino: u64| match fs.resolve_path(fs.get_path_from_inode(ino)?) {
+            Some((EntryKind::Note, id)) => Some(id),
+            _ => None,
+        };
+        let (Some(source), Some(target)) = (note_id(self, ino_in), note_id(self, ino_out)) else {
+            return Err(libc::EOPNOTSUPP);
+        };
+        if self.bodies_read_only() {
+            return Err(libc::EROFS);
+        }
+        self.commit_note(&source)?;
+        let source_len = self.body_len(&source)?;
+        let parent = |id: &str| {
+            self.db
+                .query_row("SELECT parent_id FROM notes WHERE id = ?1", [id], |row| {
+                    row.get::<_, String>(0)
+                })
+                .map_err(|_| ENOENT)
+        };
+        let duplicates = source != target
+            && offset_in == 0
+            && offset_out == 0
+            && len >= source_len
+            && self.body_len(&target)? == 0
+            && parent(&source)? == parent(&target)?;
+        if duplicates {
+            self.duplicate_note(&source, &target)?;
+            return Ok(source_len.min(u64::from(u32::MAX)) as u32);
+        }
+        if offset_in < 0 || offset_out < 0 {
+            return Err(libc::EINVAL);
+        }
+        // The source is read whole before the write, so overlapping ranges
+        // of one note copy what was there
+        self.commit_note(&target)?;
+        let body = self.stored_body_bytes(&source).map_err(|_| ENOENT)?;
+        let chunk = byte_window(&body, offset_in, len.min(u64::from(u32::MAX)));
+        if chunk.is_empty() {
+            return Ok(0);
+        }
+        self.write_body(&target, Some(offset_out), chunk)
+    }
+
+    /// Give the note `target` the body and metadata of `source`, and its tags
+    ///
+    /// Every column is copied except `DUPLICATE_RESET_COLUMNS`: `target`
+    /// keeps its id, title and notebook, is not shared, and counts as
+    /// updated now with the created time it was given. Metadata such as
+    /// `source_url`, `author`, the to-do fields and `markup_language` carry
+    /// over, whatever columns the schema has. Each of the source's
+    /// `note_tags` rows is repeated under a new id.
+    fn duplicate_note(&mut self, source: &str, target: &str) -> std::result::Result<(), c_int> {
+        let columns: Vec<String> = {
+            let mut stmt = self
+                .db
+                .prepare("SELECT name FROM pragma_table_info('notes')")
+                .map_err(|_| libc::EIO)?;
+            stmt.query_map([], |row| row.get::<_, String>(0))
+                .and_then(|rows| rows.collect::<Result<Vec<_>>>())
+                .map_err(|_| libc::EIO)?
+                .into_iter()
+                .filter(|column| !DUPLICATE_RESET_COLUMNS.contains(&column.as_str()))
+                .map(|column| format!("`{column}`"))
+                .collect()
+        };
+        let columns = columns.join(", ");
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let has_tags = self.has_table("note_tags")?;
+
+        let _lock = NoteLock::acquire(target);
+        self.in_write_transaction(|fs| {
+            fs.db
+                .execute(
+                    &format!(
+                        "UPDATE notes SET ({columns}) = (SELECT {columns} FROM notes WHERE id = ?2), \
+                         updated_time = ?3, user_updated_time = ?3 WHERE id = ?1"
+                    ),
+                    rusqlite::params![target, source, now],
+                )
+                .map_err(Self::write_errno)?;
+            if has_tags {
+                let tag_ids: Vec<String> = {
+                    let mut stmt = fs
+                        .db
+                        .prepare("SELECT tag_id FROM note_tags WHERE note_id = ?1")
+                        .map_err(|_| libc::EIO)?;
+                    stmt.query_map([source], |row| row.get(0))
+                        .and_then(|rows| rows.collect::<Result<Vec<_>>>())
+                        .map_err(|_| libc::EIO)?
+                };
+                for tag_id in tag_ids {
+                    fs.db
+                        .execute(
+                            "INSERT INTO note_tags (id, note_id, tag_id, created_time, updated_time, user_created_time, user_updated_time) \
+                             VALUES (?1, ?2, ?3, ?4, ?4, ?4, ?4)",
+                            rusqlite::params![Self::generate_uuid(), target, tag_id, now],
+                        )
+                        .map_err(Self::write_errno)?;
+                }
+            }
+            fs.db
+                .touch_note_parent(target, now)
+                .map_err(Self::write_errno)
+        })
+    }
+
+    /// Make everything written so far durable, as `fsync` and `fsyncdir` ask
+    ///
+    /// Commits the pending body of the file handle `fh`, if any, then in WAL
+    /// mode runs `PRAGMA wal_checkpoint(FULL)` so the database file itself
+    /// holds the writes. Other journal modes commit straight into the file,
+    /// so there is nothing more to do. A checkpoint that other connections
+    /// keep from completing still succeeds: the commits are durable in the
+    /// WAL. Returns EIO if the commit or the checkpoint fails.
+    fn sync_database(&mut self, fh: Option<u64>) -> std::result::Result<(), c_int> {
+        if let Some(fh) = fh {
+            self.commit_handle(fh)?;
+        }
+        if self.read_only() {
+            return Ok(());
+        }
+        let mode: String = self
+            .db
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .map_err(|_| libc::EIO)?;
+        if mode.eq_ignore_ascii_case("wal") {
+            self.db
+                .query_row("PRAGMA wal_checkpoint(FULL)", [], |_| Ok(()))
+                .map_err(|_| libc::EIO)?;
+        }
+        Ok(())
+    }
+
+    /// Commit a handle's pending body, if it has one
+    ///
+    /// Returns ENOSPC if the database's disk is full and EIO if the update
+    /// otherwise fails; either way the body stays pending.
+    fn commit_handle(&mut self, fh: u64) -> std::result::Result<(), c_int> {
+        let Some(handle) = self.file_handles.get_mut(&fh) else {
+            return Ok(());
+        };
+        let Some(pending) = handle.pending.take() else {
+            return Ok(());
+        };
+        let note_id = handle.note_id.clone();
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let result = self.db.execute(
+            "UPDATE notes SET body = ?1, updated_time = ?2, user_updated_time = ?2 WHERE id = ?3",
+            rusqlite::params![self.body_param(&pending), now, note_id],
+        )
+        .and_then(|_| self.db.touch_note_parent(&note_id, now));
+
+        let handle = self.file_handles.get_mut(&fh).unwrap();
+        if let Err(err) = result {
+            handle.pending = Some(pending);
+            return Err(Self::write_errno(err));
+        }
+        handle.last_commit = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Commit every pending body held for `note_id`
+    fn commit_note(&mut self, note_id: &str) -> std::result::Result<(), c_int> {
+        let handles: Vec<u64> = self
+            .file_handles
+            .iter()
+            .filter(|(_, handle)| handle.note_id == note_id && handle.pending.is_some())
+            .map(|(fh, _)| *fh)
+            .collect();
+        for fh in handles {
+            self.commit_handle(fh)?;
+        }
+        Ok(())
+    }
+
+    /// Commit pending writes and forget the handle
+    fn release_handle(&mut self, fh: u64) -> std::result::Result<(), c_int> {
+        self.release_resource(fh);
+        let result = self.commit_handle(fh);
+        self.file_handles.remove(&fh);
+        result
+    }
+
+    /// Apply attribute changes to a note and return its updated attributes
+    ///
+    /// Size changes are persisted (truncating or zero-extending the body) and
+    /// a new mode is kept in `note_modes` for the life of the mount; the
+    /// requested uid/gid are echoed back in the returned attributes.
+    ///
+    /// Note bodies are stored as text, so truncating into the middle of a
+    /// multi-byte UTF-8 character cuts at the last character boundary at or
+    /// below the requested size instead; the reported size reflects the cut.
+    ///
+    /// Returns the errno to reply with on failure:
+    /// - ENOENT: the inode does not name a live note
+    /// - EROFS: a size change while bodies or the database are read-only
+    /// - ENOSPC: the database's disk is full
+    /// - EIO: the database update failed
+    fn set_attr(
+        &mut self,
+        ino: u64,
+        mode: Option<u32>,
+        uid: Option<u32>,
+        gid: Option<u32>,
+        size: Option<u64>,
+    ) -> std::result::Result<FileAttr, c_int> {
+        let path = self.get_path_from_inode(ino).ok_or(ENOENT)?.clone();
+        // Truncating a control file, as `>` does, is the start of a request
+        if let Some(file) = self.control_file(&path) {
+            if size == Some(0) {
+                self.write_control(file, 0, b"")?;
+            }
+            return self.get_attr(ino);
+        }
+        let (note_id, _) = self.note_body_for_path(&path)?;
+
+        // Buffered writes land first so a truncate applies on top of them
+        self.commit_note(&note_id)?;
+
+        // Handle size changes (file truncation/extension), serialized with
+        // other writers to the note like `write_body`
+        if let Some(new_size) = size {
+            if self.bodies_read_only() {
+                return Err(libc::EROFS);
+            }
+            let _lock = NoteLock::acquire(&note_id);
+            self.in_write_transaction(|fs| {
+                let mut new_content = fs.stored_body_bytes(&note_id).map_err(|_| ENOENT)?;
+                let mut target_size = new_size as usize;
+
+                // Truncating text never splits a character; a body already
+                // holding invalid UTF-8 is cut exactly
+                if let Ok(text) = std::str::from_utf8(&new_content) {
+                    while !text.is_char_boundary(target_size.min(text.len())) {
+                        target_size -= 1;
+                    }
+                }
+                // Extension pads with null bytes
+                new_content.resize(target_size, 0);
+
+                // Update content in database
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs() as i64;
+
+                fs.db
+                    .execute(
+                        "UPDATE notes SET body = ?1, updated_time = ?2, user_updated_time = ?2 WHERE id = ?3",
+                        rusqlite::params![fs.body_param(&new_content), now, note_id],
+                    )
+                    .map_err(Self::write_errno)?;
+                fs.db.touch_note_parent(&note_id, now)
+                    .map_err(Self::write_errno)
+            })?;
+        }
+
+        if let Some(mode) = mode {
+            self.note_modes
+                .insert(note_id.clone(), (mode & 0o7777) as u16);
+        }
+
+        // Return updated file attributes, echoing the requested ownership
+        let mut attr = self.note_attr(ino, &note_id)?;
+        attr.uid = uid.unwrap_or(attr.uid);
+        attr.gid = gid.unwrap_or(attr.gid);
+        Ok(attr)
+    }
+
+    /// Reserve `[offset, offset + length)` of a note for `fallocate`
+    ///
+    /// A body shorter than `offset + length` is padded with null bytes to
+    /// that size through `set_attr`, as a growing truncate is; a region it
+    /// already covers, or any request with `FALLOC_FL_KEEP_SIZE`, leaves it
+    /// alone since the database has no space to reserve.
+    ///
+    /// Returns the errno to reply with on failure:
+    /// - EOPNOTSUPP: a mode other than `FALLOC_FL_KEEP_SIZE`, such as
+    ///   `FALLOC_FL_PUNCH_HOLE`
+    /// - EINVAL: a negative offset or a non-positive length
+    /// - EFBIG: the region ends past `i64::MAX`
+    /// - otherwise as `set_attr`
+    fn allocate_note(
+        &mut self,
+        ino: u64,
+        offset: i64,
+        length: i64,
+        mode: i32,
+    ) -> std::result::Result<(), c_int> {
+        if mode & !libc::FALLOC_FL_KEEP_SIZE != 0 {
+            return Err(libc::EOPNOTSUPP);
+        }
+        if offset < 0 || length <= 0 {
+            return Err(libc::EINVAL);
+        }
+        let end = offset.checked_add(length).ok_or(libc::EFBIG)? as u64;
+        let path = self.get_path_from_inode(ino).ok_or(ENOENT)?.clone();
+        let (note_id, _) = self.note_body_for_path(&path)?;
+        if mode & libc::FALLOC_FL_KEEP_SIZE != 0 || self.body_len(&note_id)? >= end {
+            return Ok(());
+        }
+        self.set_attr(ino, None, None, None, Some(end)).map(|_| ())
+    }
+
+    /// Check that `parent_path/old_name` may be renamed to
+    /// `new_parent_path/new_name` and find the row that would move
+    ///
+    /// Fails with EPERM for virtual entries, ENAMETOOLONG, ENOENT when the
+    /// source or a parent is missing, and EISDIR/ENOTDIR when the name is
+    /// held by an entry of the other kind. Used by `rename` and by
+    /// `/.control/preview-rename`.
+    fn rename_source(
+        &self,
+        parent_path: &str,
+        old_name: &str,
+        new_parent_path: &str,
+        new_name: &str,
+    ) -> std::result::Result<RenameSource, c_int> {
+        self.check_not_virtual(parent_path, old_name)?;
+        self.check_not_virtual(new_parent_path, new_name)?;
+        self.check_name_len(new_name)?;
+
+        // Get parent folder IDs from database
+        let parent_folder_id = self
+            .get_parent_folder_id(parent_path)
+            .map_err(Self::resolve_errno)?;
+        let new_parent_folder_id = self
+            .get_parent_folder_id(new_parent_path)
+            .map_err(Self::resolve_errno)?;
+
+        // Both kinds are renamed by the row lookup resolves the name to, so a
+        // suffixed duplicate (`Title (2).md`) or one of several same-named
+        // folders moves alone
+        let old_path = join_path(parent_path, old_name);
+        let (kind, id) = self.resolve_path(&old_path).ok_or(ENOENT)?;
+
+        // Refuse to replace an existing entry of a different kind, as
+        // rename(2) does, and replace a note with a note. The destination
+        // resolving to the source itself is a case-only rename under a
+        // case-insensitive collation.
+        let mut replaced = None;
+        if let Some((dest_kind, dest_id)) = self.resolve_path(&join_path(new_parent_path, new_name))
+            && dest_id != id
+        {
+            match (kind, dest_kind) {
+                (EntryKind::Note, EntryKind::Folder) => return Err(libc::EISDIR),
+                (EntryKind::Folder, EntryKind::Note) => return Err(libc::ENOTDIR),
+                (EntryKind::Note, EntryKind::Note) => replaced = Some(dest_id),
+                (EntryKind::Folder, EntryKind::Folder) => {}
+            }
+        }
+        Ok(RenameSource {
+            kind,
+            id,
+            parent_folder_id,
+            new_parent_folder_id,
+            replaced,
+        })
+    }
+
+    /// Rename or move the file or folder `old_name` in `parent` to `new_name` in `newparent`
+    ///
+    /// The source is resolved to one row exactly as lookup resolves it
+    /// (folders before notes) and that row alone is updated by id, in one
+    /// transaction, so a rename that only changes case (`note.md` to
+    /// `Note.md`) is applied rather than mistaken for a collision. Moving a
+    /// folder carries the inode mappings of all its descendants along with
+    /// it. A note moved onto another note deletes that note (see
+    /// `delete_row`) in the same transaction, as rename(2) replaces its
+    /// target, rather than leaving two notes with the title. The row is
+    /// stamped as `NoteStore::touch_row` stamps it.
+    ///
+    /// Returns the errno to reply with on failure:
+    /// - ENOENT: either parent is unknown or nothing named `old_name` exists
+    /// - EISDIR: a note would replace an existing folder
+    /// - ENOTDIR: a folder would replace an existing note
+    /// - EPERM: either parent is a virtual directory
+    /// - ENAMETOOLONG: `new_name` is longer than `--name-max`
+    /// - EROFS: the database is read-only
+    fn rename_entry(
+        &mut self,
+        parent: u64,
+        old_name: &str,
+        newparent: u64,
+        new_name: &str,
+    ) -> std::result::Result<(), c_int> {
+        // Get parent paths
+        let parent_path = self.get_path_from_inode(parent).ok_or(ENOENT)?.clone();
+        let new_parent_path = self.get_path_from_inode(newparent).ok_or(ENOENT)?.clone();
+        self.check_writable()?;
+        let old_name: &str = &self.canonical_name(&parent_path, old_name);
+        let RenameSource {
+            kind: source_kind,
+            id: source_id,
+            parent_folder_id,
+            new_parent_folder_id,
+            replaced,
+        } = self.rename_source(&parent_path, old_name, &new_parent_path, new_name)?;
+
+        let current_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        let old_path = join_path(&parent_path, old_name);
+
+        let new_path = join_path(&new_parent_path, new_name);
+
+        let is_symlink = source_kind == EntryKind::Note
+            && self.is_symlink_note(&source_id).map_err(|_| libc::EIO)?;
+        let (table, new_title) = match source_kind {
+            // A symlink's name is its whole title
+            EntryKind::Note if is_symlink => ("notes", new_name),
+            EntryKind::Note => ("notes", self.strip_note_suffix(new_name)),
+            // Keeping a notebook's own decoration does not add it to the title
+            EntryKind::Folder => match split_decoration(new_name) {
+                Some((tag, title))
+                    if self.decorates(&new_parent_folder_id)
+                        && self
+                            .notebook_tag(&source_id)
+                            .map_err(|_| libc::EIO)?
+                            .as_deref()
+                            == Some(tag) =>
+                {
+                    ("folders", title)
+                }
+                _ => ("folders", new_name),
+            },
+        };
+        // A note renamed to another extension takes its markup language,
+        // and into or out of the `--todo-suffix` form becomes or stops being
+        // a to-do
+        let new_ext = match source_kind {
+            EntryKind::Note if !is_symlink => self.split_note_extension(new_name).1,
+            _ => None,
+        };
+        let new_markup = match new_ext.as_deref() {
+            Some(ext) if *self.note_ext(&source_id).map_err(|_| libc::EIO)? != *ext => {
+                self.markup_for_ext(ext)
+            }
+            _ => None,
+        };
+        let new_todo = new_ext
+            .as_deref()
+            .filter(|_| self.options.todo_suffix.is_some())
+            .map(|ext| self.unmarked_ext(ext).is_some());
+        self.in_write_transaction(|fs| {
+            if let Some(victim) = &replaced {
+                fs.delete_row("notes", victim, current_time)
+                    .map_err(Self::write_errno)?;
+            }
+            fs.db
+                .execute(
+                    &format!(
+                        "UPDATE {table} SET title = ?1, parent_id = ?2, updated_time = ?3, user_updated_time = ?3 WHERE id = ?4"
+                    ),
+                    rusqlite::params![new_title, new_parent_folder_id, current_time, source_id],
+                )
+                .map_err(Self::write_errno)?;
+            if let Some(markup) = new_markup {
+                fs.db
+                    .execute(
+                        "UPDATE notes SET markup_language = ?1 WHERE id = ?2",
+                        rusqlite::params![markup, source_id],
+                    )
+                    .map_err(Self::write_errno)?;
+            }
+            if let Some(is_todo) = new_todo {
+                fs.db
+                    .execute(
+                        "UPDATE notes SET is_todo = ?1 WHERE id = ?2",
+                        rusqlite::params![is_todo, source_id],
+                    )
+                    .map_err(Self::write_errno)?;
+            }
+            fs.db.touch_folder(&parent_folder_id, current_time)
+                .and_then(|_| fs.db.touch_folder(&new_parent_folder_id, current_time))
+                .map_err(Self::write_errno)
+        })?;
+
+        // Update inode mappings for the entry and, for a folder, everything
+        // below it
+        if let Some(victim) = replaced {
+            self.inodes.remove(&new_path);
+            self.note_modes.remove(&victim);
+        }
+        self.inodes.rename(&old_path, &new_path);
+
+        Ok(())
+    }
+
+    /// Remove the row `id` of `table` (`notes` or `folders`) as of `now`
+    ///
+    /// The row moves to Joplin's trash: `deleted_time` is set and the row is
+    /// stamped as `NoteStore::touch_row` stamps it. Under `--hard-delete` it
+    /// is dropped instead. Returns the number of rows changed.
+    fn delete_row(&self, table: &str, id: &str, now: i64) -> Result<usize> {
+        if self.options.hard_delete {
+            return self
+                .db
+                .execute(&format!("DELETE FROM {table} WHERE id = ?1"), [id]);
+        }
+        self.db.execute(
+            &format!(
+                "UPDATE {table} SET deleted_time = ?1, updated_time = ?1, user_updated_time = ?1 WHERE id = ?2 AND deleted_time = 0"
+            ),
+            rusqlite::params![now, id],
+        )
+    }
+
+    /// Delete the note `filename` from the directory `parent`
+    ///
+    /// The note is resolved exactly as `lookup` resolves the name, including
+    /// a `Title (a1b2c3)` duplicate suffix, and only that row is deleted,
+    /// into Joplin's trash unless `--hard-delete` is set (see `delete_row`).
+    ///
+    /// Returns ENOENT if the parent or note does not exist, EPERM if the
+    /// parent is a virtual directory, EROFS if the database is read-only and
+    /// EIO on database failure.
+    fn unlink_note(&mut self, parent: u64, filename: &str) -> std::result::Result<(), c_int> {
+        // Get parent path
+        let parent_path = self.get_path_from_inode(parent).ok_or(ENOENT)?.clone();
+        self.check_not_virtual(&parent_path, filename)?;
+        let filename: &str = &self.canonical_name(&parent_path, filename);
+
+        let file_path = join_path(&parent_path, filename);
+
+        let note_id = match self.resolve_path(&file_path) {
+            Some((EntryKind::Note, id)) => id,
+            _ => return Err(ENOENT),
+        };
+        self.check_writable()?;
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        self.db
+            .touch_note_parent(&note_id, now)
+            .map_err(|_| libc::EIO)?;
+        let rows_affected = self
+            .delete_row("notes", &note_id, now)
+            .map_err(Self::write_errno)?;
+
+        if rows_affected == 0 {
+            // File not found
+            return Err(ENOENT);
+        }
+
+        // Successfully deleted the file
+        // Remove from inode mappings
+
+        self.inodes.remove(&file_path);
+        self.note_modes.remove(&note_id);
+
+        Ok(())
+    }
+
+    /// Delete the empty folder `dirname` from the directory `parent`
+    ///
+    /// As with notes, the folder goes to Joplin's trash unless
+    /// `--hard-delete` is set. Trashed children do not count against
+    /// emptiness. `dirname` is resolved as lookup resolves it, so
+    /// `Work (a1b2c3)` removes that duplicate and not the newest `Work`.
+    ///
+    /// Returns the errno to reply with on failure:
+    /// - ENOENT: the parent or folder does not exist
+    /// - EPERM: the parent is a virtual directory
+    /// - EROFS: the database is read-only
+    /// - ENOTEMPTY: the folder still contains folders or notes
+    /// - EIO: the database query or deletion failed
+    fn remove_directory(&mut self, parent: u64, dirname: &str) -> std::result::Result<(), c_int> {
+        // Get parent path
+        let parent_path = self.get_path_from_inode(parent).ok_or(ENOENT)?.clone();
+        self.check_not_virtual(&parent_path, dirname)?;
+        self.check_writable()?;
+        let dirname: &str = &self.canonical_name(&parent_path, dirname);
+
+        // Get parent folder ID from database
+        let parent_folder_id = self
+            .get_parent_folder_id(&parent_path)
+            .map_err(Self::resolve_errno)?;
+
+        // The exact folder the name lists, whether plain, id-suffixed or
+        // decorated
+        let dir_path = join_path(&parent_path, dirname);
+        let folder_id = match self.resolve_path(&dir_path) {
+            Some((EntryKind::Folder, id)) => id,
+            _ => return Err(ENOENT),
+        };
+
+        // Check if the directory is empty (no child folders or notes)
+        let folder_count: i64 = self
+            .db
+            .query_row(
+                "SELECT COUNT(*) FROM folders WHERE parent_id = ?1 AND deleted_time = 0",
+                [&folder_id],
+                |row| row.get(0),
+            )
+            .map_err(|_| libc::EIO)?;
+
+        let note_count: i64 = self
+            .db
+            .query_row(
+                "SELECT COUNT(*) FROM notes WHERE parent_id = ?1 AND deleted_time = 0",
+                [&folder_id],
+                |row| row.get(0),
+            )
+            .map_err(|_| libc::EIO)?;
+
+        if folder_count > 0 || note_count > 0 {
+            // Directory is not empty
+            return Err(libc::ENOTEMPTY);
+        }
+
+        // Directory is empty, proceed with deletion
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let rows_affected = self
+            .delete_row("folders", &folder_id, now)
+            .map_err(Self::write_errno)?;
+
+        if rows_affected == 0 {
+            // Should not happen since we just queried for it
+            return Err(ENOENT);
+        }
+        self.db
+            .touch_folder(&parent_folder_id, now)
+            .map_err(|_| libc::EIO)?;
+
+        // Successfully deleted the directory
+        // Remove from inode mappings
+        self.inodes.remove(&dir_path);
+
+        Ok(())
+    }
+
+    /// Reply to an operation whose only outcome is success or an errno
+    fn reply_empty(result: std::result::Result<(), c_int>, reply: impl EmptyReply) {
+        match result {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    /// Reply to an xattr query honouring the `size == 0` length probe convention
+    fn reply_xattr(data: &[u8], size: u32, reply: impl XattrReply) {
+        if size == 0 {
+            reply.size(data.len() as u32);
+        } else if data.len() > size as usize {
+            reply.error(libc::ERANGE);
+        } else {
+            reply.data(data);
+        }
+    }
+}
+
+impl Filesystem for SqliteFS {
+    /// Report which platform-dependent kernel features are in use
+    fn init(
+        &mut self,
+        _req: &Request,
+        _config: &mut KernelConfig,
+    ) -> std::result::Result<(), c_int> {
+        for line in PLATFORM.kernel_summary().lines() {
+            info!("{line}");
+        }
+        Ok(())
+    }
+
+    /// Print the `--profile` latency summary once the filesystem is unmounted
+    fn destroy(&mut self) {
+        if let Some(summary) = self.profiler.summary() {
+            eprint!("{summary}");
+        }
+    }
+
+    fn lookup(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let _span = self.profiler.span("lookup");
+        let name_str = match name.to_str() {
+            Some(s) => s,
+            None => {
+                reply.error(ENOENT);
+                return;
+            }
+        };
+
+        match self.lookup_child(parent, name_str) {
+            Ok(attr) => reply.entry(&self.ttl(), &attr, 0),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn forget(&mut self, _req: &Request, ino: u64, nlookup: u64) {
+        let _span = self.profiler.span("forget");
+        self.inodes.forget(ino, nlookup);
+    }
+
+    fn getattr(&mut self, _req: &Request, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+        let _span = self.profiler.span("getattr");
+        match self.get_attr(ino) {
+            Ok(attr) => reply.attr(&self.ttl(), &attr),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn statfs(&mut self, _req: &Request, _ino: u64, reply: ReplyStatfs) {
+        let _span = self.profiler.span("statfs");
+        match self.stat_fs() {
+            Ok(st) => reply.statfs(
+                st.blocks, st.bfree, st.bavail, st.files, st.ffree, st.bsize, st.namelen, st.frsize,
+            ),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock: Option<u64>,
+        reply: ReplyData,
+    ) {
+        let _span = self.profiler.span("read");
+        match self.read_range(ino, fh, offset, size) {
+            Ok(data) => reply.data(&data),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn copy_file_range(
+        &mut self,
+        _req: &Request<'_>,
+        ino_in: u64,
+        _fh_in: u64,
+        offset_in: i64,
+        ino_out: u64,
+        _fh_out: u64,
+        offset_out: i64,
+        len: u64,
+        _flags: u32,
+        reply: fuser::ReplyWrite,
+    ) {
+        let _span = self.profiler.span("copy_file_range");
+        match self.copy_note_range(ino_in, offset_in, ino_out, offset_out, len) {
+            Ok(written) => reply.written(written),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn symlink(
+        &mut self,
+        _req: &Request,
+        parent: u64,
+        link_name: &OsStr,
+        target: &std::path::Path,
+        reply: ReplyEntry,
+    ) {
+        let _span = self.profiler.span("symlink");
+        let Some(link_name) = link_name.to_str() else {
+            reply.error(libc::EINVAL);
+            return;
+        };
+        let Some(parent_path) = self.get_path_from_inode(parent).cloned() else {
+            reply.error(ENOENT);
+            return;
+        };
+        match self.create_symlink(
+            &parent_path,
+            link_name,
+            target.as_os_str().as_encoded_bytes(),
+        ) {
+            Ok(attr) => reply.entry(&self.ttl(), &attr, 0),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn readlink(&mut self, _req: &Request, ino: u64, reply: ReplyData) {
+        let _span = self.profiler.span("readlink");
+        match self.read_link(ino) {
+            Ok(target) => reply.data(&target),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn opendir(&mut self, _req: &Request, ino: u64, _flags: i32, reply: fuser::ReplyOpen) {
+        let _span = self.profiler.span("opendir");
+        match self.open_directory(ino) {
+            Ok(fh) => reply.opened(fh, 0),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request,
+        _ino: u64,
+        fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let _span = self.profiler.span("readdir");
+        match self.read_directory(fh, offset, |ino, offset, kind, name| {
+            reply.add(ino, offset, kind, name)
+        }) {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn releasedir(
+        &mut self,
+        _req: &Request,
+        _ino: u64,
+        fh: u64,
+        _flags: i32,
+        reply: fuser::ReplyEmpty,
+    ) {
+        let _span = self.profiler.span("releasedir");
+        self.release_directory(fh);
+        reply.ok();
+    }
+
+    /// Handle directory creation operations
+    /// This method is called when users create new directories using mkdir().
+    /// It creates a new folder record in the database with proper UUID and parent relationships.
+    ///
+    /// Key behaviors:
+    /// - Generates UUID v4 for the new folder's database ID
+    /// - Resolves parent path to parent folder UUID for database foreign key
+    /// - Sets appropriate timestamps (created_time, updated_time, etc.)
+    /// - Creates filesystem inode mapping for the new directory
+    fn mkdir(
+        &mut self,
+        _req: &Request,
+        parent: u64,
+        name: &OsStr,
+        _mode: u32,
+        _umask: u32,
+        reply: ReplyEntry,
+    ) {
+        let _span = self.profiler.span("mkdir");
+        let folder_name = match name.to_str() {
+            Some(s) => s,
+            None => {
+                reply.error(libc::EINVAL);
+                return;
+            }
+        };
+
+        // Get parent path
+        let parent_path = match self.get_path_from_inode(parent) {
+            Some(path) => path.clone(),
+            None => {
+                reply.error(ENOENT);
+                return;
+            }
+        };
+
+        match self.make_directory(&parent_path, folder_name) {
+            Ok(attr) => reply.entry(&self.ttl(), &attr, 0),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    /// Handle file creation operations
+    /// This method is called when new files are created using open() with O_CREAT flag
+    /// or when using system calls like creat(). It creates a new note in the database
+    /// and returns file attributes along with a file handle.
+    fn create(
+        &mut self,
+        _req: &Request,
+        parent: u64,
+        name: &OsStr,
+        mode: u32,
+        umask: u32,
+        flags: i32,
+        reply: fuser::ReplyCreate,
+    ) {
+        let _span = self.profiler.span("create");
+        let file_name = match name.to_str() {
+            Some(s) => s,
+            None => {
+                reply.error(libc::EINVAL);
+                return;
+            }
+        };
+
+        // Get parent path
+        let parent_path = match self.get_path_from_inode(parent) {
+            Some(path) => path.clone(),
+            None => {
+                reply.error(ENOENT);
+                return;
+            }
+        };
+
+        // Check the handle limit first so a refused create leaves no note behind
+        match self
+            .check_open_files()
+            .and_then(|()| self.create_or_open_file(&parent_path, file_name, flags, mode & !umask))
+            .and_then(|attr| Ok((attr, self.open_note(attr.ino, flags)?)))
+        {
+            Ok((attr, fh)) => reply.created(&self.ttl(), &attr, 0, fh, 0),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    /// Handle file write operations
+    /// This method is called when applications write data to open files.
+    /// The content is immediately written to the database's 'body' field, unless
+    /// `--write-debounce-ms` buffers it in the file handle.
+    ///
+    /// Key behaviors:
+    /// - Overwrites only `[offset, offset + len)` (see `write_body`), keeping
+    ///   any content beyond it; a write past the end extends the body
+    /// - Never shortens a note: only `setattr` with a size or `O_TRUNC` does
+    /// - Updates timestamps (updated_time, user_updated_time) in database
+    /// - Strips .md suffix when looking up notes in database
+    fn write(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+        data: &[u8],
+        _write_flags: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: fuser::ReplyWrite,
+    ) {
+        let _span = self.profiler.span("write");
+        match self.write_handle(fh, ino, offset, data) {
+            Ok(written) => {
+                self.debug_check_invariants(ino);
+                reply.written(written)
+            }
+            Err(e) => reply.error(e),
+        }
+    }
+
+    /// Handle file opening operations
+    /// This method is called when editors or applications use open() system call
+    /// to open existing files for reading or writing
+    fn open(&mut self, _req: &Request, ino: u64, flags: i32, reply: fuser::ReplyOpen) {
+        let _span = self.profiler.span("open");
+        match self.open_note(ino, flags) {
+            Ok(fh) => reply.opened(fh, 0),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    /// Handle file attribute setting operations
+    /// This method is called when editors or applications try to set file attributes
+    /// such as timestamps, file size, permissions, etc. Many editors require this
+    /// operation to function properly.
+    ///
+    /// Key behaviors:
+    /// - Handles size changes (truncation/extension of file content)
+    /// - Updates timestamps in the database when modified
+    /// - Validates that the file exists before making changes
+    /// - Returns updated file attributes after successful changes
+    fn setattr(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        mode: Option<u32>,
+        uid: Option<u32>,
+        gid: Option<u32>,
+        size: Option<u64>,
+        _atime: Option<fuser::TimeOrNow>,
+        _mtime: Option<fuser::TimeOrNow>,
+        _ctime: Option<SystemTime>,
+        _fh: Option<u64>,
+        _crtime: Option<SystemTime>,
+        _chgtime: Option<SystemTime>,
+        _bkuptime: Option<SystemTime>,
+        _flags: Option<u32>,
+        reply: ReplyAttr,
+    ) {
+        let _span = self.profiler.span("setattr");
+        match self.set_attr(ino, mode, uid, gid, size) {
+            Ok(attr) => {
+                self.debug_check_invariants(ino);
+                reply.attr(&self.ttl(), &attr)
+            }
+            Err(e) => reply.error(e),
+        }
+    }
+
+    /// Handle fallocate: grow a note to cover the requested region
+    fn fallocate(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        length: i64,
+        mode: i32,
+        reply: fuser::ReplyEmpty,
+    ) {
+        let _span = self.profiler.span("fallocate");
+        let result = self.allocate_note(ino, offset, length, mode);
+        if result.is_ok() {
+            self.debug_check_invariants(ino);
+        }
+        Self::reply_empty(result, reply);
+    }
+
+    /// Handle file flush operations
+    /// This method is called when editors or applications want to ensure that
+    /// all pending writes have been completed. Writes normally go straight to
+    /// the database; under `--write-debounce-ms` this commits whatever the
+    /// handle is still buffering.
+    ///
+    /// Key behaviors:
+    /// - Commits pending buffered writes, failing with EIO if that update fails
+    /// - Required for proper editor functionality (many editors call flush before close)
+    /// - Validates that the file handle corresponds to a valid file
+    fn flush(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        fh: u64,
+        _lock_owner: u64,
+        reply: fuser::ReplyEmpty,
+    ) {
+        let _span = self.profiler.span("flush");
+        // Verify that the inode exists (basic validation)
+        if self.get_path_from_inode(ino).is_some() {
+            // Commit anything the write debounce is still holding back
+            Self::reply_empty(self.commit_handle(fh), reply);
+        } else {
+            reply.error(ENOENT);
+        }
+    }
+
+    /// Handle access: check the caller against the entry's owner and mode
+    fn access(&mut self, req: &Request, ino: u64, mask: i32, reply: fuser::ReplyEmpty) {
+        let _span = self.profiler.span("access");
+        Self::reply_empty(self.check_access(ino, req.uid(), req.gid(), mask), reply);
+    }
+
+    /// Handle fsync: commit the handle's buffered writes and checkpoint the WAL
+    fn fsync(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        fh: u64,
+        _datasync: bool,
+        reply: fuser::ReplyEmpty,
+    ) {
+        let _span = self.profiler.span("fsync");
+        if self.get_path_from_inode(ino).is_some() {
+            Self::reply_empty(self.sync_database(Some(fh)), reply);
+        } else {
+            reply.error(ENOENT);
+        }
+    }
+
+    /// Handle fsyncdir: checkpoint the WAL, as `fsync` does for a file
+    fn fsyncdir(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        _fh: u64,
+        _datasync: bool,
+        reply: fuser::ReplyEmpty,
+    ) {
+        let _span = self.profiler.span("fsyncdir");
+        if self.get_path_from_inode(ino).is_some() {
+            Self::reply_empty(self.sync_database(None), reply);
+        } else {
+            reply.error(ENOENT);
+        }
+    }
+
+    /// Handle file release (close) operations
+    /// This method is called when a file handle is closed. Any writes the
+    /// handle is still buffering are committed and the handle is dropped.
+    ///
+    /// Key behaviors:
+    /// - Commits pending buffered writes, then forgets the handle
+    /// - Called when editors close files or when file handles are released
+    /// - Validates that the file handle corresponds to a valid file
+    fn release(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        fh: u64,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        _flush: bool,
+        reply: fuser::ReplyEmpty,
+    ) {
+        let _span = self.profiler.span("release");
+        // Verify that the inode exists (basic validation)
+        if self.get_path_from_inode(ino).is_some() {
+            Self::reply_empty(self.release_handle(fh), reply);
+        } else {
+            self.file_handles.remove(&fh);
+            self.release_resource(fh);
+            reply.error(ENOENT);
+        }
+    }
+
+    /// Handle file and directory renaming operations
+    /// This method is called when a file or directory is renamed (e.g., using mv command).
+    /// It updates the database to reflect the new name while preserving all other metadata.
+    ///
+    /// Key behaviors:
+    /// - Updates the 'title' field in the database for the renamed item
+    /// - Handles both files (notes) and directories (folders)
+    /// - Strips .md suffix from filenames before storing in database
+    /// - Stamps the row as changed (see `NoteStore::touch_row`)
+    /// - Replaces an existing note at the target, as rename(2) does
+    /// - Maintains proper parent-child relationships
+    /// - Required for proper file manager and shell integration
+    fn rename(
+        &mut self,
+        _req: &Request,
+        parent: u64,
+        name: &OsStr,
+        newparent: u64,
+        newname: &OsStr,
+        _flags: u32,
+        reply: fuser::ReplyEmpty,
+    ) {
+        let _span = self.profiler.span("rename");
+        let old_name = match name.to_str() {
+            Some(n) => n,
+            None => {
+                reply.error(libc::EINVAL);
+                return;
+            }
+        };
+
+        let new_name = match newname.to_str() {
+            Some(n) => n,
+            None => {
+                reply.error(libc::EINVAL);
+                return;
+            }
+        };
+
+        Self::reply_empty(
+            self.rename_entry(parent, old_name, newparent, new_name),
+            reply,
+        );
+    }
+
+    /// Handle file deletion operations
+    /// This method is called when a file is deleted (e.g., using rm command).
+    /// It moves the corresponding note to Joplin's trash.
+    ///
+    /// Key behaviors:
+    /// - Soft deletes by default, setting `deleted_time`; the row is dropped
+    ///   only under `--hard-delete`
+    /// - Deletes exactly the row the name resolves to, including a
+    ///   `Title (a1b2c3)` duplicate
+    /// - Strips .md suffix from filename before database lookup
+    /// - Updates inode mappings to reflect the deletion
+    /// - Required for proper file manager and shell integration
+    fn unlink(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: fuser::ReplyEmpty) {
+        let _span = self.profiler.span("unlink");
+        let filename = match name.to_str() {
+            Some(n) => n,
+            None => {
+                reply.error(libc::EINVAL);
+                return;
+            }
+        };
+
+        Self::reply_empty(self.unlink_note(parent, filename), reply);
+    }
+
+    /// Handle directory deletion operations
+    /// This method is called when a directory is deleted (e.g., using rmdir command).
+    /// It moves the corresponding folder to Joplin's trash.
+    ///
+    /// Key behaviors:
+    /// - Only deletes empty directories (standard rmdir behavior)
+    /// - Soft deletes by default, setting `deleted_time`; the row is dropped
+    ///   only under `--hard-delete`
+    /// - Deletes exactly the folder the name resolves to, including a
+    ///   `Work (a1b2c3)` duplicate
+    /// - Updates inode mappings to reflect the deletion
+    /// - Required for proper file manager and shell integration
+    fn rmdir(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: fuser::ReplyEmpty) {
+        let _span = self.profiler.span("rmdir");
+        let dirname = match name.to_str() {
+            Some(n) => n,
+            None => {
+                reply.error(libc::EINVAL);
+                return;
+            }
+        };
+
+        Self::reply_empty(self.remove_directory(parent, dirname), reply);
+    }
+
+    /// Handle extended attribute reads
+    /// Exposes Joplin metadata under the `user.joplin.*` namespace so scripts
+    /// can inspect fields such as the manual sort order without parsing bodies.
+    fn getxattr(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        name: &OsStr,
+        size: u32,
+        reply: fuser::ReplyXattr,
+    ) {
+        let _span = self.profiler.span("getxattr");
+        let name = match name.to_str() {
+            Some(n) => n,
+            None => {
+                reply.error(libc::ENODATA);
+                return;
+            }
+        };
+
+        match self.get_xattr(ino, name) {
+            Ok(value) => Self::reply_xattr(&value, size, reply),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    /// Handle extended attribute writes
+    /// Values are validated before being stored in the corresponding column.
+    fn setxattr(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        name: &OsStr,
+        value: &[u8],
+        _flags: i32,
+        _position: u32,
+        reply: fuser::ReplyEmpty,
+    ) {
+        let _span = self.profiler.span("setxattr");
+        let name = match name.to_str() {
+            Some(n) => n,
+            None => {
+                reply.error(libc::EINVAL);
+                return;
+            }
+        };
+
+        Self::reply_empty(self.set_xattr(ino, name, value), reply);
+    }
+
+    /// Handle extended attribute listing
+    fn listxattr(&mut self, _req: &Request, ino: u64, size: u32, reply: fuser::ReplyXattr) {
+        let _span = self.profiler.span("listxattr");
+        match self.list_xattr(ino) {
+            Ok(names) => Self::reply_xattr(&names, size, reply),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    /// Handle extended attribute removal
+    fn removexattr(&mut self, _req: &Request, ino: u64, name: &OsStr, reply: fuser::ReplyEmpty) {
+        let _span = self.profiler.span("removexattr");
+        let name = match name.to_str() {
+            Some(n) => n,
+            None => {
+                reply.error(libc::ENODATA);
+                return;
+            }
+        };
+
+        Self::reply_empty(self.remove_xattr(ino, name), reply);
+    }
+}
+
+/// The path of `name` inside the directory at `parent`
+///
+/// An empty parent or one with trailing slashes is treated as the directory
+/// it names, so joining onto `/` gives `/name` rather than `//name`.
+fn join_path(parent: &str, name: &str) -> String {
+    format!("{}/{name}", parent.trim_end_matches('/'))
+}
+
+/// The parent directory and final component of `path`, the inverse of
+/// [`join_path`]
+///
+/// Trailing slashes are ignored and a top-level entry's parent is `/`. The
+/// root itself splits into `("/", "")`.
+fn split_path(path: &str) -> (&str, &str) {
+    let path = path.trim_end_matches('/');
+    match path.rfind('/') {
+        Some(0) | None => ("/", path.trim_start_matches('/')),
+        Some(pos) => (&path[..pos], &path[pos + 1..]),
+    }
+}
+
+/// Ids referenced as `:/<id>` in a note body, in order of appearance
+fn referenced_ids(body: &str) -> impl Iterator<Item = &str> {
+    body.match_indices(":/").filter_map(|(pos, _)| {
+        body.get(pos + 2..pos + 34)
+            .filter(|id| id.bytes().all(|b| b.is_ascii_hexdigit()))
+    })
+}
+
+/// The path of the mount path `target` relative to the directory `dir`,
+/// as a Markdown link destination
+///
+/// Characters that would end the destination or start a fragment are
+/// percent-encoded.
+fn relative_link(dir: &str, target: &str) -> String {
+    let from: Vec<&str> = dir.split('/').filter(|c| !c.is_empty()).collect();
+    let to: Vec<&str> = target.split('/').filter(|c| !c.is_empty()).collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut link = String::new();
+    for component in
+        std::iter::repeat_n("..", from.len() - common).chain(to[common..].iter().copied())
+    {
+        if !link.is_empty() {
+            link.push('/');
+        }
+        for c in component.chars() {
+            match c {
+                ' ' | '%' | '(' | ')' | '<' | '>' | '#' | '?' => {
+                    link.push_str(&format!("%{:02X}", c as u32))
+                }
+                _ => link.push(c),
+            }
+        }
+    }
+    link
+}
+
+/// An emoji with surrounding whitespace and U+FE0F variation selectors removed
+fn normalize_emoji(emoji: &str) -> String {
+    emoji.trim().chars().filter(|&c| c != '\u{FE0F}').collect()
+}
+
+/// Distinct resource ids linked from a Markdown body as `:/<id>`, in order
+fn linked_resource_ids(body: &str) -> Vec<&str> {
+    let mut ids: Vec<&str> = Vec::new();
+    for (pos, _) in body.match_indices(":/") {
+        let Some(id) = body.get(pos + 2..pos + 34) else {
+            continue;
+        };
+        let bounded = !body[pos + 34..].starts_with(|c: char| c.is_ascii_hexdigit());
+        if bounded && id.bytes().all(|b| b.is_ascii_hexdigit()) && !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+    ids
+}
+
+/// Split a `--decorate-notebooks` name, `[tag] Title`, into tag and title
+fn split_decoration(name: &str) -> Option<(&str, &str)> {
+    name.strip_prefix('[')?.split_once("] ")
+}
+
+/// Up to `size` bytes of `file` from `offset` on, empty at or past its end
+fn read_window(
+    file: &mut std::fs::File,
+    offset: i64,
+    size: u64,
+) -> std::result::Result<Vec<u8>, c_int> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    file.seek(SeekFrom::Start(offset.max(0) as u64))
+        .map_err(|_| libc::EIO)?;
+    let mut data = Vec::new();
+    file.take(size)
+        .read_to_end(&mut data)
+        .map_err(|_| libc::EIO)?;
+    Ok(data)
+}
+
+/// Whether a tag titled `title` can be listed as a `/.tags` directory
+fn is_tag_name(title: &str) -> bool {
+    !title.is_empty() && title != "." && title != ".." && !title.contains('/')
+}
+
+/// The bytes of `content` in `[offset, offset + size)`, clipped to its end;
+/// empty at or past the end
+fn byte_window(content: &[u8], offset: i64, size: u64) -> &[u8] {
+    let start = (offset.max(0) as u64).min(content.len() as u64);
+    let end = start.saturating_add(size).min(content.len() as u64);
+    &content[start as usize..end as usize]
+}
+
+/// `secs` since the Unix epoch as an ISO 8601 UTC timestamp
+fn iso8601_utc(secs: i64) -> String {
+    // Days-to-civil conversion from Howard Hinnant's date algorithms
+    let (days, rem) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// Escape text for use in XML content or attribute values
+fn xml_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Best-effort plain-text projection of a Markdown body
+///
+/// Headings, emphasis and link/image markup are dropped in favour of their
+/// text, code fences are unwrapped to their contents and raw HTML is
+/// skipped. Blocks are separated by a blank line.
+fn markdown_to_plain_text(markdown: &str) -> String {
+    use pulldown_cmark::{Event, Parser, TagEnd};
+
+    let mut out = String::new();
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Text(text) | Event::Code(text) => out.push_str(&text),
+            Event::SoftBreak | Event::HardBreak => out.push('\n'),
+            Event::End(TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::CodeBlock) => {
+                let trimmed = out.trim_end_matches('\n').len();
+                out.truncate(trimmed);
+                out.push_str("\n\n");
+            }
+            Event::End(TagEnd::Item) if !out.ends_with('\n') => out.push('\n'),
+            _ => {}
+        }
+    }
+    let trimmed = out.trim_end().len();
+    out.truncate(trimmed);
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests;
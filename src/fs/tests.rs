@@ -0,0 +1,5479 @@
+use super::*;
+use crate::cli::{
+    check_resource_dir, cli, mount_options, parse_markup_ext_entry, parse_todo_marker,
+    parse_uid_map_entry, platform_mount_options,
+};
+use crate::inode::{FIRST_DYNAMIC_INODE, parse_pin_inode_entry, pinned_inodes};
+use crate::store::{
+    FolderProblem, FolderRename, MountLock, SELFTEST_DB_URI, discover_resource_dir,
+    fix_folder_problems, folder_problems, run_maintenance, selftest_database,
+};
+use fuser::MountOption;
+use std::time::SystemTime;
+
+/// What a reply helper sent, recorded in place of a real fuser reply
+#[derive(Debug, Default, PartialEq)]
+enum Captured {
+    #[default]
+    Nothing,
+    Ok,
+    Error(c_int),
+    Size(u32),
+    Data(Vec<u8>),
+}
+
+impl EmptyReply for &mut Captured {
+    fn ok(self) {
+        *self = Captured::Ok;
+    }
+
+    fn error(self, err: c_int) {
+        *self = Captured::Error(err);
+    }
+}
+
+impl XattrReply for &mut Captured {
+    fn size(self, size: u32) {
+        *self = Captured::Size(size);
+    }
+
+    fn data(self, data: &[u8]) {
+        *self = Captured::Data(data.to_vec());
+    }
+
+    fn error(self, err: c_int) {
+        *self = Captured::Error(err);
+    }
+}
+
+/// Logger recording every warning, installed once for the whole test binary
+struct WarningLog(Mutex<Vec<String>>);
+
+impl log::Log for WarningLog {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Warn
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            self.0.lock().unwrap().push(record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Warnings logged so far by any test; filter by content, tests run in parallel
+fn logged_warnings() -> Vec<String> {
+    static LOG: WarningLog = WarningLog(Mutex::new(Vec::new()));
+    static INSTALL: std::sync::Once = std::sync::Once::new();
+    INSTALL.call_once(|| {
+        log::set_logger(&LOG).unwrap();
+        log::set_max_level(log::LevelFilter::Warn);
+    });
+    LOG.0.lock().unwrap().clone()
+}
+
+/// Build a filesystem over an already-populated connection with the root and pinned inodes mapped
+fn fs_from_conn(conn: Connection, options: FsOptions) -> SqliteFS {
+    SqliteFS::from_connection(conn, options).expect("Failed to build filesystem")
+}
+
+fn create_test_db() -> rusqlite::Result<Connection> {
+    let conn = Connection::open(":memory:")?;
+
+    // Create tables
+    conn.execute(
+        "CREATE TABLE `notes`(`id` TEXT PRIMARY KEY,`parent_id` TEXT NOT NULL DEFAULT \"\",`title` TEXT NOT NULL DEFAULT \"\",`body` TEXT NOT NULL DEFAULT \"\",`created_time` INT NOT NULL,`updated_time` INT NOT NULL,`is_conflict` INT NOT NULL DEFAULT 0,`latitude` NUMERIC NOT NULL DEFAULT 0,`longitude` NUMERIC NOT NULL DEFAULT 0,`altitude` NUMERIC NOT NULL DEFAULT 0,`author` TEXT NOT NULL DEFAULT \"\",`source_url` TEXT NOT NULL DEFAULT \"\",`is_todo` INT NOT NULL DEFAULT 0,`todo_due` INT NOT NULL DEFAULT 0,`todo_completed` INT NOT NULL DEFAULT 0,`source` TEXT NOT NULL DEFAULT \"\",`source_application` TEXT NOT NULL DEFAULT \"\",`application_data` TEXT NOT NULL DEFAULT \"\",`order` NUMERIC NOT NULL DEFAULT 0,`user_created_time` INT NOT NULL DEFAULT 0,`user_updated_time` INT NOT NULL DEFAULT 0,`encryption_cipher_text` TEXT NOT NULL DEFAULT \"\",`encryption_applied` INT NOT NULL DEFAULT 0,`markup_language` INT NOT NULL DEFAULT 1,`is_shared` INT NOT NULL DEFAULT 0, share_id TEXT NOT NULL DEFAULT \"\", conflict_original_id TEXT NOT NULL DEFAULT \"\", master_key_id TEXT NOT NULL DEFAULT \"\", `user_data` TEXT NOT NULL DEFAULT \"\", `deleted_time` INT NOT NULL DEFAULT 0)",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE folders (id TEXT PRIMARY KEY, title TEXT NOT NULL DEFAULT \"\", created_time INT NOT NULL, updated_time INT NOT NULL, user_created_time INT NOT NULL DEFAULT 0, user_updated_time INT NOT NULL DEFAULT 0, encryption_cipher_text TEXT NOT NULL DEFAULT \"\", encryption_applied INT NOT NULL DEFAULT 0, parent_id TEXT NOT NULL DEFAULT \"\", is_shared INT NOT NULL DEFAULT 0, share_id TEXT NOT NULL DEFAULT \"\", master_key_id TEXT NOT NULL DEFAULT \"\", icon TEXT NOT NULL DEFAULT \"\", `user_data` TEXT NOT NULL DEFAULT \"\", `deleted_time` INT NOT NULL DEFAULT 0)",
+        [],
+    )?;
+
+    // Insert test data
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+
+    // Create a folder
+    conn.execute(
+        "INSERT INTO folders (id, title, created_time, updated_time, user_created_time, user_updated_time, parent_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        ["folder1", "Documents", &now.to_string(), &now.to_string(), &now.to_string(), &now.to_string(), ""],
+    )?;
+
+    // Create a note in root
+    conn.execute(
+        "INSERT INTO notes (id, title, body, created_time, updated_time, user_created_time, user_updated_time, parent_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        ["note1", "readme.txt", "This is a test note in root", &now.to_string(), &now.to_string(), &now.to_string(), &now.to_string(), ""],
+    )?;
+
+    // Create a note in folder
+    conn.execute(
+        "INSERT INTO notes (id, title, body, created_time, updated_time, user_created_time, user_updated_time, parent_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        ["note2", "document.md", "This is a test note in Documents folder", &now.to_string(), &now.to_string(), &now.to_string(), &now.to_string(), "folder1"],
+    )?;
+
+    Ok(conn)
+}
+
+#[test]
+fn test_filesystem_operations() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+
+    // Test direct database queries to verify our setup
+    let folder_count: i64 = fs
+        .db
+        .query_row(
+            "SELECT COUNT(*) FROM folders WHERE parent_id = '' AND deleted_time = 0",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(folder_count, 1, "Expected 1 folder in root");
+
+    let note_count: i64 = fs
+        .db
+        .query_row(
+            "SELECT COUNT(*) FROM notes WHERE parent_id = '' AND deleted_time = 0",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(note_count, 1, "Expected 1 note in root");
+
+    // Test that we can find the Documents folder
+    let documents_exists = fs.db.query_row(
+        "SELECT title FROM folders WHERE parent_id = '' AND title = 'Documents' AND deleted_time = 0",
+        [],
+        |row| row.get::<_, String>(0)
+    );
+    assert!(documents_exists.is_ok(), "Documents folder should exist");
+    assert_eq!(documents_exists.unwrap(), "Documents");
+
+    // Test that we can find the readme.txt note
+    let readme_exists = fs.db.query_row(
+        "SELECT title, body FROM notes WHERE parent_id = '' AND title = 'readme.txt' AND deleted_time = 0",
+        [],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    );
+    assert!(readme_exists.is_ok(), "readme.txt should exist");
+    let (title, body) = readme_exists.unwrap();
+    assert_eq!(title, "readme.txt");
+    assert_eq!(body, "This is a test note in root");
+
+    // Test that we can find the document in the Documents folder
+    let document_exists = fs.db.query_row(
+        "SELECT title, body FROM notes WHERE parent_id = 'folder1' AND title = 'document.md' AND deleted_time = 0",
+        [],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    );
+    assert!(
+        document_exists.is_ok(),
+        "document.md should exist in Documents folder"
+    );
+    let (title, body) = document_exists.unwrap();
+    assert_eq!(title, "document.md");
+    assert_eq!(body, "This is a test note in Documents folder");
+
+    // Test inode mapping
+    let readme_inode = fs.get_or_create_inode("/readme.txt.md").unwrap();
+    let documents_inode = fs.get_or_create_inode("/Documents").unwrap();
+
+    // Verify inodes are unique
+    assert_ne!(readme_inode, documents_inode);
+    assert_ne!(readme_inode, 1); // Not root
+    assert_ne!(documents_inode, 1); // Not root
+
+    // Test path resolution
+    assert_eq!(fs.get_path_from_inode(1), Some(&"/".to_string()));
+    assert_eq!(
+        fs.get_path_from_inode(readme_inode),
+        Some(&"/readme.txt.md".to_string())
+    );
+    assert_eq!(
+        fs.get_path_from_inode(documents_inode),
+        Some(&"/Documents".to_string())
+    );
+
+    println!("All tests passed!");
+    println!("- Database setup: ✓");
+    println!("- Folder creation: ✓");
+    println!("- Note creation: ✓");
+    println!("- Hierarchy structure: ✓");
+    println!("- Inode mapping: ✓");
+    println!("- Path resolution: ✓");
+}
+
+fn create_deep_hierarchy_db() -> rusqlite::Result<Connection> {
+    let conn = Connection::open(":memory:")?;
+
+    // Create tables
+    conn.execute(
+        "CREATE TABLE `notes`(`id` TEXT PRIMARY KEY,`parent_id` TEXT NOT NULL DEFAULT \"\",`title` TEXT NOT NULL DEFAULT \"\",`body` TEXT NOT NULL DEFAULT \"\",`created_time` INT NOT NULL,`updated_time` INT NOT NULL,`is_conflict` INT NOT NULL DEFAULT 0,`latitude` NUMERIC NOT NULL DEFAULT 0,`longitude` NUMERIC NOT NULL DEFAULT 0,`altitude` NUMERIC NOT NULL DEFAULT 0,`author` TEXT NOT NULL DEFAULT \"\",`source_url` TEXT NOT NULL DEFAULT \"\",`is_todo` INT NOT NULL DEFAULT 0,`todo_due` INT NOT NULL DEFAULT 0,`todo_completed` INT NOT NULL DEFAULT 0,`source` TEXT NOT NULL DEFAULT \"\",`source_application` TEXT NOT NULL DEFAULT \"\",`application_data` TEXT NOT NULL DEFAULT \"\",`order` NUMERIC NOT NULL DEFAULT 0,`user_created_time` INT NOT NULL DEFAULT 0,`user_updated_time` INT NOT NULL DEFAULT 0,`encryption_cipher_text` TEXT NOT NULL DEFAULT \"\",`encryption_applied` INT NOT NULL DEFAULT 0,`markup_language` INT NOT NULL DEFAULT 1,`is_shared` INT NOT NULL DEFAULT 0, share_id TEXT NOT NULL DEFAULT \"\", conflict_original_id TEXT NOT NULL DEFAULT \"\", master_key_id TEXT NOT NULL DEFAULT \"\", `user_data` TEXT NOT NULL DEFAULT \"\", `deleted_time` INT NOT NULL DEFAULT 0)",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE folders (id TEXT PRIMARY KEY, title TEXT NOT NULL DEFAULT \"\", created_time INT NOT NULL, updated_time INT NOT NULL, user_created_time INT NOT NULL DEFAULT 0, user_updated_time INT NOT NULL DEFAULT 0, encryption_cipher_text TEXT NOT NULL DEFAULT \"\", encryption_applied INT NOT NULL DEFAULT 0, parent_id TEXT NOT NULL DEFAULT \"\", is_shared INT NOT NULL DEFAULT 0, share_id TEXT NOT NULL DEFAULT \"\", master_key_id TEXT NOT NULL DEFAULT \"\", icon TEXT NOT NULL DEFAULT \"\", `user_data` TEXT NOT NULL DEFAULT \"\", `deleted_time` INT NOT NULL DEFAULT 0)",
+        [],
+    )?;
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+
+    // Level 1: Root level folder and file
+    conn.execute(
+        "INSERT INTO folders (id, title, created_time, updated_time, user_created_time, user_updated_time, parent_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        ["projects", "Projects", &now.to_string(), &now.to_string(), &now.to_string(), &now.to_string(), ""],
+    )?;
+
+    conn.execute(
+        "INSERT INTO notes (id, title, body, created_time, updated_time, user_created_time, user_updated_time, parent_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        ["root_readme", "README.md", "Root level documentation", &now.to_string(), &now.to_string(), &now.to_string(), &now.to_string(), ""],
+    )?;
+
+    // Level 2: Folders and files inside Projects
+    conn.execute(
+        "INSERT INTO folders (id, title, created_time, updated_time, user_created_time, user_updated_time, parent_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        ["rust_project", "RustProject", &now.to_string(), &now.to_string(), &now.to_string(), &now.to_string(), "projects"],
+    )?;
+
+    conn.execute(
+        "INSERT INTO folders (id, title, created_time, updated_time, user_created_time, user_updated_time, parent_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        ["python_project", "PythonProject", &now.to_string(), &now.to_string(), &now.to_string(), &now.to_string(), "projects"],
+    )?;
+
+    conn.execute(
+        "INSERT INTO notes (id, title, body, created_time, updated_time, user_created_time, user_updated_time, parent_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        ["projects_overview", "overview.txt", "Projects overview document", &now.to_string(), &now.to_string(), &now.to_string(), &now.to_string(), "projects"],
+    )?;
+
+    // Level 3: Folders and files inside RustProject
+    conn.execute(
+        "INSERT INTO folders (id, title, created_time, updated_time, user_created_time, user_updated_time, parent_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        ["rust_src", "src", &now.to_string(), &now.to_string(), &now.to_string(), &now.to_string(), "rust_project"],
+    )?;
+
+    conn.execute(
+        "INSERT INTO folders (id, title, created_time, updated_time, user_created_time, user_updated_time, parent_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        ["rust_tests", "tests", &now.to_string(), &now.to_string(), &now.to_string(), &now.to_string(), "rust_project"],
+    )?;
+
+    conn.execute(
+        "INSERT INTO notes (id, title, body, created_time, updated_time, user_created_time, user_updated_time, parent_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        ["cargo_toml", "Cargo.toml", "[package]\nname = \"test_project\"\nversion = \"0.1.0\"", &now.to_string(), &now.to_string(), &now.to_string(), &now.to_string(), "rust_project"],
+    )?;
+
+    // Level 4: Files inside src folder (deepest level)
+    conn.execute(
+        "INSERT INTO notes (id, title, body, created_time, updated_time, user_created_time, user_updated_time, parent_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        ["main_rs", "main.rs", "fn main() {\n    println!(\"Hello, world!\");\n}", &now.to_string(), &now.to_string(), &now.to_string(), &now.to_string(), "rust_src"],
+    )?;
+
+    conn.execute(
+        "INSERT INTO notes (id, title, body, created_time, updated_time, user_created_time, user_updated_time, parent_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        ["lib_rs", "lib.rs", "pub fn add(left: usize, right: usize) -> usize {\n    left + right\n}", &now.to_string(), &now.to_string(), &now.to_string(), &now.to_string(), "rust_src"],
+    )?;
+
+    // Add some files in the tests folder too
+    conn.execute(
+        "INSERT INTO notes (id, title, body, created_time, updated_time, user_created_time, user_updated_time, parent_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        ["integration_test", "integration_test.rs", "#[test]\nfn it_works() {\n    assert_eq!(2 + 2, 4);\n}", &now.to_string(), &now.to_string(), &now.to_string(), &now.to_string(), "rust_tests"],
+    )?;
+
+    // Add content to PythonProject as well
+    conn.execute(
+        "INSERT INTO notes (id, title, body, created_time, updated_time, user_created_time, user_updated_time, parent_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        ["python_main", "main.py", "def main():\n    print(\"Hello from Python!\")\n\nif __name__ == \"__main__\":\n    main()", &now.to_string(), &now.to_string(), &now.to_string(), &now.to_string(), "python_project"],
+    )?;
+
+    Ok(conn)
+}
+
+#[test]
+fn test_deep_hierarchy_navigation() {
+    let conn = create_deep_hierarchy_db().expect("Failed to create deep hierarchy database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+
+    // Test Level 1: Root directory contents
+    println!("Testing Level 1 (Root):");
+    let root_folders: Vec<String> = fs
+        .db
+        .prepare("SELECT title FROM folders WHERE parent_id = '' AND deleted_time = 0")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .filter_map(Result::ok)
+        .collect();
+
+    let root_notes: Vec<String> = fs
+        .db
+        .prepare("SELECT title FROM notes WHERE parent_id = '' AND deleted_time = 0")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .filter_map(Result::ok)
+        .collect();
+
+    assert_eq!(root_folders, vec!["Projects"]);
+    assert_eq!(root_notes, vec!["README.md"]);
+    println!("  ✓ Found folders: {:?}", root_folders);
+    println!("  ✓ Found files: {:?}", root_notes);
+
+    // Test Level 2: Projects folder contents
+    println!("Testing Level 2 (Projects):");
+    let projects_folders: Vec<String> = fs
+        .db
+        .prepare("SELECT title FROM folders WHERE parent_id = 'projects' AND deleted_time = 0")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .filter_map(Result::ok)
+        .collect();
+
+    let projects_notes_raw: Vec<String> = fs
+        .db
+        .prepare("SELECT title FROM notes WHERE parent_id = 'projects' AND deleted_time = 0")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .filter_map(Result::ok)
+        .collect();
+
+    // Convert raw database titles to filesystem display titles (with .md suffix)
+    let projects_notes: Vec<String> = projects_notes_raw
+        .iter()
+        .map(|title| fs.add_md_suffix(title))
+        .collect();
+
+    assert_eq!(projects_folders.len(), 2);
+    assert!(projects_folders.contains(&"RustProject".to_string()));
+    assert!(projects_folders.contains(&"PythonProject".to_string()));
+    assert_eq!(projects_notes, vec!["overview.txt.md"]);
+    println!("  ✓ Found folders: {:?}", projects_folders);
+    println!("  ✓ Found files: {:?}", projects_notes);
+
+    // Test Level 3: RustProject folder contents
+    println!("Testing Level 3 (RustProject):");
+    let rust_folders: Vec<String> = fs
+        .db
+        .prepare("SELECT title FROM folders WHERE parent_id = 'rust_project' AND deleted_time = 0")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .filter_map(Result::ok)
+        .collect();
+
+    let rust_notes_raw: Vec<String> = fs
+        .db
+        .prepare("SELECT title FROM notes WHERE parent_id = 'rust_project' AND deleted_time = 0")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .filter_map(Result::ok)
+        .collect();
+
+    // Convert raw database titles to filesystem display titles (with .md suffix)
+    let rust_notes: Vec<String> = rust_notes_raw
+        .iter()
+        .map(|title| fs.add_md_suffix(title))
+        .collect();
+
+    assert_eq!(rust_folders.len(), 2);
+    assert!(rust_folders.contains(&"src".to_string()));
+    assert!(rust_folders.contains(&"tests".to_string()));
+    assert_eq!(rust_notes, vec!["Cargo.toml.md"]);
+    println!("  ✓ Found folders: {:?}", rust_folders);
+    println!("  ✓ Found files: {:?}", rust_notes);
+
+    // Test Level 4: src folder contents (deepest level)
+    println!("Testing Level 4 (src):");
+    let src_files_raw: Vec<String> = fs
+        .db
+        .prepare("SELECT title FROM notes WHERE parent_id = 'rust_src' AND deleted_time = 0")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .filter_map(Result::ok)
+        .collect();
+
+    // Convert raw database titles to filesystem display titles (with .md suffix)
+    let src_files: Vec<String> = src_files_raw
+        .iter()
+        .map(|title| fs.add_md_suffix(title))
+        .collect();
+
+    assert_eq!(src_files.len(), 2);
+    assert!(src_files.contains(&"main.rs.md".to_string()));
+    assert!(src_files.contains(&"lib.rs.md".to_string()));
+    println!("  ✓ Found files: {:?}", src_files);
+
+    // Test deep path navigation and file content retrieval
+    println!("Testing deep path navigation:");
+
+    // Test inode creation for deep paths
+    let projects_inode = fs.get_or_create_inode("/Projects").unwrap();
+    let rust_project_inode = fs.get_or_create_inode("/Projects/RustProject").unwrap();
+    let src_inode = fs.get_or_create_inode("/Projects/RustProject/src").unwrap();
+    let main_rs_inode = fs
+        .get_or_create_inode("/Projects/RustProject/src/main.rs.md")
+        .unwrap();
+
+    // Verify all inodes are unique
+    let inodes = [
+        1,
+        projects_inode,
+        rust_project_inode,
+        src_inode,
+        main_rs_inode,
+    ];
+    let unique_count = inodes
+        .iter()
+        .collect::<std::collections::HashSet<_>>()
+        .len();
+    assert_eq!(unique_count, inodes.len(), "All inodes should be unique");
+    println!("  ✓ All {} inodes are unique", inodes.len());
+
+    // Test path resolution
+    assert_eq!(fs.get_path_from_inode(1), Some(&"/".to_string()));
+    assert_eq!(
+        fs.get_path_from_inode(projects_inode),
+        Some(&"/Projects".to_string())
+    );
+    assert_eq!(
+        fs.get_path_from_inode(rust_project_inode),
+        Some(&"/Projects/RustProject".to_string())
+    );
+    assert_eq!(
+        fs.get_path_from_inode(src_inode),
+        Some(&"/Projects/RustProject/src".to_string())
+    );
+    assert_eq!(
+        fs.get_path_from_inode(main_rs_inode),
+        Some(&"/Projects/RustProject/src/main.rs.md".to_string())
+    );
+    println!("  ✓ Path resolution works for all levels");
+
+    // Test file content retrieval at different levels
+    println!("Testing file content retrieval:");
+
+    // Root level file
+    let root_readme_content: String = fs.db.query_row(
+        "SELECT body FROM notes WHERE parent_id = '' AND title = 'README.md' AND deleted_time = 0",
+        [],
+        |row| row.get(0)
+    ).unwrap();
+    assert_eq!(root_readme_content, "Root level documentation");
+    println!("  ✓ Root README.md: '{}'", root_readme_content);
+
+    // Level 2 file
+    let projects_overview_content: String = fs.db.query_row(
+        "SELECT body FROM notes WHERE parent_id = 'projects' AND title = 'overview.txt' AND deleted_time = 0",
+        [],
+        |row| row.get(0)
+    ).unwrap();
+    assert_eq!(projects_overview_content, "Projects overview document");
+    println!("  ✓ Projects overview.txt: '{}'", projects_overview_content);
+
+    // Level 3 file
+    let cargo_toml_content: String = fs.db.query_row(
+        "SELECT body FROM notes WHERE parent_id = 'rust_project' AND title = 'Cargo.toml' AND deleted_time = 0",
+        [],
+        |row| row.get(0)
+    ).unwrap();
+    assert!(cargo_toml_content.contains("[package]"));
+    assert!(cargo_toml_content.contains("test_project"));
+    println!("  ✓ Cargo.toml contains expected content");
+
+    // Level 4 files (deepest)
+    let main_rs_content: String = fs.db.query_row(
+        "SELECT body FROM notes WHERE parent_id = 'rust_src' AND title = 'main.rs' AND deleted_time = 0",
+        [],
+        |row| row.get(0)
+    ).unwrap();
+    assert!(main_rs_content.contains("fn main()"));
+    assert!(main_rs_content.contains("Hello, world!"));
+    println!("  ✓ main.rs contains expected Rust code");
+
+    let lib_rs_content: String = fs.db.query_row(
+        "SELECT body FROM notes WHERE parent_id = 'rust_src' AND title = 'lib.rs' AND deleted_time = 0",
+        [],
+        |row| row.get(0)
+    ).unwrap();
+    assert!(lib_rs_content.contains("pub fn add"));
+    println!("  ✓ lib.rs contains expected Rust code");
+
+    // Test parallel branch (Python project)
+    let python_main_content: String = fs.db.query_row(
+        "SELECT body FROM notes WHERE parent_id = 'python_project' AND title = 'main.py' AND deleted_time = 0",
+        [],
+        |row| row.get(0)
+    ).unwrap();
+    assert!(python_main_content.contains("def main()"));
+    assert!(python_main_content.contains("Hello from Python!"));
+    println!("  ✓ Python main.py contains expected code");
+
+    println!("\\n🎉 All deep hierarchy tests passed!");
+    println!("- 4 levels of depth: ✓");
+    println!("- Multiple folders per level: ✓");
+    println!("- Files at all levels: ✓");
+    println!("- Unique inode mapping: ✓");
+    println!("- Deep path resolution: ✓");
+    println!("- Content retrieval at all levels: ✓");
+    println!("- Parallel branches: ✓");
+}
+
+#[test]
+fn test_parent_folder_id_resolution() {
+    let conn = create_deep_hierarchy_db().expect("Failed to create test database");
+    let fs = fs_from_conn(conn, FsOptions::default());
+
+    // Test root directory
+    let root_id = fs.get_parent_folder_id("/").unwrap();
+    assert_eq!(root_id, "");
+    println!("✓ Root directory resolves to empty parent_id");
+
+    // Test level 1 path
+    let projects_id = fs.get_parent_folder_id("/Projects").unwrap();
+    assert_eq!(projects_id, "projects");
+    println!("✓ /Projects resolves to 'projects' UUID");
+
+    // Test level 2 path
+    let rust_project_id = fs.get_parent_folder_id("/Projects/RustProject").unwrap();
+    assert_eq!(rust_project_id, "rust_project");
+    println!("✓ /Projects/RustProject resolves to 'rust_project' UUID");
+
+    // Test level 3 path
+    let src_id = fs
+        .get_parent_folder_id("/Projects/RustProject/src")
+        .unwrap();
+    assert_eq!(src_id, "rust_src");
+    println!("✓ /Projects/RustProject/src resolves to 'rust_src' UUID");
+
+    // Test that we can find items at each level using the resolved parent IDs
+
+    // Root level check
+    let root_folder_count: i64 = fs
+        .db
+        .query_row(
+            "SELECT COUNT(*) FROM folders WHERE parent_id = ?1 AND deleted_time = 0",
+            [&root_id],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(root_folder_count, 1);
+    println!(
+        "✓ Found {} folder in root using resolved parent_id",
+        root_folder_count
+    );
+
+    // Projects level check
+    let projects_folder_count: i64 = fs
+        .db
+        .query_row(
+            "SELECT COUNT(*) FROM folders WHERE parent_id = ?1 AND deleted_time = 0",
+            [&projects_id],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(projects_folder_count, 2); // RustProject and PythonProject
+    println!(
+        "✓ Found {} folders in Projects using resolved parent_id",
+        projects_folder_count
+    );
+
+    // RustProject level check
+    let rust_folder_count: i64 = fs
+        .db
+        .query_row(
+            "SELECT COUNT(*) FROM folders WHERE parent_id = ?1 AND deleted_time = 0",
+            [&rust_project_id],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(rust_folder_count, 2); // src and tests
+    println!(
+        "✓ Found {} folders in RustProject using resolved parent_id",
+        rust_folder_count
+    );
+
+    // src level check
+    let src_note_count: i64 = fs
+        .db
+        .query_row(
+            "SELECT COUNT(*) FROM notes WHERE parent_id = ?1 AND deleted_time = 0",
+            [&src_id],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(src_note_count, 2); // main.rs and lib.rs
+    println!(
+        "✓ Found {} files in src using resolved parent_id",
+        src_note_count
+    );
+
+    println!("\\n🎉 Parent folder ID resolution test passed!");
+    println!("- UUID-based parent resolution: ✓");
+    println!("- Multi-level path walking: ✓");
+    println!("- Database relationship validation: ✓");
+}
+
+#[test]
+fn test_path_resolution_follows_titles_in_one_query() {
+    let conn = create_deep_hierarchy_db().expect("Failed to create test database");
+    // A trashed twin and an older twin of `RustProject` must both lose
+    conn.execute_batch(
+        "INSERT INTO folders (id, title, created_time, updated_time, user_updated_time, parent_id, deleted_time)
+             VALUES ('trashed_project', 'RustProject', 0, 0, 9999999999, 'projects', 1);
+         INSERT INTO folders (id, title, created_time, updated_time, user_updated_time, parent_id)
+             VALUES ('old_project', 'RustProject', 0, 0, 1, 'projects');
+         INSERT INTO folders (id, title, created_time, updated_time, user_updated_time, parent_id)
+             VALUES ('old_src', 'src', 0, 0, 1, 'old_project');",
+    )
+    .unwrap();
+    let fs = fs_from_conn(conn, FsOptions::default());
+
+    assert_eq!(
+        fs.get_parent_folder_id("/Projects/RustProject/src")
+            .unwrap(),
+        "rust_src"
+    );
+    assert_eq!(
+        fs.get_parent_folder_id("/Projects//RustProject/").unwrap(),
+        "rust_project"
+    );
+    // The literal walk is answered by the recursive query alone
+    assert_eq!(
+        fs.resolve_titles("", &["Projects", "RustProject", "src"])
+            .unwrap()
+            .as_deref(),
+        Some("rust_src")
+    );
+    assert_eq!(
+        fs.resolve_titles("", &["Projects", "Missing"]).unwrap(),
+        None
+    );
+    assert!(fs.get_parent_folder_id("/Projects/Missing/src").is_err());
+    assert!(
+        fs.get_parent_folder_id("/Projects/RustProject/src/deeper")
+            .is_err()
+    );
+}
+
+#[test]
+#[ignore = "timing benchmark; run with --ignored --nocapture"]
+fn bench_cached_statements_speed_up_hot_queries() {
+    let conn = create_deep_hierarchy_db().expect("Failed to create test database");
+    for i in 0..1000 {
+        conn.execute(
+            "INSERT INTO notes (id, title, created_time, updated_time, parent_id) VALUES (?1, ?2, 0, 0, 'rust_src')",
+            [format!("bench{i:04}"), format!("note {i:04}")],
+        )
+        .unwrap();
+    }
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+    let src = fs.lookup_child(1, "Projects").unwrap().ino;
+    let src = fs.lookup_child(src, "RustProject").unwrap().ino;
+    let src = fs.lookup_child(src, "src").unwrap().ino;
+
+    // A cache of capacity 0 re-prepares every statement, as before caching
+    let measure = |fs: &mut SqliteFS, capacity| {
+        fs.db.set_prepared_statement_cache_capacity(capacity);
+        let start = Instant::now();
+        for _ in 0..1000 {
+            fs.get_parent_folder_id("/Projects/RustProject/src")
+                .unwrap();
+        }
+        let resolve = start.elapsed();
+        let start = Instant::now();
+        for (ino, _, name) in fs.list_directory(src).unwrap() {
+            if !name.starts_with('.') {
+                fs.get_attr(ino).unwrap();
+            }
+        }
+        (resolve, start.elapsed())
+    };
+    let (uncached, uncached_listing) = measure(&mut fs, 0);
+    let (cached, cached_listing) = measure(&mut fs, STATEMENT_CACHE_CAPACITY);
+    println!("1000 get_parent_folder_id: {uncached:?} uncached, {cached:?} cached");
+    println!(
+        "readdir + getattr of 1000 notes: {uncached_listing:?} uncached, {cached_listing:?} cached"
+    );
+    assert!(cached < uncached);
+}
+
+#[test]
+fn test_folder_creation() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+
+    // Test creating a folder in root
+    println!("Testing folder creation in root:");
+    let folder_id = fs.create_folder("/", "NewFolder").unwrap();
+    println!("✓ Created folder with UUID: {}", folder_id);
+
+    // Verify the folder was created in the database
+    let folder_exists: bool = fs.db.query_row(
+        "SELECT 1 FROM folders WHERE id = ?1 AND title = ?2 AND parent_id = '' AND deleted_time = 0",
+        [&folder_id, "NewFolder"],
+        |_| Ok(true)
+    ).unwrap_or(false);
+    assert!(folder_exists, "Folder should exist in database");
+    println!("✓ Folder exists in database with correct parent_id");
+
+    // Test creating a subfolder
+    println!("Testing subfolder creation:");
+    let subfolder_id = fs.create_folder("/NewFolder", "SubFolder").unwrap();
+    println!("✓ Created subfolder with UUID: {}", subfolder_id);
+
+    // Verify the subfolder was created with correct parent_id
+    let subfolder_exists: bool = fs.db.query_row(
+        "SELECT 1 FROM folders WHERE id = ?1 AND title = ?2 AND parent_id = ?3 AND deleted_time = 0",
+        [&subfolder_id, "SubFolder", &folder_id],
+        |_| Ok(true)
+    ).unwrap_or(false);
+    assert!(
+        subfolder_exists,
+        "Subfolder should exist in database with correct parent_id"
+    );
+    println!("✓ Subfolder exists in database with correct parent_id relationship");
+
+    // Test that we can find the folders using the filesystem methods
+    println!("Testing filesystem integration:");
+
+    // Check if we can find the new folder in root
+    let root_folders: Vec<String> = fs
+        .db
+        .prepare(
+            "SELECT title FROM folders WHERE parent_id = '' AND deleted_time = 0 ORDER BY title",
+        )
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .filter_map(Result::ok)
+        .collect();
+
+    assert!(
+        root_folders.contains(&"Documents".to_string()),
+        "Should still have original Documents folder"
+    );
+    assert!(
+        root_folders.contains(&"NewFolder".to_string()),
+        "Should have new NewFolder"
+    );
+    println!("✓ Root level folders: {:?}", root_folders);
+
+    // Check if we can find the subfolder
+    let new_folder_children: Vec<String> = fs
+        .db
+        .prepare("SELECT title FROM folders WHERE parent_id = ?1 AND deleted_time = 0")
+        .unwrap()
+        .query_map([&folder_id], |row| row.get(0))
+        .unwrap()
+        .filter_map(Result::ok)
+        .collect();
+
+    assert_eq!(new_folder_children, vec!["SubFolder"]);
+    println!("✓ NewFolder children: {:?}", new_folder_children);
+
+    // Test UUID format
+    assert!(
+        Uuid::parse_str(&folder_id).is_ok(),
+        "Folder ID should be valid UUID"
+    );
+    assert!(
+        Uuid::parse_str(&subfolder_id).is_ok(),
+        "Subfolder ID should be valid UUID"
+    );
+    assert_ne!(
+        folder_id, subfolder_id,
+        "Each folder should have unique UUID"
+    );
+    println!("✓ UUIDs are valid and unique");
+
+    // Test timestamps
+    let (created_time, updated_time): (i64, i64) = fs
+        .db
+        .query_row(
+            "SELECT created_time, updated_time FROM folders WHERE id = ?1",
+            [&folder_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+
+    assert!(created_time > 0, "Created time should be set");
+    assert!(updated_time > 0, "Updated time should be set");
+    assert_eq!(
+        created_time, updated_time,
+        "Created and updated times should be the same for new folder"
+    );
+    println!("✓ Timestamps are properly set");
+
+    println!("\\n🎉 Folder creation test passed!");
+    println!("- UUID v4 generation: ✓");
+    println!("- Database insertion: ✓");
+    println!("- Parent-child relationships: ✓");
+    println!("- Timestamp handling: ✓");
+    println!("- Multi-level creation: ✓");
+}
+
+#[test]
+fn test_no_md_suffix_round_trip() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            no_md_suffix: true,
+            ..FsOptions::default()
+        },
+    );
+
+    // Suffix handling becomes the identity in both directions
+    assert_eq!(fs.strip_note_suffix("report.md"), "report.md");
+    assert_eq!(fs.add_md_suffix("report"), "report");
+    assert_eq!(fs.add_md_suffix("readme.txt"), "readme.txt");
+
+    // A note created, written and renamed through the mount keeps the exact
+    // filenames as its titles
+    let dir = fs.lookup_child(1, "Documents").unwrap();
+    let created = fs.create_file("/Documents", "report.md").unwrap();
+    let fh = fs.open_note(created.ino, libc::O_WRONLY).unwrap();
+    fs.write_handle(fh, created.ino, 0, b"body").unwrap();
+    fs.release_handle(fh).unwrap();
+    assert_eq!(
+        fs.lookup_child(dir.ino, "report.md").unwrap().ino,
+        created.ino
+    );
+    assert_eq!(fs.lookup_child(dir.ino, "report").unwrap_err(), ENOENT);
+
+    fs.rename_entry(dir.ino, "report.md", dir.ino, "summary")
+        .unwrap();
+    let renamed = fs.lookup_child(dir.ino, "summary").unwrap();
+    assert_eq!(fs.read_note(renamed.ino, 0).unwrap(), b"body");
+    // Neither the old name nor a `.md` spelling of the new one resolves
+    for gone in ["report.md", "summary.md"] {
+        assert_eq!(
+            fs.lookup_child(dir.ino, gone).unwrap_err(),
+            ENOENT,
+            "{gone}"
+        );
+    }
+
+    // The default mode still strips and appends the suffix
+    let default_fs = fs_from_conn(create_test_db().unwrap(), FsOptions::default());
+    assert_eq!(default_fs.strip_note_suffix("report.md"), "report");
+    assert_eq!(default_fs.add_md_suffix("report"), "report.md");
+}
+
+#[test]
+fn test_joplin_order_and_user_data_xattrs() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+    let note_ino = fs.get_or_create_inode("/Documents/document.md.md").unwrap();
+    let folder_ino = fs.get_or_create_inode("/Documents").unwrap();
+
+    // Folders have no order or note metadata
+    assert_eq!(
+        fs.list_xattr(note_ino).unwrap(),
+        b"user.joplin.order\0user.joplin.user_data\0user.joplin.id\0user.joplin.author\0\
+          user.joplin.source_url\0user.joplin.is_todo\0user.joplin.todo_due\0\
+          user.joplin.markup_language\0"
+            .to_vec()
+    );
+    assert_eq!(
+        fs.list_xattr(folder_ino).unwrap(),
+        b"user.joplin.user_data\0user.joplin.id\0".to_vec()
+    );
+
+    // Valid writes update the columns and read back
+    fs.set_xattr(note_ino, XATTR_ORDER, b"1700000000123")
+        .unwrap();
+    assert_eq!(
+        fs.get_xattr(note_ino, XATTR_ORDER).unwrap(),
+        b"1700000000123"
+    );
+    fs.set_xattr(note_ino, XATTR_ORDER, b"2.5").unwrap();
+    assert_eq!(fs.get_xattr(note_ino, XATTR_ORDER).unwrap(), b"2.5");
+
+    let json = br#"{"app":{"pinned":true}}"#;
+    fs.set_xattr(note_ino, XATTR_USER_DATA, json).unwrap();
+    assert_eq!(
+        fs.get_xattr(note_ino, XATTR_USER_DATA).unwrap(),
+        json.to_vec()
+    );
+    fs.set_xattr(folder_ino, XATTR_USER_DATA, b"[1, 2]")
+        .unwrap();
+    let folder_data: String = fs
+        .db
+        .query_row(
+            "SELECT user_data FROM folders WHERE id = 'folder1'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(folder_data, "[1, 2]");
+    // Both update times advance, so the change syncs
+    let (updated, user_updated): (i64, i64) = fs
+        .db
+        .query_row(
+            "SELECT updated_time, user_updated_time FROM folders WHERE id = 'folder1'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert!(updated > 0);
+    assert_eq!(updated, user_updated);
+
+    // Invalid writes are rejected and leave the stored value untouched
+    assert_eq!(
+        fs.set_xattr(note_ino, XATTR_ORDER, b"first"),
+        Err(libc::EINVAL)
+    );
+    assert_eq!(
+        fs.set_xattr(note_ino, XATTR_ORDER, b"NaN"),
+        Err(libc::EINVAL)
+    );
+    assert_eq!(
+        fs.set_xattr(note_ino, XATTR_USER_DATA, b"{not json"),
+        Err(libc::EINVAL)
+    );
+    assert_eq!(fs.get_xattr(note_ino, XATTR_ORDER).unwrap(), b"2.5");
+    assert_eq!(
+        fs.get_xattr(note_ino, XATTR_USER_DATA).unwrap(),
+        json.to_vec()
+    );
+
+    // Folders have no order column
+    assert_eq!(fs.get_xattr(folder_ino, XATTR_ORDER), Err(libc::ENODATA));
+    assert_eq!(
+        fs.set_xattr(folder_ino, XATTR_ORDER, b"1"),
+        Err(libc::ENOTSUP)
+    );
+}
+
+#[test]
+fn test_note_metadata_xattrs() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute(
+        "UPDATE notes SET user_updated_time = 0 WHERE id = 'note1'",
+        [],
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+    let note_ino = fs.lookup_child(1, "readme.txt.md").unwrap().ino;
+
+    let url = b"https://joplinapp.org/help/";
+    fs.set_xattr(note_ino, "user.joplin.source_url", url)
+        .unwrap();
+    assert_eq!(
+        fs.get_xattr(note_ino, "user.joplin.source_url").unwrap(),
+        url.to_vec()
+    );
+    let (stored, updated_after): (String, i64) = fs
+        .db
+        .query_row(
+            "SELECT source_url, user_updated_time FROM notes WHERE id = 'note1'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(stored.as_bytes(), url);
+    assert!(updated_after > 0);
+
+    assert_eq!(fs.get_xattr(note_ino, XATTR_ID).unwrap(), b"note1");
+    assert_eq!(
+        fs.get_xattr(note_ino, "user.joplin.markup_language")
+            .unwrap(),
+        b"1"
+    );
+    fs.set_xattr(note_ino, "user.joplin.is_todo", b"1").unwrap();
+    fs.set_xattr(note_ino, "user.joplin.todo_due", b"1700000000000")
+        .unwrap();
+    assert_eq!(fs.get_xattr(note_ino, "user.joplin.is_todo").unwrap(), b"1");
+    assert_eq!(
+        fs.get_xattr(note_ino, "user.joplin.todo_due").unwrap(),
+        b"1700000000000"
+    );
+
+    // The id and markup language cannot be set, and flags take 0 or 1 only
+    assert_eq!(fs.set_xattr(note_ino, XATTR_ID, b"other"), Err(libc::EPERM));
+    assert_eq!(
+        fs.set_xattr(note_ino, "user.joplin.markup_language", b"2"),
+        Err(libc::EPERM)
+    );
+    assert_eq!(
+        fs.set_xattr(note_ino, "user.joplin.is_todo", b"yes"),
+        Err(libc::EINVAL)
+    );
+    assert_eq!(
+        fs.set_xattr(note_ino, "user.joplin.todo_due", b"-1"),
+        Err(libc::EINVAL)
+    );
+    fs.remove_xattr(note_ino, "user.joplin.source_url").unwrap();
+    assert_eq!(
+        fs.get_xattr(note_ino, "user.joplin.source_url").unwrap(),
+        b""
+    );
+}
+
+#[test]
+fn test_empty_note_lifecycle() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+
+    // create: an empty note, as the `create` callback does
+    fs.create_note("/Documents", "empty.md", "").unwrap();
+    let ino = fs.get_or_create_inode("/Documents/empty.md").unwrap();
+
+    // stat: size 0 with a consistent block count
+    let attr = fs.get_attr(ino).expect("empty note must not be ENOENT");
+    assert_eq!(attr.kind, FileType::RegularFile);
+    assert_eq!(attr.size, 0);
+    assert_eq!(attr.blocks, 0);
+
+    // read: empty at offset 0 and past the end
+    assert_eq!(fs.read_note(ino, 0).unwrap(), Vec::<u8>::new());
+    assert_eq!(fs.read_note(ino, 10).unwrap(), Vec::<u8>::new());
+
+    // write, then stat reflects the new size
+    let data = b"# Title\n";
+    assert_eq!(fs.write_note(ino, 0, data).unwrap(), data.len() as u32);
+    let attr = fs.get_attr(ino).unwrap();
+    assert_eq!(attr.size, data.len() as u64);
+    assert_eq!(attr.blocks, 1);
+    assert_eq!(fs.read_note(ino, 0).unwrap(), data.to_vec());
+}
+
+/// Display names from a directory listing, excluding `.` and `..`
+fn listed_names(entries: &[(u64, FileType, String)]) -> Vec<String> {
+    entries
+        .iter()
+        .filter(|(_, _, name)| name != "." && name != "..")
+        .map(|(_, _, name)| name.clone())
+        .collect()
+}
+
+#[test]
+fn test_callbacks_write_then_read_round_trip() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+
+    let attr = fs.create_file("/", "journal.md").unwrap();
+    assert_eq!(attr.kind, FileType::RegularFile);
+
+    fs.write_note(attr.ino, 0, b"Hello").unwrap();
+    fs.write_note(attr.ino, 5, b", world").unwrap();
+    assert_eq!(fs.read_note(attr.ino, 0).unwrap(), b"Hello, world".to_vec());
+    assert_eq!(fs.read_note(attr.ino, 7).unwrap(), b"world".to_vec());
+
+    // The stored title has the display suffix stripped
+    let body: String = fs
+        .db
+        .query_row(
+            "SELECT body FROM notes WHERE parent_id = '' AND title = 'journal'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(body, "Hello, world");
+}
+
+#[test]
+fn test_callbacks_readdir_lists_created_entries() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+
+    let dir = fs.make_directory("/Documents", "Archive").unwrap();
+    assert_eq!(dir.kind, FileType::Directory);
+    fs.create_file("/Documents/Archive", "old.md").unwrap();
+
+    let root = fs.list_directory(1).unwrap();
+    assert_eq!(root[0].2, ".");
+    assert_eq!(root[1].2, "..");
+    let mut names = listed_names(&root);
+    names.sort();
+    assert_eq!(names, vec!["Documents", "readme.txt.md"]);
+
+    let documents = fs.lookup_child(1, "Documents").unwrap();
+    let mut names = listed_names(&fs.list_directory(documents.ino).unwrap());
+    names.sort();
+    assert_eq!(names, vec!["Archive", "document.md.md"]);
+
+    let archive = fs.lookup_child(documents.ino, "Archive").unwrap();
+    assert_eq!(archive.ino, dir.ino);
+    let entries = fs.list_directory(archive.ino).unwrap();
+    assert_eq!(listed_names(&entries), vec!["old.md"]);
+    assert_eq!(entries[2].1, FileType::RegularFile);
+}
+
+#[test]
+fn test_callbacks_lookup_and_inode_mapping() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+
+    // Names are resolved with the display suffix, and inodes are stable
+    let first = fs.lookup_child(1, "readme.txt.md").unwrap();
+    let second = fs.lookup_child(1, "readme.txt.md").unwrap();
+    assert_eq!(first.ino, second.ino);
+    assert_eq!(first.size, "This is a test note in root".len() as u64);
+    assert_eq!(fs.get_path_from_inode(first.ino).unwrap(), "/readme.txt.md");
+
+    // Entries listed by readdir share the inode handed out by lookup
+    let listed = fs.list_directory(1).unwrap();
+    let readme = listed.iter().find(|e| e.2 == "readme.txt.md").unwrap();
+    assert_eq!(readme.0, first.ino);
+
+    fs.create_note("/Documents", "plan.md", "Nested body")
+        .unwrap();
+    let documents = fs.lookup_child(1, "Documents").unwrap();
+    let nested = fs.lookup_child(documents.ino, "plan.md").unwrap();
+    assert_eq!(
+        fs.get_path_from_inode(nested.ino).unwrap(),
+        "/Documents/plan.md"
+    );
+    assert_eq!(
+        fs.read_note(nested.ino, 0).unwrap(),
+        b"Nested body".to_vec()
+    );
+
+    // Missing names and unknown parents are ENOENT
+    assert_eq!(fs.lookup_child(1, "missing.md").unwrap_err(), ENOENT);
+    assert_eq!(fs.lookup_child(999, "readme.txt.md").unwrap_err(), ENOENT);
+    assert_eq!(fs.read_note(999, 0).unwrap_err(), ENOENT);
+}
+
+#[test]
+fn test_reply_glue_with_captured_replies() {
+    // Empty replies carry either success or the errno
+    let mut captured = Captured::default();
+    SqliteFS::reply_empty(Ok(()), &mut captured);
+    assert_eq!(captured, Captured::Ok);
+
+    let mut captured = Captured::default();
+    SqliteFS::reply_empty(Err(libc::ENOTEMPTY), &mut captured);
+    assert_eq!(captured, Captured::Error(libc::ENOTEMPTY));
+
+    // xattr replies honour the size probe, the buffer size and ERANGE
+    let mut captured = Captured::default();
+    SqliteFS::reply_xattr(b"value", 0, &mut captured);
+    assert_eq!(captured, Captured::Size(5));
+
+    let mut captured = Captured::default();
+    SqliteFS::reply_xattr(b"value", 16, &mut captured);
+    assert_eq!(captured, Captured::Data(b"value".to_vec()));
+
+    let mut captured = Captured::default();
+    SqliteFS::reply_xattr(b"value", 2, &mut captured);
+    assert_eq!(captured, Captured::Error(libc::ERANGE));
+}
+
+#[test]
+fn test_mutating_callbacks_report_errnos() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+    let documents = fs.lookup_child(1, "Documents").unwrap();
+
+    // rmdir refuses non-empty folders, unlink/rename report missing names
+    let mut captured = Captured::default();
+    SqliteFS::reply_empty(fs.remove_directory(1, "Documents"), &mut captured);
+    assert_eq!(captured, Captured::Error(libc::ENOTEMPTY));
+    assert_eq!(fs.unlink_note(1, "missing.md"), Err(ENOENT));
+    assert_eq!(fs.rename_entry(1, "missing.md", 1, "other.md"), Err(ENOENT));
+    assert_eq!(fs.open_note(documents.ino, libc::O_RDONLY), Err(ENOENT));
+
+    // Move the note out, rename it, then empty and remove the folder
+    fs.create_note("/Documents", "todo.md", "- [ ] task")
+        .unwrap();
+    let todo = fs.lookup_child(documents.ino, "todo.md").unwrap();
+    fs.rename_entry(documents.ino, "todo.md", 1, "done.md")
+        .unwrap();
+    assert_eq!(fs.get_path_from_inode(todo.ino).unwrap(), "/done.md");
+    assert_eq!(fs.read_note(todo.ino, 0).unwrap(), b"- [ ] task".to_vec());
+    fs.open_note(todo.ino, libc::O_RDWR).unwrap();
+
+    let attr = fs.set_attr(todo.ino, None, None, None, Some(5)).unwrap();
+    assert_eq!(attr.size, 5);
+    assert_eq!(fs.read_note(todo.ino, 0).unwrap(), b"- [ ]".to_vec());
+
+    fs.db
+        .execute("DELETE FROM notes WHERE parent_id = 'folder1'", [])
+        .unwrap();
+    fs.remove_directory(1, "Documents").unwrap();
+    assert_eq!(fs.lookup_child(1, "Documents").unwrap_err(), ENOENT);
+
+    fs.unlink_note(1, "done.md").unwrap();
+    assert_eq!(fs.get_path_from_inode(todo.ino), None);
+    assert_eq!(
+        listed_names(&fs.list_directory(1).unwrap()),
+        vec!["readme.txt.md"]
+    );
+}
+
+#[test]
+fn test_rename_rejects_kind_mismatch() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+    fs.make_directory("/", "Archive").unwrap();
+
+    // Note onto an existing folder
+    assert_eq!(
+        fs.rename_entry(1, "readme.txt.md", 1, "Archive"),
+        Err(libc::EISDIR)
+    );
+    // Folder onto an existing note
+    assert_eq!(
+        fs.rename_entry(1, "Archive", 1, "readme.txt.md"),
+        Err(libc::ENOTDIR)
+    );
+
+    // Nothing moved and no same-named sibling of the other kind was created
+    let mut names = listed_names(&fs.list_directory(1).unwrap());
+    names.sort();
+    assert_eq!(names, vec!["Archive", "Documents", "readme.txt.md"]);
+    let archives: i64 = fs
+        .db
+        .query_row(
+            "SELECT COUNT(*) FROM folders WHERE title = 'Archive'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(archives, 1);
+
+    // Moving into the folder still works
+    let archive = fs.lookup_child(1, "Archive").unwrap();
+    fs.rename_entry(1, "readme.txt.md", archive.ino, "readme.txt.md")
+        .unwrap();
+    assert_eq!(
+        listed_names(&fs.list_directory(archive.ino).unwrap()),
+        vec!["readme.txt.md"]
+    );
+}
+
+#[test]
+fn test_rename_replaces_an_existing_note() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "INSERT INTO folders (id, title, created_time, updated_time, parent_id) VALUES
+             ('dir', 'dir', 0, 0, '');
+         INSERT INTO notes (id, title, body, created_time, updated_time, user_updated_time, parent_id) VALUES
+             ('mover', 'a', 'moved', 0, 0, 0, ''),
+             ('victim', 'a', 'replaced', 0, 0, 0, 'dir');",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+    let dir = fs.lookup_child(1, "dir").unwrap();
+    let victim = fs.lookup_child(dir.ino, "a.md").unwrap();
+
+    fs.rename_entry(1, "a.md", dir.ino, "a.md").unwrap();
+    assert_eq!(
+        listed_names(&fs.list_directory(dir.ino).unwrap()),
+        vec!["a.md"]
+    );
+    assert_eq!(fs.lookup_child(1, "a.md"), Err(ENOENT));
+    let (ids, updated, user_updated): (String, i64, i64) = fs
+        .db
+        .query_row(
+            "SELECT group_concat(id), max(updated_time), max(user_updated_time) FROM notes WHERE title = 'a' AND deleted_time = 0",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .unwrap();
+    assert_eq!(ids, "mover");
+    assert!(updated > 0 && updated == user_updated);
+    let moved = fs.lookup_child(dir.ino, "a.md").unwrap();
+    assert_ne!(moved.ino, victim.ino);
+    assert_eq!(fs.read_note(moved.ino, 0).unwrap(), b"moved");
+}
+
+#[test]
+fn test_mount_name_and_subtype_options() {
+    let fs_name = |opts: &[MountOption]| match &opts[0] {
+        MountOption::FSName(name) => name.clone(),
+        other => panic!("expected FSName first, got {other:?}"),
+    };
+
+    // Defaults to the database basename so two mounts are distinguishable
+    let matches = cli()
+        .try_get_matches_from(["sqlite_fuse", "/data/work notes.sqlite", "/mnt/a"])
+        .unwrap();
+    let opts = mount_options(&matches);
+    assert_eq!(fs_name(&opts), "work_notes.sqlite");
+    assert!(!opts.iter().any(|o| matches!(o, MountOption::Subtype(_))));
+
+    let matches = cli()
+        .try_get_matches_from([
+            "sqlite_fuse",
+            "db.sqlite",
+            "/mnt/b",
+            "--mount-name",
+            "joplin-personal",
+            "--subtype",
+            "joplin",
+            "--auto_unmount",
+        ])
+        .unwrap();
+    let opts = mount_options(&matches);
+    assert_eq!(fs_name(&opts), "joplin-personal");
+    // Options the platform lacks are dropped rather than failing the mount
+    assert_eq!(
+        opts.contains(&MountOption::Subtype("joplin".to_string())),
+        PLATFORM.subtype
+    );
+    assert_eq!(
+        opts.contains(&MountOption::AutoUnmount),
+        PLATFORM.auto_unmount
+    );
+    #[cfg(target_os = "linux")]
+    assert!(opts.contains(&MountOption::AutoUnmount));
+
+    // Characters that would break the kernel option string are rejected
+    for bad in ["a,b", "a b", "a=b", ""] {
+        assert!(
+            cli()
+                .try_get_matches_from(["sqlite_fuse", "db", "/mnt", "--mount-name", bad])
+                .is_err(),
+            "{bad:?} should be rejected"
+        );
+    }
+    assert!(
+        cli()
+            .try_get_matches_from(["sqlite_fuse", "db", "/mnt", "--subtype", "x,y"])
+            .is_err()
+    );
+}
+
+#[test]
+fn test_mount_options_drop_what_the_platform_lacks() {
+    let matches = cli()
+        .try_get_matches_from([
+            "sqlite_fuse",
+            "db.sqlite",
+            "/mnt",
+            "--subtype",
+            "joplin",
+            "--auto_unmount",
+            "--allow-root",
+        ])
+        .unwrap();
+    let macos = PlatformSupport {
+        name: "macOS",
+        auto_unmount: false,
+        subtype: false,
+        readdirplus: false,
+        copy_file_range: false,
+    };
+    assert_eq!(
+        platform_mount_options(&matches, &macos),
+        vec![
+            MountOption::FSName("db.sqlite".to_string()),
+            MountOption::AllowRoot,
+        ]
+    );
+
+    let freebsd = PlatformSupport {
+        name: "freebsd",
+        subtype: true,
+        ..macos
+    };
+    assert_eq!(
+        platform_mount_options(&matches, &freebsd),
+        vec![
+            MountOption::FSName("db.sqlite".to_string()),
+            MountOption::Subtype("joplin".to_string()),
+            MountOption::AllowRoot,
+        ]
+    );
+}
+
+#[test]
+fn test_readdir_pages_large_folder_with_keyset() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute(
+        "CREATE INDEX idx_notes_parent_title ON notes(parent_id, title) WHERE deleted_time = 0",
+        [],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO folders (id, title, created_time, updated_time, parent_id) VALUES ('big', 'Big', 0, 0, '')",
+        [],
+    )
+    .unwrap();
+    conn.execute_batch(
+        "WITH RECURSIVE n(i) AS (SELECT 0 UNION ALL SELECT i + 1 FROM n WHERE i < 49999)
+         INSERT INTO notes (id, parent_id, title, created_time, updated_time)
+         SELECT printf('note-%05d', i), 'big', printf('entry %05d', i), 0, 0 FROM n;
+         INSERT INTO notes (id, parent_id, title, created_time, updated_time)
+         VALUES ('dup', 'big', 'entry 00042', 0, 0);",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            collision_policy: CollisionPolicy::NewestWins,
+            ..FsOptions::default()
+        },
+    );
+
+    let big = fs.lookup_child(1, "Big").unwrap();
+    let fh = fs.open_directory(big.ino).unwrap();
+
+    // Drive readdir like the kernel: a small buffer per call, resuming from
+    // the offset of the last entry that fit
+    let mut offset = 0;
+    let mut count = 0;
+    let mut last_name: Option<String> = None;
+    loop {
+        let mut accepted = 0;
+        fs.read_directory(fh, offset, |_, entry_offset, kind, name| {
+            if accepted == 100 {
+                return true;
+            }
+            accepted += 1;
+            offset = entry_offset;
+            if name != "." && name != ".." {
+                assert_eq!(kind, FileType::RegularFile);
+                assert!(
+                    last_name.as_deref() < Some(name),
+                    "{name} out of order or repeated"
+                );
+                last_name = Some(name.to_string());
+            }
+            false
+        })
+        .unwrap();
+        assert!(fs.dir_handles[&fh].page.len() <= DIR_PAGE_SIZE);
+        if accepted == 0 {
+            break;
+        }
+        count += accepted;
+    }
+    // 50,000 notes plus . and .., with the duplicate title listed once
+    assert_eq!(count, 50_002);
+    assert_eq!(last_name.as_deref(), Some("entry 49999.md"));
+
+    // Rewinding to offset 0 restarts the stream
+    let mut first = Vec::new();
+    fs.read_directory(fh, 0, |_, _, _, name| {
+        first.push(name.to_string());
+        first.len() == 3
+    })
+    .unwrap();
+    assert_eq!(first, vec![".", "..", "entry 00000.md"]);
+
+    fs.release_directory(fh);
+    assert_eq!(
+        fs.read_directory(fh, 0, |_, _, _, _| true),
+        Err(libc::EBADF)
+    );
+}
+
+#[test]
+fn test_readdir_tiny_buffer_returns_each_entry_once() {
+    for sort in [
+        SortOrder::Title,
+        SortOrder::CreatedDesc,
+        SortOrder::CreatedAsc,
+    ] {
+        for policy in [CollisionPolicy::NewestWins, CollisionPolicy::SuffixCount] {
+            let conn = create_test_db().expect("Failed to create test database");
+            conn.execute(
+                "INSERT INTO folders (id, title, created_time, updated_time, parent_id) VALUES ('big', 'Big', 0, 0, '')",
+                [],
+            )
+            .unwrap();
+            // Spans several pages, with shared titles and created times so
+            // the id tie-break and the de-duplication both come into play
+            conn.execute_batch(
+                "WITH RECURSIVE n(i) AS (SELECT 0 UNION ALL SELECT i + 1 FROM n WHERE i < 699)
+                 INSERT INTO notes (id, parent_id, title, created_time, updated_time, user_updated_time)
+                 SELECT printf('note-%03d', i), 'big', printf('entry %03d', i % 500), i / 7, i, i FROM n;
+                 INSERT INTO folders (id, title, created_time, updated_time, parent_id)
+                 SELECT printf('sub-%03d', i), printf('sub %03d', i % 250), i / 3, 0, 'big' FROM
+                 (WITH RECURSIVE n(i) AS (SELECT 0 UNION ALL SELECT i + 1 FROM n WHERE i < 299) SELECT i FROM n);",
+            )
+            .unwrap();
+            let mut fs = fs_from_conn(
+                conn,
+                FsOptions {
+                    sort,
+                    collision_policy: policy,
+                    ..FsOptions::default()
+                },
+            );
+            let big = fs.lookup_child(1, "Big").unwrap().ino;
+            let expected: Vec<String> = fs
+                .list_directory(big)
+                .unwrap()
+                .into_iter()
+                .map(|(_, _, name)| name)
+                .collect();
+            let unique: std::collections::HashSet<&String> = expected.iter().collect();
+            assert_eq!(unique.len(), expected.len(), "{sort:?} {policy:?}");
+
+            // Room for one to three entries per call, so nearly every entry
+            // is first rejected and then resumed from its offset
+            let fh = fs.open_directory(big).unwrap();
+            let mut offset = 0;
+            let mut names = Vec::new();
+            let mut offsets = Vec::new();
+            for round in 0.. {
+                let room = round % 3 + 1;
+                let mut accepted = 0;
+                fs.read_directory(fh, offset, |_, entry_offset, _, name| {
+                    if accepted == room {
+                        return true;
+                    }
+                    accepted += 1;
+                    offset = entry_offset;
+                    names.push(name.to_string());
+                    offsets.push(entry_offset);
+                    false
+                })
+                .unwrap();
+                if accepted == 0 {
+                    break;
+                }
+            }
+            assert_eq!(names, expected, "{sort:?} {policy:?}");
+            assert_eq!(offsets, (1..=expected.len() as i64).collect::<Vec<_>>());
+
+            // Seeking back to an earlier offset resumes at the entry after it
+            for back in [1, 257, expected.len() - 2] {
+                let mut next = None;
+                fs.read_directory(fh, back as i64, |_, entry_offset, _, name| {
+                    next = Some((entry_offset, name.to_string()));
+                    true
+                })
+                .unwrap();
+                let (entry_offset, name) = next.unwrap();
+                assert_eq!(entry_offset, back as i64 + 1);
+                assert_eq!(name, expected[back]);
+            }
+            fs.release_directory(fh);
+        }
+    }
+}
+
+#[test]
+fn test_persist_inodes_keeps_inodes_across_mounts() {
+    let options = || FsOptions {
+        persist_inodes: true,
+        ..FsOptions::default()
+    };
+    let mut fs = fs_from_conn(create_test_db().unwrap(), options());
+    let created = fs.create_file("/", "new.md").unwrap().ino;
+    let readme = fs.lookup_child(1, "readme.txt.md").unwrap().ino;
+    let documents = fs.lookup_child(1, "Documents").unwrap().ino;
+    let recorded: i64 = fs
+        .db
+        .query_row("SELECT COUNT(*) FROM fuse_inodes", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(recorded, 3);
+
+    // Remount on the same database, looking entries up in another order
+    let conn = std::mem::replace(&mut fs.db, Connection::open_in_memory().unwrap());
+    drop(fs);
+    let mut fs = fs_from_conn(conn, options());
+    let document = fs.get_or_create_inode("/Documents/document.md.md").unwrap();
+    assert_eq!(fs.lookup_child(1, "Documents").unwrap().ino, documents);
+    assert_eq!(fs.lookup_child(1, "readme.txt.md").unwrap().ino, readme);
+    assert_eq!(fs.lookup_child(1, "new.md").unwrap().ino, created);
+
+    // Renames keep the number, and new inodes never reuse a recorded one
+    fs.rename_entry(1, "new.md", documents, "moved.md").unwrap();
+    assert_eq!(fs.lookup_child(documents, "moved.md").unwrap().ino, created);
+    let fresh = fs.create_file("/", "fresh.md").unwrap().ino;
+    assert!(![created, readme, documents, document].contains(&fresh));
+
+    // Without the option nothing is recorded or reused
+    let conn = std::mem::replace(&mut fs.db, Connection::open_in_memory().unwrap());
+    drop(fs);
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+    assert_eq!(
+        fs.lookup_child(1, "fresh.md").unwrap().ino,
+        FIRST_DYNAMIC_INODE
+    );
+}
+
+#[test]
+fn test_tags_list_tagged_notes() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "CREATE TABLE tags (id TEXT PRIMARY KEY, title TEXT NOT NULL DEFAULT '', created_time INT NOT NULL, updated_time INT NOT NULL);
+         CREATE TABLE note_tags (id TEXT PRIMARY KEY, note_id TEXT NOT NULL, tag_id TEXT NOT NULL, created_time INT NOT NULL, updated_time INT NOT NULL);
+         INSERT INTO tags VALUES ('tag-work', 'work', 0, 7), ('tag-home', 'home', 0, 3), ('tag-bad', 'a/b', 0, 0);
+         INSERT INTO notes (id, parent_id, title, body, created_time, updated_time)
+         VALUES ('errand', '', 'errand', 'Buy milk', 0, 0),
+                ('readme2', 'folder1', 'readme.txt', 'Another readme', 0, 0);
+         INSERT INTO note_tags VALUES
+             ('nt1', 'note1', 'tag-work', 0, 0),
+             ('nt2', 'note2', 'tag-work', 0, 0),
+             ('nt3', 'note2', 'tag-home', 0, 0),
+             ('nt4', 'errand', 'tag-home', 0, 0),
+             ('nt5', 'readme2', 'tag-work', 0, 0);",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            tags: true,
+            ..FsOptions::default()
+        },
+    );
+
+    let tags = fs.lookup_child(1, ".tags").unwrap();
+    assert_eq!(tags.perm, 0o555);
+    assert_eq!(
+        listed_names(&fs.list_directory(tags.ino).unwrap()),
+        vec!["home", "work"]
+    );
+
+    // Named like the notes in their folders; a shared name gets the suffix
+    let work = fs.lookup_child(tags.ino, "work").unwrap();
+    assert_eq!((work.kind, work.perm), (FileType::Directory, 0o555));
+    assert_eq!(
+        listed_names(&fs.list_directory(work.ino).unwrap()),
+        vec!["document.md.md", "readme.txt.md", "readme.txt (readme).md"]
+    );
+    let home = fs.lookup_child(tags.ino, "home").unwrap();
+    assert_eq!(
+        listed_names(&fs.list_directory(home.ino).unwrap()),
+        vec!["document.md.md", "errand.md"]
+    );
+
+    // The files are the notes themselves
+    let readme = fs.lookup_child(work.ino, "readme.txt.md").unwrap();
+    assert_eq!(readme.kind, FileType::RegularFile);
+    assert_eq!(
+        fs.read_note(readme.ino, 0).unwrap(),
+        b"This is a test note in root"
+    );
+    let other = fs.lookup_child(work.ino, "readme.txt (readme).md").unwrap();
+    assert_eq!(fs.read_note(other.ino, 0).unwrap(), b"Another readme");
+    assert_eq!(fs.lookup_child(home.ino, "readme.txt.md"), Err(ENOENT));
+    assert_eq!(fs.lookup_child(tags.ino, "missing"), Err(ENOENT));
+    assert_eq!(fs.lookup_child(tags.ino, "a"), Err(ENOENT));
+
+    // Tag directories are not folders: nothing is created or removed there
+    assert_eq!(
+        fs.create_file("/.tags/work", "new.md").map(|_| ()),
+        Err(libc::EPERM)
+    );
+    assert_eq!(fs.unlink_note(work.ino, "errand.md"), Err(libc::EPERM));
+    assert_eq!(
+        fs.get_parent_folder_id("/.tags/work"),
+        Err(rusqlite::Error::QueryReturnedNoRows)
+    );
+
+    // Without the option there is no /.tags
+    let mut plain = fs_from_conn(create_test_db().unwrap(), FsOptions::default());
+    assert_eq!(plain.lookup_child(1, ".tags"), Err(ENOENT));
+}
+
+#[test]
+fn test_xattr_unsupported_namespaces_and_unknown_names() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+    let note_ino = fs.get_or_create_inode("/readme.txt.md").unwrap();
+
+    // Kernel-defined namespaces are unsupported on every operation
+    for name in ["security.selinux", "system.posix_acl_access", "trusted.x"] {
+        assert_eq!(fs.get_xattr(note_ino, name), Err(libc::ENOTSUP));
+        assert_eq!(fs.set_xattr(note_ino, name, b"x"), Err(libc::ENOTSUP));
+        assert_eq!(fs.remove_xattr(note_ino, name), Err(libc::ENOTSUP));
+    }
+    assert_eq!(fs.get_xattr(1, "security.selinux"), Err(libc::ENOTSUP));
+
+    // Unknown user attributes are simply absent
+    assert_eq!(
+        fs.get_xattr(note_ino, "user.joplin.nope"),
+        Err(libc::ENODATA)
+    );
+    assert_eq!(
+        fs.remove_xattr(note_ino, "user.joplin.nope"),
+        Err(libc::ENODATA)
+    );
+    assert_eq!(fs.get_xattr(1, "user.joplin.order"), Err(libc::ENODATA));
+
+    // Removing a Joplin attribute restores the column default
+    fs.set_xattr(note_ino, XATTR_ORDER, b"42").unwrap();
+    fs.set_xattr(note_ino, XATTR_USER_DATA, b"{}").unwrap();
+    fs.remove_xattr(note_ino, XATTR_ORDER).unwrap();
+    fs.remove_xattr(note_ino, XATTR_USER_DATA).unwrap();
+    assert_eq!(fs.get_xattr(note_ino, XATTR_ORDER).unwrap(), b"0");
+    assert_eq!(fs.get_xattr(note_ino, XATTR_USER_DATA).unwrap(), b"");
+}
+
+#[test]
+fn test_subtree_root_lists_only_that_folder() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+    fs.root_folder_id = "folder1".to_string();
+
+    // "/" is the Documents folder: its note, not the top-level entries
+    assert_eq!(
+        listed_names(&fs.list_directory(1).unwrap()),
+        vec!["document.md.md"]
+    );
+    assert_eq!(fs.lookup_child(1, "Documents"), Err(ENOENT));
+    assert_eq!(fs.lookup_child(1, "readme.txt.md"), Err(ENOENT));
+    let note = fs.lookup_child(1, "document.md.md").unwrap();
+    assert_eq!(
+        fs.read_note(note.ino, 0).unwrap(),
+        b"This is a test note in Documents folder"
+    );
+
+    // The root carries the folder's timestamps
+    let root = fs.get_attr(1).unwrap();
+    assert_eq!(root.kind, FileType::Directory);
+    assert_ne!(root.mtime, UNIX_EPOCH);
+
+    // Entries created at "/" land inside the subtree
+    fs.make_directory("/", "Sub").unwrap();
+    fs.create_file("/Sub", "inner.md").unwrap();
+    let parent: String = fs
+        .db
+        .query_row(
+            "SELECT parent_id FROM folders WHERE title = 'Sub'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(parent, "folder1");
+    let sub = fs.lookup_child(1, "Sub").unwrap();
+    assert_eq!(
+        listed_names(&fs.list_directory(sub.ino).unwrap()),
+        vec!["inner.md"]
+    );
+}
+
+#[test]
+fn test_folder_option_mounts_one_notebook() {
+    let mounted = |folder: &str| {
+        let conn = create_deep_hierarchy_db().expect("Failed to create test database");
+        SqliteFS::from_connection(
+            conn,
+            FsOptions {
+                folder: Some(folder.to_string()),
+                ..FsOptions::default()
+            },
+        )
+    };
+
+    // By id or by path, "/" is the Projects notebook
+    for folder in ["projects", "/Projects", "Projects/"] {
+        let mut fs = mounted(folder).unwrap();
+        assert_eq!(fs.root_folder_id, "projects");
+        assert_eq!(fs.get_parent_folder_id("/").unwrap(), "projects");
+        let mut names = listed_names(&fs.list_directory(1).unwrap());
+        names.sort();
+        assert_eq!(names, ["PythonProject", "RustProject", "overview.txt.md"]);
+        assert_eq!(fs.lookup_child(1, "Projects"), Err(ENOENT));
+        assert_eq!(
+            fs.get_parent_folder_id("/RustProject/src").unwrap(),
+            "rust_src"
+        );
+    }
+
+    assert!(matches!(
+        mounted("/Projects/Missing"),
+        Err(FsError::FolderNotFound(folder)) if folder == "/Projects/Missing"
+    ));
+}
+
+#[test]
+fn test_inline_resources_rewrites_image_links() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "CREATE TABLE resources (id TEXT PRIMARY KEY, title TEXT NOT NULL DEFAULT '', mime TEXT NOT NULL, filename TEXT NOT NULL DEFAULT '', file_extension TEXT NOT NULL DEFAULT '', size INT NOT NULL DEFAULT -1);
+         INSERT INTO resources (id, mime, file_extension) VALUES
+             ('0123456789abcdef0123456789abcdef', 'image/png', 'png'),
+             ('fedcba9876543210fedcba9876543210', 'application/pdf', 'pdf'),
+             ('11111111111111111111111111111111', 'image/png', 'png');",
+    )
+    .unwrap();
+    let body = "A ![dot](:/0123456789abcdef0123456789abcdef) and [pdf](:/fedcba9876543210fedcba9876543210) and ![big](:/11111111111111111111111111111111) and [note](:/abc)";
+    conn.execute(
+        "INSERT INTO notes (id, title, body, created_time, updated_time, parent_id) VALUES ('img', 'pic', ?1, 0, 0, '')",
+        [body],
+    )
+    .unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("0123456789abcdef0123456789abcdef.png"),
+        b"\x89PNG\r\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path().join("fedcba9876543210fedcba9876543210.pdf"),
+        b"%PDF",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path().join("11111111111111111111111111111111.png"),
+        vec![0u8; INLINE_RESOURCE_MAX_BYTES as usize + 1],
+    )
+    .unwrap();
+
+    let options = FsOptions {
+        inline_resources: true,
+        resource_dir: dir.path().to_path_buf(),
+        ..FsOptions::default()
+    };
+    let mut fs = fs_from_conn(conn, options);
+    let note = fs.lookup_child(1, "pic.md").unwrap();
+
+    let read = fs.read_note(note.ino, 0).unwrap();
+    let expected = "A ![dot](data:image/png;base64,iVBORw0K) and [pdf](:/fedcba9876543210fedcba9876543210) and ![big](:/11111111111111111111111111111111) and [note](:/abc)";
+    assert_eq!(String::from_utf8(read).unwrap(), expected);
+
+    // stat agrees with the rewritten body, not the stored one
+    assert_eq!(note.size, expected.len() as u64);
+    assert_eq!(fs.get_attr(note.ino).unwrap().size, expected.len() as u64);
+    assert_eq!(
+        fs.read_note(note.ino, 4).unwrap(),
+        &expected.as_bytes()[4..]
+    );
+
+    // The transformation is read-only
+    assert_eq!(fs.write_note(note.ino, 0, b"x"), Err(libc::EROFS));
+    assert_eq!(
+        fs.set_attr(note.ino, None, None, None, Some(0)).map(|_| ()),
+        Err(libc::EROFS)
+    );
+    let stored: String = fs
+        .db
+        .query_row("SELECT body FROM notes WHERE id = 'img'", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(stored, body);
+}
+
+#[test]
+fn test_notebooks_by_id_survives_rename() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            notebooks_by_id: true,
+            ..FsOptions::default()
+        },
+    );
+
+    // The virtual directory sits at the root and lists notebooks by id
+    let mut root = listed_names(&fs.list_directory(1).unwrap());
+    root.sort();
+    assert_eq!(root, vec![".notebooks", "Documents", "readme.txt.md"]);
+    let notebooks = fs.lookup_child(1, ".notebooks").unwrap();
+    assert_eq!(notebooks.kind, FileType::Directory);
+    assert_eq!(
+        listed_names(&fs.list_directory(notebooks.ino).unwrap()),
+        vec!["folder1"]
+    );
+
+    let by_id = fs.lookup_child(notebooks.ino, "folder1").unwrap();
+    let note = fs.lookup_child(by_id.ino, "document.md.md").unwrap();
+
+    // Renaming the notebook leaves the id path intact
+    fs.rename_entry(1, "Documents", 1, "Papers").unwrap();
+    assert_eq!(fs.lookup_child(1, "Documents"), Err(ENOENT));
+    assert_eq!(
+        fs.lookup_child(notebooks.ino, "folder1").unwrap().ino,
+        by_id.ino
+    );
+    assert_eq!(
+        listed_names(&fs.list_directory(by_id.ino).unwrap()),
+        vec!["document.md.md"]
+    );
+
+    // Reads, writes and creates pass through to the real notebook
+    fs.set_attr(note.ino, None, None, None, Some(0)).unwrap();
+    fs.write_note(note.ino, 0, b"via id").unwrap();
+    let papers = fs.lookup_child(1, "Papers").unwrap();
+    let real = fs.lookup_child(papers.ino, "document.md.md").unwrap();
+    assert_eq!(fs.read_note(real.ino, 0).unwrap(), b"via id");
+    fs.create_file("/.notebooks/folder1", "new.md").unwrap();
+    assert!(fs.lookup_child(papers.ino, "new.md").is_ok());
+
+    // The virtual level itself cannot be modified
+    assert_eq!(
+        fs.make_directory("/.notebooks", "x").map(|_| ()),
+        Err(libc::EPERM)
+    );
+    assert_eq!(
+        fs.remove_directory(notebooks.ino, "folder1"),
+        Err(libc::EPERM)
+    );
+    assert_eq!(fs.rename_entry(1, ".notebooks", 1, "nb"), Err(libc::EPERM));
+    assert_eq!(fs.lookup_child(notebooks.ino, "no-such-id"), Err(ENOENT));
+
+    // Without the option nothing is exposed
+    let mut plain = fs_from_conn(create_test_db().unwrap(), FsOptions::default());
+    assert_eq!(plain.lookup_child(1, ".notebooks"), Err(ENOENT));
+}
+
+#[test]
+fn test_reply_attrs_match_getattr() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+
+    // create and mkdir replies are exactly what a follow-up getattr reports
+    let created = fs.create_file("/Documents", "fresh.md").unwrap();
+    assert_eq!(fs.get_attr(created.ino).unwrap(), created);
+    let made = fs.make_directory("/Documents", "Nested").unwrap();
+    assert_eq!(fs.get_attr(made.ino).unwrap(), made);
+
+    // So are lookup and setattr, including after the body grows
+    let documents = fs.lookup_child(1, "Documents").unwrap();
+    assert_eq!(fs.lookup_child(documents.ino, "fresh.md").unwrap(), created);
+    fs.write_note(created.ino, 0, &[b'x'; 513]).unwrap();
+    let resized = fs.set_attr(created.ino, None, None, None, None).unwrap();
+    assert_eq!(resized.size, 513);
+    assert_eq!(resized.blocks, 2);
+    assert_eq!(fs.get_attr(created.ino).unwrap(), resized);
+    assert_eq!(fs.get_attr(documents.ino).unwrap(), documents);
+}
+
+#[test]
+fn test_truncate_inside_multibyte_character() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+    let note = fs.create_file("/", "emoji.md").unwrap();
+    fs.write_note(note.ino, 0, "hi 😀!".as_bytes()).unwrap();
+
+    // "hi " is 3 bytes and the emoji 4 more: byte 5 falls inside the emoji
+    let attr = fs.set_attr(note.ino, None, None, None, Some(5)).unwrap();
+    assert_eq!(attr.size, 3);
+
+    let stored: String = fs
+        .db
+        .query_row("SELECT body FROM notes WHERE title = 'emoji'", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(stored, "hi ");
+    assert!(!stored.contains('\u{FFFD}'));
+
+    // Boundary-aligned truncation and extension stay byte exact
+    fs.write_note(note.ino, 0, "hi 😀!".as_bytes()).unwrap();
+    assert_eq!(
+        fs.set_attr(note.ino, None, None, None, Some(7))
+            .unwrap()
+            .size,
+        7
+    );
+    assert_eq!(fs.read_note(note.ino, 0).unwrap(), "hi 😀".as_bytes());
+    assert_eq!(
+        fs.set_attr(note.ino, None, None, None, Some(9))
+            .unwrap()
+            .size,
+        9
+    );
+    assert_eq!(fs.read_note(note.ino, 7).unwrap(), b"\0\0");
+}
+
+#[test]
+fn test_write_debounce_coalesces_rapid_writes() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "CREATE TABLE body_commits (note_id TEXT);
+         CREATE TRIGGER count_body_commits AFTER UPDATE OF body ON notes
+         BEGIN INSERT INTO body_commits VALUES (NEW.id); END;",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            write_debounce: Some(Duration::from_secs(60)),
+            ..FsOptions::default()
+        },
+    );
+    let commits = |fs: &SqliteFS| -> i64 {
+        fs.db
+            .query_row("SELECT COUNT(*) FROM body_commits", [], |row| row.get(0))
+            .unwrap()
+    };
+
+    let note = fs.lookup_child(1, "readme.txt.md").unwrap();
+    let fh = fs.open_note(note.ino, libc::O_RDWR).unwrap();
+    fs.set_attr(note.ino, None, None, None, Some(0)).unwrap();
+    let baseline = commits(&fs);
+
+    // Ten autosaves in quick succession: only the first reaches the database
+    let mut expected = String::new();
+    for i in 0..10 {
+        let chunk = format!("line {i}\n");
+        fs.write_handle(fh, note.ino, expected.len() as i64, chunk.as_bytes())
+            .unwrap();
+        expected.push_str(&chunk);
+    }
+    assert_eq!(commits(&fs) - baseline, 1);
+
+    // Readers and stat already see the buffered body
+    assert_eq!(fs.read_note(note.ino, 0).unwrap(), expected.as_bytes());
+    assert_eq!(fs.get_attr(note.ino).unwrap().size, expected.len() as u64);
+
+    // Release commits the rest in one go
+    fs.release_handle(fh).unwrap();
+    assert_eq!(commits(&fs) - baseline, 2);
+    let stored: String = fs
+        .db
+        .query_row("SELECT body FROM notes WHERE id = 'note1'", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(stored, expected);
+    assert!(fs.file_handles.is_empty());
+
+    // Without the option every write commits
+    let mut direct = fs_from_conn(create_test_db().unwrap(), FsOptions::default());
+    let note = direct.lookup_child(1, "readme.txt.md").unwrap();
+    let fh = direct
+        .open_note(note.ino, libc::O_RDWR | libc::O_TRUNC)
+        .unwrap();
+    direct.write_handle(fh, note.ino, 0, b"now").unwrap();
+    assert_eq!(direct.read_note(note.ino, 0).unwrap(), b"now");
+    assert!(direct.file_handles[&fh].pending.is_none());
+}
+
+#[test]
+fn test_chunked_write_keeps_earlier_chunks() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+    let note = fs.lookup_child(1, "readme.txt.md").unwrap();
+    fs.set_attr(note.ino, None, None, None, Some(0)).unwrap();
+
+    fs.write_note(note.ino, 0, b"AAAA").unwrap();
+    fs.write_note(note.ino, 4, b"BB").unwrap();
+    assert_eq!(fs.read_note(note.ino, 0).unwrap(), b"AAAABB");
+
+    // A write at offset 0 overwrites only its own bytes
+    fs.write_note(note.ino, 0, b"C").unwrap();
+    assert_eq!(fs.read_note(note.ino, 0).unwrap(), b"CAAABB");
+}
+
+#[test]
+fn test_character_split_across_writes_survives() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+    let note = fs.create_file("/", "euro.md").unwrap();
+    let euro = "€".as_bytes();
+    assert_eq!(euro.len(), 3);
+
+    // The body holds a partial character between the two writes
+    fs.write_note(note.ino, 0, &euro[..1]).unwrap();
+    assert_eq!(fs.read_note(note.ino, 0).unwrap(), &euro[..1]);
+    fs.write_note(note.ino, 1, &euro[1..]).unwrap();
+    assert_eq!(fs.read_note(note.ino, 0).unwrap(), euro);
+    let stored: String = fs
+        .db
+        .query_row("SELECT body FROM notes WHERE title = 'euro'", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(stored, "€");
+
+    // Likewise through a write-debounce buffer
+    fs.options.write_debounce = Some(Duration::from_secs(60));
+    let fh = fs
+        .open_note(note.ino, libc::O_RDWR | libc::O_TRUNC)
+        .unwrap();
+    fs.write_handle(fh, note.ino, 0, &euro[..2]).unwrap();
+    fs.write_handle(fh, note.ino, 2, &euro[2..]).unwrap();
+    fs.release_handle(fh).unwrap();
+    assert_eq!(fs.read_note(note.ino, 0).unwrap(), euro);
+}
+
+#[test]
+fn test_read_returns_at_most_size_bytes() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+    let note = fs.create_file("/", "digits.md").unwrap();
+    fs.write_note(note.ino, 0, b"0123456789").unwrap();
+
+    assert_eq!(fs.read_range(note.ino, 0, 2, 3).unwrap(), b"234");
+    assert_eq!(fs.read_range(note.ino, 0, 8, 3).unwrap(), b"89");
+    assert_eq!(fs.read_range(note.ino, 0, 0, 0).unwrap(), b"");
+    assert_eq!(fs.read_range(note.ino, 0, 10, 3).unwrap(), b"");
+    assert_eq!(fs.read_range(note.ino, 0, 42, 3).unwrap(), b"");
+}
+
+#[test]
+fn test_diff_sidecar_previews_pending_writes() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            write_debounce: Some(Duration::from_secs(60)),
+            ..FsOptions::default()
+        },
+    );
+    let note = fs.lookup_child(1, "readme.txt.md").unwrap();
+
+    // With nothing pending (not even open) the sidecar is empty and read-only
+    let sidecar = fs.lookup_child(1, "readme.txt.md.diff").unwrap();
+    assert_eq!(sidecar.size, 0);
+    assert_eq!(sidecar.perm, 0o444);
+    assert_eq!(fs.read_note(sidecar.ino, 0).unwrap(), b"");
+    assert_eq!(fs.open_note(sidecar.ino, libc::O_WRONLY), Err(libc::EACCES));
+    fs.open_note(sidecar.ino, libc::O_RDONLY).unwrap();
+    assert_eq!(fs.lookup_child(1, "missing.md.diff"), Err(ENOENT));
+    assert_eq!(fs.lookup_child(1, "Documents.diff"), Err(ENOENT));
+
+    // The first write commits; the next one stays buffered and shows up as a diff
+    let fh = fs.open_note(note.ino, libc::O_RDWR).unwrap();
+    fs.set_attr(note.ino, None, None, None, Some(0)).unwrap();
+    fs.write_handle(fh, note.ino, 0, b"first\n").unwrap();
+    fs.write_handle(fh, note.ino, 6, b"second\n").unwrap();
+    let expected = "--- a/readme.txt.md\n+++ b/readme.txt.md\n@@ -1 +1,2 @@\n first\n+second\n";
+    assert_eq!(
+        fs.get_attr(sidecar.ino).unwrap().size,
+        expected.len() as u64
+    );
+    assert_eq!(
+        String::from_utf8(fs.read_note(sidecar.ino, 0).unwrap()).unwrap(),
+        expected
+    );
+
+    // Once flushed there is nothing left to preview
+    fs.commit_handle(fh).unwrap();
+    assert_eq!(fs.read_note(sidecar.ino, 0).unwrap(), b"");
+    assert!(
+        !listed_names(&fs.list_directory(1).unwrap()).contains(&"readme.txt.md.diff".to_string())
+    );
+}
+
+#[test]
+fn test_o_append_writes_always_go_to_the_end() {
+    for write_debounce in [None, Some(Duration::from_secs(60))] {
+        let conn = create_test_db().expect("Failed to create test database");
+        let mut fs = fs_from_conn(
+            conn,
+            FsOptions {
+                write_debounce,
+                ..FsOptions::default()
+            },
+        );
+        let note = fs.lookup_child(1, "readme.txt.md").unwrap();
+        let original = fs.read_note(note.ino, 0).unwrap();
+
+        // Writes at offset 0 nonetheless append
+        let fh = fs
+            .open_note(note.ino, libc::O_WRONLY | libc::O_APPEND)
+            .unwrap();
+        fs.write_handle(fh, note.ino, 0, b"\nlog 1").unwrap();
+        fs.write_handle(fh, note.ino, 0, b"\nlog 2").unwrap();
+        fs.release_handle(fh).unwrap();
+
+        let mut expected = original.clone();
+        expected.extend_from_slice(b"\nlog 1\nlog 2");
+        assert_eq!(fs.read_note(note.ino, 0).unwrap(), expected);
+
+        // A handle without O_APPEND still honours the offset
+        let fh = fs.open_note(note.ino, libc::O_WRONLY).unwrap();
+        fs.write_handle(fh, note.ino, 1, b"H").unwrap();
+        fs.release_handle(fh).unwrap();
+        assert_eq!(fs.read_note(note.ino, 0).unwrap()[..2], *b"TH");
+    }
+}
+
+#[test]
+fn test_size_invariant_holds_for_written_and_foreign_bodies() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            debug_invariants: true,
+            ..FsOptions::default()
+        },
+    );
+    let note = fs.lookup_child(1, "readme.txt.md").unwrap();
+
+    // Healthy notes agree before and after writes and truncation
+    assert_eq!(fs.size_invariant_violation(note.ino), None);
+    fs.write_note(note.ino, 0, "héllo".as_bytes()).unwrap();
+    assert_eq!(fs.size_invariant_violation(note.ino), None);
+    fs.set_attr(note.ino, None, None, None, Some(2)).unwrap();
+    assert_eq!(fs.size_invariant_violation(note.ino), None);
+
+    // A body another tool stored as invalid UTF-8 reads back byte for byte
+    fs.db
+        .execute(
+            "UPDATE notes SET body = CAST(x'68ff69' AS TEXT) WHERE id = 'note1'",
+            [],
+        )
+        .unwrap();
+    assert_eq!(fs.size_invariant_violation(note.ino), None);
+    assert_eq!(fs.read_note(note.ino, 0).unwrap(), b"h\xffi");
+    fs.debug_check_invariants(note.ino);
+}
+
+#[test]
+fn test_sort_by_creation_time() {
+    let listing = |sort: SortOrder| {
+        let conn = create_test_db().expect("Failed to create test database");
+        conn.execute_batch(
+            "DELETE FROM notes;
+             INSERT INTO notes (id, title, created_time, updated_time, parent_id) VALUES
+                 ('n-b', 'beta', 300, 0, ''),
+                 ('n-a', 'alpha', 100, 0, ''),
+                 ('n-d', 'delta', 200, 0, ''),
+                 ('n-c', 'gamma', 200, 0, '');
+             INSERT INTO folders (id, title, created_time, updated_time, parent_id) VALUES
+                 ('f-old', 'Old', 1, 0, '');
+             UPDATE folders SET created_time = 500 WHERE id = 'folder1';",
+        )
+        .unwrap();
+        let mut fs = fs_from_conn(
+            conn,
+            FsOptions {
+                sort,
+                ..FsOptions::default()
+            },
+        );
+        listed_names(&fs.list_directory(1).unwrap())
+    };
+
+    assert_eq!(
+        listing(SortOrder::Title),
+        vec![
+            "Documents",
+            "Old",
+            "alpha.md",
+            "beta.md",
+            "delta.md",
+            "gamma.md"
+        ]
+    );
+    // Folders first, each group by the same key; equal times fall back to id
+    assert_eq!(
+        listing(SortOrder::CreatedDesc),
+        vec![
+            "Documents",
+            "Old",
+            "beta.md",
+            "delta.md",
+            "gamma.md",
+            "alpha.md"
+        ]
+    );
+    assert_eq!(
+        listing(SortOrder::CreatedAsc),
+        vec![
+            "Old",
+            "Documents",
+            "alpha.md",
+            "gamma.md",
+            "delta.md",
+            "beta.md"
+        ]
+    );
+}
+
+#[test]
+fn test_created_sort_pages_without_skipping_ties() {
+    let conn = create_test_db().expect("Failed to create test database");
+    // More rows than a page, all sharing a timestamp, plus a duplicate title
+    conn.execute_batch(
+        "WITH RECURSIVE n(i) AS (SELECT 0 UNION ALL SELECT i + 1 FROM n WHERE i < 599)
+         INSERT INTO notes (id, parent_id, title, created_time, updated_time)
+         SELECT printf('id-%04d', i), 'folder1', printf('t%04d', i), 42, 0 FROM n;
+         INSERT INTO notes (id, parent_id, title, created_time, updated_time, user_updated_time)
+         VALUES ('id-dup', 'folder1', 't0007', 42, 0, -1);",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            sort: SortOrder::CreatedDesc,
+            // The duplicate is represented by its newest row alone
+            collision_policy: CollisionPolicy::NewestWins,
+            ..FsOptions::default()
+        },
+    );
+    let documents = fs.lookup_child(1, "Documents").unwrap();
+    let names = listed_names(&fs.list_directory(documents.ino).unwrap());
+    assert_eq!(names.len(), 601);
+    let unique: std::collections::HashSet<_> = names.iter().collect();
+    assert_eq!(unique.len(), 601);
+    // The fixture's own note was created "now", then ties descend by id
+    assert_eq!(names[0], "document.md.md");
+    assert_eq!(names[1], "t0599.md");
+    assert_eq!(names[600], "t0000.md");
+}
+
+#[test]
+fn test_owner_defaults_to_the_mounting_user() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+    let note = fs.lookup_child(1, "readme.txt.md").unwrap();
+    // SAFETY: getuid and getgid cannot fail and touch no memory
+    let mounting_user = unsafe { (libc::getuid(), libc::getgid()) };
+    assert_eq!((note.uid, note.gid), mounting_user);
+
+    let matches = cli()
+        .try_get_matches_from(["sqlite_fuse", "db", "/mnt", "--uid", "4242"])
+        .unwrap();
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            uid: matches.get_one::<u32>("uid").copied(),
+            gid: matches.get_one::<u32>("gid").copied(),
+            ..FsOptions::default()
+        },
+    );
+    let note = fs.lookup_child(1, "readme.txt.md").unwrap();
+    assert_eq!((note.uid, note.gid), (4242, mounting_user.1));
+    let folder = fs.make_directory("/", "Mine").unwrap();
+    assert_eq!(folder.uid, 4242);
+    let created = fs.create_file("/", "mine.md").unwrap();
+    assert_eq!(created.uid, 4242);
+}
+
+#[test]
+fn test_access_checks_owner_group_and_other_bits() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            uid: Some(1000),
+            gid: Some(100),
+            ..FsOptions::default()
+        },
+    );
+    let note = fs.lookup_child(1, "readme.txt.md").unwrap();
+    assert_eq!((note.uid, note.gid), (1000, 100));
+    fs.set_attr(note.ino, Some(0o444), None, None, None)
+        .unwrap();
+
+    // A read-only note: nobody but root may write it
+    assert_eq!(fs.check_access(note.ino, 1000, 100, libc::R_OK), Ok(()));
+    assert_eq!(
+        fs.check_access(note.ino, 1000, 100, libc::W_OK),
+        Err(libc::EACCES)
+    );
+    assert_eq!(
+        fs.check_access(note.ino, 2000, 2000, libc::R_OK | libc::W_OK),
+        Err(libc::EACCES)
+    );
+    assert_eq!(fs.check_access(note.ino, 0, 0, libc::W_OK), Ok(()));
+    assert_eq!(
+        fs.check_access(note.ino, 0, 0, libc::X_OK),
+        Err(libc::EACCES)
+    );
+
+    // Group and other bits apply to everyone but the owner
+    fs.set_attr(note.ino, Some(0o460), None, None, None)
+        .unwrap();
+    assert_eq!(
+        fs.check_access(note.ino, 1000, 100, libc::W_OK),
+        Err(libc::EACCES)
+    );
+    assert_eq!(fs.check_access(note.ino, 2000, 100, libc::W_OK), Ok(()));
+    assert_eq!(
+        fs.check_access(note.ino, 2000, 2000, libc::R_OK),
+        Err(libc::EACCES)
+    );
+    assert_eq!(fs.check_access(note.ino, 2000, 2000, libc::F_OK), Ok(()));
+    assert_eq!(fs.check_access(999, 1000, 100, libc::F_OK), Err(ENOENT));
+    assert_eq!(
+        fs.check_access(1, 2000, 2000, libc::R_OK | libc::X_OK),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_uid_map_sets_per_notebook_ownership() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "INSERT INTO folders (id, title, created_time, updated_time, parent_id) VALUES
+             ('shared', 'Shared', 0, 0, ''),
+             ('inner', 'Inner', 0, 0, 'shared');
+         INSERT INTO notes (id, title, created_time, updated_time, parent_id) VALUES
+             ('deep', 'deep', 0, 0, 'inner');",
+    )
+    .unwrap();
+    let matches = cli()
+        .try_get_matches_from([
+            "sqlite_fuse",
+            "db",
+            "/mnt",
+            "--uid-map",
+            "folder1=1001",
+            "--uid-map",
+            "shared=1002:300",
+        ])
+        .unwrap();
+    let uid_map = matches
+        .get_many::<(String, u32, Option<u32>)>("uid-map")
+        .unwrap()
+        .map(|(folder_id, uid, gid)| (folder_id.clone(), (*uid, *gid)))
+        .collect();
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            uid_map,
+            uid: Some(501),
+            gid: Some(20),
+            ..FsOptions::default()
+        },
+    );
+    let owner = |attr: FileAttr| (attr.uid, attr.gid);
+
+    // Mapped notebooks and their notes, keeping the global gid when none is given
+    let documents = fs.lookup_child(1, "Documents").unwrap();
+    assert_eq!(owner(documents), (1001, 20));
+    assert_eq!(
+        owner(fs.lookup_child(documents.ino, "document.md.md").unwrap()),
+        (1001, 20)
+    );
+
+    // Mappings reach nested notebooks
+    let shared = fs.lookup_child(1, "Shared").unwrap();
+    assert_eq!(owner(shared), (1002, 300));
+    let inner = fs.lookup_child(shared.ino, "Inner").unwrap();
+    assert_eq!(owner(inner), (1002, 300));
+    assert_eq!(
+        owner(fs.lookup_child(inner.ino, "deep.md").unwrap()),
+        (1002, 300)
+    );
+
+    // Everything else falls back to the global owner
+    assert_eq!(owner(fs.get_attr(1).unwrap()), (501, 20));
+    assert_eq!(
+        owner(fs.lookup_child(1, "readme.txt.md").unwrap()),
+        (501, 20)
+    );
+
+    for bad in ["folder1", "=5", "folder1=abc", "folder1=1:x"] {
+        assert!(
+            parse_uid_map_entry(bad).is_err(),
+            "{bad:?} should be rejected"
+        );
+    }
+}
+
+#[test]
+fn test_unlink_removes_the_resolved_duplicate() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "INSERT INTO notes (id, title, body, created_time, updated_time, user_updated_time, parent_id) VALUES
+             ('a1b2c3d4e5f60718293a4b5c6d7e8f90', 'Meeting', 'older', 0, 0, 100, ''),
+             ('f0e1d2c3b4a5968778695a4b3c2d1e0f', 'Meeting', 'newer', 0, 0, 200, '');",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+
+    // The plain name is the newest duplicate, the suffixed one the older
+    let newer = fs.lookup_child(1, "Meeting.md").unwrap();
+    let older = fs.lookup_child(1, "Meeting (a1b2c3).md").unwrap();
+    assert_eq!(fs.read_note(older.ino, 0).unwrap(), b"older".to_vec());
+    assert_eq!(fs.read_note(newer.ino, 0).unwrap(), b"newer".to_vec());
+    assert_eq!(
+        fs.lookup_child(1, "Meeting (000000).md").unwrap_err(),
+        ENOENT
+    );
+
+    fs.unlink_note(1, "Meeting (a1b2c3).md").unwrap();
+    let remaining: Vec<String> = fs
+        .db
+        .prepare("SELECT id FROM notes WHERE title = 'Meeting' AND deleted_time = 0")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(remaining, vec!["f0e1d2c3b4a5968778695a4b3c2d1e0f"]);
+    assert_eq!(fs.read_note(newer.ino, 0).unwrap(), b"newer".to_vec());
+}
+
+#[test]
+fn test_unlink_and_rmdir_move_rows_to_the_trash() {
+    let setup = |hard_delete| {
+        let conn = create_test_db().expect("Failed to create test database");
+        let mut fs = fs_from_conn(
+            conn,
+            FsOptions {
+                hard_delete,
+                ..FsOptions::default()
+            },
+        );
+        fs.make_directory("/", "Empty").unwrap();
+        fs.unlink_note(1, "readme.txt.md").unwrap();
+        fs.remove_directory(1, "Empty").unwrap();
+        assert_eq!(
+            listed_names(&fs.list_directory(1).unwrap()),
+            vec!["Documents"]
+        );
+        fs
+    };
+    let deleted_time = |fs: &SqliteFS, sql: &str| -> Option<i64> {
+        fs.db
+            .query_row(sql, [], |row| row.get(0))
+            .optional()
+            .unwrap()
+    };
+
+    let mut fs = setup(false);
+    let note = deleted_time(&fs, "SELECT deleted_time FROM notes WHERE id = 'note1'");
+    assert!(note.is_some_and(|time| time != 0));
+    let folder = deleted_time(
+        &fs,
+        "SELECT deleted_time FROM folders WHERE title = 'Empty'",
+    );
+    assert!(folder.is_some_and(|time| time != 0));
+    assert_eq!(fs.lookup_child(1, "readme.txt.md"), Err(ENOENT));
+
+    // A folder whose only notes are trashed counts as empty
+    let documents = fs.lookup_child(1, "Documents").unwrap();
+    fs.unlink_note(documents.ino, "document.md.md").unwrap();
+    fs.remove_directory(1, "Documents").unwrap();
+
+    let fs = setup(true);
+    assert_eq!(
+        deleted_time(&fs, "SELECT deleted_time FROM notes WHERE id = 'note1'"),
+        None
+    );
+    assert_eq!(
+        deleted_time(
+            &fs,
+            "SELECT deleted_time FROM folders WHERE title = 'Empty'"
+        ),
+        None
+    );
+}
+
+#[test]
+fn test_directory_nlink_counts_live_subfolders() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+
+    let archive = fs.make_directory("/", "Archive").unwrap();
+    assert_eq!(archive.nlink, 2);
+    fs.make_directory("/Archive", "2023").unwrap();
+    fs.make_directory("/Archive", "2024").unwrap();
+    assert_eq!(fs.get_attr(archive.ino).unwrap().nlink, 4);
+    assert_eq!(fs.lookup_child(1, "Archive").unwrap().nlink, 4);
+
+    // Documents and Archive below the root; trashed folders do not count
+    assert_eq!(fs.get_attr(1).unwrap().nlink, 4);
+    fs.remove_directory(archive.ino, "2024").unwrap();
+    assert_eq!(fs.get_attr(archive.ino).unwrap().nlink, 3);
+}
+
+#[test]
+fn test_ignore_extension_case_matches_any_md_casing() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+    assert_eq!(fs.lookup_child(1, "readme.txt.MD").unwrap_err(), ENOENT);
+
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            ignore_extension_case: true,
+            ..FsOptions::default()
+        },
+    );
+    for name in ["readme.txt.md", "readme.txt.MD", "readme.txt.Md"] {
+        let attr = fs.lookup_child(1, name).unwrap();
+        assert_eq!(
+            fs.read_note(attr.ino, 0).unwrap(),
+            b"This is a test note in root".to_vec(),
+            "{name}"
+        );
+    }
+    // The stored title keeps its casing and listings still use `.md`
+    assert_eq!(fs.lookup_child(1, "README.TXT.md").unwrap_err(), ENOENT);
+    assert!(listed_names(&fs.list_directory(1).unwrap()).contains(&"readme.txt.md".to_string()));
+}
+
+#[test]
+fn test_full_database_reports_enospc() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+    // Cap the database at its current size so any growth fails with SQLITE_FULL
+    let pages: i64 = fs
+        .db
+        .query_row("PRAGMA page_count", [], |row| row.get(0))
+        .unwrap();
+    fs.db
+        .query_row(&format!("PRAGMA max_page_count = {pages}"), [], |_| Ok(()))
+        .unwrap();
+    let readme = fs.lookup_child(1, "readme.txt.md").unwrap();
+
+    let big = vec![b'x'; 256 * 1024];
+    assert_eq!(fs.write_note(readme.ino, 0, &big), Err(libc::ENOSPC));
+    assert_eq!(
+        fs.set_attr(readme.ino, None, None, None, Some(big.len() as u64)),
+        Err(libc::ENOSPC)
+    );
+    assert_eq!(
+        fs.read_note(readme.ino, 0).unwrap(),
+        b"This is a test note in root".to_vec()
+    );
+
+    // Other failures still surface as EIO
+    fs.db.execute_batch("DROP TABLE folders").unwrap();
+    assert_eq!(fs.make_directory("/", "Archive").unwrap_err(), libc::EIO);
+}
+
+#[test]
+fn test_virtual_directories_have_fixed_inodes_and_attrs() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "UPDATE notes SET deleted_time = 1700000000 WHERE id = 'note2';
+         UPDATE folders SET updated_time = 1600000000;",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            notebooks_by_id: true,
+            include_deleted: true,
+            ..FsOptions::default()
+        },
+    );
+
+    // Whatever order they are looked up in, each keeps its reserved inode
+    let readme = fs.lookup_child(1, "readme.txt.md").unwrap();
+    assert!(readme.ino >= FIRST_DYNAMIC_INODE);
+    let trash = fs.lookup_child(1, ".trash").unwrap();
+    assert_eq!(trash.ino, VirtualDir::Trash.inode());
+    assert_eq!(trash.kind, FileType::Directory);
+    assert_eq!(trash.perm, 0o555);
+    assert_eq!(trash.mtime, UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+    assert_eq!(fs.get_attr(trash.ino).unwrap(), trash);
+
+    let notebooks = fs.lookup_child(1, ".notebooks").unwrap();
+    assert_eq!(notebooks.ino, VirtualDir::Notebooks.inode());
+    assert_eq!(
+        notebooks.mtime,
+        UNIX_EPOCH + Duration::from_secs(1_600_000_000)
+    );
+
+    assert_eq!(
+        listed_names(&fs.list_directory(1).unwrap()),
+        vec![".notebooks", ".trash", "Documents", "readme.txt.md"]
+    );
+    assert_eq!(fs.make_directory("/", ".trash").unwrap_err(), libc::EPERM);
+    assert_eq!(fs.create_file("/.trash", "x.md").unwrap_err(), libc::EPERM);
+}
+
+#[test]
+fn test_trashed_notes_are_listed_under_trash() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute(
+        "INSERT INTO folders (id, title, created_time, updated_time, parent_id, deleted_time) VALUES ('old', 'Old/Stuff', 0, 0, '', 1600000000)",
+        [],
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            include_deleted: true,
+            ..FsOptions::default()
+        },
+    );
+
+    let documents = fs.lookup_child(1, "Documents").unwrap().ino;
+    fs.unlink_note(documents, "document.md.md").unwrap();
+    fs.db
+        .execute(
+            "UPDATE notes SET deleted_time = 1700000000 WHERE id = 'note2'",
+            [],
+        )
+        .unwrap();
+
+    // Gone from its folder, listed in the trash under title and deletion time
+    assert_eq!(
+        listed_names(&fs.list_directory(documents).unwrap()),
+        Vec::<String>::new()
+    );
+    let trash = fs.lookup_child(1, ".trash").unwrap().ino;
+    let entries = fs.list_directory(trash).unwrap();
+    assert_eq!(
+        listed_names(&entries),
+        vec![
+            "Old_Stuff (2020-09-13T12:26:40Z)",
+            "document.md (2023-11-14T22:13:20Z).md"
+        ]
+    );
+    assert_eq!(entries[2].1, FileType::Directory);
+    assert_eq!(entries[3].1, FileType::RegularFile);
+
+    let note = fs
+        .lookup_child(trash, "document.md (2023-11-14T22:13:20Z).md")
+        .unwrap();
+    assert_eq!(note.perm, 0o444);
+    let text = b"This is a test note in Documents folder";
+    assert_eq!(note.size, text.len() as u64);
+    let fh = fs.open_note(note.ino, libc::O_RDONLY).unwrap();
+    assert_eq!(fs.read_note(note.ino, 0).unwrap(), text);
+    fs.release_handle(fh).unwrap();
+    assert_eq!(
+        fs.open_note(note.ino, libc::O_WRONLY).unwrap_err(),
+        libc::EROFS
+    );
+
+    let folder = fs
+        .lookup_child(trash, "Old_Stuff (2020-09-13T12:26:40Z)")
+        .unwrap();
+    assert_eq!(folder.kind, FileType::Directory);
+    assert_eq!(
+        listed_names(&fs.list_directory(folder.ino).unwrap()),
+        Vec::<String>::new()
+    );
+}
+
+#[test]
+fn test_body_read_only_allows_organizing_but_not_editing() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            body_read_only: true,
+            ..FsOptions::default()
+        },
+    );
+    let readme = fs.lookup_child(1, "readme.txt.md").unwrap();
+
+    assert_eq!(fs.write_note(readme.ino, 0, b"edited"), Err(libc::EROFS));
+    assert_eq!(
+        fs.set_attr(readme.ino, None, None, None, Some(0)),
+        Err(libc::EROFS)
+    );
+    assert_eq!(
+        fs.read_note(readme.ino, 0).unwrap(),
+        b"This is a test note in root".to_vec()
+    );
+
+    // Creating, renaming and removing still work
+    let new = fs.create_file("/", "new.md").unwrap();
+    assert_eq!(new.size, 0);
+    fs.make_directory("/", "Archive").unwrap();
+    fs.rename_entry(1, "readme.txt.md", 1, "renamed.md")
+        .unwrap();
+    fs.unlink_note(1, "new.md").unwrap();
+    fs.remove_directory(1, "Archive").unwrap();
+    assert_eq!(
+        listed_names(&fs.list_directory(1).unwrap()),
+        vec!["Documents", "renamed.md"]
+    );
+}
+
+#[test]
+fn test_folder_mtime_advances_when_children_change() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "UPDATE folders SET updated_time = 1000;
+         INSERT INTO folders (id, title, created_time, updated_time, parent_id) VALUES ('archive', 'Archive', 0, 1000, '');",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+    let documents = fs.lookup_child(1, "Documents").unwrap();
+    let archive = fs.lookup_child(1, "Archive").unwrap();
+    let old = UNIX_EPOCH + Duration::from_secs(1000);
+    let mtime = |fs: &SqliteFS, ino| fs.get_attr(ino).unwrap().mtime;
+    let reset = |fs: &SqliteFS| {
+        fs.db
+            .execute("UPDATE folders SET updated_time = 1000", [])
+            .unwrap();
+    };
+
+    // create
+    fs.create_file("/Documents", "new.md").unwrap();
+    assert!(mtime(&fs, documents.ino) > old);
+
+    // write
+    reset(&fs);
+    let new = fs.lookup_child(documents.ino, "new.md").unwrap();
+    fs.write_note(new.ino, 0, b"content").unwrap();
+    assert!(mtime(&fs, documents.ino) > old);
+
+    // rename across folders advances both sides
+    reset(&fs);
+    fs.rename_entry(documents.ino, "new.md", archive.ino, "moved.md")
+        .unwrap();
+    assert!(mtime(&fs, documents.ino) > old);
+    assert!(mtime(&fs, archive.ino) > old);
+
+    // unlink
+    reset(&fs);
+    fs.unlink_note(archive.ino, "moved.md").unwrap();
+    assert!(mtime(&fs, archive.ino) > old);
+    assert_eq!(mtime(&fs, documents.ino), old);
+}
+
+#[test]
+fn test_profiler_summarizes_latency_per_op() {
+    assert_eq!(Profiler::default().summary(), None);
+    drop(Profiler::new(false).span("lookup"));
+
+    let profiler = Profiler::new(true);
+    for ms in 1..=100 {
+        profiler.record("read", Duration::from_millis(ms));
+    }
+    for _ in 0..3 {
+        let _span = profiler.span("lookup");
+    }
+
+    let summary = profiler.summary().unwrap();
+    let lines: Vec<Vec<&str>> = summary
+        .lines()
+        .map(|line| line.split_whitespace().collect())
+        .collect();
+    assert_eq!(lines[0], vec!["op", "count", "p50", "p95", "p99", "total"]);
+    assert_eq!(lines[1][..2], ["lookup", "3"]);
+    assert_eq!(
+        lines[2],
+        vec!["read", "100", "50ms", "95ms", "99ms", "5.05s"]
+    );
+}
+
+#[test]
+fn test_plain_text_sidecar_strips_markdown() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute(
+        "UPDATE notes SET body = ?1 WHERE id = 'note1'",
+        ["# Title\n\nSome *emphasis* and a [link](https://example.com).\nNext line with `code`.\n\n- one\n- two\n\n```rust\nfn main() {}\n```\n"],
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+
+    let sidecar = fs.lookup_child(1, "readme.txt.md.txt").unwrap();
+    let expected =
+        "Title\n\nSome emphasis and a link.\nNext line with code.\n\none\ntwo\nfn main() {}\n";
+    assert_eq!(fs.read_note(sidecar.ino, 0).unwrap(), expected.as_bytes());
+    assert_eq!(sidecar.size, expected.len() as u64);
+    assert_eq!(sidecar.perm, 0o444);
+    assert_eq!(fs.open_note(sidecar.ino, libc::O_WRONLY), Err(libc::EACCES));
+    assert_eq!(fs.lookup_child(1, "Documents.txt"), Err(ENOENT));
+    assert!(
+        !listed_names(&fs.list_directory(1).unwrap()).contains(&"readme.txt.md.txt".to_string())
+    );
+}
+
+#[test]
+fn test_statfs_reports_configured_limits() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+    let st = fs.stat_fs().unwrap();
+    assert_eq!((st.bsize, st.frsize, st.namelen), (512, 512, 255));
+    assert_eq!(st.files, 3);
+    // Both fixture bodies fit in one block, plus one block for the folder
+    assert_eq!(st.blocks, 2);
+
+    // Used blocks grow with the body bytes of live notes
+    let body = "x".repeat(3000);
+    fs.db
+        .execute("UPDATE notes SET body = ?1 WHERE id = 'note1'", [&body])
+        .unwrap();
+    let body_bytes: u64 = fs
+        .db
+        .query_row(
+            "SELECT SUM(length(CAST(body AS BLOB))) FROM notes",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    let st = fs.stat_fs().unwrap();
+    assert_eq!(st.blocks, body_bytes.div_ceil(512) + 1);
+    fs.db
+        .execute("UPDATE notes SET deleted_time = 1 WHERE id = 'note1'", [])
+        .unwrap();
+    assert_eq!(fs.stat_fs().unwrap().blocks, 2);
+    fs.db
+        .execute("UPDATE notes SET deleted_time = 0 WHERE id = 'note1'", [])
+        .unwrap();
+
+    let matches = cli()
+        .try_get_matches_from([
+            "sqlite_fuse",
+            "db",
+            "/mnt",
+            "--name-max",
+            "16",
+            "--block-size",
+            "4096",
+        ])
+        .unwrap();
+    fs.options.name_max = matches.get_one::<u32>("name-max").copied();
+    fs.options.block_size = matches.get_one::<u32>("block-size").copied();
+    fs.attr = AttrConfig::from_options(&fs.options);
+    let st = fs.stat_fs().unwrap();
+    assert_eq!((st.bsize, st.frsize, st.namelen), (4096, 4096, 16));
+    assert_eq!(fs.get_attr(1).unwrap().blksize, 4096);
+
+    // The same limit drives ENAMETOOLONG
+    let long = "a-name-over-16-bytes.md";
+    assert_eq!(fs.create_file("/", long).unwrap_err(), libc::ENAMETOOLONG);
+    assert_eq!(
+        fs.make_directory("/", long).unwrap_err(),
+        libc::ENAMETOOLONG
+    );
+    assert_eq!(fs.lookup_child(1, long).unwrap_err(), libc::ENAMETOOLONG);
+    assert_eq!(
+        fs.rename_entry(1, "readme.txt.md", 1, long),
+        Err(libc::ENAMETOOLONG)
+    );
+    fs.create_file("/", "short.md").unwrap();
+}
+
+#[test]
+fn test_null_body_reads_as_empty() {
+    let conn = create_test_db().expect("Failed to create test database");
+    // CREATE TABLE ... AS drops the NOT NULL constraint, as some external writers do
+    conn.execute_batch(
+        "ALTER TABLE notes RENAME TO notes_strict;
+         CREATE TABLE notes AS SELECT * FROM notes_strict;
+         DROP TABLE notes_strict;
+         INSERT INTO notes (id, title, body, created_time, updated_time, user_updated_time, parent_id, deleted_time)
+             VALUES ('nullbody', 'empty', NULL, 0, 0, 0, '', 0);",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+
+    let note = fs.lookup_child(1, "empty.md").unwrap();
+    assert_eq!(note.size, 0);
+    assert_eq!(fs.read_note(note.ino, 0).unwrap(), Vec::<u8>::new());
+    let plain = fs.lookup_child(1, "empty.md.txt").unwrap();
+    assert_eq!(fs.read_note(plain.ino, 0).unwrap(), Vec::<u8>::new());
+
+    assert_eq!(
+        fs.set_attr(note.ino, None, None, None, Some(2))
+            .unwrap()
+            .size,
+        2
+    );
+    fs.db
+        .execute("UPDATE notes SET body = NULL WHERE id = 'nullbody'", [])
+        .unwrap();
+    assert_eq!(fs.write_note(note.ino, 3, b"x"), Ok(1));
+    assert_eq!(fs.read_note(note.ino, 0).unwrap(), b"\0\0\0x".to_vec());
+}
+
+#[test]
+fn test_strict_parent_rejects_duplicate_sibling_folders() {
+    let setup = |strict_parent| {
+        let conn = create_test_db().expect("Failed to create test database");
+        conn.execute_batch(
+            "INSERT INTO folders (id, title, created_time, updated_time, user_updated_time, parent_id) VALUES
+                 ('dup1', 'Projects', 0, 0, 1, ''),
+                 ('dup2', 'Projects', 0, 0, 2, '');
+             INSERT INTO notes (id, title, created_time, updated_time, parent_id) VALUES
+                 ('plan', 'plan', 0, 0, 'dup2');",
+        )
+        .unwrap();
+        fs_from_conn(
+            conn,
+            FsOptions {
+                strict_parent,
+                ..FsOptions::default()
+            },
+        )
+    };
+
+    // By default the newest duplicate wins
+    let mut fs = setup(false);
+    let projects = fs.lookup_child(1, "Projects").unwrap();
+    fs.lookup_child(projects.ino, "plan.md").unwrap();
+    assert_eq!(fs.get_parent_folder_id("/Projects").unwrap(), "dup2");
+
+    let mut fs = setup(true);
+    assert_eq!(fs.lookup_child(1, "Projects"), Err(libc::EIO));
+    assert!(matches!(
+        fs.get_parent_folder_id("/Projects"),
+        Err(rusqlite::Error::QueryReturnedMoreThanOneRow)
+    ));
+    assert_eq!(
+        fs.make_directory("/Projects", "sub").unwrap_err(),
+        libc::EIO
+    );
+    // Unambiguous paths resolve as usual
+    fs.lookup_child(1, "Documents").unwrap();
+    fs.lookup_child(1, "readme.txt.md").unwrap();
+}
+
+#[test]
+fn test_create_parents_builds_missing_folders() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+    assert_eq!(fs.create_file("/A/B/C", "note.md").unwrap_err(), libc::EIO);
+    let folders = |fs: &SqliteFS| -> i64 {
+        fs.db
+            .query_row("SELECT COUNT(*) FROM folders", [], |row| row.get(0))
+            .unwrap()
+    };
+    assert_eq!(folders(&fs), 1);
+
+    fs.options.create_parents = true;
+    fs.create_file("/Documents/A/B/C", "note.md").unwrap();
+    assert_eq!(folders(&fs), 4);
+    let mut ino = fs.lookup_child(1, "Documents").unwrap().ino;
+    for name in ["A", "B", "C"] {
+        let attr = fs.lookup_child(ino, name).unwrap();
+        assert_eq!(attr.kind, FileType::Directory);
+        ino = attr.ino;
+    }
+    assert_eq!(
+        listed_names(&fs.list_directory(ino).unwrap()),
+        vec!["note.md"]
+    );
+
+    // Existing folders are reused, and a failed create leaves no folders behind
+    fs.create_file("/Documents/A/B", "other.md").unwrap();
+    assert_eq!(folders(&fs), 4);
+    fs.db
+        .execute_batch(
+            "CREATE TRIGGER no_notes BEFORE INSERT ON notes BEGIN SELECT RAISE(ABORT, 'no'); END;",
+        )
+        .unwrap();
+    assert_eq!(fs.create_file("/X/Y", "note.md").unwrap_err(), libc::EIO);
+    assert_eq!(folders(&fs), 4);
+}
+
+#[test]
+fn test_pinned_inodes_are_stable_across_mounts() {
+    let matches = cli()
+        .try_get_matches_from([
+            "sqlite_fuse",
+            "db",
+            "/mnt",
+            "--pin-inode",
+            "/Documents=100",
+            "--pin-inode",
+            "/Documents/document.md.md/=101",
+        ])
+        .unwrap();
+    let pins = pinned_inodes(
+        matches
+            .get_many::<(String, u64)>("pin-inode")
+            .unwrap()
+            .cloned(),
+    )
+    .unwrap();
+
+    let mount = || {
+        let conn = create_test_db().expect("Failed to create test database");
+        let mut fs = fs_from_conn(
+            conn,
+            FsOptions {
+                pinned_inodes: pins.clone(),
+                ..FsOptions::default()
+            },
+        );
+        // Touch other paths first so dynamic numbering differs from the pins
+        fs.lookup_child(1, "readme.txt.md").unwrap();
+        let documents = fs.lookup_child(1, "Documents").unwrap();
+        let document = fs.lookup_child(documents.ino, "document.md.md").unwrap();
+        (documents.ino, document.ino)
+    };
+    assert_eq!(mount(), (100, 101));
+    assert_eq!(mount(), (100, 101));
+
+    // Pins must stay out of the reserved and dynamic ranges and not conflict
+    assert!(parse_pin_inode_entry("/Inbox=2").is_err());
+    assert!(parse_pin_inode_entry(&format!("/Inbox={FIRST_DYNAMIC_INODE}")).is_err());
+    assert!(parse_pin_inode_entry("Inbox=100").is_err());
+    assert!(pinned_inodes([("/A".to_string(), 100), ("/B".to_string(), 100)]).is_err());
+    assert!(pinned_inodes([("/A".to_string(), 100), ("/A".to_string(), 101)]).is_err());
+    assert!(pinned_inodes([("/A".to_string(), 100), ("/A".to_string(), 100)]).is_ok());
+}
+
+#[test]
+fn test_md_suffix_names_round_trip_at_create() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+    let titles = |fs: &SqliteFS| -> Vec<String> {
+        fs.db
+            .prepare("SELECT title FROM notes WHERE parent_id = '' ORDER BY title")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    };
+
+    // The trailing `.md` is the display extension, not part of the title
+    let report = fs.create_file("/", "report.md").unwrap();
+    let nested = fs.create_file("/", "report.md.md").unwrap();
+    assert_eq!(titles(&fs), vec!["readme.txt", "report", "report.md"]);
+    assert_eq!(
+        listed_names(&fs.list_directory(1).unwrap()),
+        vec!["Documents", "readme.txt.md", "report.md", "report.md.md"]
+    );
+
+    // A bare `report` is the same note as `report.md`, so it cannot be created again
+    assert_eq!(fs.create_file("/", "report").unwrap_err(), libc::EEXIST);
+    fs.write_note(report.ino, 0, b"one").unwrap();
+    fs.write_note(nested.ino, 0, b"two").unwrap();
+    let bare = fs.lookup_child(1, "report").unwrap();
+    assert_eq!(fs.read_note(bare.ino, 0).unwrap(), b"one".to_vec());
+    for (name, body) in [("report.md", "one"), ("report.md.md", "two")] {
+        let attr = fs.lookup_child(1, name).unwrap();
+        assert_eq!(
+            fs.read_note(attr.ino, 0).unwrap(),
+            body.as_bytes(),
+            "{name}"
+        );
+    }
+    assert_eq!(fs.create_file("/", "report.md").unwrap_err(), libc::EEXIST);
+
+    // A bare name in an empty slot is stored verbatim and listed with `.md`
+    fs.create_file("/", "plain").unwrap();
+    assert!(listed_names(&fs.list_directory(1).unwrap()).contains(&"plain.md".to_string()));
+}
+
+#[test]
+fn test_gpx_track_lists_geotagged_notes() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "INSERT INTO notes (id, title, created_time, updated_time, user_created_time, parent_id, latitude, longitude, altitude) VALUES
+             ('paris', 'Paris & co', 0, 5, 1700000000, 'folder1', 48.8566, 2.3522, 35),
+             ('sydney', 'Sydney', 0, 7, 1600000000, 'folder1', -33.8688, 151.2093, 0);",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            gpx: true,
+            ..FsOptions::default()
+        },
+    );
+    let documents = fs.lookup_child(1, "Documents").unwrap();
+    assert!(
+        listed_names(&fs.list_directory(documents.ino).unwrap())
+            .contains(&".track.gpx".to_string())
+    );
+    // The root has no geotagged notes, so no track
+    assert!(!listed_names(&fs.list_directory(1).unwrap()).contains(&".track.gpx".to_string()));
+    assert_eq!(fs.lookup_child(1, ".track.gpx"), Err(ENOENT));
+
+    let track = fs.lookup_child(documents.ino, ".track.gpx").unwrap();
+    let gpx = String::from_utf8(fs.read_note(track.ino, 0).unwrap()).unwrap();
+    assert_eq!(track.size, gpx.len() as u64);
+    assert_eq!(track.perm, 0o444);
+    assert_eq!(track.mtime, UNIX_EPOCH + Duration::from_secs(7));
+    assert_eq!(gpx.matches("<wpt ").count(), 2);
+    let sydney = gpx.find("lat=\"-33.8688\" lon=\"151.2093\"").unwrap();
+    let paris = gpx.find("lat=\"48.8566\" lon=\"2.3522\"").unwrap();
+    assert!(sydney < paris, "waypoints are oldest first");
+    assert!(gpx.contains("<ele>35</ele>"));
+    assert!(gpx.contains("<time>2023-11-14T22:13:20Z</time>"));
+    assert!(gpx.contains("<name>Paris &amp; co</name>"));
+    assert_eq!(fs.open_note(track.ino, libc::O_RDWR), Err(libc::EACCES));
+}
+
+#[test]
+fn test_encoding_round_trips_latin1_body() {
+    let conn = create_test_db().expect("Failed to create test database");
+    // "Café" in Latin-1: é is the single byte 0xE9
+    conn.execute(
+        "INSERT INTO notes (id, title, body, created_time, updated_time, parent_id) VALUES ('latin', 'menu', CAST(X'436166E9' AS TEXT), 0, 0, '')",
+        [],
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            encoding: Some(encoding_rs::WINDOWS_1252),
+            ..FsOptions::default()
+        },
+    );
+
+    let note = fs.lookup_child(1, "menu.md").unwrap();
+    assert_eq!(note.size, "Café".len() as u64);
+    assert_eq!(fs.read_note(note.ino, 0).unwrap(), "Café".as_bytes());
+
+    fs.write_note(note.ino, 0, "Crème brûlée".as_bytes())
+        .unwrap();
+    let stored: Vec<u8> = fs
+        .db
+        .query_row(
+            "SELECT CAST(body AS BLOB) FROM notes WHERE id = 'latin'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(stored, b"Cr\xe8me br\xfbl\xe9e");
+    assert_eq!(
+        fs.read_note(note.ino, 0).unwrap(),
+        "Crème brûlée".as_bytes()
+    );
+    assert_eq!(
+        fs.get_attr(note.ino).unwrap().size,
+        "Crème brûlée".len() as u64
+    );
+
+    // Characters Latin-1 cannot hold are refused rather than mangled
+    assert_eq!(
+        fs.write_note(note.ino, 0, "日本".as_bytes()),
+        Err(libc::EILSEQ)
+    );
+    assert_eq!(
+        fs.read_note(note.ino, 0).unwrap(),
+        "Crème brûlée".as_bytes()
+    );
+}
+
+#[test]
+fn test_readdir_skips_malformed_row() {
+    logged_warnings();
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "INSERT INTO notes (id, title, created_time, updated_time, parent_id) VALUES
+             ('blobtitle', X'00ff', 0, 0, ''),
+             ('zebra', 'zebra', 0, 0, '');",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+
+    let names = listed_names(&fs.list_directory(1).unwrap());
+    assert!(names.contains(&"readme.txt.md".to_string()));
+    assert!(names.contains(&"zebra.md".to_string()));
+    assert!(names.contains(&"Documents".to_string()));
+    assert_eq!(names.len(), 3);
+    assert!(
+        logged_warnings()
+            .iter()
+            .any(|w| w.starts_with("skipping malformed row in /") && w.contains("Blob"))
+    );
+}
+
+#[test]
+fn test_attachments_lists_note_resources() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "CREATE TABLE resources (id TEXT PRIMARY KEY, title TEXT NOT NULL DEFAULT '', mime TEXT NOT NULL, filename TEXT NOT NULL DEFAULT '', file_extension TEXT NOT NULL DEFAULT '', size INT NOT NULL DEFAULT -1);
+         INSERT INTO resources (id, title, mime, file_extension) VALUES
+             ('0123456789abcdef0123456789abcdef', 'photo.png', 'image/png', 'png'),
+             ('fedcba9876543210fedcba9876543210', 'report', 'application/pdf', 'pdf'),
+             ('22222222222222222222222222222222', 'other', 'text/plain', 'txt');
+         INSERT INTO notes (id, title, body, created_time, updated_time, parent_id) VALUES
+             ('trip', 'trip', 'See ![](:/0123456789abcdef0123456789abcdef) and [r](:/fedcba9876543210fedcba9876543210)', 0, 0, '');",
+    )
+    .unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("0123456789abcdef0123456789abcdef.png"),
+        b"\x89PNG",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path().join("fedcba9876543210fedcba9876543210.pdf"),
+        b"%PDF-1.7",
+    )
+    .unwrap();
+    let options = FsOptions {
+        resource_dir: dir.path().to_path_buf(),
+        ..FsOptions::default()
+    };
+    let mut fs = fs_from_conn(conn, options);
+
+    // Without note_resources the body's links are parsed
+    let attachments = fs.lookup_child(1, "trip.md.attachments").unwrap();
+    assert_eq!(attachments.kind, FileType::Directory);
+    let mut names = listed_names(&fs.list_directory(attachments.ino).unwrap());
+    names.sort();
+    assert_eq!(names, vec!["photo.png", "report.pdf"]);
+
+    let pdf = fs.lookup_child(attachments.ino, "report.pdf").unwrap();
+    assert_eq!(pdf.size, 8);
+    assert_eq!(pdf.perm, 0o444);
+    assert_eq!(fs.read_note(pdf.ino, 0).unwrap(), b"%PDF-1.7");
+    assert_eq!(fs.read_note(pdf.ino, 5).unwrap(), b"1.7");
+    assert_eq!(fs.open_note(pdf.ino, libc::O_WRONLY), Err(libc::EACCES));
+
+    // note_resources is authoritative once present, whatever the body says
+    fs.db
+        .execute_batch(
+            "CREATE TABLE note_resources (id INTEGER PRIMARY KEY, note_id TEXT NOT NULL, resource_id TEXT NOT NULL, is_associated INT NOT NULL, last_seen_time INT NOT NULL);
+             INSERT INTO note_resources (note_id, resource_id, is_associated, last_seen_time) VALUES
+                 ('trip', '0123456789abcdef0123456789abcdef', 1, 0),
+                 ('trip', '22222222222222222222222222222222', 1, 0),
+                 ('trip', 'fedcba9876543210fedcba9876543210', 0, 0);",
+        )
+        .unwrap();
+    assert_eq!(
+        listed_names(&fs.list_directory(attachments.ino).unwrap()),
+        vec!["other.txt", "photo.png"]
+    );
+    assert_eq!(fs.lookup_child(attachments.ino, "report.pdf"), Err(ENOENT));
+}
+
+#[test]
+fn test_resources_directory_streams_resource_files() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "CREATE TABLE resources (id TEXT PRIMARY KEY, title TEXT NOT NULL DEFAULT '', mime TEXT NOT NULL, filename TEXT NOT NULL DEFAULT '', created_time INT NOT NULL, updated_time INT NOT NULL, file_extension TEXT NOT NULL DEFAULT '', size INT NOT NULL DEFAULT -1);
+         INSERT INTO resources (id, title, mime, created_time, updated_time, file_extension, size) VALUES
+             ('0123456789abcdef0123456789abcdef', 'photo', 'image/png', 1, 1700000000, 'png', 6),
+             ('fedcba9876543210fedcba9876543210', '', 'application/pdf', 1, 1, 'pdf', -1);",
+    )
+    .unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("0123456789abcdef0123456789abcdef.png"),
+        b"\x89PNG\r\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path().join("fedcba9876543210fedcba9876543210.pdf"),
+        b"%PDF-1.7",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            resources: true,
+            resource_dir: dir.path().to_path_buf(),
+            ..FsOptions::default()
+        },
+    );
+
+    let resources = fs.lookup_child(1, ".resources").unwrap();
+    assert_eq!(resources.ino, VirtualDir::Resources.inode());
+    assert_eq!(resources.perm, 0o555);
+    assert_eq!(
+        listed_names(&fs.list_directory(resources.ino).unwrap()),
+        vec!["fedcba9876543210fedcba9876543210.pdf", "photo.png"]
+    );
+
+    // The size comes from the row, or from the file while Joplin has none
+    let photo = fs.lookup_child(resources.ino, "photo.png").unwrap();
+    assert_eq!(
+        (photo.kind, photo.perm, photo.size),
+        (FileType::RegularFile, 0o444, 6)
+    );
+    assert_eq!(photo.mtime, UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+    let pdf = fs
+        .lookup_child(resources.ino, "fedcba9876543210fedcba9876543210.pdf")
+        .unwrap();
+    assert_eq!(pdf.size, 8);
+
+    let fh = fs.open_note(photo.ino, libc::O_RDONLY).unwrap();
+    assert_eq!(
+        fs.read_range(photo.ino, fh, 0, 4096).unwrap(),
+        b"\x89PNG\r\n"
+    );
+    assert_eq!(fs.read_range(photo.ino, fh, 4, 4096).unwrap(), b"\r\n");
+    fs.release_handle(fh).unwrap();
+    assert_eq!(fs.read_note(pdf.ino, 0).unwrap(), b"%PDF-1.7");
+
+    assert_eq!(
+        fs.open_note(photo.ino, libc::O_WRONLY).unwrap_err(),
+        libc::EACCES
+    );
+    assert_eq!(
+        fs.create_file("/.resources", "new.png").unwrap_err(),
+        libc::EPERM
+    );
+}
+
+#[test]
+fn test_metadata_sidecar_formats_share_field_set() {
+    let mut field_sets = Vec::new();
+    for format in [
+        MetadataFormat::Json,
+        MetadataFormat::Toml,
+        MetadataFormat::Yaml,
+    ] {
+        let conn = create_test_db().expect("Failed to create test database");
+        conn.execute(
+            "UPDATE notes SET author = 'Ann \"A\": b', latitude = 1.5, is_todo = 1 WHERE id = 'note1'",
+            [],
+        )
+        .unwrap();
+        let mut fs = fs_from_conn(
+            conn,
+            FsOptions {
+                metadata_format: format,
+                ..FsOptions::default()
+            },
+        );
+        let name = format!("readme.txt.md{}", format.suffix());
+        let sidecar = fs.lookup_child(1, &name).unwrap();
+        let text = String::from_utf8(fs.read_note(sidecar.ino, 0).unwrap()).unwrap();
+        assert_eq!(sidecar.size, text.len() as u64, "{format:?} size");
+        assert_eq!(sidecar.perm, 0o444);
+
+        // Parse back and normalize each format to (field, display value)
+        let fields: Vec<(String, String)> = match format {
+            MetadataFormat::Json => {
+                let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+                value
+                    .as_object()
+                    .unwrap()
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.as_str().map_or(v.to_string(), String::from)))
+                    .collect()
+            }
+            MetadataFormat::Toml => {
+                let value: toml::Table = text.parse().unwrap();
+                value
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.as_str().map_or(v.to_string(), String::from)))
+                    .collect()
+            }
+            MetadataFormat::Yaml => {
+                let docs = yaml_rust2::YamlLoader::load_from_str(&text).unwrap();
+                docs[0]
+                    .as_hash()
+                    .unwrap()
+                    .iter()
+                    .map(|(k, v)| {
+                        let value = match v {
+                            yaml_rust2::Yaml::String(s) => s.clone(),
+                            yaml_rust2::Yaml::Integer(n) => n.to_string(),
+                            yaml_rust2::Yaml::Real(x) => x.clone(),
+                            other => panic!("unexpected YAML value {other:?}"),
+                        };
+                        (k.as_str().unwrap().to_string(), value)
+                    })
+                    .collect()
+            }
+        };
+        let mut fields = fields;
+        fields.sort();
+        assert!(!fields.iter().any(|(k, _)| k == "body"));
+        assert!(fields.contains(&("author".to_string(), "Ann \"A\": b".to_string())));
+        assert!(fields.contains(&("title".to_string(), "readme.txt".to_string())));
+        assert!(fields.contains(&("latitude".to_string(), "1.5".to_string())));
+        field_sets.push(fields);
+    }
+    assert_eq!(field_sets[0], field_sets[1]);
+    assert_eq!(field_sets[0], field_sets[2]);
+}
+
+#[test]
+fn test_concurrent_create_inserts_one_note() {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("joplin.sqlite");
+    create_test_db()
+        .unwrap()
+        .execute("VACUUM INTO ?1", [db_path.to_str().unwrap()])
+        .unwrap();
+
+    for (name, flags) in [("shared.md", 0), ("exclusive.md", libc::O_EXCL)] {
+        let barrier = Arc::new(std::sync::Barrier::new(2));
+        let racers: Vec<_> = (0..2)
+            .map(|_| {
+                // Separate connections, as two mounting processes would have
+                let mut fs =
+                    SqliteFS::new(db_path.to_str().unwrap(), FsOptions::default()).unwrap();
+                let barrier = Arc::clone(&barrier);
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    fs.create_or_open_file("/Documents", name, flags, 0o644)
+                        .map(|attr| attr.ino)
+                })
+            })
+            .collect();
+        let results: Vec<_> = racers
+            .into_iter()
+            .map(|racer| racer.join().unwrap())
+            .collect();
+
+        let conn = Connection::open(&db_path).unwrap();
+        let title = &name[..name.len() - 3];
+        let rows: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM notes WHERE title = ?1",
+                [title],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(rows, 1, "{name}: {results:?}");
+        let failures: Vec<_> = results.iter().filter_map(|r| r.err()).collect();
+        if flags & libc::O_EXCL != 0 {
+            assert_eq!(failures, vec![libc::EEXIST], "{name}: {results:?}");
+        } else {
+            assert!(failures.is_empty(), "{name}: {results:?}");
+        }
+    }
+}
+
+#[test]
+fn test_duplicate_titles_are_all_listed_by_default() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "INSERT INTO notes (id, title, body, created_time, updated_time, user_updated_time, parent_id) VALUES
+             ('a1b2c3d4', 'Meeting', 'monday', 0, 0, 10, ''),
+             ('e5f6a7b8', 'Meeting', 'tuesday', 0, 0, 20, '');
+         INSERT INTO folders (id, title, created_time, updated_time, user_updated_time, parent_id) VALUES
+             ('c0ffee01', 'Projects', 0, 0, 1, ''),
+             ('d00dad02', 'Projects', 0, 0, 2, '');
+         INSERT INTO notes (id, title, body, created_time, updated_time, parent_id) VALUES
+             ('plan', 'plan', 'old plan', 0, 0, 'c0ffee01');",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+
+    let names = listed_names(&fs.list_directory(1).unwrap());
+    for name in [
+        "Meeting.md",
+        "Meeting (a1b2c3).md",
+        "Projects",
+        "Projects (c0ffee)",
+    ] {
+        assert!(
+            names.contains(&name.to_string()),
+            "{name} missing from {names:?}"
+        );
+    }
+    let newer = fs.lookup_child(1, "Meeting.md").unwrap();
+    let older = fs.lookup_child(1, "Meeting (a1b2c3).md").unwrap();
+    assert_ne!(newer.ino, older.ino);
+    assert_eq!(fs.read_note(newer.ino, 0).unwrap(), b"tuesday");
+    assert_eq!(fs.read_note(older.ino, 0).unwrap(), b"monday");
+
+    // The older notebook and the notes inside it are reachable too
+    let projects = fs.lookup_child(1, "Projects (c0ffee)").unwrap();
+    assert_eq!(
+        listed_names(&fs.list_directory(projects.ino).unwrap()),
+        vec!["plan.md"]
+    );
+    let plan = fs.lookup_child(projects.ino, "plan.md").unwrap();
+    assert_eq!(fs.read_note(plan.ino, 0).unwrap(), b"old plan");
+    assert_eq!(
+        fs.get_parent_folder_id("/Projects (c0ffee)").unwrap(),
+        "c0ffee01"
+    );
+    assert_eq!(fs.get_parent_folder_id("/Projects").unwrap(), "d00dad02");
+}
+
+#[test]
+fn test_rmdir_removes_the_resolved_duplicate() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "INSERT INTO folders (id, title, created_time, updated_time, user_updated_time, parent_id) VALUES
+             ('aaaaaa11', 'Dup', 0, 0, 1, ''),
+             ('bbbbbb22', 'Dup', 0, 0, 2, '');",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+    let live = |fs: &SqliteFS| -> Vec<String> {
+        fs.db
+            .prepare("SELECT id FROM folders WHERE title = 'Dup' AND deleted_time = 0 ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    };
+
+    // The older duplicate goes, though the plain name means the newer one
+    fs.remove_directory(1, "Dup (aaaaaa)").unwrap();
+    assert_eq!(live(&fs), ["bbbbbb22"]);
+    assert_eq!(fs.remove_directory(1, "Dup (aaaaaa)"), Err(ENOENT));
+    assert_eq!(fs.remove_directory(1, "readme.txt.md"), Err(ENOENT));
+    fs.remove_directory(1, "Dup").unwrap();
+    assert!(live(&fs).is_empty());
+}
+
+#[test]
+fn test_symlink_is_a_note_holding_its_target() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+
+    let link = fs
+        .create_symlink("/", "shortcut", b"/Documents/document.md")
+        .unwrap();
+    assert_eq!(link.kind, FileType::Symlink);
+    assert_eq!(link.size, "/Documents/document.md".len() as u64);
+    assert_eq!(fs.read_link(link.ino).unwrap(), b"/Documents/document.md");
+    assert_eq!(
+        fs.create_symlink("/", "shortcut", b"elsewhere"),
+        Err(libc::EEXIST)
+    );
+
+    let entries = fs.list_directory(1).unwrap();
+    assert!(entries.contains(&(link.ino, FileType::Symlink, "shortcut".to_string())));
+    assert_eq!(
+        fs.lookup_child(1, "shortcut").unwrap().kind,
+        FileType::Symlink
+    );
+    assert_eq!(fs.lookup_child(1, "shortcut.md"), Err(ENOENT));
+    // An ordinary note is not a link
+    let readme = fs.lookup_child(1, "readme.txt.md").unwrap();
+    assert_eq!(fs.read_link(readme.ino), Err(libc::EINVAL));
+
+    // Renaming keeps the whole name as the title, extension and all
+    fs.rename_entry(1, "shortcut", 1, "doc.md").unwrap();
+    let renamed = fs.lookup_child(1, "doc.md").unwrap();
+    assert_eq!(renamed.kind, FileType::Symlink);
+    assert_eq!(
+        fs.read_link(renamed.ino).unwrap(),
+        b"/Documents/document.md"
+    );
+}
+
+#[test]
+fn test_title_collision_policies() {
+    let setup = |policy| {
+        let conn = create_test_db().expect("Failed to create test database");
+        conn.execute_batch(
+            "INSERT INTO notes (id, title, body, created_time, updated_time, user_updated_time, parent_id) VALUES
+                 ('aaaaaa01', 'dup', 'older', 0, 0, 10, 'folder1'),
+                 ('bbbbbb02', 'dup', 'newer', 0, 0, 20, 'folder1'),
+                 ('cccccc03', 'clash', 'hidden', 0, 0, 0, 'folder1');
+             INSERT INTO folders (id, title, created_time, updated_time, parent_id) VALUES
+                 ('clashdir', 'clash.md', 0, 0, 'folder1');",
+        )
+        .unwrap();
+        let mut fs = fs_from_conn(
+            conn,
+            FsOptions {
+                collision_policy: policy,
+                ..FsOptions::default()
+            },
+        );
+        let documents = fs.lookup_child(1, "Documents").unwrap().ino;
+        let mut names = listed_names(&fs.list_directory(documents).unwrap());
+        names.sort();
+        (fs, documents, names)
+    };
+    let note_id = |fs: &SqliteFS, name: &str| match fs.resolve_path(&format!("/Documents/{name}")) {
+        Some((EntryKind::Note, id)) => id,
+        other => panic!("{name} resolved to {other:?}"),
+    };
+
+    let (mut fs, documents, names) = setup(CollisionPolicy::NewestWins);
+    assert_eq!(names, vec!["clash.md", "document.md.md", "dup.md"]);
+    assert_eq!(
+        fs.lookup_child(documents, "clash.md").unwrap().kind,
+        FileType::Directory
+    );
+    assert_eq!(note_id(&fs, "dup.md"), "bbbbbb02");
+
+    let (mut fs, documents, names) = setup(CollisionPolicy::SuffixId);
+    assert_eq!(
+        names,
+        vec![
+            "clash (cccccc).md",
+            "clash.md",
+            "document.md.md",
+            "dup (aaaaaa).md",
+            "dup.md"
+        ]
+    );
+    for name in &names {
+        fs.lookup_child(documents, name).unwrap();
+    }
+    assert_eq!(note_id(&fs, "dup (aaaaaa).md"), "aaaaaa01");
+    assert_eq!(note_id(&fs, "clash (cccccc).md"), "cccccc03");
+    let older = fs.lookup_child(documents, "dup (aaaaaa).md").unwrap();
+    assert_eq!(fs.read_note(older.ino, 0).unwrap(), b"older");
+    // Renaming a suffixed duplicate moves that row only
+    fs.rename_entry(documents, "dup (aaaaaa).md", documents, "older.md")
+        .unwrap();
+    assert_eq!(note_id(&fs, "older.md"), "aaaaaa01");
+    assert_eq!(note_id(&fs, "dup.md"), "bbbbbb02");
+
+    let (mut fs, documents, names) = setup(CollisionPolicy::SuffixCount);
+    assert_eq!(
+        names,
+        vec![
+            "clash (2).md",
+            "clash.md",
+            "document.md.md",
+            "dup (2).md",
+            "dup.md"
+        ]
+    );
+    for name in &names {
+        fs.lookup_child(documents, name).unwrap();
+    }
+    assert_eq!(note_id(&fs, "dup (2).md"), "aaaaaa01");
+    assert_eq!(note_id(&fs, "clash (2).md"), "cccccc03");
+    let older = fs.lookup_child(documents, "dup (2).md").unwrap();
+    fs.write_note(older.ino, 0, b"edited").unwrap();
+    let body: String = fs
+        .db
+        .query_row("SELECT body FROM notes WHERE id = 'aaaaaa01'", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(body, "edited");
+    assert_eq!(fs.lookup_child(documents, "dup (3).md"), Err(ENOENT));
+
+    let (mut fs, documents, names) = setup(CollisionPolicy::Error);
+    assert_eq!(names, vec!["clash.md", "document.md.md", "dup.md"]);
+    assert_eq!(fs.lookup_child(documents, "dup.md"), Err(libc::EIO));
+    assert_eq!(fs.lookup_child(documents, "clash.md"), Err(libc::EIO));
+    fs.lookup_child(documents, "document.md.md").unwrap();
+}
+
+#[test]
+fn test_fsync_commits_and_checkpoints_writes() {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("joplin.sqlite");
+    create_test_db()
+        .unwrap()
+        .execute("VACUUM INTO ?1", [db_path.to_str().unwrap()])
+        .unwrap();
+    Connection::open(&db_path)
+        .unwrap()
+        .query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))
+        .unwrap();
+
+    let mut fs = SqliteFS::new(
+        db_path.to_str().unwrap(),
+        FsOptions {
+            write_debounce: Some(Duration::from_secs(60)),
+            ..FsOptions::default()
+        },
+    )
+    .unwrap();
+    fs.db
+        .execute_batch("PRAGMA wal_autocheckpoint = 0")
+        .unwrap();
+    let note = fs.lookup_child(1, "readme.txt.md").unwrap();
+    let fh = fs
+        .open_note(note.ino, libc::O_RDWR | libc::O_TRUNC)
+        .unwrap();
+    fs.write_handle(fh, note.ino, 0, b"first").unwrap();
+    // Held back by the debounce until something commits it
+    fs.write_handle(fh, note.ino, 5, b" and second").unwrap();
+
+    fs.sync_database(Some(fh)).unwrap();
+    let wal = std::fs::metadata(dir.path().join("joplin.sqlite-wal")).unwrap();
+    assert!(wal.len() > 0);
+    let body: String = Connection::open(&db_path)
+        .unwrap()
+        .query_row("SELECT body FROM notes WHERE id = 'note1'", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(body, "first and second");
+    // The checkpoint copied every frame into the database file
+    let (busy, logged, checkpointed): (i64, i64, i64) = fs
+        .db
+        .query_row("PRAGMA wal_checkpoint(PASSIVE)", [], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })
+        .unwrap();
+    assert_eq!((busy, logged), (0, checkpointed));
+
+    fs.sync_database(None).unwrap();
+    fs.release_handle(fh).unwrap();
+}
+
+#[test]
+fn test_fallocate_pads_the_body_with_null_bytes() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+    let note = fs.create_file("/", "draft.md").unwrap();
+    let body_len = |fs: &SqliteFS| -> i64 {
+        fs.db
+            .query_row(
+                "SELECT length(CAST(body AS BLOB)) FROM notes WHERE title = 'draft'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap()
+    };
+
+    fs.allocate_note(note.ino, 0, 1024, 0).unwrap();
+    assert_eq!(body_len(&fs), 1024);
+    assert_eq!(fs.get_attr(note.ino).unwrap().size, 1024);
+    assert!(fs.read_note(note.ino, 0).unwrap().iter().all(|&b| b == 0));
+
+    // Covered regions and FALLOC_FL_KEEP_SIZE leave the body alone
+    fs.allocate_note(note.ino, 512, 512, 0).unwrap();
+    fs.allocate_note(note.ino, 0, 4096, libc::FALLOC_FL_KEEP_SIZE)
+        .unwrap();
+    assert_eq!(body_len(&fs), 1024);
+
+    let punch = libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE;
+    assert_eq!(
+        fs.allocate_note(note.ino, 0, 10, punch).unwrap_err(),
+        libc::EOPNOTSUPP
+    );
+    assert_eq!(
+        fs.allocate_note(note.ino, 0, 0, 0).unwrap_err(),
+        libc::EINVAL
+    );
+}
+
+#[test]
+fn test_maintenance_truncates_wal_and_purges_trash() {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("joplin.sqlite");
+    let wal_path = dir.path().join("joplin.sqlite-wal");
+    create_test_db()
+        .unwrap()
+        .execute("VACUUM INTO ?1", [db_path.to_str().unwrap()])
+        .unwrap();
+    let conn = Connection::open(&db_path).unwrap();
+    let mode: String = conn
+        .query_row("PRAGMA journal_mode = WAL", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(mode, "wal");
+    conn.execute_batch(
+        "PRAGMA wal_autocheckpoint = 0;
+         INSERT INTO notes (id, title, created_time, updated_time, parent_id, deleted_time) VALUES
+             ('old', 'old', 0, 0, '', 1000),
+             ('recent', 'recent', 0, 0, '', 9000);",
+    )
+    .unwrap();
+
+    let mut fs = SqliteFS::new(db_path.to_str().unwrap(), FsOptions::default()).unwrap();
+    fs.db
+        .execute_batch("PRAGMA wal_autocheckpoint = 0")
+        .unwrap();
+    let note = fs.create_file("/", "batch.md").unwrap();
+    for chunk in 0..50 {
+        fs.write_note(note.ino, chunk * 100, &[b'x'; 100]).unwrap();
+    }
+    assert!(std::fs::metadata(&wal_path).unwrap().len() > 0);
+
+    let maintenance = Connection::open(&db_path).unwrap();
+    maintenance.busy_timeout(Duration::ZERO).unwrap();
+
+    // A write in progress elsewhere makes the round skip instead of waiting
+    fs.db.execute_batch("BEGIN IMMEDIATE").unwrap();
+    let skipped = run_maintenance(&maintenance, Some(Duration::from_secs(3600)), 10_000).unwrap();
+    assert!(skipped.busy);
+    assert_eq!(skipped.purged_notes, 0);
+    fs.db.execute_batch("COMMIT").unwrap();
+
+    let report = run_maintenance(&maintenance, Some(Duration::from_secs(3600)), 10_000).unwrap();
+    assert!(!report.busy);
+    assert!(report.wal_bytes_freed > 0);
+    assert_eq!(report.purged_notes, 1);
+    assert_eq!(std::fs::metadata(&wal_path).unwrap().len(), 0);
+    let left: Vec<String> = conn
+        .prepare("SELECT id FROM notes WHERE deleted_time != 0")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<rusqlite::Result<_>>()
+        .unwrap();
+    assert_eq!(left, vec!["recent"]);
+    assert_eq!(fs.read_note(note.ino, 0).unwrap().len(), 5000);
+}
+
+#[test]
+fn test_gzip_sidecar_decompresses_to_body() {
+    use std::io::Read;
+
+    let gunzip = |data: &[u8]| {
+        let mut out = Vec::new();
+        flate2::read::GzDecoder::new(data)
+            .read_to_end(&mut out)
+            .unwrap();
+        out
+    };
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+    let note = fs.lookup_child(1, "readme.txt.md").unwrap();
+
+    let gz = fs.lookup_child(1, "readme.txt.md.gz").unwrap();
+    assert_eq!(gz.perm, 0o444);
+    let compressed = fs.read_note(gz.ino, 0).unwrap();
+    assert_eq!(gz.size, compressed.len() as u64);
+    assert_eq!(gunzip(&compressed), b"This is a test note in root");
+    assert!(fs.gzip_cache.lock().unwrap().contains_key("note1"));
+    assert_eq!(fs.read_note(gz.ino, 0).unwrap(), compressed);
+    assert_eq!(fs.read_note(gz.ino, 10).unwrap(), compressed[10..]);
+
+    // A new version is recompressed even within the same second
+    let rewritten = b"This is a rewritten note in the root folder";
+    fs.write_note(note.ino, 0, rewritten).unwrap();
+    let recompressed = fs.read_note(gz.ino, 0).unwrap();
+    assert_eq!(gunzip(&recompressed), rewritten);
+    assert_eq!(fs.get_attr(gz.ino).unwrap().size, recompressed.len() as u64);
+    assert_eq!(fs.open_note(gz.ino, libc::O_WRONLY), Err(libc::EACCES));
+}
+
+#[test]
+fn test_hide_icon_omits_matching_notebooks() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "INSERT INTO folders (id, title, created_time, updated_time, parent_id, icon) VALUES
+             ('system', 'System', 0, 0, '', '{\"emoji\":\"🗄️\",\"name\":\"file cabinet\",\"type\":1}'),
+             ('travel', 'Travel', 0, 0, '', '{\"emoji\":\"✈️\",\"name\":\"airplane\",\"type\":1}'),
+             ('bare', 'Bare', 0, 0, '', '🗄');
+         INSERT INTO notes (id, title, created_time, updated_time, parent_id) VALUES
+             ('secret', 'secret', 0, 0, 'system');",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            hide_icon: Some("🗄".to_string()),
+            notebooks_by_id: true,
+            ..FsOptions::default()
+        },
+    );
+
+    let names = listed_names(&fs.list_directory(1).unwrap());
+    assert!(names.contains(&"Travel".to_string()));
+    assert!(names.contains(&"Documents".to_string()));
+    assert!(!names.contains(&"System".to_string()));
+    assert!(!names.contains(&"Bare".to_string()));
+    assert_eq!(fs.lookup_child(1, "System"), Err(ENOENT));
+    assert!(fs.resolve_path("/System/secret.md").is_none());
+    fs.lookup_child(1, "Travel").unwrap();
+
+    let notebooks = fs.lookup_child(1, ".notebooks").unwrap();
+    let ids = listed_names(&fs.list_directory(notebooks.ino).unwrap());
+    assert!(ids.contains(&"travel".to_string()));
+    assert!(!ids.contains(&"system".to_string()));
+    assert_eq!(fs.lookup_child(notebooks.ino, "system"), Err(ENOENT));
+}
+
+#[test]
+fn test_join_path() {
+    assert_eq!(join_path("/", "Documents"), "/Documents");
+    assert_eq!(join_path("", "Documents"), "/Documents");
+    assert_eq!(join_path("/Documents", "note.md"), "/Documents/note.md");
+    assert_eq!(join_path("/Documents/", "note.md"), "/Documents/note.md");
+    assert_eq!(join_path("/a/b/c", "d e (1).md"), "/a/b/c/d e (1).md");
+}
+
+#[test]
+fn test_split_path() {
+    assert_eq!(split_path("/"), ("/", ""));
+    assert_eq!(split_path("/Documents"), ("/", "Documents"));
+    assert_eq!(split_path("/Documents/"), ("/", "Documents"));
+    assert_eq!(split_path("/Documents/note.md"), ("/Documents", "note.md"));
+    assert_eq!(split_path("/a/b/c/d e (1).md"), ("/a/b/c", "d e (1).md"));
+    for path in ["/Documents", "/a/b/c/d.md"] {
+        let (parent, name) = split_path(path);
+        assert_eq!(join_path(parent, name), path);
+    }
+}
+
+#[test]
+fn test_read_only_database_refuses_writes_with_erofs() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("joplin.sqlite");
+    create_test_db()
+        .unwrap()
+        .execute("VACUUM INTO ?1", [path.to_str().unwrap()])
+        .unwrap();
+    let conn =
+        Connection::open_with_flags(&path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY).unwrap();
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+
+    let note = fs.lookup_child(1, "readme.txt.md").unwrap();
+    assert_eq!(
+        fs.read_note(note.ino, 0).unwrap(),
+        b"This is a test note in root"
+    );
+    let fh = fs.open_note(note.ino, libc::O_RDONLY).unwrap();
+    fs.release_handle(fh).unwrap();
+
+    assert_eq!(fs.open_note(note.ino, libc::O_RDWR), Err(libc::EROFS));
+    assert_eq!(fs.write_note(note.ino, 0, b"changed"), Err(libc::EROFS));
+    assert_eq!(
+        fs.set_attr(note.ino, None, None, None, Some(0)).map(|_| ()),
+        Err(libc::EROFS)
+    );
+    assert_eq!(fs.create_file("/", "new.md").map(|_| ()), Err(libc::EROFS));
+    assert_eq!(fs.make_directory("/", "New").map(|_| ()), Err(libc::EROFS));
+    assert_eq!(
+        fs.rename_entry(1, "readme.txt.md", 1, "renamed.md"),
+        Err(libc::EROFS)
+    );
+    assert_eq!(fs.unlink_note(1, "readme.txt.md"), Err(libc::EROFS));
+    assert_eq!(fs.remove_directory(1, "Documents"), Err(libc::EROFS));
+    assert_eq!(
+        fs.read_note(note.ino, 0).unwrap(),
+        b"This is a test note in root"
+    );
+}
+
+#[test]
+fn test_read_only_flag_refuses_changes_on_a_writable_database() {
+    let options = || FsOptions {
+        read_only: true,
+        ..FsOptions::default()
+    };
+    let mut fs = fs_from_conn(create_test_db().unwrap(), options());
+    // Not even the lookup indexes are created
+    let indexes: i64 = fs
+        .db
+        .query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'index' AND name LIKE 'idx_%'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(indexes, 0);
+
+    let note = fs.lookup_child(1, "readme.txt.md").unwrap();
+    assert_eq!(
+        fs.read_note(note.ino, 0).unwrap(),
+        b"This is a test note in root"
+    );
+    assert_eq!(
+        listed_names(&fs.list_directory(1).unwrap()),
+        listed_names(
+            &fs_from_conn(create_test_db().unwrap(), FsOptions::default())
+                .list_directory(1)
+                .unwrap()
+        )
+    );
+
+    assert_eq!(fs.create_file("/", "new.md").map(|_| ()), Err(libc::EROFS));
+    assert_eq!(fs.make_directory("/", "New").map(|_| ()), Err(libc::EROFS));
+    assert_eq!(fs.write_note(note.ino, 0, b"changed"), Err(libc::EROFS));
+    assert_eq!(
+        fs.set_attr(note.ino, None, None, None, Some(0)).map(|_| ()),
+        Err(libc::EROFS)
+    );
+    assert_eq!(
+        fs.rename_entry(1, "readme.txt.md", 1, "renamed.md"),
+        Err(libc::EROFS)
+    );
+    assert_eq!(fs.unlink_note(1, "readme.txt.md"), Err(libc::EROFS));
+    assert_eq!(fs.remove_directory(1, "Documents"), Err(libc::EROFS));
+    let unchanged: i64 = fs
+        .db
+        .query_row(
+            "SELECT COUNT(*) FROM notes WHERE body = 'This is a test note in root' AND title = 'readme.txt' AND deleted_time = 0",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(unchanged, 1);
+
+    // A database opened by path is opened read-only
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("joplin.sqlite");
+    create_test_db()
+        .unwrap()
+        .execute("VACUUM INTO ?1", [path.to_str().unwrap()])
+        .unwrap();
+    let fs = SqliteFS::new(path.to_str().unwrap(), options()).unwrap();
+    assert!(fs.db.is_readonly(rusqlite::MAIN_DB).unwrap());
+
+    // The kernel is told, and startup repairs that write are refused
+    let matches = cli()
+        .try_get_matches_from(["sqlite_fuse", "db", "/mnt", "--read-only"])
+        .unwrap();
+    assert!(mount_options(&matches).contains(&MountOption::RO));
+    assert!(
+        cli()
+            .try_get_matches_from(["sqlite_fuse", "db", "/mnt", "--read-only", "--rebuild-fts"])
+            .is_err()
+    );
+}
+
+#[test]
+fn test_number_by_order_prefixes_rank_with_shared_width() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "INSERT INTO folders (id, title, created_time, updated_time, parent_id) VALUES
+             ('small', 'Small', 0, 0, ''), ('big', 'Big', 0, 0, '');
+         INSERT INTO notes (id, title, created_time, updated_time, parent_id, `order`, body) VALUES
+             ('s1', 'zebra', 0, 0, 'small', 0, ''),
+             ('s2', 'apple', 0, 0, 'small', 0, ''),
+             ('s3', 'pinned', 0, 0, 'small', 500, 'top');",
+    )
+    .unwrap();
+    for i in 0..12 {
+        conn.execute(
+            "INSERT INTO notes (id, title, created_time, updated_time, parent_id) VALUES (?1, ?2, 0, 0, 'big')",
+            [format!("b{i}"), format!("note {i:02}")],
+        )
+        .unwrap();
+    }
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            number_by_order: true,
+            ..FsOptions::default()
+        },
+    );
+
+    let small = fs.lookup_child(1, "Small").unwrap();
+    let mut names = listed_names(&fs.list_directory(small.ino).unwrap());
+    names.sort();
+    assert_eq!(names, ["001-pinned.md", "002-apple.md", "003-zebra.md"]);
+    let big = fs.lookup_child(1, "Big").unwrap();
+    let names = listed_names(&fs.list_directory(big.ino).unwrap());
+    assert!(names.contains(&"001-note 00.md".to_string()));
+    assert!(names.contains(&"012-note 11.md".to_string()));
+
+    // Prefixed names resolve, a wrong rank does not, plain names still do
+    let pinned = fs.lookup_child(small.ino, "001-pinned.md").unwrap();
+    assert_eq!(fs.read_note(pinned.ino, 0).unwrap(), b"top");
+    assert_eq!(fs.lookup_child(small.ino, "002-pinned.md"), Err(ENOENT));
+    fs.lookup_child(small.ino, "apple.md").unwrap();
+
+    // One notebook outgrowing three digits widens every prefix
+    fs.db
+        .execute_batch(
+            "WITH RECURSIVE n(i) AS (SELECT 12 UNION ALL SELECT i + 1 FROM n WHERE i < 999)
+             INSERT INTO notes (id, title, created_time, updated_time, parent_id)
+             SELECT 'b' || i, 'note ' || i, 0, 0, 'big' FROM n",
+        )
+        .unwrap();
+    let mut names = listed_names(&fs.list_directory(small.ino).unwrap());
+    names.sort();
+    assert_eq!(names, ["0001-pinned.md", "0002-apple.md", "0003-zebra.md"]);
+    fs.lookup_child(small.ino, "0003-zebra.md").unwrap();
+}
+
+#[test]
+fn test_selftest_database_mounts_with_default_options() {
+    let _db = selftest_database().unwrap();
+    let mut fs = SqliteFS::new(SELFTEST_DB_URI, FsOptions::default()).unwrap();
+
+    let names = listed_names(&fs.list_directory(1).unwrap());
+    assert!(names.contains(&"welcome.md".to_string()));
+    assert!(names.contains(&"Notebook".to_string()));
+
+    let probe = fs.create_file("/", "selftest-probe.md").unwrap();
+    assert_eq!(fs.write_note(probe.ino, 0, b"selftest"), Ok(8));
+    assert_eq!(fs.read_note(probe.ino, 0).unwrap(), b"selftest");
+    fs.unlink_note(1, "selftest-probe.md").unwrap();
+    assert_eq!(fs.lookup_child(1, "selftest-probe.md"), Err(ENOENT));
+}
+
+#[test]
+fn test_resource_dir_discovered_from_profile_layout() {
+    let profile = tempfile::tempdir().unwrap();
+    let db_path = profile.path().join("database.sqlite");
+    let db_path = db_path.to_str().unwrap();
+    create_test_db()
+        .unwrap()
+        .execute("VACUUM INTO ?1", [db_path])
+        .unwrap();
+    std::fs::create_dir(profile.path().join("resources")).unwrap();
+
+    let dir = discover_resource_dir(db_path);
+    assert_eq!(dir, profile.path().join("resources"));
+    assert_eq!(check_resource_dir(&dir), Ok(()));
+
+    // A resourceDir setting takes precedence, relative to the database
+    let conn = Connection::open(db_path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE settings (key TEXT PRIMARY KEY, value TEXT);
+         INSERT INTO settings VALUES ('resourceDir', '\"attachments\"');",
+    )
+    .unwrap();
+    assert_eq!(
+        discover_resource_dir(db_path),
+        profile.path().join("attachments")
+    );
+}
+
+#[test]
+fn test_missing_resource_dir_is_a_clear_error() {
+    let profile = tempfile::tempdir().unwrap();
+    let db_path = profile.path().join("database.sqlite");
+    create_test_db()
+        .unwrap()
+        .execute("VACUUM INTO ?1", [db_path.to_str().unwrap()])
+        .unwrap();
+
+    let dir = discover_resource_dir(db_path.to_str().unwrap());
+    let err = check_resource_dir(&dir).unwrap_err();
+    assert!(
+        err.starts_with(&format!(
+            "resource directory {} is not readable",
+            dir.display()
+        )),
+        "{err}"
+    );
+}
+
+#[test]
+fn test_concurrent_appends_to_one_note_lose_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("joplin.sqlite");
+    let path = path.to_str().unwrap().to_string();
+    create_test_db()
+        .unwrap()
+        .execute("VACUUM INTO ?1", [&path])
+        .unwrap();
+
+    const CHUNKS: usize = 25;
+    let writers: Vec<_> = ["a", "b"]
+        .into_iter()
+        .map(|writer| {
+            let path = path.clone();
+            std::thread::spawn(move || {
+                let mut fs = SqliteFS::new(&path, FsOptions::default()).unwrap();
+                let note = fs.lookup_child(1, "readme.txt.md").unwrap();
+                let fh = fs
+                    .open_note(note.ino, libc::O_WRONLY | libc::O_APPEND)
+                    .unwrap();
+                for i in 0..CHUNKS {
+                    let chunk = format!("[{writer}{i}]");
+                    assert_eq!(
+                        fs.write_handle(fh, note.ino, 0, chunk.as_bytes()),
+                        Ok(chunk.len() as u32)
+                    );
+                }
+                fs.release_handle(fh).unwrap();
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.join().unwrap();
+    }
+
+    let mut fs = SqliteFS::new(&path, FsOptions::default()).unwrap();
+    let note = fs.lookup_child(1, "readme.txt.md").unwrap();
+    let body = String::from_utf8(fs.read_note(note.ino, 0).unwrap()).unwrap();
+    assert!(body.starts_with("This is a test note in root"));
+    for writer in ["a", "b"] {
+        for i in 0..CHUNKS {
+            assert_eq!(body.matches(&format!("[{writer}{i}]")).count(), 1, "{body}");
+        }
+    }
+    assert_eq!(
+        body.len(),
+        "This is a test note in root".len() + 2 * 10 * 4 + 2 * 15 * 5
+    );
+}
+
+#[test]
+fn test_recent_lists_newest_notes_as_symlinks() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "UPDATE notes SET user_updated_time = 100 WHERE id = 'note1';
+         UPDATE notes SET user_updated_time = 300 WHERE id = 'note2';
+         INSERT INTO folders (id, title, created_time, updated_time, parent_id) VALUES
+             ('sub', 'Sub', 0, 0, 'folder1');
+         INSERT INTO notes (id, title, created_time, updated_time, user_updated_time, parent_id, deleted_time) VALUES
+             ('newest', 'plan', 0, 0, 400, 'sub', 0),
+             ('twin', 'readme.txt', 0, 0, 200, 'folder1', 0),
+             ('gone', 'deleted', 0, 0, 500, '', 99);",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            recent_count: 3,
+            ..FsOptions::default()
+        },
+    );
+
+    assert!(listed_names(&fs.list_directory(1).unwrap()).contains(&".recent".to_string()));
+    let recent = fs.lookup_child(1, ".recent").unwrap();
+    let entries = fs.list_directory(recent.ino).unwrap();
+    let names: Vec<_> = entries
+        .iter()
+        .skip(2)
+        .map(|(_, _, name)| name.as_str())
+        .collect();
+    // Newest first, three of them, and the older readme.txt.md disambiguated
+    assert_eq!(names, ["plan.md", "document.md.md", "readme.txt.md"]);
+    assert!(
+        entries
+            .iter()
+            .skip(2)
+            .all(|(_, kind, _)| *kind == FileType::Symlink)
+    );
+
+    let link = fs.lookup_child(recent.ino, "plan.md").unwrap();
+    assert_eq!(link.kind, FileType::Symlink);
+    assert_eq!(fs.read_link(link.ino).unwrap(), b"../Documents/Sub/plan.md");
+    let link = fs.lookup_child(recent.ino, "readme.txt.md").unwrap();
+    assert_eq!(
+        fs.read_link(link.ino).unwrap(),
+        b"../Documents/readme.txt.md"
+    );
+
+    fs.options.recent_count = 4;
+    let names = listed_names(&fs.list_directory(recent.ino).unwrap());
+    assert!(
+        names.contains(&"readme.txt (note1).md".to_string()),
+        "{names:?}"
+    );
+    let link = fs
+        .lookup_child(recent.ino, "readme.txt (note1).md")
+        .unwrap();
+    assert_eq!(fs.read_link(link.ino).unwrap(), b"../readme.txt.md");
+
+    assert_eq!(
+        fs.create_file("/.recent", "new.md").map(|_| ()),
+        Err(libc::EPERM)
+    );
+    assert_eq!(fs.lookup_child(recent.ino, "deleted.md"), Err(ENOENT));
+}
+
+#[test]
+fn test_create_mode_is_reported_by_getattr() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+
+    let attr = fs
+        .create_or_open_file("/", "secret.md", libc::O_EXCL | libc::O_WRONLY, 0o600)
+        .unwrap();
+    assert_eq!(attr.perm, 0o600);
+    assert_eq!(fs.get_attr(attr.ino).unwrap().perm, 0o600);
+    let fh = fs.open_note(attr.ino, libc::O_WRONLY).unwrap();
+    assert_ne!(fh, attr.ino);
+    fs.release_handle(fh).unwrap();
+
+    // Reopening without O_EXCL keeps the mode; chmod replaces it
+    let again = fs.create_or_open_file("/", "secret.md", 0, 0o666).unwrap();
+    assert_eq!(again.perm, 0o600);
+    fs.set_attr(attr.ino, Some(0o100640), None, None, None)
+        .unwrap();
+    assert_eq!(fs.get_attr(attr.ino).unwrap().perm, 0o640);
+
+    // Notes created elsewhere keep the default
+    let readme = fs.lookup_child(1, "readme.txt.md").unwrap();
+    assert_eq!(readme.perm, 0o644);
+}
+
+#[test]
+fn test_open_with_o_trunc_empties_the_note() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+    let readme = fs.lookup_child(1, "readme.txt.md").unwrap();
+
+    let fh = fs
+        .open_note(readme.ino, libc::O_RDONLY | libc::O_TRUNC)
+        .unwrap();
+    fs.release_handle(fh).unwrap();
+    assert_eq!(fs.get_attr(readme.ino).unwrap().size, 27);
+
+    let fh = fs
+        .open_note(readme.ino, libc::O_WRONLY | libc::O_TRUNC)
+        .unwrap();
+    assert_eq!(fs.get_attr(readme.ino).unwrap().size, 0);
+    fs.write_handle(fh, readme.ino, 0, b"fresh").unwrap();
+    fs.release_handle(fh).unwrap();
+    assert_eq!(fs.read_note(readme.ino, 0).unwrap(), b"fresh");
+}
+
+#[test]
+fn test_folder_index_aliases_the_index_note() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute(
+        "INSERT INTO notes (id, title, body, created_time, updated_time, parent_id) VALUES ('idx', 'README', 'About these documents', 0, 0, 'folder1')",
+        [],
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            folder_index: Some("README".to_string()),
+            ..FsOptions::default()
+        },
+    );
+
+    // With an index note: listed beside its normal entry, same content
+    let documents = fs.lookup_child(1, "Documents").unwrap();
+    let names = listed_names(&fs.list_directory(documents.ino).unwrap());
+    assert!(names.contains(&".index.md".to_string()));
+    assert!(names.contains(&"README.md".to_string()));
+    let index = fs.lookup_child(documents.ino, ".index.md").unwrap();
+    assert_eq!(
+        fs.read_note(index.ino, 0).unwrap(),
+        b"About these documents"
+    );
+    fs.set_attr(index.ino, None, None, None, Some(0)).unwrap();
+    fs.write_note(index.ino, 0, b"Rewritten").unwrap();
+    let readme = fs.lookup_child(documents.ino, "README.md").unwrap();
+    assert_eq!(fs.read_note(readme.ino, 0).unwrap(), b"Rewritten");
+
+    // Without one: no entry and no lookup, until one is created
+    assert!(!listed_names(&fs.list_directory(1).unwrap()).contains(&".index.md".to_string()));
+    assert_eq!(fs.lookup_child(1, ".index.md"), Err(ENOENT));
+    fs.create_file("/", ".index.md").unwrap();
+    assert!(listed_names(&fs.list_directory(1).unwrap()).contains(&"README.md".to_string()));
+    fs.lookup_child(1, ".index.md").unwrap();
+}
+
+#[test]
+fn test_max_open_files_refuses_opens_past_the_limit() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            max_open_files: Some(2),
+            ..FsOptions::default()
+        },
+    );
+    let readme = fs.lookup_child(1, "readme.txt.md").unwrap();
+
+    let first = fs.open_note(readme.ino, libc::O_RDONLY).unwrap();
+    let second = fs.open_note(readme.ino, libc::O_RDWR).unwrap();
+    assert_eq!(fs.open_note(readme.ino, libc::O_RDONLY), Err(libc::EMFILE));
+    assert_eq!(fs.check_open_files(), Err(libc::EMFILE));
+
+    // Sidecars hold no handle and stay openable
+    let sidecar = fs.lookup_child(1, "readme.txt.md.txt").unwrap();
+    assert_eq!(fs.open_note(sidecar.ino, libc::O_RDONLY), Ok(0));
+
+    fs.release_handle(first).unwrap();
+    let third = fs.open_note(readme.ino, libc::O_RDONLY).unwrap();
+    assert_ne!(third, second);
+    fs.release_handle(second).unwrap();
+    fs.release_handle(third).unwrap();
+    assert_eq!(fs.check_open_files(), Ok(()));
+}
+
+#[test]
+fn test_rename_changing_only_case() {
+    let conn = create_test_db().expect("Failed to create test database");
+    // Also cover a schema comparing titles case-insensitively
+    let sql: String = conn
+        .query_row(
+            "SELECT sql FROM sqlite_master WHERE name = 'notes'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    let nocase = create_test_db().unwrap();
+    nocase
+        .execute_batch(&format!(
+            "ALTER TABLE notes RENAME TO old_notes;
+             {};
+             INSERT INTO notes SELECT * FROM old_notes;
+             DROP TABLE old_notes;",
+            sql.replace(
+                "`title` TEXT NOT NULL",
+                "`title` TEXT NOT NULL COLLATE NOCASE"
+            )
+        ))
+        .unwrap();
+
+    for conn in [conn, nocase] {
+        let mut fs = fs_from_conn(conn, FsOptions::default());
+        let note = fs.create_file("/", "note.md").unwrap();
+        let folder = fs.lookup_child(1, "Documents").unwrap();
+        let inner = fs.lookup_child(folder.ino, "document.md.md").unwrap();
+
+        fs.rename_entry(1, "note.md", 1, "Note.md").unwrap();
+        let title: String = fs
+            .db
+            .query_row(
+                "SELECT title FROM notes WHERE title = 'Note' COLLATE BINARY",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(title, "Note");
+        assert_eq!(fs.get_path_from_inode(note.ino).unwrap(), "/Note.md");
+        assert_eq!(fs.lookup_child(1, "Note.md").unwrap().ino, note.ino);
+        assert!(listed_names(&fs.list_directory(1).unwrap()).contains(&"Note.md".to_string()));
+
+        // Folders too, carrying their descendants' inodes along
+        fs.rename_entry(1, "Documents", 1, "documents").unwrap();
+        assert_eq!(fs.get_path_from_inode(folder.ino).unwrap(), "/documents");
+        assert_eq!(
+            fs.get_path_from_inode(inner.ino).unwrap(),
+            "/documents/document.md.md"
+        );
+        assert!(listed_names(&fs.list_directory(1).unwrap()).contains(&"documents".to_string()));
+    }
+}
+
+#[test]
+fn test_from_connection_serves_an_open_connection() {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch(
+        "CREATE TABLE notes (id TEXT PRIMARY KEY, parent_id TEXT NOT NULL DEFAULT '', title TEXT NOT NULL DEFAULT '', body TEXT NOT NULL DEFAULT '', created_time INT NOT NULL, updated_time INT NOT NULL, user_created_time INT NOT NULL DEFAULT 0, user_updated_time INT NOT NULL DEFAULT 0, markup_language INT NOT NULL DEFAULT 1, deleted_time INT NOT NULL DEFAULT 0);
+         CREATE TABLE folders (id TEXT PRIMARY KEY, title TEXT NOT NULL DEFAULT '', parent_id TEXT NOT NULL DEFAULT '', created_time INT NOT NULL, updated_time INT NOT NULL, user_created_time INT NOT NULL DEFAULT 0, user_updated_time INT NOT NULL DEFAULT 0, icon TEXT NOT NULL DEFAULT '', deleted_time INT NOT NULL DEFAULT 0);
+         INSERT INTO folders (id, title, created_time, updated_time) VALUES ('f1', 'Projects', 0, 0);
+         INSERT INTO notes (id, title, body, created_time, updated_time, parent_id) VALUES ('n1', 'todo', 'ship it', 0, 0, 'f1');",
+    )
+    .unwrap();
+
+    let mut fs = SqliteFS::from_connection(conn, FsOptions::default()).unwrap();
+    let fh = fs.open_directory(1).unwrap();
+    let mut entries = Vec::new();
+    fs.read_directory(fh, 0, |ino, _, kind, name| {
+        entries.push((ino, kind, name.to_string()));
+        false
+    })
+    .unwrap();
+    fs.release_directory(fh);
+    assert!(listed_names(&entries).contains(&"Projects".to_string()));
+
+    let projects = fs.lookup_child(1, "Projects").unwrap();
+    assert_eq!(
+        listed_names(&fs.list_directory(projects.ino).unwrap()),
+        ["todo.md"]
+    );
+    let todo = fs.lookup_child(projects.ino, "todo.md").unwrap();
+    assert_eq!(fs.read_note(todo.ino, 0).unwrap(), b"ship it");
+}
+
+#[test]
+fn test_rewrite_note_links_uses_relative_paths() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "INSERT INTO folders (id, title, created_time, updated_time, parent_id) VALUES
+             ('projects', 'My Projects', 0, 0, '');
+         INSERT INTO notes (id, title, body, created_time, updated_time, parent_id) VALUES
+             ('aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa', 'Plan (v2)', 'See [home](:/bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb#top)', 0, 0, 'projects'),
+             ('bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb', 'home', 'Read [plan](:/aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa) and [gone](:/cccccccccccccccccccccccccccccccc)', 0, 0, '');",
+    )
+    .unwrap();
+    let options = FsOptions {
+        rewrite_note_links: true,
+        ..FsOptions::default()
+    };
+    let mut fs = fs_from_conn(conn, options);
+
+    let home = fs.lookup_child(1, "home.md").unwrap();
+    let expected = "Read [plan](My%20Projects/Plan%20%28v2%29.md) and [gone](:/cccccccccccccccccccccccccccccccc)";
+    assert_eq!(
+        String::from_utf8(fs.read_note(home.ino, 0).unwrap()).unwrap(),
+        expected
+    );
+    assert_eq!(home.size, expected.len() as u64);
+
+    // Links climb out of the linking note's folder and keep their anchor
+    let projects = fs.lookup_child(1, "My Projects").unwrap();
+    let plan = fs.lookup_child(projects.ino, "Plan (v2).md").unwrap();
+    let expected = "See [home](../home.md#top)";
+    assert_eq!(
+        String::from_utf8(fs.read_note(plan.ino, 0).unwrap()).unwrap(),
+        expected
+    );
+    assert_eq!(fs.get_attr(plan.ino).unwrap().size, expected.len() as u64);
+
+    // The stored body keeps Joplin's links, and cannot be overwritten with
+    // the rewritten ones
+    assert_eq!(fs.write_note(plan.ino, 0, b"x"), Err(libc::EROFS));
+}
+
+#[test]
+fn test_relative_link() {
+    assert_eq!(relative_link("/", "/a.md"), "a.md");
+    assert_eq!(relative_link("/x/y", "/x/z/b.md"), "../z/b.md");
+    assert_eq!(relative_link("/x", "/x/b c.md"), "b%20c.md");
+}
+
+#[test]
+fn test_folder_cycle_detection_and_loop_guard() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "INSERT INTO folders (id, title, created_time, updated_time, parent_id) VALUES
+             ('cyca', 'A', 0, 0, 'cycb'),
+             ('cycb', 'B', 0, 0, 'cyca'),
+             ('below', 'Below', 0, 0, 'cyca'),
+             ('orphan', 'Orphan', 0, 0, 'nowhere');",
+    )
+    .unwrap();
+
+    let problems = folder_problems(&conn).unwrap();
+    assert_eq!(
+        problems,
+        [
+            FolderProblem::Cycle(vec!["cyca".to_string(), "cycb".to_string()]),
+            FolderProblem::MissingParent {
+                id: "orphan".to_string(),
+                parent_id: "nowhere".to_string(),
+            },
+        ]
+    );
+    assert_eq!(
+        problems[0].to_string(),
+        "folder cycle: cyca -> cycb -> cyca"
+    );
+
+    // The mount refuses to follow the cycle round instead of nesting forever:
+    // the folder met again can be looked up but not entered
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            notebooks_by_id: true,
+            ..FsOptions::default()
+        },
+    );
+    let notebooks = fs.lookup_child(1, ".notebooks").unwrap();
+    let a = fs.lookup_child(notebooks.ino, "cyca").unwrap();
+    let b = fs.lookup_child(a.ino, "B").unwrap();
+    let again = fs.lookup_child(b.ino, "A").unwrap();
+    assert_eq!(fs.open_directory(again.ino), Err(libc::EIO));
+    assert_eq!(fs.lookup_child(again.ino, "B").map(|_| ()), Err(libc::EIO));
+
+    // --fix breaks the cycle at its first folder and keeps the rest nested
+    fix_folder_problems(&fs.db, &problems, 1000).unwrap();
+    assert!(folder_problems(&fs.db).unwrap().is_empty());
+    let parents: Vec<(String, String, i64, i64)> = fs
+        .db
+        .prepare(
+            "SELECT id, parent_id, updated_time, user_updated_time FROM folders WHERE id IN ('cyca', 'cycb', 'orphan') ORDER BY id",
+        )
+        .unwrap()
+        .query_map([], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })
+        .unwrap()
+        .collect::<rusqlite::Result<_>>()
+        .unwrap();
+    // Moved folders are stamped so the new parent syncs
+    let moved: Vec<_> = parents
+        .iter()
+        .map(|(id, parent, updated, user_updated)| {
+            (
+                id.as_str(),
+                parent.as_str(),
+                *updated == 1000 && *user_updated == 1000,
+            )
+        })
+        .collect();
+    assert_eq!(
+        moved,
+        [
+            ("cyca", "", true),
+            ("cycb", "cyca", false),
+            ("orphan", "", true)
+        ]
+    );
+    let a = fs.lookup_child(1, "A").unwrap();
+    assert!(fs.lookup_child(a.ino, "B").is_ok());
+
+    let matches = cli()
+        .try_get_matches_from(["sqlite_fuse", "doctor", "db", "--fix"])
+        .unwrap();
+    let (name, doctor) = matches.subcommand().unwrap();
+    assert_eq!(name, "doctor");
+    assert!(doctor.get_flag("fix"));
+}
+
+#[test]
+fn test_markup_ext_mapping() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "INSERT INTO notes (id, title, body, created_time, updated_time, parent_id, markup_language) VALUES
+             ('org1', 'agenda', '* TODO', 1, 1, '', 3),
+             ('html1', 'page', '<p>hi</p>', 1, 1, '', 2);",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            markup_exts: HashMap::from([(3, "org".to_string())]),
+            ..FsOptions::default()
+        },
+    );
+
+    let mut names = listed_names(&fs.list_directory(1).unwrap());
+    names.sort();
+    assert_eq!(
+        names,
+        ["Documents", "agenda.org", "page.html", "readme.txt.md"]
+    );
+    let agenda = fs.lookup_child(1, "agenda.org").unwrap();
+    assert_eq!(fs.read_note(agenda.ino, 0).unwrap(), b"* TODO".to_vec());
+    let bare = fs.lookup_child(1, "agenda").unwrap();
+    assert_eq!(fs.read_note(bare.ino, 0).unwrap(), b"* TODO".to_vec());
+    assert_eq!(fs.lookup_child(1, "agenda.md").unwrap_err(), ENOENT);
+
+    // Renaming keeps the markup language; a new extension switches it
+    fs.rename_entry(1, "agenda.org", 1, "plan.org").unwrap();
+    fs.rename_entry(1, "page.html", 1, "page.md").unwrap();
+    let markup = |fs: &SqliteFS, id: &str| -> i64 {
+        fs.db
+            .query_row(
+                "SELECT markup_language FROM notes WHERE id = ?1",
+                [id],
+                |row| row.get(0),
+            )
+            .unwrap()
+    };
+    assert_eq!(markup(&fs, "org1"), 3);
+    assert_eq!(markup(&fs, "html1"), 1);
+    assert_eq!(fs.get_path_from_inode(agenda.ino).unwrap(), "/plan.org");
+
+    fs.create_or_open_file("/", "inbox.org", libc::O_WRONLY, 0o644)
+        .unwrap();
+    let id: String = fs
+        .db
+        .query_row("SELECT id FROM notes WHERE title = 'inbox'", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(markup(&fs, &id), 3);
+    assert!(fs.lookup_child(1, "inbox.org").is_ok());
+
+    assert_eq!(
+        parse_markup_ext_entry("3=.org").unwrap(),
+        (3, "org".to_string())
+    );
+    assert!(parse_markup_ext_entry("3=o/g").is_err());
+    assert!(parse_markup_ext_entry("org").is_err());
+}
+
+#[test]
+fn test_html_and_markdown_notes_in_one_folder() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "INSERT INTO notes (id, title, body, created_time, updated_time, parent_id, markup_language) VALUES
+             ('html1', 'page', '<p>hi</p>', 1, 1, 'folder1', 2),
+             ('md1', 'note', '# hi', 1, 1, 'folder1', 1);",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+
+    let documents = fs.lookup_child(1, "Documents").unwrap().ino;
+    let mut names = listed_names(&fs.list_directory(documents).unwrap());
+    names.sort();
+    assert_eq!(names, ["document.md.md", "note.md", "page.html"]);
+    assert_eq!(fs.strip_note_suffix("page.html"), "page");
+    assert_eq!(fs.strip_note_suffix("note.md"), "note");
+
+    // Each suffix resolves to its own row for getattr, read and write
+    let page = fs.lookup_child(documents, "page.html").unwrap();
+    assert_eq!(page.size, 9);
+    assert_eq!(fs.read_note(page.ino, 0).unwrap(), b"<p>hi</p>".to_vec());
+    let fh = fs.open_note(page.ino, libc::O_WRONLY).unwrap();
+    fs.write_handle(fh, page.ino, 0, b"<p>yo</p>").unwrap();
+    fs.release_handle(fh).unwrap();
+    let note = fs.lookup_child(documents, "note.md").unwrap();
+    assert_eq!(fs.read_note(note.ino, 0).unwrap(), b"# hi".to_vec());
+    let body: String = fs
+        .db
+        .query_row("SELECT body FROM notes WHERE id = 'html1'", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(body, "<p>yo</p>");
+    assert_eq!(fs.lookup_child(documents, "page.md").unwrap_err(), ENOENT);
+}
+
+#[test]
+fn test_attachment_reads_stream_in_windows() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "CREATE TABLE resources (id TEXT PRIMARY KEY, title TEXT NOT NULL DEFAULT '', mime TEXT NOT NULL, filename TEXT NOT NULL DEFAULT '', file_extension TEXT NOT NULL DEFAULT '', size INT NOT NULL DEFAULT -1);
+         INSERT INTO resources (id, title, mime, file_extension) VALUES
+             ('abcdefabcdefabcdefabcdefabcdefab', 'clip', 'video/mp4', 'mp4');
+         INSERT INTO notes (id, title, body, created_time, updated_time, parent_id) VALUES
+             ('film', 'film', '[clip](:/abcdefabcdefabcdefabcdefabcdefab)', 0, 0, '');",
+    )
+    .unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let video: Vec<u8> = (0..1024 * 1024u32).map(|i| (i * 7 % 251) as u8).collect();
+    std::fs::write(
+        dir.path().join("abcdefabcdefabcdefabcdefabcdefab.mp4"),
+        &video,
+    )
+    .unwrap();
+    let options = FsOptions {
+        resource_dir: dir.path().to_path_buf(),
+        ..FsOptions::default()
+    };
+    let mut fs = fs_from_conn(conn, options);
+
+    let attachments = fs.lookup_child(1, "film.md.attachments").unwrap();
+    let clip = fs.lookup_child(attachments.ino, "clip.mp4").unwrap();
+    let fh = fs.open_note(clip.ino, libc::O_RDONLY).unwrap();
+    let second = fs.open_note(clip.ino, libc::O_RDONLY).unwrap();
+    assert_eq!(fs.open_resources.len(), 1);
+
+    const WINDOW: u32 = 64 * 1024;
+    let mut streamed = Vec::new();
+    loop {
+        let window = fs
+            .read_range(clip.ino, fh, streamed.len() as i64, WINDOW)
+            .unwrap();
+        assert!(window.len() <= WINDOW as usize);
+        if window.is_empty() {
+            break;
+        }
+        streamed.extend_from_slice(&window);
+    }
+    assert_eq!(streamed, video);
+    assert_eq!(
+        fs.read_range(clip.ino, second, 1000, 4).unwrap(),
+        video[1000..1004]
+    );
+
+    // The file closes with the last handle on it
+    fs.release_handle(fh).unwrap();
+    assert_eq!(fs.open_resources.len(), 1);
+    fs.release_handle(second).unwrap();
+    assert!(fs.open_resources.is_empty());
+    assert!(fs.resource_handles.is_empty());
+}
+
+#[test]
+fn test_inline_attachment_files_sit_beside_notes() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "CREATE TABLE resources (id TEXT PRIMARY KEY, title TEXT NOT NULL DEFAULT '', mime TEXT NOT NULL, filename TEXT NOT NULL DEFAULT '', file_extension TEXT NOT NULL DEFAULT '', size INT NOT NULL DEFAULT -1);
+         CREATE TABLE note_resources (id INTEGER PRIMARY KEY, note_id TEXT NOT NULL, resource_id TEXT NOT NULL, is_associated INT NOT NULL, last_seen_time INT NOT NULL);
+         INSERT INTO resources (id, title, mime, file_extension) VALUES
+             ('0123456789abcdef0123456789abcdef', 'photo.png', 'image/png', 'png');
+         INSERT INTO notes (id, title, body, created_time, updated_time, user_updated_time, parent_id) VALUES
+             ('trip1', 'trip', '![](:/0123456789abcdef0123456789abcdef)', 0, 0, 2, 'folder1'),
+             ('trip2', 'trip', '![](:/0123456789abcdef0123456789abcdef)', 0, 0, 1, 'folder1');
+         INSERT INTO note_resources (note_id, resource_id, is_associated, last_seen_time) VALUES
+             ('trip1', '0123456789abcdef0123456789abcdef', 1, 0),
+             ('trip2', '0123456789abcdef0123456789abcdef', 1, 0);",
+    )
+    .unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("0123456789abcdef0123456789abcdef.png"),
+        b"\x89PNG\r\n",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            resource_dir: dir.path().to_path_buf(),
+            inline_attachment_files: true,
+            ..FsOptions::default()
+        },
+    );
+
+    let documents = fs.lookup_child(1, "Documents").unwrap();
+    let names = listed_names(&fs.list_directory(documents.ino).unwrap());
+    assert!(names.contains(&"trip.photo.png".to_string()));
+    assert!(names.contains(&"trip (trip2).photo.png".to_string()));
+
+    let photo = fs.lookup_child(documents.ino, "trip.photo.png").unwrap();
+    assert_eq!(photo.size, 6);
+    assert_eq!(photo.perm, 0o444);
+    let fh = fs.open_note(photo.ino, libc::O_RDONLY).unwrap();
+    assert_eq!(
+        fs.read_range(photo.ino, fh, 0, 4096).unwrap(),
+        b"\x89PNG\r\n"
+    );
+    fs.release_handle(fh).unwrap();
+    assert_eq!(fs.open_note(photo.ino, libc::O_WRONLY), Err(libc::EACCES));
+    assert!(
+        fs.lookup_child(documents.ino, "trip (trip2).photo.png")
+            .is_ok()
+    );
+
+    fs.options.inline_attachment_files = false;
+    assert_eq!(
+        fs.lookup_child(documents.ino, "trip.photo.png"),
+        Err(ENOENT)
+    );
+}
+
+#[test]
+fn test_adaptive_ttl_drops_on_external_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("joplin.sqlite");
+    create_test_db()
+        .unwrap()
+        .execute("VACUUM INTO ?1", [db_path.to_str().unwrap()])
+        .unwrap();
+    let options = FsOptions {
+        adaptive_ttl: true,
+        ..FsOptions::default()
+    };
+    let mut fs = SqliteFS::new(db_path.to_str().unwrap(), options).unwrap();
+    let quiet = || Instant::now() - Duration::from_secs(60);
+
+    // Uncached until the quiet period since mount has passed
+    assert_eq!(fs.ttl(), Duration::ZERO);
+    fs.change_watch.last_change = quiet();
+    assert_eq!(fs.ttl(), Duration::from_secs(30));
+
+    // The mount's own writes leave the TTL long
+    fs.create_file("/", "mine.md").unwrap();
+    assert_eq!(fs.ttl(), Duration::from_secs(30));
+
+    // A commit from another connection, as Joplin syncing, drops it
+    let joplin = Connection::open(&db_path).unwrap();
+    joplin
+        .execute(
+            "UPDATE notes SET updated_time = updated_time + 1 WHERE id = 'note1'",
+            [],
+        )
+        .unwrap();
+    assert_eq!(fs.ttl(), Duration::ZERO);
+    assert_eq!(fs.ttl(), Duration::ZERO);
+    fs.change_watch.last_change = quiet();
+    assert_eq!(fs.ttl(), Duration::from_secs(30));
+
+    fs.options.adaptive_ttl = false;
+    assert_eq!(fs.ttl(), fs.attr_config().ttl);
+}
+
+#[test]
+fn test_decorate_notebooks_from_user_data() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "UPDATE folders SET user_data = '{\"color\":\"red\"}' WHERE id = 'folder1';
+         INSERT INTO folders (id, title, created_time, updated_time, parent_id, user_data) VALUES
+             ('nested', 'Inner', 0, 0, 'folder1', '{\"tag\":\"x\"}'),
+             ('plain', 'Plain', 0, 0, '', '{\"tag\":\"a/b\"}');",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            decorate_notebooks: true,
+            ..FsOptions::default()
+        },
+    );
+
+    let mut names = listed_names(&fs.list_directory(1).unwrap());
+    names.sort();
+    assert_eq!(names, ["Plain", "[red] Documents", "readme.txt.md"]);
+
+    let documents = fs.lookup_child(1, "[red] Documents").unwrap();
+    assert_eq!(
+        fs.lookup_child(1, "Documents").unwrap().kind,
+        FileType::Directory
+    );
+    assert_eq!(fs.lookup_child(1, "[blue] Documents"), Err(ENOENT));
+    // Only top-level notebooks are decorated
+    assert_eq!(
+        listed_names(&fs.list_directory(documents.ino).unwrap()),
+        ["Inner", "document.md.md"]
+    );
+    let note = fs.lookup_child(documents.ino, "document.md.md").unwrap();
+    assert_eq!(
+        fs.read_note(note.ino, 0).unwrap(),
+        b"This is a test note in Documents folder"
+    );
+
+    fs.rename_entry(1, "[red] Documents", 1, "[red] Papers")
+        .unwrap();
+    let title: String = fs
+        .db
+        .query_row(
+            "SELECT title FROM folders WHERE id = 'folder1'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(title, "Papers");
+}
+
+#[test]
+fn test_copy_duplicates_tagged_todo() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "CREATE TABLE note_tags (id TEXT PRIMARY KEY, note_id TEXT NOT NULL, tag_id TEXT NOT NULL, created_time INT NOT NULL, updated_time INT NOT NULL, user_created_time INT NOT NULL DEFAULT 0, user_updated_time INT NOT NULL DEFAULT 0);
+         INSERT INTO notes (id, title, body, created_time, updated_time, parent_id, is_todo, todo_due, todo_completed, source_url) VALUES
+             ('task', 'task', '- ship it', 5, 5, 'folder1', 1, 7000, 0, 'https://example.com');
+         INSERT INTO note_tags (id, note_id, tag_id, created_time, updated_time) VALUES
+             ('nt1', 'task', 'urgent', 5, 5),
+             ('nt2', 'task', 'work', 5, 5);",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+    let documents = fs.lookup_child(1, "Documents").unwrap();
+    let task = fs.lookup_child(documents.ino, "task.md").unwrap();
+
+    // `cp task.md copy.md`: create the copy, then copy the whole range
+    let copy = fs.create_file("/Documents", "copy.md").unwrap();
+    assert_eq!(fs.copy_note_range(task.ino, 0, copy.ino, 0, 1 << 30), Ok(9));
+    assert_eq!(fs.read_note(copy.ino, 0).unwrap(), b"- ship it");
+
+    let (id, title, todo, due, url): (String, String, i64, i64, String) = fs
+        .db
+        .query_row(
+            "SELECT id, title, is_todo, todo_due, source_url FROM notes WHERE title = 'copy'",
+            [],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            },
+        )
+        .unwrap();
+    assert_ne!(id, "task");
+    assert_eq!((title.as_str(), todo, due), ("copy", 1, 7000));
+    assert_eq!(url, "https://example.com");
+    let mut tags: Vec<String> = fs
+        .db
+        .prepare("SELECT tag_id FROM note_tags WHERE note_id = ?1")
+        .unwrap()
+        .query_map([&id], |row| row.get(0))
+        .unwrap()
+        .collect::<rusqlite::Result<_>>()
+        .unwrap();
+    tags.sort();
+    assert_eq!(tags, ["urgent", "work"]);
+
+    // Partial ranges and copies into other notebooks copy only bytes
+    let other = fs.create_file("/Documents", "other.md").unwrap();
+    assert_eq!(fs.copy_note_range(task.ino, 2, other.ino, 0, 4), Ok(4));
+    assert_eq!(fs.read_note(other.ino, 0).unwrap(), b"ship");
+    let outside = fs.create_file("/", "outside.md").unwrap();
+    assert_eq!(
+        fs.copy_note_range(task.ino, 0, outside.ino, 0, 1 << 30),
+        Ok(9)
+    );
+    let todo: i64 = fs
+        .db
+        .query_row(
+            "SELECT is_todo FROM notes WHERE title = 'outside'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(todo, 0);
+}
+
+#[test]
+fn test_copy_file_range_patches_bytes_between_and_within_notes() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute(
+        "UPDATE notes SET body = 'hello world', updated_time = 1 WHERE id = 'note1'",
+        [],
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+    let readme = fs.lookup_child(1, "readme.txt.md").unwrap();
+    let documents = fs.lookup_child(1, "Documents").unwrap();
+    let document = fs.lookup_child(documents.ino, "document.md.md").unwrap();
+
+    // The first 5 bytes of one note over the start of another
+    assert_eq!(fs.copy_note_range(document.ino, 0, readme.ino, 0, 5), Ok(5));
+    assert_eq!(fs.read_note(readme.ino, 0).unwrap(), b"This  world");
+    let updated: i64 = fs
+        .db
+        .query_row(
+            "SELECT updated_time FROM notes WHERE id = 'note1'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert!(updated > 1);
+
+    // Within one note, overlapping ranges copy the original bytes
+    assert_eq!(
+        fs.copy_note_range(document.ino, 10, document.ino, 0, 4),
+        Ok(4)
+    );
+    assert_eq!(
+        fs.read_note(document.ino, 0).unwrap(),
+        b"test is a test note in Documents folder"
+    );
+    // Past the end of the source nothing is copied
+    assert_eq!(
+        fs.copy_note_range(readme.ino, 1000, document.ino, 0, 5),
+        Ok(0)
+    );
+}
+
+#[test]
+fn test_mount_lock_refuses_second_writable_mount() {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("joplin.sqlite");
+    let db_path = db_path.to_str().unwrap();
+
+    let first = MountLock::acquire(db_path).unwrap();
+    let pid = std::process::id().to_string();
+    let err = MountLock::acquire(db_path).unwrap_err();
+    assert!(err.contains("already mounted writable"), "{err}");
+    assert!(err.contains(&pid), "{err}");
+
+    // Unmounting releases it for the next mount
+    drop(first);
+    let _again = MountLock::acquire(db_path).unwrap();
+    assert!(std::path::Path::new(&format!("{db_path}.fuse-lock")).exists());
+
+    let matches = cli()
+        .try_get_matches_from(["sqlite_fuse", "db", "mnt", "--force"])
+        .unwrap();
+    assert!(matches.get_flag("force"));
+}
+
+#[test]
+fn test_control_preview_rename_reports_descendants() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "INSERT INTO folders (id, title, created_time, updated_time, parent_id) VALUES
+             ('sub', 'Sub', 0, 0, 'folder1'),
+             ('archive', 'Archive', 0, 0, '');
+         INSERT INTO notes (id, title, body, created_time, updated_time, parent_id) VALUES
+             ('deep', 'deep', '', 0, 0, 'sub');",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            control: true,
+            ..FsOptions::default()
+        },
+    );
+    assert!(listed_names(&fs.list_directory(1).unwrap()).contains(&".control".to_string()));
+    let control = fs.lookup_child(1, ".control").unwrap();
+    assert_eq!(
+        listed_names(&fs.list_directory(control.ino).unwrap()),
+        ["preview-rename", "rebuild-fts"]
+    );
+    let preview = fs.lookup_child(control.ino, "preview-rename").unwrap();
+    let fh = fs
+        .open_note(preview.ino, libc::O_WRONLY | libc::O_TRUNC)
+        .unwrap();
+    let ask = |fs: &mut SqliteFS, request: &str| {
+        fs.set_attr(preview.ino, None, None, None, Some(0)).unwrap();
+        fs.write_handle(fh, preview.ino, 0, request.as_bytes())
+            .unwrap();
+        String::from_utf8(fs.read_note(preview.ino, 0).unwrap()).unwrap()
+    };
+
+    // A folder never listed still reports its whole subtree
+    let expected = "/Documents\t/Archive/Papers\n\
+         /Documents/Sub\t/Archive/Papers/Sub\n\
+         /Documents/Sub/deep.md\t/Archive/Papers/Sub/deep.md\n\
+         /Documents/document.md.md\t/Archive/Papers/document.md.md\n";
+    assert_eq!(ask(&mut fs, "/Documents\n/Archive/Papers\n"), expected);
+    assert_eq!(
+        fs.get_attr(preview.ino).unwrap().size,
+        expected.len() as u64
+    );
+    // Nothing moved
+    let documents = fs.lookup_child(1, "Documents").unwrap();
+    assert!(fs.lookup_child(documents.ino, "Sub").is_ok());
+    assert_eq!(fs.get_path_from_inode(documents.ino).unwrap(), "/Documents");
+
+    assert!(
+        ask(&mut fs, "/Missing\n/Archive/Missing")
+            .starts_with("error: /Missing -> /Archive/Missing: No such file")
+    );
+    assert!(ask(&mut fs, "/Documents\n/readme.txt.md").contains("Not a directory"));
+    assert!(ask(&mut fs, "/readme.txt.md\n/Archive").contains("Is a directory"));
+    assert!(ask(&mut fs, "/Documents").starts_with("error: expected"));
+    assert_eq!(
+        ask(&mut fs, "/readme.txt.md\n/Archive/readme.md"),
+        "/readme.txt.md\t/Archive/readme.md\n"
+    );
+}
+
+#[test]
+fn test_normalize_unicode_matches_decomposed_names() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "INSERT INTO folders (id, title, created_time, updated_time, parent_id) VALUES
+             ('resumes', 'R\u{e9}sum\u{e9}s', 0, 0, '');
+         INSERT INTO notes (id, title, body, created_time, updated_time, parent_id) VALUES
+             ('cafe', 'Caf\u{e9}', 'espresso', 0, 0, '');",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            normalize_unicode: true,
+            ..FsOptions::default()
+        },
+    );
+
+    // As macOS sends them: e followed by a combining acute accent
+    let note = fs.lookup_child(1, "Cafe\u{301}.md").unwrap();
+    assert_eq!(fs.read_note(note.ino, 0).unwrap(), b"espresso");
+    assert_eq!(fs.get_path_from_inode(note.ino).unwrap(), "/Caf\u{e9}.md");
+    let folder = fs.lookup_child(1, "Re\u{301}sume\u{301}s").unwrap();
+    assert_eq!(folder.kind, FileType::Directory);
+    assert_eq!(fs.lookup_child(1, "Cafe\u{301}s.md"), Err(ENOENT));
+
+    fs.rename_entry(1, "Cafe\u{301}.md", folder.ino, "Coffee.md")
+        .unwrap();
+    assert_eq!(
+        listed_names(&fs.list_directory(folder.ino).unwrap()),
+        ["Coffee.md"]
+    );
+
+    let matches = cli()
+        .try_get_matches_from(["sqlite_fuse", "db", "/mnt", "--normalize-unicode=false"])
+        .unwrap();
+    assert_eq!(matches.get_one::<bool>("normalize-unicode"), Some(&false));
+    let mut fs = fs_from_conn(create_test_db().unwrap(), FsOptions::default());
+    fs.create_file("/", "Cafe\u{301}.md").unwrap();
+    assert_eq!(fs.lookup_child(1, "Caf\u{e9}.md"), Err(ENOENT));
+}
+
+#[test]
+fn test_backlinks_list_linking_notes() {
+    let conn = create_test_db().expect("Failed to create test database");
+    let target = "b0000000000000000000000000000001";
+    conn.execute_batch(&format!(
+        "INSERT INTO notes (id, title, body, created_time, updated_time, parent_id) VALUES
+             ('{target}', 'B', 'target', 0, 0, ''),
+             ('a0000000000000000000000000000001', 'A', 'See [B](:/{target}) and [again](:/{target}#x)', 0, 0, 'folder1'),
+             ('c0000000000000000000000000000001', 'C', 'no links', 0, 0, '');"
+    ))
+    .unwrap();
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            backlinks: true,
+            ..FsOptions::default()
+        },
+    );
+
+    // Looked up by name only
+    assert!(!listed_names(&fs.list_directory(1).unwrap()).contains(&"B.md.backlinks".to_string()));
+    let dir = fs.lookup_child(1, "B.md.backlinks").unwrap();
+    assert_eq!(dir.kind, FileType::Directory);
+    assert_eq!(listed_names(&fs.list_directory(dir.ino).unwrap()), ["A.md"]);
+    let link = fs.lookup_child(dir.ino, "A.md").unwrap();
+    assert_eq!(link.kind, FileType::Symlink);
+    assert_eq!(fs.read_link(link.ino).unwrap(), b"../Documents/A.md");
+    let none = fs.lookup_child(1, "C.md.backlinks").unwrap();
+    assert!(listed_names(&fs.list_directory(none.ino).unwrap()).is_empty());
+
+    // A new link shows up once the index notices the change
+    fs.db
+        .execute(
+            &format!("UPDATE notes SET body = ':/{target}', updated_time = (SELECT MAX(updated_time) + 1 FROM notes) WHERE id = 'c0000000000000000000000000000001'"),
+            [],
+        )
+        .unwrap();
+    assert_eq!(
+        listed_names(&fs.list_directory(dir.ino).unwrap()),
+        ["A.md", "C.md"]
+    );
+
+    let mut fs = fs_from_conn(create_test_db().unwrap(), FsOptions::default());
+    assert_eq!(fs.lookup_child(1, "readme.txt.md.backlinks"), Err(ENOENT));
+}
+
+#[test]
+fn test_todo_suffix_marks_todos_in_names() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "INSERT INTO notes (id, title, body, created_time, updated_time, parent_id) VALUES
+             ('odd', 'odd.todo', 'plain', 0, 0, '');",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            todo_suffix: Some("todo".to_string()),
+            ..FsOptions::default()
+        },
+    );
+    let is_todo = |fs: &SqliteFS, title: &str| -> i64 {
+        fs.db
+            .query_row(
+                "SELECT is_todo FROM notes WHERE title = ?1",
+                [title],
+                |row| row.get(0),
+            )
+            .unwrap()
+    };
+
+    // Create as a to-do: the marker is stripped from the title
+    fs.create_file("/", "task.todo.md").unwrap();
+    assert_eq!(is_todo(&fs, "task"), 1);
+    let mut names = listed_names(&fs.list_directory(1).unwrap());
+    names.sort();
+    assert_eq!(
+        names,
+        ["Documents", "odd.todo.md", "readme.txt.md", "task.todo.md"]
+    );
+    assert!(fs.lookup_child(1, "task").is_ok());
+    assert_eq!(fs.lookup_child(1, "task.md"), Err(ENOENT));
+    // A plain note whose title ends in the marker still opens
+    let odd = fs.lookup_child(1, "odd.todo.md").unwrap();
+    assert_eq!(fs.read_note(odd.ino, 0).unwrap(), b"plain");
+
+    // Rename to the marked form and back
+    fs.rename_entry(1, "readme.txt.md", 1, "readme.txt.todo.md")
+        .unwrap();
+    assert_eq!(is_todo(&fs, "readme.txt"), 1);
+    assert!(fs.lookup_child(1, "readme.txt.todo.md").is_ok());
+    fs.rename_entry(1, "readme.txt.todo.md", 1, "readme.txt.md")
+        .unwrap();
+    assert_eq!(is_todo(&fs, "readme.txt"), 0);
+    assert_eq!(fs.lookup_child(1, "readme.txt.todo.md"), Err(ENOENT));
+
+    assert_eq!(parse_todo_marker(".todo"), Ok("todo".to_string()));
+    assert!(parse_todo_marker("a.b").is_err());
+    assert!(parse_todo_marker("").is_err());
+}
+
+#[test]
+fn test_rebuild_fts_makes_new_notes_findable() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "CREATE VIRTUAL TABLE notes_fts USING fts4(content=\"notes\", id, title, body);",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            control: true,
+            ..FsOptions::default()
+        },
+    );
+    let file = fs.create_file("/", "lighthouse.md").unwrap();
+    fs.write_note(file.ino, 0, b"keeper of the beacon").unwrap();
+    fs.commit_note(&fs.resolve_path("/lighthouse.md").unwrap().1)
+        .unwrap();
+    let found = |fs: &SqliteFS| -> Vec<String> {
+        let mut stmt = fs
+            .db
+            .prepare("SELECT title FROM notes_fts WHERE notes_fts MATCH 'beacon'")
+            .unwrap();
+        stmt.query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap()
+    };
+    assert!(found(&fs).is_empty());
+
+    let control = fs.lookup_child(1, ".control").unwrap();
+    let rebuild = fs.lookup_child(control.ino, "rebuild-fts").unwrap();
+    // Truncating alone does not rebuild
+    fs.set_attr(rebuild.ino, None, None, None, Some(0)).unwrap();
+    assert!(found(&fs).is_empty());
+    fs.write_note(rebuild.ino, 0, b"\n").unwrap();
+    assert_eq!(
+        fs.read_note(rebuild.ino, 0).unwrap(),
+        b"rebuilt notes_fts\n"
+    );
+    assert_eq!(found(&fs), ["lighthouse"]);
+
+    let fs = fs_from_conn(create_test_db().unwrap(), FsOptions::default());
+    assert_eq!(fs.rebuild_fts(), Ok(false));
+}
+
+#[test]
+fn test_max_inodes_fails_lookup_when_all_are_referenced() {
+    let conn = create_test_db().expect("Failed to create test database");
+    for i in 0..6 {
+        conn.execute(
+            "INSERT INTO notes (id, title, body, created_time, updated_time, parent_id) VALUES (?1, ?1, '', 0, 0, '')",
+            [format!("n{i}")],
+        )
+        .unwrap();
+    }
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            max_inodes: Some(6),
+            ..FsOptions::default()
+        },
+    );
+
+    // The root and five notes the kernel still holds fill the table
+    let held: Vec<u64> = (0..5)
+        .map(|i| fs.lookup_child(1, &format!("n{i}.md")).unwrap().ino)
+        .collect();
+    assert_eq!(fs.lookup_child(1, "n5.md"), Err(libc::ENFILE));
+    assert_eq!(fs.create_file("/", "new.md"), Err(libc::ENFILE));
+    assert!(fs.resolve_path("/new.md").is_none());
+    assert_eq!(fs.list_directory(1).map(|_| ()), Err(libc::ENFILE));
+    // Known paths still resolve to their inodes
+    assert_eq!(fs.lookup_child(1, "n0.md").unwrap().ino, held[0]);
+
+    // Once the kernel forgets an inode it makes way for the next
+    fs.inodes.forget(held[1], 1);
+    let n5 = fs.lookup_child(1, "n5.md").unwrap();
+    assert!(!held.contains(&n5.ino));
+    assert!(fs.get_path_from_inode(held[1]).is_none());
+    assert_eq!(fs.get_path_from_inode(held[2]).unwrap(), "/n2.md");
+}
+
+#[test]
+fn test_todo_as_exec_reflects_is_todo() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "INSERT INTO notes (id, title, body, created_time, updated_time, parent_id, is_todo, todo_completed) VALUES
+             ('open', 'open', '', 0, 0, '', 1, 0),
+             ('done', 'done', '', 0, 0, '', 1, 1700000000000);",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(
+        conn,
+        FsOptions {
+            todo_as_exec: Some(TodoExec::All),
+            ..FsOptions::default()
+        },
+    );
+    let perm = |fs: &mut SqliteFS, name: &str| fs.lookup_child(1, name).unwrap().perm;
+    assert_eq!(perm(&mut fs, "open.md"), 0o755);
+    assert_eq!(perm(&mut fs, "done.md"), 0o755);
+    assert_eq!(perm(&mut fs, "readme.txt.md"), 0o644);
+
+    // chmod neither adds the bits to a plain note nor clears is_todo
+    let readme = fs.lookup_child(1, "readme.txt.md").unwrap();
+    fs.set_attr(readme.ino, Some(0o750), None, None, None)
+        .unwrap();
+    assert_eq!(perm(&mut fs, "readme.txt.md"), 0o640);
+    let open = fs.lookup_child(1, "open.md").unwrap();
+    fs.set_attr(open.ino, Some(0o600), None, None, None)
+        .unwrap();
+    assert_eq!(perm(&mut fs, "open.md"), 0o700);
+
+    fs.options.todo_as_exec = Some(TodoExec::Open);
+    assert_eq!(perm(&mut fs, "done.md"), 0o644);
+    assert_eq!(perm(&mut fs, "open.md"), 0o700);
+}
+
+#[test]
+fn test_resolve_duplicate_parents_renames_siblings() {
+    let conn = create_test_db().expect("Failed to create test database");
+    conn.execute_batch(
+        "INSERT INTO folders (id, title, created_time, updated_time, user_updated_time, parent_id) VALUES
+             ('work1', 'Work', 0, 0, 10, ''),
+             ('work2', 'Work', 0, 0, 20, ''),
+             ('work3', 'Work', 0, 0, 5, ''),
+             ('other', 'Work', 0, 0, 0, 'folder1');
+         INSERT INTO notes (id, title, body, created_time, updated_time, parent_id) VALUES
+             ('old', 'plan', 'older work', 0, 0, 'work1'),
+             ('new', 'plan', 'newer work', 0, 0, 'work2');",
+    )
+    .unwrap();
+    let mut fs = fs_from_conn(conn, FsOptions::default());
+
+    let renames = fs.rename_duplicate_folders().unwrap();
+    assert_eq!(
+        renames
+            .iter()
+            .map(FolderRename::to_string)
+            .collect::<Vec<_>>(),
+        [
+            "renamed folder work1 from \"Work\" to \"Work (work1)\"",
+            "renamed folder work3 from \"Work\" to \"Work (work3)\"",
+        ]
+    );
+    let (updated, user_updated): (i64, i64) = fs
+        .db
+        .query_row(
+            "SELECT updated_time, user_updated_time FROM folders WHERE id = 'work1'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert!(updated > 0);
+    assert_eq!(updated, user_updated);
+    let mut names = listed_names(&fs.list_directory(1).unwrap());
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            "Documents",
+            "Work",
+            "Work (work1)",
+            "Work (work3)",
+            "readme.txt.md"
+        ]
+    );
+
+    // Each folder kept its notes; a title repeated at another level is fine
+    for (folder, body) in [("Work", "newer work"), ("Work (work1)", "older work")] {
+        let dir = fs.lookup_child(1, folder).unwrap();
+        let note = fs.lookup_child(dir.ino, "plan.md").unwrap();
+        assert_eq!(fs.read_note(note.ino, 0).unwrap(), body.as_bytes());
+    }
+    assert!(fs.rename_duplicate_folders().unwrap().is_empty());
+}
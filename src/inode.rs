@@ -0,0 +1,243 @@
+//! Allocation of inode numbers and the maps between inodes and paths
+
+use log::warn;
+use std::collections::HashMap;
+
+/// First inode available to `--pin-inode`; those below it belong to the root
+/// and the virtual directories
+pub(crate) const FIRST_PINNABLE_INODE: u64 = 16;
+/// First inode handed out for ordinary paths; the ones below it are reserved
+/// so every virtual directory and pinned path keeps a fixed inode
+pub(crate) const FIRST_DYNAMIC_INODE: u64 = 1024;
+
+/// Parses a `--pin-inode` entry of the form `/path=number`
+pub(crate) fn parse_pin_inode_entry(value: &str) -> std::result::Result<(String, u64), String> {
+    let (path, inode) = value
+        .rsplit_once('=')
+        .ok_or_else(|| "expected <path>=<inode>".to_string())?;
+    let path = path.trim_end_matches('/');
+    if !path.starts_with('/') {
+        return Err(format!(
+            "{path:?} is not an absolute path below the mount root"
+        ));
+    }
+    let inode: u64 = inode
+        .parse()
+        .map_err(|_| format!("invalid inode {inode:?}"))?;
+    if !(FIRST_PINNABLE_INODE..FIRST_DYNAMIC_INODE).contains(&inode) {
+        return Err(format!(
+            "inode {inode} is outside the pinnable range {FIRST_PINNABLE_INODE}..{FIRST_DYNAMIC_INODE}"
+        ));
+    }
+    Ok((path.to_string(), inode))
+}
+
+/// Combine `--pin-inode` entries, rejecting a path pinned to two inodes or
+/// an inode pinned to two paths
+pub(crate) fn pinned_inodes(
+    entries: impl IntoIterator<Item = (String, u64)>,
+) -> std::result::Result<HashMap<String, u64>, String> {
+    let mut pins = HashMap::new();
+    let mut owners = HashMap::new();
+    for (path, inode) in entries {
+        if let Some(other) = pins.insert(path.clone(), inode)
+            && other != inode
+        {
+            return Err(format!("{path} is pinned to both {other} and {inode}"));
+        }
+        if let Some(other) = owners.insert(inode, path.clone())
+            && other != path
+        {
+            return Err(format!(
+                "inode {inode} is pinned to both {other} and {path}"
+            ));
+        }
+    }
+    Ok(pins)
+}
+
+/// The inode of every path handed to the kernel, and the path of every inode
+///
+/// The root and the `--pin-inode` paths are seeded up front; other paths are
+/// numbered from `FIRST_DYNAMIC_INODE` on first lookup and keep their number
+/// for the life of the mount, across renames. Under `--persist-inodes` the
+/// caller reserves the numbers earlier mounts recorded and assigns them
+/// itself.
+///
+/// Under `--max-inodes` the table stops growing at the cap: the oldest
+/// dynamic inodes the kernel holds no reference to are dropped to make room,
+/// and when every one is still referenced nothing more is allocated.
+#[derive(Debug)]
+pub(crate) struct InodeTable {
+    by_path: HashMap<String, u64>,
+    by_inode: HashMap<u64, String>,
+    next: u64,
+    /// Lookups the kernel has not yet forgotten, per inode
+    lookups: HashMap<u64, u64>,
+    max: Option<usize>,
+    /// Whether the table has been reported as nearly full since it last
+    /// had room to spare
+    warned: bool,
+}
+
+impl InodeTable {
+    /// A table mapping the root to inode 1 and every pinned path to its number
+    pub(crate) fn new(pins: &HashMap<String, u64>) -> Self {
+        let mut table = InodeTable {
+            by_path: HashMap::new(),
+            by_inode: HashMap::new(),
+            next: FIRST_DYNAMIC_INODE,
+            lookups: HashMap::new(),
+            max: None,
+            warned: false,
+        };
+        table.insert("/", 1);
+        for (path, &inode) in pins {
+            table.insert(path, inode);
+        }
+        table
+    }
+
+    pub(crate) fn inode(&self, path: &str) -> Option<u64> {
+        self.by_path.get(path).copied()
+    }
+
+    pub(crate) fn path(&self, inode: u64) -> Option<&String> {
+        self.by_inode.get(&inode)
+    }
+
+    /// Map `path` to `inode` in both directions
+    pub(crate) fn insert(&mut self, path: &str, inode: u64) {
+        self.by_path.insert(path.to_string(), inode);
+        self.by_inode.insert(inode, path.to_string());
+    }
+
+    /// Cap the table at `max` entries (`--max-inodes`)
+    pub(crate) fn set_limit(&mut self, max: Option<usize>) {
+        self.max = max;
+    }
+
+    /// Map `path` to the next unused dynamic inode and return it, or None
+    /// when the table is at its cap and nothing can be evicted
+    pub(crate) fn allocate(&mut self, path: &str) -> Option<u64> {
+        if !self.make_room() {
+            return None;
+        }
+        let inode = self.next;
+        self.next += 1;
+        self.insert(path, inode);
+        Some(inode)
+    }
+
+    /// Map `path` to `inode`, a dynamic inode the caller chose and no other
+    /// path holds, subject to the cap like `allocate`; false when there is
+    /// no room
+    pub(crate) fn assign(&mut self, path: &str, inode: u64) -> bool {
+        if !self.make_room() {
+            return false;
+        }
+        self.insert(path, inode);
+        true
+    }
+
+    /// Allocate only inodes above `inode`, which is spoken for elsewhere
+    pub(crate) fn reserve_through(&mut self, inode: u64) {
+        self.next = self.next.max(inode + 1);
+    }
+
+    /// Whether one more inode fits under the cap, evicting unreferenced
+    /// ones first when the table is full
+    ///
+    /// A full table drops the oldest eighth of its unreferenced dynamic
+    /// inodes at once, so the scan is not repeated on every allocation.
+    /// Warns once when the table passes nine tenths of the cap.
+    pub(crate) fn make_room(&mut self) -> bool {
+        let Some(max) = self.max else {
+            return true;
+        };
+        if self.by_inode.len() >= max {
+            let mut unreferenced: Vec<u64> = self
+                .by_inode
+                .keys()
+                .copied()
+                .filter(|inode| *inode >= FIRST_DYNAMIC_INODE && !self.lookups.contains_key(inode))
+                .collect();
+            unreferenced.sort_unstable();
+            unreferenced.truncate((max / 8).max(1));
+            for inode in unreferenced {
+                if let Some(path) = self.by_inode.remove(&inode) {
+                    self.by_path.remove(&path);
+                }
+            }
+        }
+        let len = self.by_inode.len();
+        if len >= max {
+            return false;
+        }
+        if len * 10 >= max * 9 {
+            if !self.warned {
+                warn!(
+                    "{len} of --max-inodes {max} inodes in use; lookups fail with ENFILE once all are held"
+                );
+                self.warned = true;
+            }
+        } else {
+            self.warned = false;
+        }
+        true
+    }
+
+    /// Count a reference to `inode` handed to the kernel in an entry reply
+    pub(crate) fn remember(&mut self, inode: u64) {
+        *self.lookups.entry(inode).or_default() += 1;
+    }
+
+    /// Drop `nlookup` of the kernel's references to `inode`, as `forget`
+    /// reports; with none left the inode may be evicted
+    pub(crate) fn forget(&mut self, inode: u64, nlookup: u64) {
+        if let Some(count) = self.lookups.get_mut(&inode) {
+            *count = count.saturating_sub(nlookup);
+            if *count == 0 {
+                self.lookups.remove(&inode);
+            }
+        }
+    }
+
+    /// Forget `path` and its inode
+    pub(crate) fn remove(&mut self, path: &str) -> Option<u64> {
+        let inode = self.by_path.remove(path)?;
+        self.by_inode.remove(&inode);
+        Some(inode)
+    }
+
+    /// Move `old_path` and, for a directory, everything below it to
+    /// `new_path`, keeping their inodes
+    pub(crate) fn rename(&mut self, old_path: &str, new_path: &str) {
+        for (path, moved_path) in self.renamed_paths(old_path, new_path) {
+            let inode = self
+                .by_path
+                .remove(&path)
+                .expect("path listed by renamed_paths");
+            self.insert(&moved_path, inode);
+        }
+    }
+
+    /// The known paths `rename(old_path, new_path)` moves, as `(from, to)`
+    /// in path order: `old_path` and every path below it
+    pub(crate) fn renamed_paths(&self, old_path: &str, new_path: &str) -> Vec<(String, String)> {
+        let old_prefix = format!("{old_path}/");
+        let mut moved: Vec<_> = self
+            .by_path
+            .keys()
+            .filter(|path| *path == old_path || path.starts_with(&old_prefix))
+            .map(|path| {
+                (
+                    path.clone(),
+                    format!("{new_path}{}", &path[old_path.len()..]),
+                )
+            })
+            .collect();
+        moved.sort();
+        moved
+    }
+}
@@ -0,0 +1,13 @@
+//! A FUSE filesystem over the SQLite database of a Joplin profile
+//!
+//! Notebooks appear as directories and notes as Markdown files. The
+//! `sqlite_fuse` binary is a thin wrapper around [`run`].
+
+mod cli;
+mod fs;
+mod inode;
+mod store;
+
+pub use cli::run;
+pub use fs::{AttrConfig, FsError, FsOptions, SqliteFS};
+pub use store::NoteStore;
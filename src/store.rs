@@ -0,0 +1,596 @@
+//! Database access shared by the filesystem and the background maintenance
+
+use encoding_rs::Encoding;
+use log::{info, warn};
+use rusqlite::ToSql;
+use rusqlite::types::{ToSqlOutput, ValueRef};
+use rusqlite::{Connection, OptionalExtension, Result};
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long a write waits for another process holding the database's write
+/// lock before failing
+pub(crate) const DB_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Joplin's full-text index of note titles and bodies, an FTS table whose
+/// content comes from the notes
+pub(crate) const FTS_TABLE: &str = "notes_fts";
+
+/// Where `--persist-inodes` records the inode each note and folder id was
+/// given, so later mounts hand out the same numbers
+pub(crate) const INODE_TABLE: &str = "fuse_inodes";
+
+/// The queries on Joplin's tables that depend on nothing but the database
+///
+/// Implemented for `rusqlite::Connection`; everything that needs the mount
+/// options (listed names, encodings, the mounted subtree) stays on `SqliteFS`.
+pub trait NoteStore {
+    /// Create the indexes the directory lookups and listings rely on, unless
+    /// they already exist
+    fn ensure_indexes(&self) -> Result<()>;
+
+    /// Advance a folder's `updated_time` (its mtime) to `now`
+    ///
+    /// Called whenever an entry inside it is created, edited, moved or
+    /// removed, so tools that skip unchanged directories by mtime notice.
+    /// The top level has no row and is left alone.
+    fn touch_folder(&self, folder_id: &str, now: i64) -> Result<()>;
+
+    /// `touch_folder` for the folder holding `note_id`
+    fn touch_note_parent(&self, note_id: &str, now: i64) -> Result<()>;
+
+    /// Stamp the row `id` of `table` (`notes` or `folders`) as changed at `now`
+    ///
+    /// Joplin's sync uploads the rows whose `updated_time` has advanced,
+    /// while its editor shows `user_updated_time` as when the user last
+    /// changed the note, so a change made through the mount advances both.
+    /// Returns the number of rows stamped.
+    fn touch_row(&self, table: &str, id: &str, now: i64) -> Result<usize>;
+
+    /// Live notes titled `title` in `parent_id`, newest first
+    ///
+    /// This order decides which duplicate keeps the plain name and how the
+    /// rest are numbered under `--title-collision-policy`.
+    fn note_candidates(&self, parent_id: &str, title: &str) -> Result<Vec<String>>;
+
+    /// Repopulate `FTS_TABLE` from its content table with FTS's `rebuild`
+    /// command, so notes written without Joplin's triggers become findable
+    ///
+    /// Returns false, changing nothing, when the database has no such table.
+    fn rebuild_fts(&self) -> Result<bool>;
+
+    /// Create `INODE_TABLE` unless it already exists
+    fn ensure_inode_table(&self) -> Result<()>;
+
+    /// The inode recorded in `INODE_TABLE` for each id; empty when the
+    /// table does not exist
+    fn persisted_inodes(&self) -> Result<HashMap<String, u64>>;
+
+    /// Record in `INODE_TABLE` that `id` has `inode`
+    fn persist_inode(&self, id: &str, inode: u64) -> Result<()>;
+}
+
+impl NoteStore for Connection {
+    fn ensure_indexes(&self) -> Result<()> {
+        self.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_folders_parent_title ON folders(parent_id, title) WHERE deleted_time = 0;
+             CREATE INDEX IF NOT EXISTS idx_notes_parent_title ON notes(parent_id, title) WHERE deleted_time = 0;
+             CREATE INDEX IF NOT EXISTS idx_folders_parent_updated ON folders(parent_id, user_updated_time) WHERE deleted_time = 0;
+             CREATE INDEX IF NOT EXISTS idx_notes_parent_updated ON notes(parent_id, user_updated_time) WHERE deleted_time = 0;",
+        )
+    }
+
+    fn touch_folder(&self, folder_id: &str, now: i64) -> Result<()> {
+        self.execute(
+            "UPDATE folders SET updated_time = MAX(updated_time, ?1) WHERE id = ?2",
+            rusqlite::params![now, folder_id],
+        )?;
+        Ok(())
+    }
+
+    fn touch_note_parent(&self, note_id: &str, now: i64) -> Result<()> {
+        self.execute(
+            "UPDATE folders SET updated_time = MAX(updated_time, ?1) WHERE id = (SELECT parent_id FROM notes WHERE id = ?2)",
+            rusqlite::params![now, note_id],
+        )?;
+        Ok(())
+    }
+
+    fn touch_row(&self, table: &str, id: &str, now: i64) -> Result<usize> {
+        self.execute(
+            &format!("UPDATE {table} SET updated_time = ?1, user_updated_time = ?1 WHERE id = ?2"),
+            rusqlite::params![now, id],
+        )
+    }
+
+    fn note_candidates(&self, parent_id: &str, title: &str) -> Result<Vec<String>> {
+        // The unary + keeps the planner on the (parent_id, title) index
+        // rather than scanning the whole folder in user_updated_time order
+        let mut stmt = self.prepare_cached(
+            "SELECT id FROM notes WHERE parent_id = ?1 AND title = ?2 AND deleted_time = 0 ORDER BY +user_updated_time DESC, id",
+        )?;
+        stmt.query_map([parent_id, title], |row| row.get(0))?
+            .collect()
+    }
+
+    fn rebuild_fts(&self) -> Result<bool> {
+        let exists: bool = self.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+            [FTS_TABLE],
+            |row| row.get(0),
+        )?;
+        if exists {
+            self.execute(
+                &format!("INSERT INTO {FTS_TABLE}({FTS_TABLE}) VALUES('rebuild')"),
+                [],
+            )?;
+        }
+        Ok(exists)
+    }
+
+    fn ensure_inode_table(&self) -> Result<()> {
+        self.execute(
+            &format!(
+                "CREATE TABLE IF NOT EXISTS {INODE_TABLE} (id TEXT PRIMARY KEY, inode INTEGER NOT NULL)"
+            ),
+            [],
+        )?;
+        Ok(())
+    }
+
+    fn persisted_inodes(&self) -> Result<HashMap<String, u64>> {
+        let exists: bool = self.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+            [INODE_TABLE],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            return Ok(HashMap::new());
+        }
+        let mut stmt = self.prepare(&format!("SELECT id, inode FROM {INODE_TABLE}"))?;
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect()
+    }
+
+    fn persist_inode(&self, id: &str, inode: u64) -> Result<()> {
+        self.execute(
+            &format!("INSERT OR REPLACE INTO {INODE_TABLE} (id, inode) VALUES (?1, ?2)"),
+            rusqlite::params![id, inode as i64],
+        )?;
+        Ok(())
+    }
+}
+
+/// Ids of the notes whose bodies are mid read-modify-write in this process
+///
+/// Process-wide rather than per `SqliteFS`, so every connection the process
+/// opens on a database takes turns on the same note. Notes are keyed by id
+/// alone: two databases sharing an id merely wait on each other.
+pub(crate) static LOCKED_NOTES: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// Signalled whenever a note leaves `LOCKED_NOTES`
+pub(crate) static NOTE_UNLOCKED: Condvar = Condvar::new();
+
+/// Exclusive hold on one note's body, released on drop
+///
+/// Writers to different notes never wait on each other.
+pub(crate) struct NoteLock {
+    note_id: String,
+}
+
+impl NoteLock {
+    /// Block until no other writer holds `note_id`, then hold it
+    pub(crate) fn acquire(note_id: &str) -> Self {
+        let mut locked = LOCKED_NOTES.lock().unwrap_or_else(|e| e.into_inner());
+        while locked.contains(note_id) {
+            locked = NOTE_UNLOCKED
+                .wait(locked)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        locked.insert(note_id.to_string());
+        NoteLock {
+            note_id: note_id.to_string(),
+        }
+    }
+}
+
+impl Drop for NoteLock {
+    fn drop(&mut self) {
+        LOCKED_NOTES
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.note_id);
+        NOTE_UNLOCKED.notify_all();
+    }
+}
+
+/// Suffix of the lock file a writable mount holds beside its database
+pub(crate) const MOUNT_LOCK_SUFFIX: &str = ".fuse-lock";
+
+/// Advisory lock held by a writable mount on `<database>.fuse-lock`, so a
+/// second writable mount of the same file is refused
+///
+/// An exclusive `flock`, released when dropped or when the process exits,
+/// crash included, so a stale lock never blocks a later mount. The file
+/// keeps the holder's pid for the refusal message and is left in place:
+/// deleting it could let two mounts hold locks on different files.
+#[derive(Debug)]
+pub(crate) struct MountLock {
+    _file: std::fs::File,
+}
+
+impl MountLock {
+    /// Take the lock for the database at `database_path`, or describe who
+    /// holds it
+    pub(crate) fn acquire(database_path: &str) -> std::result::Result<Self, String> {
+        use std::io::{Read, Seek, Write};
+        use std::os::fd::AsRawFd;
+
+        let path = format!("{database_path}{MOUNT_LOCK_SUFFIX}");
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(|e| format!("cannot open lock file {path}: {e}"))?;
+        // SAFETY: flock only reads the descriptor, which `file` keeps open
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            let mut holder = String::new();
+            let _ = file.read_to_string(&mut holder);
+            let holder = match holder.trim() {
+                "" => String::new(),
+                pid => format!(" (pid {pid})"),
+            };
+            return Err(format!(
+                "{database_path} is already mounted writable by another process{holder}, per {path}"
+            ));
+        }
+        let _ = file
+            .set_len(0)
+            .and_then(|()| file.rewind())
+            .and_then(|()| writeln!(file, "{}", std::process::id()));
+        Ok(MountLock { _file: file })
+    }
+}
+
+/// A note body bound as TEXT in the `--encoding` it is stored in
+///
+/// Binding fails with `ToSqlConversionFailure` when the body holds characters
+/// the encoding cannot represent, rather than storing a lossy substitute.
+pub(crate) struct StoredBody<'a> {
+    pub(crate) bytes: Cow<'a, [u8]>,
+    /// The encoding that could not represent every character, if any
+    pub(crate) unmappable_in: Option<&'static Encoding>,
+}
+
+impl ToSql for StoredBody<'_> {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        if let Some(encoding) = self.unmappable_in {
+            return Err(rusqlite::Error::ToSqlConversionFailure(
+                format!("note body is not representable in {}", encoding.name()).into(),
+            ));
+        }
+        Ok(ToSqlOutput::Borrowed(ValueRef::Text(&self.bytes)))
+    }
+}
+
+/// Where Joplin keeps the resource files of the database at `db_path`
+///
+/// A `resourceDir` entry in Joplin's `settings` table wins, resolved against
+/// the database's directory when relative. Otherwise the profile layout is
+/// assumed: `resources/` beside `database.sqlite`.
+pub(crate) fn discover_resource_dir(db_path: &str) -> std::path::PathBuf {
+    let db_dir = std::path::Path::new(db_path)
+        .parent()
+        .unwrap_or(std::path::Path::new("."));
+    // Databases without a settings table, or unreadable ones, use the layout
+    let configured =
+        Connection::open_with_flags(db_path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .and_then(|conn| {
+                conn.query_row(
+                    "SELECT value FROM settings WHERE key = 'resourceDir'",
+                    [],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()
+            })
+            .ok()
+            .flatten()
+            // Joplin may store the value JSON-encoded
+            .map(|value| serde_json::from_str::<String>(&value).unwrap_or(value))
+            .filter(|value| !value.trim().is_empty());
+    match configured {
+        Some(dir) => db_dir.join(dir),
+        None => db_dir.join("resources"),
+    }
+}
+
+/// What one round of background maintenance did
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct MaintenanceReport {
+    /// The checkpoint could not finish because another connection held the
+    /// database; it is retried next round
+    pub(crate) busy: bool,
+    /// Bytes the WAL shrank by (0 outside WAL mode)
+    pub(crate) wal_bytes_freed: u64,
+    /// Trashed notes and folders deleted for good
+    pub(crate) purged_notes: usize,
+    pub(crate) purged_folders: usize,
+}
+
+/// One round of maintenance on `conn`: checkpoint and truncate the WAL and,
+/// with a `trash_retention`, delete rows trashed longer ago than that
+///
+/// Nothing waits on locks: `conn` should have no busy timeout, so a write in
+/// progress elsewhere makes this round stop and report `busy` instead of
+/// contending with it.
+pub(crate) fn run_maintenance(
+    conn: &Connection,
+    trash_retention: Option<Duration>,
+    now: i64,
+) -> Result<MaintenanceReport> {
+    let mut report = MaintenanceReport::default();
+    // Purge first so the checkpoint also folds the deletions into the database
+    if let Some(retention) = trash_retention {
+        let cutoff = now - retention.as_secs() as i64;
+        let purge = |table: &str| {
+            conn.execute(
+                &format!("DELETE FROM {table} WHERE deleted_time != 0 AND deleted_time < ?1"),
+                [cutoff],
+            )
+        };
+        match purge("notes").and_then(|notes| Ok((notes, purge("folders")?))) {
+            Ok((notes, folders)) => {
+                report.purged_notes = notes;
+                report.purged_folders = folders;
+            }
+            Err(err) if err.sqlite_error_code() == Some(rusqlite::ErrorCode::DatabaseBusy) => {
+                report.busy = true;
+                return Ok(report);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    let wal_len = || {
+        conn.path()
+            .and_then(|path| std::fs::metadata(format!("{path}-wal")).ok())
+            .map_or(0, |meta| meta.len())
+    };
+    let wal_before = wal_len();
+    report.busy = conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| {
+        Ok(row.get::<_, i64>(0)? != 0)
+    })?;
+    report.wal_bytes_freed = wal_before.saturating_sub(wal_len());
+    Ok(report)
+}
+
+/// Run `run_maintenance` every `interval` on a connection of its own for as
+/// long as the process lives, logging what each round did
+pub(crate) fn spawn_maintenance(
+    db_path: String,
+    interval: Duration,
+    trash_retention: Option<Duration>,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        let conn = match Connection::open(&db_path)
+            .and_then(|conn| conn.busy_timeout(Duration::ZERO).map(|_| conn))
+        {
+            Ok(conn) => conn,
+            Err(e) => {
+                warn!("maintenance disabled: cannot open {db_path}: {e}");
+                return;
+            }
+        };
+        loop {
+            std::thread::sleep(interval);
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs() as i64;
+            match run_maintenance(&conn, trash_retention, now) {
+                Ok(report) if report.busy => {
+                    info!("maintenance: database busy, retrying in {interval:?}")
+                }
+                Ok(report) => info!(
+                    "maintenance: freed {} bytes of WAL, purged {} notes and {} folders from the trash",
+                    report.wal_bytes_freed, report.purged_notes, report.purged_folders
+                ),
+                Err(e) => warn!("maintenance failed: {e}"),
+            }
+        }
+    })
+}
+
+/// Shared in-memory database that `--selftest` mounts, so the connection
+/// holding its schema and the filesystem's own connection see one database
+pub(crate) const SELFTEST_DB_URI: &str = "file:sqlite-fuse-selftest?mode=memory&cache=shared";
+
+/// Create the `--selftest` database: Joplin's `folders` and `notes` tables
+/// holding one notebook and one note
+///
+/// The database lives until the returned connection is dropped.
+pub(crate) fn selftest_database() -> Result<Connection> {
+    let conn = Connection::open(SELFTEST_DB_URI)?;
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS notes (id TEXT PRIMARY KEY, parent_id TEXT NOT NULL DEFAULT '', title TEXT NOT NULL DEFAULT '', body TEXT NOT NULL DEFAULT '', created_time INT NOT NULL, updated_time INT NOT NULL, is_conflict INT NOT NULL DEFAULT 0, latitude NUMERIC NOT NULL DEFAULT 0, longitude NUMERIC NOT NULL DEFAULT 0, altitude NUMERIC NOT NULL DEFAULT 0, author TEXT NOT NULL DEFAULT '', source_url TEXT NOT NULL DEFAULT '', is_todo INT NOT NULL DEFAULT 0, todo_due INT NOT NULL DEFAULT 0, todo_completed INT NOT NULL DEFAULT 0, source TEXT NOT NULL DEFAULT '', source_application TEXT NOT NULL DEFAULT '', application_data TEXT NOT NULL DEFAULT '', `order` NUMERIC NOT NULL DEFAULT 0, user_created_time INT NOT NULL DEFAULT 0, user_updated_time INT NOT NULL DEFAULT 0, encryption_cipher_text TEXT NOT NULL DEFAULT '', encryption_applied INT NOT NULL DEFAULT 0, markup_language INT NOT NULL DEFAULT 1, is_shared INT NOT NULL DEFAULT 0, share_id TEXT NOT NULL DEFAULT '', conflict_original_id TEXT NOT NULL DEFAULT '', master_key_id TEXT NOT NULL DEFAULT '', user_data TEXT NOT NULL DEFAULT '', deleted_time INT NOT NULL DEFAULT 0);
+         CREATE TABLE IF NOT EXISTS folders (id TEXT PRIMARY KEY, title TEXT NOT NULL DEFAULT '', created_time INT NOT NULL, updated_time INT NOT NULL, user_created_time INT NOT NULL DEFAULT 0, user_updated_time INT NOT NULL DEFAULT 0, encryption_cipher_text TEXT NOT NULL DEFAULT '', encryption_applied INT NOT NULL DEFAULT 0, parent_id TEXT NOT NULL DEFAULT '', is_shared INT NOT NULL DEFAULT 0, share_id TEXT NOT NULL DEFAULT '', master_key_id TEXT NOT NULL DEFAULT '', icon TEXT NOT NULL DEFAULT '', user_data TEXT NOT NULL DEFAULT '', deleted_time INT NOT NULL DEFAULT 0);
+         INSERT OR IGNORE INTO folders (id, title, created_time, updated_time) VALUES ('selftestfolder', 'Notebook', 0, 0);
+         INSERT OR IGNORE INTO notes (id, title, body, created_time, updated_time) VALUES ('selftestnote', 'welcome', 'Hello from the self-test', 0, 0);",
+    )?;
+    Ok(conn)
+}
+
+/// A folder hierarchy fault found by `doctor`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum FolderProblem {
+    /// Folders whose `parent_id`s lead round in a loop, starting from the
+    /// smallest id; each one's parent is the next, the last's the first
+    Cycle(Vec<String>),
+    /// A folder whose `parent_id` names no folder row
+    MissingParent { id: String, parent_id: String },
+}
+
+impl FolderProblem {
+    /// The folder `doctor --fix` moves to the top level: the orphan, or the
+    /// first folder of a cycle, which breaks it while keeping the rest nested
+    pub(crate) fn offender(&self) -> &str {
+        match self {
+            FolderProblem::Cycle(ids) => &ids[0],
+            FolderProblem::MissingParent { id, .. } => id,
+        }
+    }
+}
+
+impl std::fmt::Display for FolderProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FolderProblem::Cycle(ids) => {
+                write!(f, "folder cycle: {} -> {}", ids.join(" -> "), ids[0])
+            }
+            FolderProblem::MissingParent { id, parent_id } => {
+                write!(f, "folder {id} has missing parent {parent_id}")
+            }
+        }
+    }
+}
+
+/// Every `parent_id` cycle and dangling `parent_id` among the folders,
+/// trashed ones included, in id order
+pub(crate) fn folder_problems(conn: &Connection) -> Result<Vec<FolderProblem>> {
+    let parents: std::collections::BTreeMap<String, String> = conn
+        .prepare("SELECT id, parent_id FROM folders")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_>>()?;
+
+    let mut problems = Vec::new();
+    // Folders whose chain is known to end, at the top level or in a fault
+    // already reported
+    let mut settled = BTreeSet::new();
+    for start in parents.keys() {
+        let mut chain: Vec<&String> = Vec::new();
+        let mut current = start;
+        loop {
+            if settled.contains(current) {
+                break;
+            }
+            if let Some(pos) = chain.iter().position(|id| *id == current) {
+                let mut cycle: Vec<String> = chain[pos..].iter().map(|id| id.to_string()).collect();
+                let first = (0..cycle.len()).min_by_key(|&i| &cycle[i]).unwrap_or(0);
+                cycle.rotate_left(first);
+                problems.push(FolderProblem::Cycle(cycle));
+                break;
+            }
+            chain.push(current);
+            let parent = &parents[current];
+            if parent.is_empty() {
+                break;
+            }
+            match parents.get_key_value(parent) {
+                Some((parent, _)) => current = parent,
+                None => {
+                    problems.push(FolderProblem::MissingParent {
+                        id: current.clone(),
+                        parent_id: parent.clone(),
+                    });
+                    break;
+                }
+            }
+        }
+        settled.extend(chain.into_iter().cloned());
+    }
+    Ok(problems)
+}
+
+/// Move the offending folder of each problem to the top level, in one
+/// transaction, stamping each move with `touch_row`
+pub(crate) fn fix_folder_problems(
+    conn: &Connection,
+    problems: &[FolderProblem],
+    now: i64,
+) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    for problem in problems {
+        tx.execute(
+            "UPDATE folders SET parent_id = '' WHERE id = ?1",
+            [problem.offender()],
+        )?;
+        tx.touch_row("folders", problem.offender(), now)?;
+    }
+    tx.commit()
+}
+
+/// A folder `rename_duplicate_folders` gave a title of its own
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FolderRename {
+    pub(crate) id: String,
+    pub(crate) old_title: String,
+    pub(crate) new_title: String,
+}
+
+impl std::fmt::Display for FolderRename {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "renamed folder {} from {:?} to {:?}",
+            self.id, self.old_title, self.new_title
+        )
+    }
+}
+
+/// Give every live folder that shares its title with a sibling a title of
+/// its own, in one transaction, so each folder has a path
+///
+/// The most recently updated folder, the one paths already resolve to,
+/// keeps the title; the others get the start of their id, `Work (a1b2c3)`,
+/// as duplicate notes are listed, or the whole id should that be taken
+/// too. Nothing is merged or moved, so the notes and folders inside keep
+/// their parent. Each rename is stamped with `touch_row`.
+pub(crate) fn rename_duplicate_folders(conn: &Connection, now: i64) -> Result<Vec<FolderRename>> {
+    let folders: Vec<(String, String, String)> = conn
+        .prepare(
+            "SELECT id, parent_id, title FROM folders WHERE deleted_time = 0 \
+             ORDER BY parent_id, title, user_updated_time DESC, id",
+        )?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<Result<_>>()?;
+
+    let mut taken: BTreeSet<(&str, String)> = folders
+        .iter()
+        .map(|(_, parent_id, title)| (parent_id.as_str(), title.clone()))
+        .collect();
+    let mut renames = Vec::new();
+    for pair in folders.windows(2) {
+        let [(_, prev_parent, prev_title), (id, parent_id, title)] = pair else {
+            continue;
+        };
+        if prev_parent != parent_id || prev_title != title {
+            continue;
+        }
+        let fragment: String = id.chars().take(6).collect();
+        let mut new_title = format!("{title} ({fragment})");
+        if taken.contains(&(parent_id.as_str(), new_title.clone())) {
+            new_title = format!("{title} ({id})");
+        }
+        taken.insert((parent_id.as_str(), new_title.clone()));
+        renames.push(FolderRename {
+            id: id.clone(),
+            old_title: title.clone(),
+            new_title,
+        });
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    for rename in &renames {
+        tx.execute(
+            "UPDATE folders SET title = ?1 WHERE id = ?2",
+            [&rename.new_title, &rename.id],
+        )?;
+        tx.touch_row("folders", &rename.id, now)?;
+    }
+    tx.commit()?;
+    Ok(renames)
+}
@@ -0,0 +1,79 @@
+use rusqlite::Connection;
+use sqlite_fuse::{AttrConfig, FsOptions, NoteStore, SqliteFS};
+
+fn joplin_db() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch(
+        "CREATE TABLE notes (id TEXT PRIMARY KEY, parent_id TEXT NOT NULL DEFAULT '', title TEXT NOT NULL DEFAULT '', body TEXT NOT NULL DEFAULT '', created_time INT NOT NULL, updated_time INT NOT NULL, user_created_time INT NOT NULL DEFAULT 0, user_updated_time INT NOT NULL DEFAULT 0, markup_language INT NOT NULL DEFAULT 1, deleted_time INT NOT NULL DEFAULT 0);
+         CREATE TABLE folders (id TEXT PRIMARY KEY, parent_id TEXT NOT NULL DEFAULT '', title TEXT NOT NULL DEFAULT '', created_time INT NOT NULL, updated_time INT NOT NULL, user_created_time INT NOT NULL DEFAULT 0, user_updated_time INT NOT NULL DEFAULT 0, deleted_time INT NOT NULL DEFAULT 0);
+         INSERT INTO folders (id, title, created_time, updated_time) VALUES ('folder1', 'Documents', 0, 0);
+         INSERT INTO notes (id, parent_id, title, created_time, updated_time) VALUES ('note1', 'folder1', 'readme', 0, 0);",
+    )
+    .unwrap();
+    conn
+}
+
+fn assert_filesystem<T: fuser::Filesystem>(_: &T) {}
+
+#[test]
+fn test_library_builds_filesystem() {
+    let fs = SqliteFS::from_connection(joplin_db(), FsOptions::default())
+        .expect("Failed to build filesystem");
+    assert_filesystem(&fs);
+    assert_eq!(fs.attr_config(), AttrConfig::default());
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("database.sqlite");
+    joplin_db()
+        .execute("VACUUM INTO ?1", [path.to_str().unwrap()])
+        .unwrap();
+    assert!(SqliteFS::new(path.to_str().unwrap(), FsOptions::default()).is_ok());
+
+    let missing = dir.path().join("missing").join("database.sqlite");
+    let err = SqliteFS::new(missing.to_str().unwrap(), FsOptions::default())
+        .err()
+        .expect("opening a database in a missing directory should fail");
+    assert!(!err.to_string().is_empty());
+}
+
+#[test]
+fn test_options_set_through_the_builder() {
+    let options = FsOptions::default().block_size(4096).uid(4242);
+    let fs = SqliteFS::from_connection(joplin_db(), options).unwrap();
+    let config = fs.attr_config();
+    assert_eq!(config.block_size, 4096);
+    assert_eq!(config.uid, 4242);
+    assert_eq!(config.gid, AttrConfig::default().gid);
+
+    let missing = SqliteFS::from_connection(joplin_db(), FsOptions::default().folder("Nowhere"));
+    assert!(missing.is_err());
+}
+
+#[test]
+fn test_note_store_on_connection() {
+    let conn = joplin_db();
+    conn.ensure_indexes().unwrap();
+    assert_eq!(
+        conn.note_candidates("folder1", "readme").unwrap(),
+        vec!["note1".to_string()]
+    );
+    conn.touch_note_parent("note1", 42).unwrap();
+    let updated: i64 = conn
+        .query_row(
+            "SELECT updated_time FROM folders WHERE id = 'folder1'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(updated, 42);
+
+    assert_eq!(conn.touch_row("notes", "note1", 7).unwrap(), 1);
+    let stamps: (i64, i64) = conn
+        .query_row(
+            "SELECT updated_time, user_updated_time FROM notes WHERE id = 'note1'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(stamps, (7, 7));
+}